@@ -0,0 +1,292 @@
+//! Read a GPS module wired to the edge connector, and show where it is from the serial port.
+//!
+//! Wire the module's TX pin to the edge connector's pin 1, its RX pin to pin 0, and its power
+//! and ground to 3V and GND. The board listens to it on the second UARTE, at 9600 baud unless
+//! told otherwise with `gps-line`, and picks its position out of the NMEA sentences it sends.
+//! Type `help` in the terminal for the commands.
+//!
+//! Both `line`, for the port to the computer, and `gps-line` are kept in the settings store, so
+//! they survive a reset. After changing `line`, switch the terminal to match and press `y`;
+//! without that the board goes back to the old settings and doesn't keep the new ones.
+
+#![no_main]
+#![no_std]
+
+use core::fmt::Write;
+
+use cortex_m_rt::entry;
+use panic_rtt_target as _;
+use rtt_target::{rprintln, rtt_init_print};
+
+use microbit::{
+    hal::{nvmc::Nvmc, uarte},
+    pac::{self, interrupt, NVMC, UARTE0, UARTE1},
+};
+
+use nmea::{parse, Gga, Rmc, Sentence, Sentences};
+use serial_setup::{edge_pins, parse_line, BufferedUarte, LineSettings, Parity, UartePort};
+use settings::{keys, Key, Settings};
+use shell::{dispatch, write_help, Args, Command, CommandError, Editor};
+
+/// The serial port to the computer.
+static SERIAL: BufferedUarte<UARTE0, 64, 256> = BufferedUarte::new();
+
+/// The serial port to the GPS module, with room for a few sentences while a command runs.
+static GPS: BufferedUarte<UARTE1, 256, 32> = BufferedUarte::new();
+
+#[pac::interrupt]
+fn UARTE0_UART0() {
+    SERIAL.on_interrupt();
+}
+
+#[pac::interrupt]
+fn UARTE1() {
+    GPS.on_interrupt();
+}
+
+const SAVE_FAILED: CommandError = CommandError::Failed("couldn't save the setting");
+
+/// Everything the commands use.
+struct Tracker {
+    serial: UartePort<UARTE0>,
+    gps: UartePort<UARTE1>,
+    settings: Settings<Nvmc<NVMC>>,
+    line: LineSettings,
+    gps_line: LineSettings,
+    /// The latest of each sentence with a position.
+    gga: Option<Gga>,
+    rmc: Option<Rmc>,
+    /// Whether to pass the module's sentences on to the terminal as they arrive.
+    raw: bool,
+    /// Sentences read, and sentences that were damaged or couldn't be read.
+    good: u32,
+    bad: u32,
+}
+
+const COMMANDS: &[Command<Tracker>] = &[
+    Command {
+        name: "fix",
+        usage: "",
+        help: "show the latest position, time and speed",
+        run: fix,
+    },
+    Command {
+        name: "raw",
+        usage: "[on|off]",
+        help: "pass the module's sentences on as they arrive, or stop",
+        run: raw,
+    },
+    Command {
+        name: "stats",
+        usage: "",
+        help: "count the sentences read and lost",
+        run: stats,
+    },
+    Command {
+        name: "gps-line",
+        usage: "[baud] [none|even]",
+        help: "show or change the GPS module's baud rate and parity",
+        run: gps_line,
+    },
+    Command {
+        name: "line",
+        usage: "[baud] [none|even]",
+        help: "show or change this port's baud rate and parity",
+        run: line,
+    },
+    Command {
+        name: "help",
+        usage: "",
+        help: "list the commands",
+        run: help,
+    },
+];
+
+fn fix(tracker: &mut Tracker, args: &mut Args) -> Result<(), CommandError> {
+    args.end()?;
+    let (Some(gga), Some(rmc)) = (tracker.gga, tracker.rmc) else {
+        write!(tracker.serial, "nothing from the module yet\r\n").unwrap();
+        return Ok(());
+    };
+    match (rmc.valid, gga.position) {
+        (true, Some(position)) => {
+            write!(tracker.serial, "{}", position).unwrap();
+            if let Some(altitude) = gga.altitude {
+                write!(tracker.serial, ", {:.1} m up", altitude).unwrap();
+            }
+            write!(
+                tracker.serial,
+                ", {} satellites\r\n",
+                gga.satellites.unwrap_or(0)
+            )
+            .unwrap();
+        }
+        _ => write!(tracker.serial, "no fix yet\r\n").unwrap(),
+    }
+    if let (Some(date), Some(time)) = (rmc.date, rmc.time) {
+        write!(tracker.serial, "{} {} UTC\r\n", date, time).unwrap();
+    }
+    if let (true, Some(speed)) = (rmc.valid, rmc.speed) {
+        write!(tracker.serial, "{:.1} knots", speed).unwrap();
+        if let Some(course) = rmc.course {
+            write!(tracker.serial, " heading {:.0}", course).unwrap();
+        }
+        write!(tracker.serial, "\r\n").unwrap();
+    }
+    Ok(())
+}
+
+fn raw(tracker: &mut Tracker, args: &mut Args) -> Result<(), CommandError> {
+    match args.next() {
+        None => {
+            let state = if tracker.raw { "on" } else { "off" };
+            write!(tracker.serial, "{}\r\n", state).unwrap();
+        }
+        Some("on") => tracker.raw = true,
+        Some("off") => tracker.raw = false,
+        Some(_) => return Err(CommandError::Invalid("[on|off]")),
+    }
+    args.end()
+}
+
+fn stats(tracker: &mut Tracker, args: &mut Args) -> Result<(), CommandError> {
+    args.end()?;
+    write!(
+        tracker.serial,
+        "{} sentences read, {} damaged, {} bytes lost\r\n",
+        tracker.good,
+        tracker.bad,
+        tracker.gps.stats().rx_overruns
+    )
+    .unwrap();
+    Ok(())
+}
+
+fn gps_line(tracker: &mut Tracker, args: &mut Args) -> Result<(), CommandError> {
+    let Some(line) = parse_line(args, tracker.gps_line)? else {
+        write!(tracker.serial, "{}\r\n", tracker.gps_line).unwrap();
+        return Ok(());
+    };
+    save_line(&mut tracker.settings, keys::GPS_LINE, line)?;
+    tracker.gps.set_line(&line).unwrap();
+    tracker.gps_line = line;
+    Ok(())
+}
+
+fn line(tracker: &mut Tracker, args: &mut Args) -> Result<(), CommandError> {
+    let Some(line) = parse_line(args, tracker.line)? else {
+        write!(tracker.serial, "{}\r\n", tracker.line).unwrap();
+        return Ok(());
+    };
+    if !tracker.serial.switch_line(&tracker.line, &line).unwrap() {
+        return Err(CommandError::Failed("not confirmed, so not saved"));
+    }
+    tracker.line = line;
+    save_line(&mut tracker.settings, keys::SERIAL_LINE, line)
+}
+
+fn help(tracker: &mut Tracker, args: &mut Args) -> Result<(), CommandError> {
+    args.end()?;
+    write_help(COMMANDS, &mut tracker.serial).unwrap();
+    Ok(())
+}
+
+/// The line settings stored under `key`, if there are any the UARTEs can use.
+fn stored_line(settings: &mut Settings<Nvmc<NVMC>>, key: Key) -> Option<LineSettings> {
+    let mut bytes = [0; 5];
+    let len = settings.read(key, &mut bytes).unwrap()?;
+    LineSettings::from_bytes(bytes.get(..len)?)
+}
+
+fn save_line(
+    settings: &mut Settings<Nvmc<NVMC>>,
+    key: Key,
+    line: LineSettings,
+) -> Result<(), CommandError> {
+    settings
+        .write(key, &line.to_bytes())
+        .map_err(|_| SAVE_FAILED)
+}
+
+impl Tracker {
+    /// Read whatever the module has sent.
+    fn poll_gps(&mut self, sentences: &mut Sentences) {
+        while let Some(byte) = self.gps.try_read().unwrap() {
+            let Some(sentence) = sentences.push(byte) else {
+                continue;
+            };
+            if self.raw {
+                write!(self.serial, "{}\r\n", sentence).unwrap();
+            }
+            match parse(sentence) {
+                Ok(Sentence::Gga(gga)) => self.gga = Some(gga),
+                Ok(Sentence::Rmc(rmc)) => self.rmc = Some(rmc),
+                Ok(Sentence::Other(_)) => {}
+                Err(e) => {
+                    rprintln!("{}: {}", e, sentence);
+                    self.bad += 1;
+                    continue;
+                }
+            }
+            self.good += 1;
+        }
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    rtt_init_print!();
+    let board = microbit::Board::take().unwrap();
+
+    let mut settings = Settings::new(settings::flash(board.NVMC), 0);
+    let line = stored_line(&mut settings, keys::SERIAL_LINE).unwrap_or_default();
+    // What most modules start up at.
+    let gps_line = stored_line(&mut settings, keys::GPS_LINE)
+        .or(LineSettings::new(9_600, Parity::None))
+        .unwrap();
+
+    let serial = uarte::Uarte::new(
+        board.UARTE0,
+        board.uart.into(),
+        line.hal_parity(),
+        line.baudrate(),
+    );
+    let gps = uarte::Uarte::new(
+        board.UARTE1,
+        edge_pins(board.edge.e00.degrade(), board.edge.e01.degrade()),
+        gps_line.hal_parity(),
+        gps_line.baudrate(),
+    );
+
+    let mut tracker = Tracker {
+        serial: SERIAL.start(serial),
+        gps: GPS.start(gps),
+        settings,
+        line,
+        gps_line,
+        gga: None,
+        rmc: None,
+        raw: false,
+        good: 0,
+        bad: 0,
+    };
+    let mut sentences = Sentences::new();
+    let mut editor: Editor<32, 4> = Editor::new("> ");
+    editor.prompt(&mut tracker.serial).unwrap();
+    rprintln!("ready, talking to the GPS module at {}", gps_line);
+
+    loop {
+        tracker.poll_gps(&mut sentences);
+
+        let Some(byte) = tracker.serial.try_read().unwrap() else {
+            continue;
+        };
+        let names = COMMANDS.iter().map(|c| c.name);
+        if let Some(line) = editor.feed(byte, names, &mut tracker.serial).unwrap() {
+            if let Err(e) = dispatch(COMMANDS, line, &mut tracker) {
+                write!(tracker.serial, "error: {}\r\n", e).unwrap();
+            }
+            editor.prompt(&mut tracker.serial).unwrap();
+        }
+    }
+}
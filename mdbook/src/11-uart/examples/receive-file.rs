@@ -0,0 +1,98 @@
+//! Receive a file over the serial port with XMODEM, and keep it in flash.
+//!
+//! Once this is running, send a file from your terminal program: in minicom, `Ctrl+A S` and pick
+//! `xmodem`; from a shell, `sx -k file < /dev/ttyACM0 > /dev/ttyACM0` (1K blocks, or leave out
+//! `-k` for 128-byte ones). Start sending within a minute. How it went, and the start of the file,
+//! show up in the RTT output; press any key in the terminal to receive another.
+//!
+//! The file ends up at `xmodem::FILES_ADDR`, padded to a whole number of blocks, where another
+//! program can read it without this one, as long as nothing is flashed over it.
+
+#![no_main]
+#![no_std]
+
+use cortex_m_rt::entry;
+use embedded_io::Write;
+use embedded_storage::nor_flash::ReadNorFlash;
+use panic_rtt_target as _;
+use rtt_target::{rprintln, rtt_init_print};
+
+use microbit::{
+    hal::{
+        uarte::{self, Baudrate, Parity},
+        Timer,
+    },
+    pac::{self, interrupt, UARTE0},
+};
+
+use serial_setup::BufferedUarte;
+use xmodem::Receiver;
+
+/// The serial port, buffered so that no byte of a block is lost while the last one is written to
+/// flash. Erasing a page stops the CPU for up to 85 ms, but XMODEM senders wait for the `ACK`
+/// before sending more, so a buffer one block long is plenty.
+static SERIAL: BufferedUarte<UARTE0, 1100, 64> = BufferedUarte::new();
+
+#[pac::interrupt]
+fn UARTE0_UART0() {
+    SERIAL.on_interrupt();
+}
+
+#[entry]
+fn main() -> ! {
+    rtt_init_print!();
+    let board = microbit::Board::take().unwrap();
+
+    let serial = uarte::Uarte::new(
+        board.UARTE0,
+        board.uart.into(),
+        Parity::EXCLUDED,
+        Baudrate::BAUD115200,
+    );
+    let mut serial = SERIAL.start(serial);
+    let mut flash = xmodem::flash(board.NVMC);
+
+    // Counts milliseconds, for XMODEM's timeouts.
+    let mut clock = Timer::periodic(board.TIMER0);
+    clock.start(1000u32);
+    let mut now_ms: u32 = 0;
+
+    loop {
+        rprintln!("waiting for a file");
+        let mut receiver = Receiver::new(flash, now_ms);
+        while receiver.outcome().is_none() {
+            if clock.reset_if_finished() {
+                now_ms = now_ms.wrapping_add(1);
+            }
+            let reply = match serial.try_read().unwrap() {
+                Some(byte) => receiver.receive(byte, now_ms),
+                None => receiver.tick(now_ms),
+            };
+            serial.write_all(reply).unwrap();
+        }
+        serial.flush().unwrap();
+
+        match receiver.outcome().unwrap() {
+            Ok(len) => rprintln!("received {} bytes", len),
+            Err(e) => rprintln!("failed: {:?}", e),
+        }
+        flash = receiver.into_flash();
+        let mut start = [0; 32];
+        flash.read(0, &mut start).unwrap();
+        rprintln!("starts {:02x?}", start);
+
+        // The sender may still be sending after a failure, so wait for a second's quiet before
+        // waiting for a key.
+        let mut quiet_ms = 0;
+        while quiet_ms < 1000 {
+            if serial.try_read().unwrap().is_some() {
+                quiet_ms = 0;
+            } else if clock.reset_if_finished() {
+                now_ms = now_ms.wrapping_add(1);
+                quiet_ms += 1;
+            }
+        }
+        rprintln!("press a key to receive another file");
+        serial.read().unwrap();
+    }
+}
@@ -0,0 +1,210 @@
+//! Draw on the LED matrix from the serial port.
+//!
+//! Type `help` in the terminal for the commands. `image` takes a whole picture as 25 digits from
+//! 0 (off) to 9 (brightest), a row at a time from the top left, so a script on your computer can
+//! draw with a single line; `companion send-image` (in `src/companion`) sends one from a file.
+
+#![no_main]
+#![no_std]
+
+use core::fmt::Write;
+
+use cortex_m_rt::entry;
+use panic_rtt_target as _;
+use rtt_target::{rprintln, rtt_init_print};
+
+use microbit::{
+    hal::{
+        uarte::{self, Baudrate, Parity},
+        Timer,
+    },
+    pac::{self, interrupt, TIMER1, UARTE0},
+};
+
+use led_matrix::{
+    mb2::SharedDisplay,
+    remote::{
+        parse_brightness, parse_clear, parse_image, parse_set, parse_text, Animation, Pixel,
+        Screen,
+    },
+};
+use serial_setup::{BufferedUarte, UartePort};
+use shell::{dispatch, write_help, Args, Command, CommandError, Editor};
+
+/// The serial port, buffered so that nothing typed is lost while the display is being updated.
+static SERIAL: BufferedUarte<UARTE0, 64, 256> = BufferedUarte::new();
+
+/// The display, refreshed from its timer interrupt so it can show shades of grey.
+static DISPLAY: SharedDisplay<TIMER1> = SharedDisplay::new();
+
+#[pac::interrupt]
+fn UARTE0_UART0() {
+    SERIAL.on_interrupt();
+}
+
+#[pac::interrupt]
+fn TIMER1() {
+    DISPLAY.on_interrupt();
+}
+
+/// Everything the commands use.
+struct Remote {
+    serial: UartePort<UARTE0>,
+    screen: Screen,
+}
+
+const COMMANDS: &[Command<Remote>] = &[
+    Command {
+        name: "image",
+        usage: "<leds>",
+        help: "show 25 brightnesses from 0 to 9, row by row from the top left",
+        run: image,
+    },
+    Command {
+        name: "set",
+        usage: "<x> <y> [level]",
+        help: "light one LED, counting from 0 at the top left",
+        run: set,
+    },
+    Command {
+        name: "clear",
+        usage: "[<x> <y>]",
+        help: "turn off one LED, or all of them",
+        run: clear,
+    },
+    Command {
+        name: "scroll",
+        usage: "<text>",
+        help: "scroll text across once",
+        run: scroll,
+    },
+    Command {
+        name: "play",
+        usage: "<spin|pulse|wipe>",
+        help: "play an animation until stopped",
+        run: play,
+    },
+    Command {
+        name: "stop",
+        usage: "",
+        help: "stop the text or animation, and show the picture again",
+        run: stop,
+    },
+    Command {
+        name: "brightness",
+        usage: "[0-9]",
+        help: "show or set how bright everything is",
+        run: brightness,
+    },
+    Command {
+        name: "help",
+        usage: "",
+        help: "list the commands",
+        run: help,
+    },
+];
+
+fn image(remote: &mut Remote, args: &mut Args) -> Result<(), CommandError> {
+    remote.screen.show(parse_image(args)?);
+    Ok(())
+}
+
+fn set(remote: &mut Remote, args: &mut Args) -> Result<(), CommandError> {
+    remote.screen.set(parse_set(args)?);
+    Ok(())
+}
+
+fn clear(remote: &mut Remote, args: &mut Args) -> Result<(), CommandError> {
+    match parse_clear(args)? {
+        Some((x, y)) => remote.screen.set(Pixel { x, y, level: 0 }),
+        None => remote.screen.show([[0; 5]; 5]),
+    }
+    Ok(())
+}
+
+fn scroll(remote: &mut Remote, args: &mut Args) -> Result<(), CommandError> {
+    remote.screen.scroll(parse_text(args)?);
+    Ok(())
+}
+
+fn play(remote: &mut Remote, args: &mut Args) -> Result<(), CommandError> {
+    let animation: Animation = args.parse("<spin|pulse|wipe>")?;
+    args.end()?;
+    remote.screen.play(animation);
+    Ok(())
+}
+
+fn stop(remote: &mut Remote, args: &mut Args) -> Result<(), CommandError> {
+    args.end()?;
+    remote.screen.stop();
+    Ok(())
+}
+
+fn brightness(remote: &mut Remote, args: &mut Args) -> Result<(), CommandError> {
+    match parse_brightness(args)? {
+        Some(brightness) => remote.screen.set_brightness(brightness),
+        None => write!(
+            remote.serial,
+            "brightness {}, showing {}\r\n",
+            remote.screen.brightness(),
+            remote.screen.activity()
+        )
+        .unwrap(),
+    }
+    Ok(())
+}
+
+fn help(remote: &mut Remote, args: &mut Args) -> Result<(), CommandError> {
+    args.end()?;
+    write_help(COMMANDS, &mut remote.serial).unwrap();
+    Ok(())
+}
+
+#[entry]
+fn main() -> ! {
+    rtt_init_print!();
+    let board = microbit::Board::take().unwrap();
+
+    let serial = uarte::Uarte::new(
+        board.UARTE0,
+        board.uart.into(),
+        Parity::EXCLUDED,
+        Baudrate::BAUD115200,
+    );
+    let serial = SERIAL.start(serial);
+    DISPLAY.start(board.TIMER1, board.display_pins);
+
+    // Counts milliseconds, for the text and animations.
+    let mut clock = Timer::periodic(board.TIMER0);
+    clock.start(1000u32);
+    let mut now_ms: u32 = 0;
+
+    let mut remote = Remote {
+        serial,
+        screen: Screen::new(),
+    };
+    // Long enough for `image` and its 25 digits, with room to spare for `scroll`.
+    let mut editor: Editor<64, 4> = Editor::new("> ");
+    editor.prompt(&mut remote.serial).unwrap();
+    rprintln!("ready");
+
+    loop {
+        if clock.reset_if_finished() {
+            now_ms = now_ms.wrapping_add(1);
+        }
+        if let Some(frame) = remote.screen.tick(now_ms) {
+            DISPLAY.show(&frame);
+        }
+
+        let Some(byte) = remote.serial.try_read().unwrap() else {
+            continue;
+        };
+        let names = COMMANDS.iter().map(|c| c.name);
+        if let Some(line) = editor.feed(byte, names, &mut remote.serial).unwrap() {
+            if let Err(e) = dispatch(COMMANDS, line, &mut remote) {
+                write!(remote.serial, "error: {}\r\n", e).unwrap();
+            }
+            editor.prompt(&mut remote.serial).unwrap();
+        }
+    }
+}
@@ -1,50 +1,56 @@
 #![no_main]
 #![no_std]
 
-use core::fmt::Write;
 use cortex_m_rt::entry;
-use heapless::Vec;
-use microbit::hal::uarte::{self, Baudrate, Parity};
+use embedded_io::Write;
+use microbit::hal::uarte;
 use panic_rtt_target as _;
 use rtt_target::rtt_init_print;
-use serial_setup::UartePort;
+use serial_setup::{LineSettings, UartePort};
+use settings::{keys, Settings};
+use shell::Editor;
 
 #[entry]
 fn main() -> ! {
     rtt_init_print!();
     let board = microbit::Board::take().unwrap();
 
+    // 115200 baud without parity, unless `line`, in the GPS example or the I2C chapter's console,
+    // has saved something else.
+    let mut settings = Settings::new(settings::flash(board.NVMC), 0);
+    let mut stored = [0; 5];
+    let line = settings
+        .read(keys::SERIAL_LINE, &mut stored)
+        .unwrap()
+        .and_then(|len| stored.get(..len))
+        .and_then(LineSettings::from_bytes)
+        .unwrap_or_default();
+
     let mut serial = {
         let serial = uarte::Uarte::new(
             board.UARTE0,
             board.uart.into(),
-            Parity::EXCLUDED,
-            Baudrate::BAUD115200,
+            line.hal_parity(),
+            line.baudrate(),
         );
         UartePort::new(serial)
     };
 
-    // A buffer with 32 bytes of capacity
-    let mut buffer: Vec<u8, 32> = Vec::new();
+    // Lines of up to 32 bytes, remembering the last 4 for the up arrow
+    let mut editor: Editor<32, 4> = Editor::new("> ");
+    editor.prompt(&mut serial).unwrap();
 
     loop {
-        buffer.clear();
-
-        loop {
-            // We assume that the receiving cannot fail
-            let byte = serial.read().unwrap();
-
-            if buffer.push(byte).is_err() {
-                write!(serial, "error: buffer full\r\n").unwrap();
-                break;
-            }
+        // We assume that the receiving cannot fail
+        let byte = serial.read().unwrap();
 
-            if byte == b'\r' {
-                for byte in buffer.iter().rev().chain(&[b'\n', b'\r']) {
-                    serial.write(*byte).unwrap();
-                }
-                break;
+        // There are no commands to complete, so tab does nothing
+        if let Some(line) = editor.feed(byte, [], &mut serial).unwrap() {
+            for byte in line.bytes().rev() {
+                serial.write(byte).unwrap();
             }
+            serial.write_all(b"\r\n").unwrap();
+            editor.prompt(&mut serial).unwrap();
         }
         serial.flush().unwrap()
     }
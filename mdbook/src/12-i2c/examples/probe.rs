@@ -0,0 +1,210 @@
+//! Look around the I2C buses from the serial port.
+//!
+//! `chip-id` reads two registers it already knows about. This does the same for any chip, on
+//! either bus: `scan` lists the addresses that answer, `id` says which chip is at one, and
+//! `read`, `write` and `dump` get at its registers, all in hex. Type `help` for the details.
+//!
+//! The external bus is on the edge connector's pins 19 (SCL) and 20 (SDA). Most breakout boards
+//! bring their own pull-up resistors, so one can be wired straight to those pins, 3V and GND.
+
+#![no_main]
+#![no_std]
+
+use core::fmt::Write;
+
+use cortex_m_rt::entry;
+use panic_rtt_target as _;
+use rtt_target::{rprintln, rtt_init_print};
+
+use microbit::{
+    hal::{
+        twim::{self, Twim},
+        uarte::{self, Baudrate, Parity},
+    },
+    pac::{twim0::frequency::FREQUENCY_A, TWIM0, UARTE0},
+};
+
+use i2c::probe::{
+    bus_error, identify, parse_dump, parse_id, parse_read, parse_write, read_register,
+    read_registers, scan, write_dump, write_register, write_scan, Bus, MAX_DUMP,
+};
+use serial_setup::UartePort;
+use shell::{dispatch, write_help, Args, Command, CommandError, Editor};
+
+/// The one TWIM, moved between the two buses' pins as needed.
+struct Buses {
+    i2c: Option<Twim<TWIM0>>,
+    bus: Bus,
+    /// The pins of the bus not in use.
+    other: Option<twim::Pins>,
+}
+
+impl Buses {
+    fn i2c(&mut self) -> &mut Twim<TWIM0> {
+        self.i2c.as_mut().unwrap()
+    }
+
+    fn select(&mut self, bus: Bus) {
+        if bus == self.bus {
+            return;
+        }
+        let (twim, pins) = self.i2c.take().unwrap().free();
+        let other = self.other.replace(pins).unwrap();
+        self.i2c = Some(Twim::new(twim, other, FREQUENCY_A::K100));
+        self.bus = bus;
+    }
+}
+
+/// Everything the commands use.
+struct Probe {
+    serial: UartePort<UARTE0>,
+    buses: Buses,
+}
+
+const COMMANDS: &[Command<Probe>] = &[
+    Command {
+        name: "bus",
+        usage: "[internal|external]",
+        help: "show or change which bus the other commands use",
+        run: bus,
+    },
+    Command {
+        name: "scan",
+        usage: "",
+        help: "list the addresses chips answer at, and what the chips are",
+        run: scan_bus,
+    },
+    Command {
+        name: "id",
+        usage: "<addr>",
+        help: "say which chip is at an address, from its ID register",
+        run: id,
+    },
+    Command {
+        name: "read",
+        usage: "<addr> <reg>",
+        help: "read a register",
+        run: read,
+    },
+    Command {
+        name: "write",
+        usage: "<addr> <reg> <value>",
+        help: "write a register",
+        run: write,
+    },
+    Command {
+        name: "dump",
+        usage: "<addr> [reg] [count]",
+        help: "read count (16) registers from reg (00) on",
+        run: dump,
+    },
+    Command {
+        name: "help",
+        usage: "",
+        help: "list the commands",
+        run: help,
+    },
+];
+
+fn bus(probe: &mut Probe, args: &mut Args) -> Result<(), CommandError> {
+    match args.parse_optional("[internal|external]")? {
+        Some(bus) => probe.buses.select(bus),
+        None => write!(probe.serial, "{}\r\n", probe.buses.bus.name()).unwrap(),
+    }
+    args.end()
+}
+
+fn scan_bus(probe: &mut Probe, args: &mut Args) -> Result<(), CommandError> {
+    args.end()?;
+    let found = scan(probe.buses.i2c());
+    write_scan(&mut probe.serial, &found).unwrap();
+    for address in found.found.iter() {
+        match identify(probe.buses.i2c(), address) {
+            Ok(Some(chip)) => write!(probe.serial, "{:02x}: {}\r\n", address, chip.name).unwrap(),
+            Ok(None) => {}
+            Err(e) => return Err(bus_error(e)),
+        }
+    }
+    if found.found.is_empty() {
+        write!(
+            probe.serial,
+            "nothing on the {} bus\r\n",
+            probe.buses.bus.name()
+        )
+        .unwrap();
+    }
+    Ok(())
+}
+
+fn id(probe: &mut Probe, args: &mut Args) -> Result<(), CommandError> {
+    let address = parse_id(args)?;
+    match identify(probe.buses.i2c(), address).map_err(bus_error)? {
+        Some(chip) => write!(probe.serial, "{}\r\n", chip.name).unwrap(),
+        None => write!(probe.serial, "not a chip I know\r\n").unwrap(),
+    }
+    Ok(())
+}
+
+fn read(probe: &mut Probe, args: &mut Args) -> Result<(), CommandError> {
+    let (address, register) = parse_read(args)?;
+    let value = read_register(probe.buses.i2c(), address, register).map_err(bus_error)?;
+    write!(probe.serial, "{:02x}\r\n", value).unwrap();
+    Ok(())
+}
+
+fn write(probe: &mut Probe, args: &mut Args) -> Result<(), CommandError> {
+    let (address, register, value) = parse_write(args)?;
+    write_register(probe.buses.i2c(), address, register, value).map_err(bus_error)
+}
+
+fn dump(probe: &mut Probe, args: &mut Args) -> Result<(), CommandError> {
+    let dump = parse_dump(args)?;
+    let mut values = [0; MAX_DUMP];
+    let values = &mut values[..dump.count];
+    read_registers(probe.buses.i2c(), dump.address, dump.first, values).map_err(bus_error)?;
+    write_dump(&mut probe.serial, dump.first, values).unwrap();
+    Ok(())
+}
+
+fn help(probe: &mut Probe, args: &mut Args) -> Result<(), CommandError> {
+    args.end()?;
+    write_help(COMMANDS, &mut probe.serial).unwrap();
+    Ok(())
+}
+
+#[entry]
+fn main() -> ! {
+    rtt_init_print!();
+    let board = microbit::Board::take().unwrap();
+
+    let serial = uarte::Uarte::new(
+        board.UARTE0,
+        board.uart.into(),
+        Parity::EXCLUDED,
+        Baudrate::BAUD115200,
+    );
+    let i2c = Twim::new(board.TWIM0, board.i2c_internal.into(), FREQUENCY_A::K100);
+
+    let mut probe = Probe {
+        serial: UartePort::new(serial),
+        buses: Buses {
+            i2c: Some(i2c),
+            bus: Bus::Internal,
+            other: Some(board.i2c_external.into()),
+        },
+    };
+    let mut editor: Editor<32, 8> = Editor::new("> ");
+    editor.prompt(&mut probe.serial).unwrap();
+    rprintln!("ready");
+
+    loop {
+        let byte = probe.serial.read().unwrap();
+        let names = COMMANDS.iter().map(|c| c.name);
+        if let Some(line) = editor.feed(byte, names, &mut probe.serial).unwrap() {
+            if let Err(e) = dispatch(COMMANDS, line, &mut probe) {
+                write!(probe.serial, "error: {}\r\n", e).unwrap();
+            }
+            editor.prompt(&mut probe.serial).unwrap();
+        }
+    }
+}
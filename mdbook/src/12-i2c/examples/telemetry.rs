@@ -0,0 +1,165 @@
+//! Stream the sensors over the serial port as binary telemetry.
+//!
+//! Sends the accelerometer at 400 Hz, the magnetometer at 50 Hz, the microphone's loudness ten
+//! times a second and every press and release of the buttons, as packets from the book's
+//! `telemetry` crate. It isn't meant for a terminal: decode it on your computer with
+//! `telemetry-csv` (in `src/telemetry/host`). If the serial port falls behind, packets are
+//! dropped rather than delayed, and `telemetry-csv` counts the gaps.
+
+#![no_main]
+#![no_std]
+
+use cortex_m_rt::entry;
+use embedded_hal::digital::InputPin;
+use panic_rtt_target as _;
+use rtt_target::{rprintln, rtt_init_print};
+
+use microbit::{
+    hal::{
+        gpio,
+        saadc::{Gain, Oversample, Reference, Resistor, Resolution, Saadc, SaadcConfig, Time},
+        twim,
+        uarte::{self, Baudrate, Parity},
+        Timer,
+    },
+    pac::{self, interrupt, twim0::frequency::FREQUENCY_A, UARTE0},
+};
+
+use lsm303agr::{AccelMode, AccelOutputDataRate, Lsm303agr, MagMode, MagOutputDataRate};
+use sensors::{Accelerometer, Magnetometer};
+use serial_setup::BufferedUarte;
+use telemetry::{Button, Encoder, Frame, Message};
+
+/// How often the microphone's loudness is sent, in ms.
+const MIC_PERIOD_MS: u32 = 100;
+
+static SERIAL: BufferedUarte<UARTE0, 16, 512> = BufferedUarte::new();
+
+#[pac::interrupt]
+fn UARTE0_UART0() {
+    SERIAL.on_interrupt();
+}
+
+/// Queue `frame` whole, or not at all.
+fn send(frame: &Frame) -> bool {
+    let bytes = frame.as_bytes();
+    if SERIAL.tx_room() < bytes.len() {
+        return false;
+    }
+    for &b in bytes {
+        SERIAL.try_write(b);
+    }
+    true
+}
+
+fn to_i16(value: i32) -> i16 {
+    value.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+}
+
+#[entry]
+fn main() -> ! {
+    rtt_init_print!();
+    let board = microbit::Board::take().unwrap();
+
+    let serial = uarte::Uarte::new(
+        board.UARTE0,
+        board.uart.into(),
+        Parity::EXCLUDED,
+        Baudrate::BAUD115200,
+    );
+    SERIAL.start(serial);
+
+    // 400 readings a second don't leave time for the slower bus speed.
+    let i2c = { twim::Twim::new(board.TWIM0, board.i2c_internal.into(), FREQUENCY_A::K400) };
+    let mut timer0 = Timer::new(board.TIMER0);
+    // Counts microseconds, for the packets' timestamps. Starts again from 0 after 71 minutes.
+    let mut clock = Timer::periodic(board.TIMER1);
+    clock.start(u32::MAX);
+
+    let mut sensor = Lsm303agr::new_with_i2c(i2c);
+    sensor.init().unwrap();
+    sensor
+        .set_accel_mode_and_odr(&mut timer0, AccelMode::Normal, AccelOutputDataRate::Hz400)
+        .unwrap();
+    sensor
+        .set_mag_mode_and_odr(
+            &mut timer0,
+            MagMode::HighResolution,
+            MagOutputDataRate::Hz50,
+        )
+        .unwrap();
+    let mut sensor = sensor.into_mag_continuous().ok().unwrap();
+
+    let saadc_config = SaadcConfig {
+        resolution: Resolution::_12BIT,
+        oversample: Oversample::BYPASS,
+        reference: Reference::VDD1_4,
+        gain: Gain::GAIN1_2,
+        resistor: Resistor::BYPASS,
+        time: Time::_10US,
+    };
+    let mut saadc = Saadc::new(board.ADC, saadc_config);
+    let _mic_run = board
+        .microphone_pins
+        .mic_run
+        .into_push_pull_output(gpio::Level::High);
+    let mut mic_in = board.microphone_pins.mic_in.into_floating_input();
+
+    let mut buttons = [
+        (Button::A, board.buttons.button_a.degrade(), false),
+        (Button::B, board.buttons.button_b.degrade(), false),
+    ];
+
+    let mut encoder = Encoder::new();
+    let mut dropped: u32 = 0;
+    let (mut mic_min, mut mic_max) = (i16::MAX, i16::MIN);
+    let mut mic_sent_ms = 0;
+    rprintln!("streaming telemetry");
+
+    loop {
+        let t_ms = clock.read() / 1000;
+        let mut messages: [Option<Message>; 5] = [None; 5];
+
+        if sensor.accel_ready().unwrap() {
+            let (x, y, z) = sensor.read_accel().unwrap();
+            messages[0] = Some(Message::Accel {
+                x: to_i16(x),
+                y: to_i16(y),
+                z: to_i16(z),
+            });
+        }
+        if sensor.mag_ready().unwrap() {
+            let (x, y, z) = sensor.read_mag().unwrap();
+            messages[1] = Some(Message::Mag { x, y, z });
+        }
+
+        let sample = saadc.read_channel(&mut mic_in).unwrap_or(0);
+        mic_min = mic_min.min(sample);
+        mic_max = mic_max.max(sample);
+        if t_ms.wrapping_sub(mic_sent_ms) >= MIC_PERIOD_MS {
+            messages[2] = Some(Message::MicLevel(mic_max.abs_diff(mic_min)));
+            (mic_min, mic_max) = (i16::MAX, i16::MIN);
+            mic_sent_ms = t_ms;
+        }
+
+        for (i, (button, pin, was_pressed)) in buttons.iter_mut().enumerate() {
+            let pressed = pin.is_low().unwrap();
+            if pressed != *was_pressed {
+                *was_pressed = pressed;
+                messages[3 + i] = Some(Message::Button {
+                    button: *button,
+                    pressed,
+                });
+            }
+        }
+
+        for message in messages.iter().flatten() {
+            if !send(&encoder.encode(t_ms, message)) {
+                dropped += 1;
+                if dropped.is_power_of_two() {
+                    rprintln!("{} packets dropped", dropped);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,432 @@
+#![no_std]
+
+//! The sensor console's command words, output formats and sensor reading, kept apart from the
+//! hardware so they can be checked anywhere. [`probe`] does the same for the `probe` example's
+//! bus scanning and register reading.
+
+use core::{fmt, str::FromStr};
+
+use embedded_hal::delay::DelayNs;
+use lsm303agr::{AccelMode, AccelOutputDataRate, AccelScale, MagMode, MagOutputDataRate};
+use sensors::{accel_cal::AccelCalibration, Accelerometer, Magnetometer};
+use shell::{Args, CommandError};
+
+pub mod probe;
+
+/// Fastest `stream` rate, in Hz. Much faster and the serial port can't keep up.
+pub const MAX_STREAM_HZ: u16 = 100;
+
+/// An x, y, z reading, in mg or nT.
+pub type Xyz = (i32, i32, i32);
+
+/// Which of the LSM303AGR's two sensors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sensor {
+    Acc,
+    Mag,
+}
+
+impl Sensor {
+    pub fn name(self) -> &'static str {
+        match self {
+            Sensor::Acc => "acc",
+            Sensor::Mag => "mag",
+        }
+    }
+}
+
+impl FromStr for Sensor {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "acc" => Ok(Sensor::Acc),
+            "mag" => Ok(Sensor::Mag),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How readings are written out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// For reading on a terminal.
+    Human,
+    /// Comma-separated, after a header line, for a spreadsheet.
+    Csv,
+    /// One JSON object to a line.
+    Json,
+}
+
+impl Format {
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Human => "human",
+            Format::Csv => "csv",
+            Format::Json => "json",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "human" => Ok(Format::Human),
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+/// What readings are written out in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    /// Thousandths of standard gravity, as the accelerometer reports.
+    MilliG,
+    MetresPerSecondSquared,
+    /// As the magnetometer reports.
+    Nanotesla,
+    Microtesla,
+}
+
+/// Standard gravity, 9.80665 m/s², in hundred-thousandths: 1 mg is this many hundredths of a
+/// µm/s².
+const STANDARD_GRAVITY: i64 = 980_665;
+
+impl Unit {
+    /// The sensor whose readings this unit is for.
+    pub fn sensor(self) -> Sensor {
+        match self {
+            Unit::MilliG | Unit::MetresPerSecondSquared => Sensor::Acc,
+            Unit::Nanotesla | Unit::Microtesla => Sensor::Mag,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Unit::MilliG => "mg",
+            Unit::MetresPerSecondSquared => "m/s2",
+            Unit::Nanotesla => "nT",
+            Unit::Microtesla => "uT",
+        }
+    }
+
+    /// Write `value`, a reading in mg or nT, in this unit.
+    fn write_value<W: fmt::Write>(self, out: &mut W, value: i32) -> fmt::Result {
+        match self {
+            Unit::MilliG | Unit::Nanotesla => write!(out, "{}", value),
+            Unit::MetresPerSecondSquared => {
+                // Rounded to the nearest mm/s².
+                let scaled = value as i64 * STANDARD_GRAVITY;
+                write_thousandths(out, (scaled + scaled.signum() * 50_000) / 100_000)
+            }
+            Unit::Microtesla => write_thousandths(out, value as i64),
+        }
+    }
+}
+
+impl FromStr for Unit {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "mg" => Ok(Unit::MilliG),
+            "m/s2" | "m/s²" => Ok(Unit::MetresPerSecondSquared),
+            "nT" | "nt" => Ok(Unit::Nanotesla),
+            "uT" | "ut" | "µT" => Ok(Unit::Microtesla),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Write `value` thousandths with three decimal places, as in `-0.042`.
+fn write_thousandths<W: fmt::Write>(out: &mut W, value: i64) -> fmt::Result {
+    let sign = if value < 0 { "-" } else { "" };
+    let value = value.unsigned_abs();
+    write!(out, "{}{}.{:03}", sign, value / 1000, value % 1000)
+}
+
+/// How the console writes readings out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Output {
+    pub format: Format,
+    pub acc_unit: Unit,
+    pub mag_unit: Unit,
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Self {
+            format: Format::Human,
+            acc_unit: Unit::MilliG,
+            mag_unit: Unit::Nanotesla,
+        }
+    }
+}
+
+/// One reading to write out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reading {
+    pub sensor: Sensor,
+    /// Milliseconds since the stream started; `None` for a single reading.
+    pub t_ms: Option<u32>,
+    pub xyz: Xyz,
+}
+
+impl Output {
+    pub fn unit(&self, sensor: Sensor) -> Unit {
+        match sensor {
+            Sensor::Acc => self.acc_unit,
+            Sensor::Mag => self.mag_unit,
+        }
+    }
+
+    /// Use `unit` for the readings of the sensor it belongs to.
+    pub fn set_unit(&mut self, unit: Unit) {
+        match unit.sensor() {
+            Sensor::Acc => self.acc_unit = unit,
+            Sensor::Mag => self.mag_unit = unit,
+        }
+    }
+
+    /// Write the line that goes before a run of readings, if the format has one.
+    pub fn write_header<W: fmt::Write>(&self, out: &mut W) -> fmt::Result {
+        match self.format {
+            Format::Csv => write!(out, "t_ms,sensor,x,y,z,unit\r\n"),
+            Format::Human | Format::Json => Ok(()),
+        }
+    }
+
+    /// Write `reading` as a line.
+    pub fn write_reading<W: fmt::Write>(&self, out: &mut W, reading: &Reading) -> fmt::Result {
+        let unit = self.unit(reading.sensor);
+        let (x, y, z) = reading.xyz;
+        match self.format {
+            Format::Human => {
+                if let Some(t) = reading.t_ms {
+                    write!(out, "{:>8} ms  ", t)?;
+                }
+                let name = match reading.sensor {
+                    Sensor::Acc => "Accelerometer",
+                    Sensor::Mag => "Magnetometer",
+                };
+                write!(out, "{}: x ", name)?;
+                unit.write_value(out, x)?;
+                write!(out, " y ")?;
+                unit.write_value(out, y)?;
+                write!(out, " z ")?;
+                unit.write_value(out, z)?;
+                write!(out, " {}", unit.symbol())?;
+            }
+            Format::Csv => {
+                if let Some(t) = reading.t_ms {
+                    write!(out, "{}", t)?;
+                }
+                write!(out, ",{},", reading.sensor.name())?;
+                for value in [x, y, z] {
+                    unit.write_value(out, value)?;
+                    write!(out, ",")?;
+                }
+                write!(out, "{}", unit.symbol())?;
+            }
+            Format::Json => {
+                write!(out, "{{")?;
+                if let Some(t) = reading.t_ms {
+                    write!(out, "\"t_ms\":{},", t)?;
+                }
+                write!(out, "\"sensor\":\"{}\"", reading.sensor.name())?;
+                for (axis, value) in [("x", x), ("y", y), ("z", z)] {
+                    write!(out, ",\"{}\":", axis)?;
+                    unit.write_value(out, value)?;
+                }
+                write!(out, ",\"unit\":\"{}\"}}", unit.symbol())?;
+            }
+        }
+        write!(out, "\r\n")
+    }
+}
+
+/// Wait for the next reading from one of `sensors`, stamped with `t_ms`.
+///
+/// This goes through the `sensors` traits, so it reads the LSM303AGR on the board and a
+/// [`sensors::mock::Script`] just the same.
+pub fn take_reading<S, D>(
+    sensors: &mut S,
+    delay: &mut D,
+    sensor: Sensor,
+    t_ms: Option<u32>,
+) -> Result<Reading, S::Error>
+where
+    S: Accelerometer + Magnetometer,
+    D: DelayNs,
+{
+    let xyz = match sensor {
+        Sensor::Acc => sensors.wait_accel(delay)?,
+        Sensor::Mag => sensors.wait_mag(delay)?,
+    };
+    Ok(Reading { sensor, t_ms, xyz })
+}
+
+/// What `stream` was asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stream {
+    pub sensor: Sensor,
+    pub hz: u16,
+}
+
+impl Stream {
+    /// Microseconds between readings.
+    pub fn period_us(&self) -> u32 {
+        1_000_000 / self.hz as u32
+    }
+}
+
+/// Parse `stream <acc|mag> [hz]`'s arguments. The rate defaults to 10 Hz.
+pub fn parse_stream(args: &mut Args) -> Result<Stream, CommandError> {
+    let sensor = args.parse("<acc|mag>")?;
+    let hz = args.parse_optional("[hz]")?.unwrap_or(10);
+    args.end()?;
+    if !(1..=MAX_STREAM_HZ).contains(&hz) {
+        return Err(CommandError::Invalid("[hz]"));
+    }
+    Ok(Stream { sensor, hz })
+}
+
+/// A new output data rate for one of the sensors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Odr {
+    Acc(AccelOutputDataRate),
+    Mag(MagOutputDataRate),
+}
+
+/// Parse `odr <acc|mag> <hz>`'s arguments.
+///
+/// The accelerometer runs at 1, 10, 25, 50, 100, 200 or 400 Hz, and the magnetometer at 10, 20,
+/// 50 or 100 Hz. The accelerometer's faster rates depend on its mode, so aren't offered.
+pub fn parse_odr(args: &mut Args) -> Result<Odr, CommandError> {
+    let sensor = args.parse("<acc|mag>")?;
+    let hz: u16 = args.parse("<hz>")?;
+    args.end()?;
+    let odr = match sensor {
+        Sensor::Acc => match AccelOutputDataRate::from_hertz(hz) {
+            Some(odr) if hz <= 400 => Some(Odr::Acc(odr)),
+            _ => None,
+        },
+        Sensor::Mag => MagOutputDataRate::from_hertz(hz).map(Odr::Mag),
+    };
+    odr.ok_or(CommandError::Invalid("<hz>"))
+}
+
+/// How many readings a second `odr` gives.
+pub fn odr_hz(odr: Odr) -> u16 {
+    match odr {
+        Odr::Acc(odr) => match odr {
+            AccelOutputDataRate::Hz1 => 1,
+            AccelOutputDataRate::Hz10 => 10,
+            AccelOutputDataRate::Hz25 => 25,
+            AccelOutputDataRate::Hz50 => 50,
+            AccelOutputDataRate::Hz100 => 100,
+            AccelOutputDataRate::Hz200 => 200,
+            AccelOutputDataRate::Hz400 => 400,
+            AccelOutputDataRate::Khz1_344 => 1344,
+            AccelOutputDataRate::Khz1_620LowPower => 1620,
+            AccelOutputDataRate::Khz5_376LowPower => 5376,
+        },
+        Odr::Mag(odr) => match odr {
+            MagOutputDataRate::Hz10 => 10,
+            MagOutputDataRate::Hz20 => 20,
+            MagOutputDataRate::Hz50 => 50,
+            MagOutputDataRate::Hz100 => 100,
+        },
+    }
+}
+
+/// Parse `scale <g>`'s argument: the accelerometer's full scale, 2, 4, 8 or 16 g, with or
+/// without the `g`.
+pub fn parse_scale(args: &mut Args) -> Result<AccelScale, CommandError> {
+    let word = args.next_arg("<g>")?;
+    args.end()?;
+    match word.strip_suffix('g').unwrap_or(word) {
+        "2" => Ok(AccelScale::G2),
+        "4" => Ok(AccelScale::G4),
+        "8" => Ok(AccelScale::G8),
+        "16" => Ok(AccelScale::G16),
+        _ => Err(CommandError::Invalid("<g>")),
+    }
+}
+
+/// The accelerometer's full scale, in g.
+pub fn scale_g(scale: AccelScale) -> u8 {
+    match scale {
+        AccelScale::G2 => 2,
+        AccelScale::G4 => 4,
+        AccelScale::G8 => 8,
+        AccelScale::G16 => 16,
+    }
+}
+
+/// A new mode for one of the sensors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    Acc(AccelMode),
+    Mag(MagMode),
+}
+
+/// Parse `mode <acc|mag> <mode>`'s arguments. Both sensors take `low-power` and `high-res`;
+/// the accelerometer also takes `normal`.
+pub fn parse_mode(args: &mut Args) -> Result<Mode, CommandError> {
+    let sensor = args.parse("<acc|mag>")?;
+    let word = args.next_arg("<mode>")?;
+    args.end()?;
+    let mode = match (sensor, word) {
+        (Sensor::Acc, "low-power") => Mode::Acc(AccelMode::LowPower),
+        (Sensor::Acc, "normal") => Mode::Acc(AccelMode::Normal),
+        (Sensor::Acc, "high-res") => Mode::Acc(AccelMode::HighResolution),
+        (Sensor::Mag, "low-power") => Mode::Mag(MagMode::LowPower),
+        (Sensor::Mag, "high-res") => Mode::Mag(MagMode::HighResolution),
+        _ => return Err(CommandError::Invalid("<mode>")),
+    };
+    Ok(mode)
+}
+
+/// Parse `accel-cal [hex]`'s argument: an accelerometer calibration as `companion calibrate`
+/// prints it, two hex digits to a byte, or nothing to show the saved one.
+pub fn parse_calibration(args: &mut Args) -> Result<Option<AccelCalibration>, CommandError> {
+    let Some(word) = args.next() else {
+        return Ok(None);
+    };
+    args.end()?;
+    const INVALID: CommandError = CommandError::Invalid("[hex]");
+    let mut bytes = [0; AccelCalibration::BYTES];
+    if word.len() != 2 * bytes.len() || !word.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(INVALID);
+    }
+    for (byte, pair) in bytes.iter_mut().zip(word.as_bytes().chunks_exact(2)) {
+        let pair = core::str::from_utf8(pair).map_err(|_| INVALID)?;
+        *byte = u8::from_str_radix(pair, 16).map_err(|_| INVALID)?;
+    }
+    AccelCalibration::from_bytes(&bytes)
+        .map(Some)
+        .ok_or(INVALID)
+}
+
+/// A mode's name, as [`parse_mode`] takes it.
+pub fn accel_mode_name(mode: AccelMode) -> &'static str {
+    match mode {
+        AccelMode::PowerDown => "power-down",
+        AccelMode::LowPower => "low-power",
+        AccelMode::Normal => "normal",
+        AccelMode::HighResolution => "high-res",
+    }
+}
+
+/// A mode's name, as [`parse_mode`] takes it.
+pub fn mag_mode_name(mode: MagMode) -> &'static str {
+    match mode {
+        MagMode::LowPower => "low-power",
+        MagMode::HighResolution => "high-res",
+    }
+}
@@ -1,95 +1,378 @@
 #![no_main]
 #![no_std]
 
-use core::str;
-
 use cortex_m_rt::entry;
-use embedded_hal::delay::DelayNs;
 use panic_rtt_target as _;
 use rtt_target::{rtt_init_print, rprintln};
 
 use microbit::{
-    hal::uarte::{self, Baudrate, Parity},
-    hal::{twim, Timer},
-    pac::twim0::frequency::FREQUENCY_A,
+    hal::uarte,
+    hal::{nvmc::Nvmc, timer::Periodic, twim, Timer},
+    pac::{self, interrupt, twim0::frequency::FREQUENCY_A, NVMC, TIMER0, TIMER1, TWIM0, UARTE0},
 };
 
 use core::fmt::Write;
-use heapless::Vec;
-use lsm303agr::{AccelMode, AccelOutputDataRate, Lsm303agr, MagMode, MagOutputDataRate};
+use lsm303agr::{
+    interface::I2cInterface, mode::MagContinuous, AccelMode, AccelOutputDataRate, Lsm303agr,
+    MagMode, MagOutputDataRate,
+};
+
+use i2c::{
+    accel_mode_name, mag_mode_name, odr_hz, parse_calibration, parse_mode, parse_odr,
+    parse_scale, parse_stream, scale_g, take_reading, Mode, Odr, Output, Reading, Sensor, Unit,
+};
+use sensors::accel_cal::AccelCalibration;
+use serial_setup::{parse_line, BufferedUarte, LineSettings, UartePort};
+use settings::{keys, Settings};
+use shell::{dispatch, write_help, Args, Command, CommandError, Editor};
+
+/// The serial port, buffered so that typing ahead while the sensor is being read loses nothing.
+static SERIAL: BufferedUarte<UARTE0, 64, 256> = BufferedUarte::new();
+
+#[pac::interrupt]
+fn UARTE0_UART0() {
+    SERIAL.on_interrupt();
+}
+
+const SENSOR_FAILED: CommandError = CommandError::Failed("sensor not responding");
+
+/// Everything the commands use.
+struct Console {
+    serial: UartePort<UARTE0>,
+    sensor: Lsm303agr<I2cInterface<twim::Twim<TWIM0>>, MagContinuous>,
+    timer: Timer<TIMER0>,
+    /// Paces `stream`.
+    clock: Timer<TIMER1, Periodic>,
+    output: Output,
+    /// The driver doesn't say what rates the sensors are running at, so they are kept here.
+    acc_odr: AccelOutputDataRate,
+    mag_odr: MagOutputDataRate,
+    settings: Settings<Nvmc<NVMC>>,
+    line: LineSettings,
+}
+
+impl Console {
+    fn read(&mut self, sensor: Sensor, t_ms: Option<u32>) -> Result<Reading, CommandError> {
+        take_reading(&mut self.sensor, &mut self.timer, sensor, t_ms).map_err(|_| SENSOR_FAILED)
+    }
 
-use serial_setup::UartePort;
+    fn odr(&self, sensor: Sensor) -> Odr {
+        match sensor {
+            Sensor::Acc => Odr::Acc(self.acc_odr),
+            Sensor::Mag => Odr::Mag(self.mag_odr),
+        }
+    }
+}
+
+const COMMANDS: &[Command<Console>] = &[
+    Command {
+        name: "acc",
+        usage: "",
+        help: "read the accelerometer",
+        run: acc,
+    },
+    Command {
+        name: "mag",
+        usage: "",
+        help: "read the magnetometer",
+        run: mag,
+    },
+    Command {
+        name: "stream",
+        usage: "<acc|mag> [hz]",
+        help: "read a sensor over and over, until a key is pressed",
+        run: stream,
+    },
+    Command {
+        name: "format",
+        usage: "<human|csv|json>",
+        help: "how to write readings",
+        run: format,
+    },
+    Command {
+        name: "unit",
+        usage: "<mg|m/s2|nT|uT>",
+        help: "what to write readings in",
+        run: unit,
+    },
+    Command {
+        name: "odr",
+        usage: "<acc|mag> <hz>",
+        help: "set a sensor's output data rate",
+        run: odr,
+    },
+    Command {
+        name: "scale",
+        usage: "<2|4|8|16>",
+        help: "set the accelerometer's full scale, in g",
+        run: scale,
+    },
+    Command {
+        name: "mode",
+        usage: "<acc|mag> <low-power|normal|high-res>",
+        help: "set a sensor's power mode",
+        run: mode,
+    },
+    Command {
+        name: "status",
+        usage: "",
+        help: "show the sensors' settings and the output format",
+        run: status,
+    },
+    Command {
+        name: "line",
+        usage: "[baud] [none|even]",
+        help: "show or change the serial port's baud rate and parity, kept across resets",
+        run: line,
+    },
+    Command {
+        name: "accel-cal",
+        usage: "[hex]",
+        help: "show or save the accelerometer calibration from `companion calibrate`",
+        run: accel_cal,
+    },
+    Command {
+        name: "help",
+        usage: "",
+        help: "list the commands",
+        run: help,
+    },
+];
+
+fn read_once(console: &mut Console, sensor: Sensor) -> Result<(), CommandError> {
+    let reading = console.read(sensor, None)?;
+    console
+        .output
+        .write_reading(&mut console.serial, &reading)
+        .unwrap();
+    Ok(())
+}
+
+fn acc(console: &mut Console, args: &mut Args) -> Result<(), CommandError> {
+    args.end()?;
+    read_once(console, Sensor::Acc)
+}
+
+fn mag(console: &mut Console, args: &mut Args) -> Result<(), CommandError> {
+    args.end()?;
+    read_once(console, Sensor::Mag)
+}
+
+fn stream(console: &mut Console, args: &mut Args) -> Result<(), CommandError> {
+    let stream = parse_stream(args)?;
+    if stream.hz > odr_hz(console.odr(stream.sensor)) {
+        return Err(CommandError::Failed(
+            "faster than the sensor's output data rate, see `odr`",
+        ));
+    }
+    console.output.write_header(&mut console.serial).unwrap();
+    console.clock.start(stream.period_us());
+    // Kept in µs, as wide as it needs to be: a u32 of them runs out after 71 minutes.
+    let mut elapsed_us: u64 = 0;
+    loop {
+        let t_ms = (elapsed_us / 1000) as u32;
+        let reading = console.read(stream.sensor, Some(t_ms))?;
+        console
+            .output
+            .write_reading(&mut console.serial, &reading)
+            .unwrap();
+        elapsed_us += u64::from(stream.period_us());
+
+        // Watch for a key until the next reading is due.
+        while !console.clock.reset_if_finished() {
+            if console.serial.try_read().unwrap().is_some() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn format(console: &mut Console, args: &mut Args) -> Result<(), CommandError> {
+    console.output.format = args.parse("<human|csv|json>")?;
+    args.end()
+}
+
+fn unit(console: &mut Console, args: &mut Args) -> Result<(), CommandError> {
+    let unit: Unit = args.parse("<unit>")?;
+    args.end()?;
+    console.output.set_unit(unit);
+    Ok(())
+}
+
+fn odr(console: &mut Console, args: &mut Args) -> Result<(), CommandError> {
+    match parse_odr(args)? {
+        Odr::Acc(odr) => {
+            let mode = console.sensor.get_accel_mode();
+            console
+                .sensor
+                .set_accel_mode_and_odr(&mut console.timer, mode, odr)
+                .map_err(|_| SENSOR_FAILED)?;
+            console.acc_odr = odr;
+        }
+        Odr::Mag(odr) => {
+            let mode = console.sensor.get_mag_mode();
+            console
+                .sensor
+                .set_mag_mode_and_odr(&mut console.timer, mode, odr)
+                .map_err(|_| SENSOR_FAILED)?;
+            console.mag_odr = odr;
+        }
+    }
+    Ok(())
+}
+
+fn scale(console: &mut Console, args: &mut Args) -> Result<(), CommandError> {
+    let scale = parse_scale(args)?;
+    console
+        .sensor
+        .set_accel_scale(scale)
+        .map_err(|_| SENSOR_FAILED)
+}
+
+fn mode(console: &mut Console, args: &mut Args) -> Result<(), CommandError> {
+    match parse_mode(args)? {
+        Mode::Acc(mode) => console
+            .sensor
+            .set_accel_mode_and_odr(&mut console.timer, mode, console.acc_odr)
+            .map_err(|_| SENSOR_FAILED),
+        Mode::Mag(mode) => console
+            .sensor
+            .set_mag_mode_and_odr(&mut console.timer, mode, console.mag_odr)
+            .map_err(|_| SENSOR_FAILED),
+    }
+}
+
+fn status(console: &mut Console, args: &mut Args) -> Result<(), CommandError> {
+    args.end()?;
+    let acc_mode = console.sensor.get_accel_mode();
+    let scale = console.sensor.get_accel_scale();
+    let mag_mode = console.sensor.get_mag_mode();
+    write!(
+        console.serial,
+        "acc: {} Hz, {}, +/-{} g, in {}\r\nmag: {} Hz, {}, in {}\r\nformat: {}\r\n",
+        odr_hz(Odr::Acc(console.acc_odr)),
+        accel_mode_name(acc_mode),
+        scale_g(scale),
+        console.output.acc_unit.symbol(),
+        odr_hz(Odr::Mag(console.mag_odr)),
+        mag_mode_name(mag_mode),
+        console.output.mag_unit.symbol(),
+        console.output.format.name(),
+    )
+    .unwrap();
+    Ok(())
+}
+
+fn line(console: &mut Console, args: &mut Args) -> Result<(), CommandError> {
+    let Some(line) = parse_line(args, console.line)? else {
+        write!(console.serial, "{}\r\n", console.line).unwrap();
+        return Ok(());
+    };
+    if !console.serial.switch_line(&console.line, &line).unwrap() {
+        return Err(CommandError::Failed("not confirmed, so not saved"));
+    }
+    console.line = line;
+    console
+        .settings
+        .write(keys::SERIAL_LINE, &line.to_bytes())
+        .map_err(|_| CommandError::Failed("couldn't save the setting"))
+}
+
+fn accel_cal(console: &mut Console, args: &mut Args) -> Result<(), CommandError> {
+    let bytes = match parse_calibration(args)? {
+        Some(calibration) => {
+            let bytes = calibration.to_bytes();
+            console
+                .settings
+                .write(keys::ACCEL_CALIBRATION, &bytes)
+                .map_err(|_| CommandError::Failed("couldn't save the setting"))?;
+            bytes
+        }
+        None => {
+            let mut bytes = [0; AccelCalibration::BYTES];
+            match console.settings.read(keys::ACCEL_CALIBRATION, &mut bytes) {
+                Ok(Some(len)) if len == bytes.len() => bytes,
+                _ => {
+                    write!(console.serial, "none saved\r\n").unwrap();
+                    return Ok(());
+                }
+            }
+        }
+    };
+    for byte in bytes {
+        write!(console.serial, "{:02x}", byte).unwrap();
+    }
+    write!(console.serial, "\r\n").unwrap();
+    Ok(())
+}
+
+fn help(console: &mut Console, args: &mut Args) -> Result<(), CommandError> {
+    args.end()?;
+    write_help(COMMANDS, &mut console.serial).unwrap();
+    Ok(())
+}
 
 #[entry]
 fn main() -> ! {
     rtt_init_print!();
     let board = microbit::Board::take().unwrap();
 
+    // 115200 baud without parity, unless `line` has saved something else.
+    let mut settings = Settings::new(settings::flash(board.NVMC), 0);
+    let mut stored = [0; 5];
+    let line = settings
+        .read(keys::SERIAL_LINE, &mut stored)
+        .unwrap()
+        .and_then(|len| stored.get(..len))
+        .and_then(LineSettings::from_bytes)
+        .unwrap_or_default();
     let serial = uarte::Uarte::new(
         board.UARTE0,
         board.uart.into(),
-        Parity::EXCLUDED,
-        Baudrate::BAUD115200,
+        line.hal_parity(),
+        line.baudrate(),
     );
-    let mut serial = UartePort::new(serial);
+    let serial = SERIAL.start(serial);
 
     let i2c = { twim::Twim::new(board.TWIM0, board.i2c_internal.into(), FREQUENCY_A::K100) };
     let mut timer0 = Timer::new(board.TIMER0);
 
+    let acc_odr = AccelOutputDataRate::Hz50;
+    let mag_odr = MagOutputDataRate::Hz50;
     let mut sensor = Lsm303agr::new_with_i2c(i2c);
     sensor.init().unwrap();
     sensor
-        .set_accel_mode_and_odr(
-            &mut timer0,
-            AccelMode::HighResolution,
-            AccelOutputDataRate::Hz50,
-        )
+        .set_accel_mode_and_odr(&mut timer0, AccelMode::HighResolution, acc_odr)
         .unwrap();
     sensor
-        .set_mag_mode_and_odr(
-            &mut timer0,
-            MagMode::HighResolution,
-            MagOutputDataRate::Hz50,
-        )
+        .set_mag_mode_and_odr(&mut timer0, MagMode::HighResolution, mag_odr)
         .unwrap();
-    let mut sensor = sensor.into_mag_continuous().ok().unwrap();
-    let mut buffer: Vec<u8, 32> = Vec::new();
+    let sensor = sensor.into_mag_continuous().ok().unwrap();
     rprintln!("setup complete");
 
-    loop {
-        buffer.clear();
-
-        loop {
-            let byte = serial.read().unwrap();
-            serial.write(byte).unwrap();
+    let mut console = Console {
+        serial,
+        sensor,
+        timer: timer0,
+        clock: Timer::periodic(board.TIMER1),
+        output: Output::default(),
+        acc_odr,
+        mag_odr,
+        settings,
+        line,
+    };
+    // Long enough for `accel-cal` and its 48 hex digits.
+    let mut editor: Editor<64, 8> = Editor::new("> ");
+    editor.prompt(&mut console.serial).unwrap();
 
-            if byte == b'\r' {
-                serial.write(b'\n').unwrap();
-                break;
-            }
-
-            if buffer.push(byte).is_err() {
-                write!(serial, "error: buffer full\r\n").unwrap();
-                break;
-            }
-        }
-
-        if str::from_utf8(&buffer).unwrap().trim() == "acc" {
-            while !sensor.accel_status().unwrap().xyz_new_data() {
-                timer0.delay_ms(1u32);
-            }
-
-            let (x, y, z) = sensor.acceleration().unwrap().xyz_mg();
-            write!(serial, "Accelerometer: x {} y {} z {}\r\n", x, y, z).unwrap();
-        } else if str::from_utf8(&buffer).unwrap().trim() == "mag" {
-            while !sensor.mag_status().unwrap().xyz_new_data() {
-                timer0.delay_ms(1u32);
+    loop {
+        let byte = console.serial.read().unwrap();
+        let names = COMMANDS.iter().map(|c| c.name);
+        if let Some(line) = editor.feed(byte, names, &mut console.serial).unwrap() {
+            if let Err(e) = dispatch(COMMANDS, line, &mut console) {
+                write!(console.serial, "error: {}\r\n", e).unwrap();
             }
-
-            let (x, y, z) = sensor.magnetic_field().unwrap().xyz_nt();
-            write!(serial, "Magnetometer: x {} y {} z {}\r\n", x, y, z).unwrap();
-        } else {
-            write!(serial, "error: command not detected\r\n").unwrap();
+            editor.prompt(&mut console.serial).unwrap();
         }
     }
 }
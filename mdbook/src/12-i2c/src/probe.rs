@@ -0,0 +1,345 @@
+//! Finding out what is on an I2C bus, and reading and writing its chips' registers.
+//!
+//! Everything here goes through `embedded-hal`'s [`I2c`], so the `probe` example runs it over the
+//! MB2's TWIM, and it can be tried out on a desktop machine against a pretend bus.
+
+use core::{fmt, str::FromStr};
+
+use embedded_hal::i2c::{Error, ErrorKind, I2c, NoAcknowledgeSource};
+use shell::{Args, CommandError};
+
+/// The lowest and highest addresses a chip can have; the rest are reserved.
+pub const FIRST_ADDRESS: u8 = 0x08;
+pub const LAST_ADDRESS: u8 = 0x77;
+
+/// Most registers `dump` shows at once.
+pub const MAX_DUMP: usize = 256;
+
+/// Which of the MB2's I2C buses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bus {
+    /// The motion sensor and the interface chip, inside the board.
+    Internal,
+    /// Pins 19 (SCL) and 20 (SDA) on the edge connector.
+    External,
+}
+
+impl Bus {
+    pub fn name(self) -> &'static str {
+        match self {
+            Bus::Internal => "internal",
+            Bus::External => "external",
+        }
+    }
+}
+
+impl FromStr for Bus {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "internal" | "int" => Ok(Bus::Internal),
+            "external" | "ext" => Ok(Bus::External),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A byte written in hex, with or without a leading `0x`.
+fn hex(word: &str) -> Option<u8> {
+    let digits = word.strip_prefix("0x").unwrap_or(word);
+    if digits.is_empty() || digits.len() > 2 {
+        return None;
+    }
+    u8::from_str_radix(digits, 16).ok()
+}
+
+fn parse_hex(args: &mut Args, name: &'static str) -> Result<u8, CommandError> {
+    hex(args.next_arg(name)?).ok_or(CommandError::Invalid(name))
+}
+
+fn parse_address(args: &mut Args) -> Result<u8, CommandError> {
+    let address = parse_hex(args, "<addr>")?;
+    if !(FIRST_ADDRESS..=LAST_ADDRESS).contains(&address) {
+        return Err(CommandError::Invalid("<addr>"));
+    }
+    Ok(address)
+}
+
+/// The arguments to `id`: `<addr>`, in hex.
+pub fn parse_id(args: &mut Args) -> Result<u8, CommandError> {
+    let address = parse_address(args)?;
+    args.end()?;
+    Ok(address)
+}
+
+/// The arguments to `read`: `<addr> <reg>`, in hex.
+pub fn parse_read(args: &mut Args) -> Result<(u8, u8), CommandError> {
+    let address = parse_address(args)?;
+    let register = parse_hex(args, "<reg>")?;
+    args.end()?;
+    Ok((address, register))
+}
+
+/// The arguments to `write`: `<addr> <reg> <value>`, in hex.
+pub fn parse_write(args: &mut Args) -> Result<(u8, u8, u8), CommandError> {
+    let address = parse_address(args)?;
+    let register = parse_hex(args, "<reg>")?;
+    let value = parse_hex(args, "<value>")?;
+    args.end()?;
+    Ok((address, register, value))
+}
+
+/// Registers to show with `dump`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dump {
+    pub address: u8,
+    pub first: u8,
+    pub count: usize,
+}
+
+/// The arguments to `dump`: `<addr> [reg] [count]`, the register in hex and the count in decimal.
+/// It starts from register 0 and shows 16 unless told otherwise, stopping at register `ff`.
+pub fn parse_dump(args: &mut Args) -> Result<Dump, CommandError> {
+    let address = parse_address(args)?;
+    let first = match args.next() {
+        Some(word) => hex(word).ok_or(CommandError::Invalid("[reg]"))?,
+        None => 0,
+    };
+    let count = args.parse_optional("[count]")?.unwrap_or(16);
+    if count == 0 || first as usize + count > MAX_DUMP {
+        return Err(CommandError::Invalid("[count]"));
+    }
+    args.end()?;
+    Ok(Dump {
+        address,
+        first,
+        count,
+    })
+}
+
+/// A set of bus addresses.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Addresses(u128);
+
+impl Addresses {
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    pub fn insert(&mut self, address: u8) {
+        self.0 |= 1 << (address & 0x7f);
+    }
+
+    pub fn contains(&self, address: u8) -> bool {
+        address < 0x80 && self.0 & (1 << address) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..0x80).filter(|a| self.contains(*a))
+    }
+}
+
+/// What a scan found.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Scan {
+    /// The addresses a chip answered at.
+    pub found: Addresses,
+    /// The addresses where the bus went wrong, rather than simply going unanswered.
+    pub failed: Addresses,
+}
+
+/// Whether a chip answers at `address`, by reading a byte from it.
+///
+/// Reading is safer than writing: an empty write would do, but not every controller can send
+/// one, and some chips act on any write.
+pub fn probe<I: I2c>(i2c: &mut I, address: u8) -> Result<bool, I::Error> {
+    match i2c.read(address, &mut [0]) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Probe every address a chip can have.
+pub fn scan<I: I2c>(i2c: &mut I) -> Scan {
+    let mut scan = Scan::default();
+    for address in FIRST_ADDRESS..=LAST_ADDRESS {
+        match probe(i2c, address) {
+            Ok(true) => scan.found.insert(address),
+            Ok(false) => {}
+            Err(_) => scan.failed.insert(address),
+        }
+    }
+    scan
+}
+
+/// Write `scan` as a table of addresses, 16 to a row, the way Linux's `i2cdetect` does: the
+/// address where a chip answered, `--` where none did, and `XX` where the bus went wrong.
+pub fn write_scan<W: fmt::Write>(out: &mut W, scan: &Scan) -> fmt::Result {
+    out.write_str("    0  1  2  3  4  5  6  7  8  9  a  b  c  d  e  f\r\n")?;
+    for row in (0..0x80u8).step_by(16) {
+        write!(out, "{:02x}:", row)?;
+        for address in row..row + 16 {
+            if !(FIRST_ADDRESS..=LAST_ADDRESS).contains(&address) {
+                out.write_str("   ")?;
+            } else if scan.found.contains(address) {
+                write!(out, " {:02x}", address)?;
+            } else if scan.failed.contains(address) {
+                out.write_str(" XX")?;
+            } else {
+                out.write_str(" --")?;
+            }
+        }
+        out.write_str("\r\n")?;
+    }
+    Ok(())
+}
+
+/// A chip that can be recognised by the value of an ID register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chip {
+    pub name: &'static str,
+    pub addresses: &'static [u8],
+    pub id_register: u8,
+    pub id: u8,
+}
+
+/// The chips `identify` knows: the MB2's own, and some often wired to the edge connector.
+pub const KNOWN_CHIPS: &[Chip] = &[
+    Chip {
+        name: "LSM303AGR accelerometer",
+        addresses: &[0x19],
+        id_register: 0x0f,
+        id: 0x33,
+    },
+    Chip {
+        name: "LSM303AGR magnetometer",
+        addresses: &[0x1e],
+        id_register: 0x4f,
+        id: 0x40,
+    },
+    Chip {
+        name: "FXOS8700CQ accelerometer and magnetometer",
+        addresses: &[0x1e, 0x1f],
+        id_register: 0x0d,
+        id: 0xc7,
+    },
+    Chip {
+        name: "LIS3DH accelerometer",
+        addresses: &[0x18, 0x19],
+        id_register: 0x0f,
+        id: 0x33,
+    },
+    Chip {
+        name: "BME280 pressure, temperature and humidity sensor",
+        addresses: &[0x76, 0x77],
+        id_register: 0xd0,
+        id: 0x60,
+    },
+    Chip {
+        name: "BMP280 pressure and temperature sensor",
+        addresses: &[0x76, 0x77],
+        id_register: 0xd0,
+        id: 0x58,
+    },
+    Chip {
+        name: "MPU-6050 accelerometer and gyroscope",
+        addresses: &[0x68, 0x69],
+        id_register: 0x75,
+        id: 0x68,
+    },
+    Chip {
+        name: "HTS221 humidity and temperature sensor",
+        addresses: &[0x5f],
+        id_register: 0x0f,
+        id: 0xbc,
+    },
+];
+
+/// The first of [`KNOWN_CHIPS`] that could be at `address` and has the right ID there.
+///
+/// Only the chips' ID registers are read, but two chips can share an address and an ID, so the
+/// answer is a good guess rather than a certainty.
+pub fn identify<I: I2c>(i2c: &mut I, address: u8) -> Result<Option<&'static Chip>, I::Error> {
+    for chip in KNOWN_CHIPS {
+        if !chip.addresses.contains(&address) {
+            continue;
+        }
+        match read_register(i2c, address, chip.id_register) {
+            Ok(id) if id == chip.id => return Ok(Some(chip)),
+            Ok(_) => {}
+            // Not every chip has every register.
+            Err(e) if e.kind() == ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(None)
+}
+
+/// Read one register: write its number, then read a byte back.
+pub fn read_register<I: I2c>(i2c: &mut I, address: u8, register: u8) -> Result<u8, I::Error> {
+    let mut value = [0];
+    i2c.write_read(address, &[register], &mut value)?;
+    Ok(value[0])
+}
+
+pub fn write_register<I: I2c>(
+    i2c: &mut I,
+    address: u8,
+    register: u8,
+    value: u8,
+) -> Result<(), I::Error> {
+    i2c.write(address, &[register, value])
+}
+
+/// Read `values.len()` registers from `first` on, one at a time.
+///
+/// Chips differ in whether, and how, they move on to the next register during a longer read:
+/// the LSM303AGR's accelerometer only does if the top bit of the register number is set. One
+/// register at a time works on all of them.
+pub fn read_registers<I: I2c>(
+    i2c: &mut I,
+    address: u8,
+    first: u8,
+    values: &mut [u8],
+) -> Result<(), I::Error> {
+    for (register, value) in (first..=u8::MAX).zip(values) {
+        *value = read_register(i2c, address, register)?;
+    }
+    Ok(())
+}
+
+/// Write registers `first` on, holding `values`, 16 to a row and lined up on multiples of 16.
+pub fn write_dump<W: fmt::Write>(out: &mut W, first: u8, values: &[u8]) -> fmt::Result {
+    let first = first as usize;
+    let last = first + values.len();
+    for row in (first & !0xf..last).step_by(16) {
+        write!(out, "{:02x}:", row)?;
+        for register in row..row + 16 {
+            match register.checked_sub(first).and_then(|i| values.get(i)) {
+                Some(value) => write!(out, " {:02x}", value)?,
+                None if register < last => out.write_str("   ")?,
+                None => break,
+            }
+        }
+        out.write_str("\r\n")?;
+    }
+    Ok(())
+}
+
+/// What went wrong on the bus, for the console.
+pub fn bus_error<E: Error>(error: E) -> CommandError {
+    CommandError::Failed(match error.kind() {
+        ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address) => "no chip answered",
+        ErrorKind::NoAcknowledge(_) => "the chip refused a byte",
+        ErrorKind::ArbitrationLoss => "another controller took over the bus",
+        ErrorKind::Bus => "bus error",
+        ErrorKind::Overrun => "overrun",
+        _ => "bus failed",
+    })
+}
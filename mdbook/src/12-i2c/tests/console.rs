@@ -0,0 +1,563 @@
+//! Tests the sensor console's command parsing and output formats, and the bus probe's scanning
+//! and register access, from the `i2c` crate.
+//!
+//! ```text
+//! cargo test-host -p i2c
+//! ```
+//!
+//! Each test runs argument lines through one of the console's parsers, readings from a
+//! [`Script`] of sensor readings through [`take_reading`] and an [`Output`], or the probe's
+//! commands over a [`MockBus`] of pretend chips, and compares the result with what the console or
+//! probe should do or print.
+
+use std::{collections::BTreeMap, fmt::Debug};
+
+use embedded_hal::{
+    delay::DelayNs,
+    i2c::{ErrorKind, ErrorType, I2c, NoAcknowledgeSource, Operation},
+};
+use i2c::{
+    odr_hz, parse_calibration, parse_mode, parse_odr, parse_scale, parse_stream,
+    probe::{
+        bus_error, identify, parse_dump, parse_read, parse_write, read_register, read_registers,
+        scan, write_dump, write_register, write_scan, Dump, Scan,
+    },
+    take_reading, Format, Mode, Odr, Output, Reading, Sensor, Stream, Unit,
+};
+use lsm303agr::{AccelMode, AccelOutputDataRate, AccelScale, MagMode, MagOutputDataRate};
+use sensors::{
+    accel_cal::AccelCalibration,
+    mock::{Exhausted, Reading as Scripted, Script},
+};
+use shell::{Args, CommandError};
+
+/// Run each argument line through `parse`, and compare with what it should give.
+fn assert_parses<T: Debug + PartialEq>(
+    parse: fn(&mut Args) -> Result<T, CommandError>,
+    cases: &[(&str, Result<T, CommandError>)],
+) {
+    for (line, expected) in cases {
+        assert_eq!(parse(&mut Args::new(line)), *expected, "{:?}", line);
+    }
+}
+
+#[test]
+fn stream_arguments() {
+    let stream = |sensor, hz| Ok(Stream { sensor, hz });
+    assert_parses(
+        parse_stream,
+        &[
+            ("acc 50", stream(Sensor::Acc, 50)),
+            ("mag", stream(Sensor::Mag, 10)),
+            ("  acc   1 ", stream(Sensor::Acc, 1)),
+            ("acc 100", stream(Sensor::Acc, 100)),
+            ("", Err(CommandError::Missing("<acc|mag>"))),
+            ("gyro 5", Err(CommandError::Invalid("<acc|mag>"))),
+            ("acc 0", Err(CommandError::Invalid("[hz]"))),
+            ("acc 101", Err(CommandError::Invalid("[hz]"))),
+            ("acc fast", Err(CommandError::Invalid("[hz]"))),
+            ("acc 50 now", Err(CommandError::TooMany)),
+        ],
+    );
+    let stream = Stream {
+        sensor: Sensor::Acc,
+        hz: 50,
+    };
+    assert_eq!(stream.period_us(), 20_000);
+}
+
+#[test]
+fn odr_arguments() {
+    assert_parses(
+        parse_odr,
+        &[
+            ("acc 1", Ok(Odr::Acc(AccelOutputDataRate::Hz1))),
+            ("acc 400", Ok(Odr::Acc(AccelOutputDataRate::Hz400))),
+            ("mag 20", Ok(Odr::Mag(MagOutputDataRate::Hz20))),
+            ("acc 1344", Err(CommandError::Invalid("<hz>"))),
+            ("acc 20", Err(CommandError::Invalid("<hz>"))),
+            ("mag 25", Err(CommandError::Invalid("<hz>"))),
+            ("mag", Err(CommandError::Missing("<hz>"))),
+            ("mag 10 20", Err(CommandError::TooMany)),
+        ],
+    );
+    assert_eq!(odr_hz(Odr::Acc(AccelOutputDataRate::Hz25)), 25);
+    assert_eq!(odr_hz(Odr::Mag(MagOutputDataRate::Hz100)), 100);
+}
+
+#[test]
+fn scale_arguments() {
+    assert_parses(
+        parse_scale,
+        &[
+            ("2", Ok(AccelScale::G2)),
+            ("4g", Ok(AccelScale::G4)),
+            ("16g", Ok(AccelScale::G16)),
+            ("3", Err(CommandError::Invalid("<g>"))),
+            ("g", Err(CommandError::Invalid("<g>"))),
+            ("", Err(CommandError::Missing("<g>"))),
+        ],
+    );
+}
+
+#[test]
+fn mode_arguments() {
+    assert_parses(
+        parse_mode,
+        &[
+            ("acc low-power", Ok(Mode::Acc(AccelMode::LowPower))),
+            ("acc normal", Ok(Mode::Acc(AccelMode::Normal))),
+            ("acc high-res", Ok(Mode::Acc(AccelMode::HighResolution))),
+            ("mag low-power", Ok(Mode::Mag(MagMode::LowPower))),
+            ("mag high-res", Ok(Mode::Mag(MagMode::HighResolution))),
+            ("mag normal", Err(CommandError::Invalid("<mode>"))),
+            ("acc", Err(CommandError::Missing("<mode>"))),
+        ],
+    );
+}
+
+#[test]
+fn accel_cal_arguments() {
+    let calibration = AccelCalibration::new([40.0, -25.0, 70.0], [0.98, 1.03, 1.01]);
+    let hex: String = calibration
+        .to_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let upper = hex.to_uppercase();
+    // Right length, but a gain of zero.
+    let zero_gain = format!("{}00000000", &hex[..40]);
+    let lines = [
+        hex.clone(),
+        upper,
+        String::new(),
+        hex[..46].to_string(),
+        format!("{}00", hex),
+        format!("+{}", &hex[1..]),
+        format!("{}zz", &hex[..46]),
+        zero_gain,
+        format!("{} {}", hex, hex),
+    ];
+    let expected = [
+        Ok(Some(calibration)),
+        Ok(Some(calibration)),
+        Ok(None),
+        Err(CommandError::Invalid("[hex]")),
+        Err(CommandError::Invalid("[hex]")),
+        Err(CommandError::Invalid("[hex]")),
+        Err(CommandError::Invalid("[hex]")),
+        Err(CommandError::Invalid("[hex]")),
+        Err(CommandError::TooMany),
+    ];
+    let cases: Vec<_> = lines.iter().map(String::as_str).zip(expected).collect();
+    assert_parses(parse_calibration, &cases);
+}
+
+#[test]
+fn unit_and_format_words() {
+    let cases = [
+        ("mg", Ok(Unit::MilliG)),
+        ("m/s2", Ok(Unit::MetresPerSecondSquared)),
+        ("m/s²", Ok(Unit::MetresPerSecondSquared)),
+        ("nT", Ok(Unit::Nanotesla)),
+        ("uT", Ok(Unit::Microtesla)),
+        ("µT", Ok(Unit::Microtesla)),
+        ("g", Err(())),
+    ];
+    for (word, expected) in cases {
+        assert_eq!(word.parse::<Unit>(), expected, "{:?}", word);
+    }
+    for format in [Format::Human, Format::Csv, Format::Json] {
+        assert_eq!(format.name().parse(), Ok(format));
+    }
+}
+
+/// Write `readings` the way `output` says, header first, and compare with `expected`.
+fn assert_output(output: Output, readings: &[Reading], expected: &str) {
+    let mut got = String::new();
+    output.write_header(&mut got).unwrap();
+    for reading in readings {
+        output.write_reading(&mut got, reading).unwrap();
+    }
+    assert_eq!(got, expected);
+}
+
+const ACC: Reading = Reading {
+    sensor: Sensor::Acc,
+    t_ms: None,
+    xyz: (-3, 1, 1000),
+};
+
+const MAG: Reading = Reading {
+    sensor: Sensor::Mag,
+    t_ms: None,
+    xyz: (-42, 123_456, 0),
+};
+
+fn streamed(reading: Reading, t_ms: u32) -> Reading {
+    Reading {
+        t_ms: Some(t_ms),
+        ..reading
+    }
+}
+
+#[test]
+fn human_format() {
+    assert_output(
+        Output::default(),
+        &[ACC, MAG],
+        "Accelerometer: x -3 y 1 z 1000 mg\r\n\
+         Magnetometer: x -42 y 123456 z 0 nT\r\n",
+    );
+    assert_output(
+        Output::default(),
+        &[streamed(ACC, 0), streamed(ACC, 20)],
+        "       0 ms  Accelerometer: x -3 y 1 z 1000 mg\r\n\
+         \x20     20 ms  Accelerometer: x -3 y 1 z 1000 mg\r\n",
+    );
+}
+
+#[test]
+fn unit_conversion() {
+    let mut output = Output::default();
+    output.set_unit(Unit::MetresPerSecondSquared);
+    output.set_unit(Unit::Microtesla);
+    assert_eq!(output.acc_unit, Unit::MetresPerSecondSquared);
+    assert_eq!(output.mag_unit, Unit::Microtesla);
+    assert_output(
+        output,
+        &[ACC, MAG],
+        "Accelerometer: x -0.029 y 0.010 z 9.807 m/s2\r\n\
+             Magnetometer: x -0.042 y 123.456 z 0.000 uT\r\n",
+    );
+}
+
+#[test]
+fn csv_format() {
+    let output = Output {
+        format: Format::Csv,
+        ..Output::default()
+    };
+    assert_output(
+        output,
+        &[streamed(ACC, 0), streamed(MAG, 100), ACC],
+        "t_ms,sensor,x,y,z,unit\r\n\
+         0,acc,-3,1,1000,mg\r\n\
+         100,mag,-42,123456,0,nT\r\n\
+         ,acc,-3,1,1000,mg\r\n",
+    );
+}
+
+#[test]
+fn json_format() {
+    let output = Output {
+        format: Format::Json,
+        acc_unit: Unit::MetresPerSecondSquared,
+        mag_unit: Unit::Microtesla,
+    };
+    assert_output(
+        output,
+        &[streamed(ACC, 40), MAG],
+        "{\"t_ms\":40,\"sensor\":\"acc\",\"x\":-0.029,\"y\":0.010,\"z\":9.807,\"unit\":\"m/s2\"}\r\n\
+         {\"sensor\":\"mag\",\"x\":-0.042,\"y\":123.456,\"z\":0.000,\"unit\":\"uT\"}\r\n",
+    );
+}
+
+/// A delay that returns at once: a [`Script`] never has to be waited for.
+struct NoDelay;
+
+impl DelayNs for NoDelay {
+    fn delay_ns(&mut self, _ns: u32) {}
+}
+
+#[test]
+fn readings_from_a_scripted_sensor() {
+    let mut script = Script::new([
+        Scripted::Accel((-3, 1, 1000)),
+        Scripted::Mag((-42, 123_456, 0)),
+        Scripted::Accel((5, -7, 990)),
+        Scripted::Accel((6, -8, 995)),
+        Scripted::Mag((40, 120_000, -15)),
+    ]);
+    let mut read = |sensor, t_ms| take_reading(&mut script, &mut NoDelay, sensor, t_ms);
+    assert_eq!(read(Sensor::Acc, None), Ok(ACC), "first acc");
+    assert_eq!(read(Sensor::Mag, None), Ok(MAG), "first mag");
+    // Waiting for the magnetometer steps over two accelerometer readings, and only the newer one
+    // is still there to be read afterwards, as with the sensor's own data registers.
+    let mag = read(Sensor::Mag, Some(20)).unwrap();
+    let acc = read(Sensor::Acc, Some(40)).unwrap();
+    assert_eq!(read(Sensor::Acc, None), Err(Exhausted), "script runs out");
+    assert_output(
+        Output::default(),
+        &[mag, acc],
+        "      20 ms  Magnetometer: x 40 y 120000 z -15 nT\r\n\
+         \x20     40 ms  Accelerometer: x 6 y -8 z 995 mg\r\n",
+    );
+}
+
+/// A chip on a [`MockBus`]: 256 registers, and a pointer that moves on after each byte.
+struct MockChip {
+    registers: [u8; 256],
+    pointer: u8,
+    /// Refuses writes to these registers.
+    read_only: Vec<u8>,
+}
+
+/// A pretend I2C bus, behaving like the LSM303AGR's registers as far as `probe` can tell.
+#[derive(Default)]
+struct MockBus {
+    chips: BTreeMap<u8, MockChip>,
+    /// Addresses where the bus goes wrong.
+    broken: Vec<u8>,
+    transactions: usize,
+}
+
+impl MockBus {
+    fn with_chip(mut self, address: u8, registers: &[(u8, u8)]) -> Self {
+        let mut chip = MockChip {
+            registers: [0; 256],
+            pointer: 0,
+            read_only: Vec::new(),
+        };
+        for &(register, value) in registers {
+            chip.registers[register as usize] = value;
+            chip.read_only.push(register);
+        }
+        self.chips.insert(address, chip);
+        self
+    }
+
+    /// The MB2's own bus: the LSM303AGR's accelerometer and magnetometer, and the interface chip.
+    fn mb2() -> Self {
+        MockBus::default()
+            .with_chip(0x19, &[(0x0f, 0x33)])
+            .with_chip(0x1e, &[(0x4f, 0x40)])
+            .with_chip(0x70, &[])
+    }
+}
+
+impl ErrorType for MockBus {
+    type Error = ErrorKind;
+}
+
+impl I2c for MockBus {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), ErrorKind> {
+        self.transactions += 1;
+        if self.broken.contains(&address) {
+            return Err(ErrorKind::Bus);
+        }
+        let chip = self
+            .chips
+            .get_mut(&address)
+            .ok_or(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address))?;
+        for operation in operations {
+            match operation {
+                Operation::Write(bytes) => {
+                    let Some((&register, values)) = bytes.split_first() else {
+                        continue;
+                    };
+                    chip.pointer = register;
+                    for &value in values {
+                        if chip.read_only.contains(&chip.pointer) {
+                            return Err(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data));
+                        }
+                        chip.registers[chip.pointer as usize] = value;
+                        chip.pointer = chip.pointer.wrapping_add(1);
+                    }
+                }
+                Operation::Read(buffer) => {
+                    for byte in buffer.iter_mut() {
+                        *byte = chip.registers[chip.pointer as usize];
+                        chip.pointer = chip.pointer.wrapping_add(1);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn probe_arguments() {
+    let dump = |address, first, count| {
+        Ok(Dump {
+            address,
+            first,
+            count,
+        })
+    };
+    assert_parses(
+        parse_read,
+        &[
+            ("19 0f", Ok((0x19, 0x0f))),
+            ("0x1e 0x4F", Ok((0x1e, 0x4f))),
+            ("77 ff", Ok((0x77, 0xff))),
+            ("", Err(CommandError::Missing("<addr>"))),
+            ("19", Err(CommandError::Missing("<reg>"))),
+            ("07 00", Err(CommandError::Invalid("<addr>"))),
+            ("78 00", Err(CommandError::Invalid("<addr>"))),
+            ("19 100", Err(CommandError::Invalid("<reg>"))),
+            ("19 0x", Err(CommandError::Invalid("<reg>"))),
+            ("19 0f 0", Err(CommandError::TooMany)),
+        ],
+    );
+    assert_parses(
+        parse_write,
+        &[
+            ("19 20 57", Ok((0x19, 0x20, 0x57))),
+            ("19 20", Err(CommandError::Missing("<value>"))),
+            ("19 20 x", Err(CommandError::Invalid("<value>"))),
+        ],
+    );
+    assert_parses(
+        parse_dump,
+        &[
+            ("19", dump(0x19, 0, 16)),
+            ("19 20", dump(0x19, 0x20, 16)),
+            ("19 28 6", dump(0x19, 0x28, 6)),
+            ("19 0 256", dump(0x19, 0, 256)),
+            ("19 f0 16", dump(0x19, 0xf0, 16)),
+            ("19 f1 16", Err(CommandError::Invalid("[count]"))),
+            ("19 0 0", Err(CommandError::Invalid("[count]"))),
+            ("19 zz", Err(CommandError::Invalid("[reg]"))),
+        ],
+    );
+}
+
+#[test]
+fn scanning() {
+    let mut bus = MockBus::mb2();
+    bus.broken.push(0x42);
+    let found = scan(&mut bus);
+    assert_eq!(
+        found.found.iter().collect::<Vec<_>>(),
+        vec![0x19, 0x1e, 0x70],
+        "found"
+    );
+    assert_eq!(
+        found.failed.iter().collect::<Vec<_>>(),
+        vec![0x42],
+        "failed"
+    );
+    assert_eq!(bus.transactions, 0x78 - 0x08, "probes");
+    let mut table = String::new();
+    write_scan(&mut table, &found).unwrap();
+    assert_eq!(
+        table.as_str(),
+        "    0  1  2  3  4  5  6  7  8  9  a  b  c  d  e  f\r\n\
+         00:                         -- -- -- -- -- -- -- --\r\n\
+         10: -- -- -- -- -- -- -- -- -- 19 -- -- -- -- 1e --\r\n\
+         20: -- -- -- -- -- -- -- -- -- -- -- -- -- -- -- --\r\n\
+         30: -- -- -- -- -- -- -- -- -- -- -- -- -- -- -- --\r\n\
+         40: -- -- XX -- -- -- -- -- -- -- -- -- -- -- -- --\r\n\
+         50: -- -- -- -- -- -- -- -- -- -- -- -- -- -- -- --\r\n\
+         60: -- -- -- -- -- -- -- -- -- -- -- -- -- -- -- --\r\n\
+         70: 70 -- -- -- -- -- -- --                        \r\n",
+        "table"
+    );
+}
+
+#[test]
+fn scan_of_an_empty_bus() {
+    let mut table = String::new();
+    let found = scan(&mut MockBus::default());
+    write_scan(&mut table, &found).unwrap();
+    assert_eq!(found, Scan::default(), "found");
+    assert_eq!(table.matches(" --").count(), 112, "rows");
+}
+
+#[test]
+fn identifying() {
+    let mut bus = MockBus::mb2()
+        .with_chip(0x18, &[(0x0f, 0x33)])
+        .with_chip(0x76, &[(0xd0, 0x58)])
+        .with_chip(0x77, &[(0xd0, 0x99)]);
+    let mut name = |address| identify(&mut bus, address).map(|chip| chip.map(|c| c.name));
+    assert_eq!(name(0x19), Ok(Some("LSM303AGR accelerometer")), "19");
+    assert_eq!(name(0x1e), Ok(Some("LSM303AGR magnetometer")), "1e");
+    assert_eq!(name(0x18), Ok(Some("LIS3DH accelerometer")), "18");
+    assert_eq!(
+        name(0x76),
+        Ok(Some("BMP280 pressure and temperature sensor")),
+        "76"
+    );
+    assert_eq!(name(0x77), Ok(None), "77");
+    assert_eq!(name(0x70), Ok(None), "70");
+    assert_eq!(
+        name(0x68),
+        Err(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address)),
+        "nothing there"
+    );
+}
+
+#[test]
+fn registers() {
+    let mut bus = MockBus::mb2();
+    assert_eq!(read_register(&mut bus, 0x19, 0x0f), Ok(0x33), "read");
+    assert_eq!(write_register(&mut bus, 0x19, 0x20, 0x57), Ok(()), "write");
+    assert_eq!(read_register(&mut bus, 0x19, 0x20), Ok(0x57), "read back");
+    assert_eq!(
+        write_register(&mut bus, 0x19, 0x0f, 0).map_err(bus_error),
+        Err(CommandError::Failed("the chip refused a byte")),
+        "read-only"
+    );
+    assert_eq!(
+        read_register(&mut bus, 0x20, 0).map_err(bus_error),
+        Err(CommandError::Failed("no chip answered")),
+        "absent"
+    );
+    bus.broken.push(0x19);
+    assert_eq!(
+        read_register(&mut bus, 0x19, 0).map_err(bus_error),
+        Err(CommandError::Failed("bus error")),
+        "broken"
+    );
+}
+
+/// Run `dump` over the pretend bus, as the probe would.
+fn dumped(bus: &mut MockBus, line: &str) -> String {
+    let dump = parse_dump(&mut Args::new(line)).unwrap();
+    let mut values = vec![0; dump.count];
+    read_registers(bus, dump.address, dump.first, &mut values).unwrap();
+    let mut out = String::new();
+    write_dump(&mut out, dump.first, &values).unwrap();
+    out
+}
+
+#[test]
+fn dump() {
+    let registers: Vec<(u8, u8)> = (0..=255).map(|r| (r, r ^ 0xa5)).collect();
+    let mut bus = MockBus::default().with_chip(0x19, &registers);
+    let transactions = bus.transactions;
+    assert_eq!(
+        dumped(&mut bus, "19"),
+        "00: a5 a4 a7 a6 a1 a0 a3 a2 ad ac af ae a9 a8 ab aa\r\n".to_string(),
+        "first row"
+    );
+    assert_eq!(
+        bus.transactions - transactions,
+        16,
+        "one register at a time"
+    );
+    assert_eq!(
+        dumped(&mut bus, "19 1e 4"),
+        "10:                                           bb ba\r\n\
+         20: 85 84\r\n"
+            .to_string(),
+        "part rows"
+    );
+    assert_eq!(
+        dumped(&mut bus, "19 fe 2"),
+        "f0:                                           5b 5a\r\n".to_string(),
+        "to the end"
+    );
+    assert_eq!(
+        dumped(&mut bus, "19 0 256").lines().count(),
+        16,
+        "everything"
+    );
+}
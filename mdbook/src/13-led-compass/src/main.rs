@@ -2,33 +2,79 @@
 #![no_main]
 #![no_std]
 
+use core::fmt::Write;
+use core::str;
+
 use cortex_m_rt::entry;
-use embedded_hal::delay::DelayNs;
+use heapless::Vec;
 use panic_rtt_target as _;
 use rtt_target::rtt_init_print;
 
-// You'll find these useful ;-).
-use core::f32::consts::PI;
-use libm::{atan2f, floorf};
-
 use microbit::{
     display::blocking::Display,
+    hal::uarte::{self, Baudrate, Parity},
     hal::{twim, Timer},
     pac::twim0::frequency::FREQUENCY_A,
 };
 
-use lsm303agr::{Lsm303agr, MagMode, MagOutputDataRate};
+use lsm303agr::{AccelMode, AccelOutputDataRate, Lsm303agr, MagMode, MagOutputDataRate};
+use mag_cal::{
+    calc_calibration, calibrated_measurement,
+    declination::declination,
+    filter::{HeadingFilter, SectorHysteresis},
+    heading::{
+        build_declination, magnetic_heading, north_bearing, tilt_compensated_heading, true_heading,
+        DeclinationCommand,
+    },
+    Measurement,
+};
+use sensors::{
+    attitude::{AttitudeEstimator, Filter},
+    Accelerometer, Magnetometer,
+};
+use serial_setup::UartePort;
+use settings::{keys, Settings};
+
+/// Smoothing time constant for the heading, in seconds. Larger is steadier but slower.
+const HEADING_TIME_CONSTANT: f32 = 0.5;
+/// How far, in degrees, the heading must go past the edge of an LED's sector before the next LED
+/// takes over.
+const SECTOR_HYSTERESIS: f32 = 4.0;
+/// Time between magnetometer samples at 10 Hz, in seconds.
+const SAMPLE_PERIOD: f32 = 0.1;
+
+/// Index into the LED ring below of the LED for `sector`, counted clockwise from the top.
+fn ring_index(sector: usize) -> usize {
+    // The ring starts at W (sector 12) and runs anticlockwise.
+    (12 + 16 - sector) % 16
+}
 
 #[entry]
 fn main() -> ! {
     rtt_init_print!();
     let board = microbit::Board::take().unwrap();
 
+    let serial = uarte::Uarte::new(
+        board.UARTE0,
+        board.uart.into(),
+        Parity::EXCLUDED,
+        Baudrate::BAUD115200,
+    );
+    let mut serial = UartePort::new(serial);
+
     let i2c = { twim::Twim::new(board.TWIM0, board.i2c_internal.into(), FREQUENCY_A::K100) };
 
     let mut timer0 = Timer::new(board.TIMER0);
     let mut display = Display::new(board.display_pins);
 
+    // A declination saved over serial wins over one set at build time.
+    let mut settings = Settings::new(settings::flash(board.NVMC), 0);
+    let mut declination_deg = settings
+        .read_f32(keys::DECLINATION)
+        .unwrap()
+        .or_else(build_declination)
+        .unwrap_or(0.0);
+
     let mut sensor = Lsm303agr::new_with_i2c(i2c);
     sensor.init().unwrap();
     sensor
@@ -38,9 +84,24 @@ fn main() -> ! {
             MagOutputDataRate::Hz10,
         )
         .unwrap();
+    // The calibration tilt cursor, and the heading when the board isn't level, need the
+    // accelerometer too.
+    sensor
+        .set_accel_mode_and_odr(
+            &mut timer0,
+            AccelMode::HighResolution,
+            AccelOutputDataRate::Hz10,
+        )
+        .unwrap();
     let mut sensor = sensor.into_mag_continuous().ok().unwrap();
 
+    let calibration = calc_calibration(&mut sensor, &mut display, &mut timer0);
+
     let mut leds = [[0u8; 5]; 5];
+    let mut buffer: Vec<u8, 32> = Vec::new();
+    let mut filter = HeadingFilter::new(HEADING_TIME_CONSTANT, SAMPLE_PERIOD);
+    let mut sectors = SectorHysteresis::new(16, SECTOR_HYSTERESIS);
+    let mut attitude = AttitudeEstimator::new(Filter::default());
 
     // Indexes of the 16 LEDs to be used in the display, and their
     // compass directions.
@@ -65,28 +126,50 @@ fn main() -> ! {
     ];
 
     loop {
-        while !sensor.mag_status().unwrap().xyz_new_data() {
-            timer0.delay_ms(1u32);
+        // Handle any serial commands without holding up the compass.
+        while serial.read_ready().unwrap() {
+            let byte = serial.read().unwrap();
+            serial.write(byte).unwrap();
+            if byte != b'\r' {
+                if buffer.push(byte).is_err() {
+                    write!(serial, "\r\nerror: buffer full\r\n").unwrap();
+                    buffer.clear();
+                }
+                continue;
+            }
+            serial.write(b'\n').unwrap();
+
+            let line = str::from_utf8(&buffer).unwrap_or("");
+            let new_declination = match line.parse() {
+                Ok(DeclinationCommand::Show) => None,
+                Ok(DeclinationCommand::Set(deg)) => Some(deg),
+                Ok(DeclinationCommand::Locate { lat, lon }) => Some(declination(lat, lon)),
+                Err(e) => {
+                    write!(serial, "error: {}\r\n", e).unwrap();
+                    None
+                }
+            };
+            if let Some(deg) = new_declination {
+                declination_deg = deg;
+                settings.write_f32(keys::DECLINATION, deg).unwrap();
+            }
+            write!(serial, "declination: {}°\r\n", declination_deg).unwrap();
+            serial.flush().unwrap();
+            buffer.clear();
         }
-        let (x, y, _) = sensor.magnetic_field().unwrap().xyz_nt();
-
-        // Get an angle between -180° and 180° from the x axis.
-        let theta = atan2f(y as f32, x as f32);
-
-        // Cut the unit circle into thirty-two segments,
-        // with pairs of adjacent segments corresponding to
-        // each compass direction.
-        let seg = floorf(16.0 * theta / PI) as i8;
-
-        // Figure out what LED index to blink.
-        let index = if seg >= 15 || seg <= -15 {
-            8
-        } else if seg >= 0 {
-            (seg / 2) as usize
-        } else {
-            ((31 + seg) / 2) as usize
+
+        attitude.update(sensor.wait_accel(&mut timer0).unwrap(), SAMPLE_PERIOD);
+        let raw = Measurement::new(sensor.wait_mag(&mut timer0).unwrap());
+        let field = calibrated_measurement(raw, &calibration);
+        let magnetic = match attitude.gravity() {
+            Some(gravity) => tilt_compensated_heading(field, gravity),
+            None => magnetic_heading(field),
         };
 
+        // Smoothed heading from true north, and where true north is on the board.
+        let heading = filter.update(true_heading(magnetic, declination_deg));
+        let index = ring_index(sectors.update(north_bearing(heading)));
+
         // Blink the given LED.
         let (r, c) = indices[index];
         leds[r][c] = 255u8;
@@ -0,0 +1,65 @@
+//! Measurements of a single recorded punch.
+
+/// Standard gravity, in m/s² per g.
+const G: f32 = 9.80665;
+
+/// What a punch looked like.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Punch {
+    /// Largest acceleration, in mg.
+    pub peak_mg: i32,
+    /// Time from the start of the punch to the peak.
+    pub time_to_peak_ms: u32,
+    /// Total time spent above the threshold.
+    pub duration_ms: u32,
+    /// Change in speed while above the threshold, in m/s: the impulse per unit mass.
+    pub delta_v: f32,
+}
+
+/// Analyse a punch from its acceleration magnitudes in mg, sampled at `sample_rate_hz` starting
+/// from the reading that first crossed `threshold_mg`.
+pub fn analyse(magnitudes: &[i32], sample_rate_hz: u32, threshold_mg: i32) -> Punch {
+    let ms = |samples: usize| (samples as u64 * 1000 / sample_rate_hz as u64) as u32;
+    let (mut peak_at, mut peak_mg) = (0, 0);
+    let (mut above, mut sum_mg) = (0, 0.0);
+    for (i, &m) in magnitudes.iter().enumerate() {
+        if m > peak_mg {
+            (peak_at, peak_mg) = (i, m);
+        }
+        if m > threshold_mg {
+            above += 1;
+            sum_mg += m as f32;
+        }
+    }
+    Punch {
+        peak_mg,
+        time_to_peak_ms: ms(peak_at),
+        duration_ms: ms(above),
+        delta_v: sum_mg / 1000.0 * G / sample_rate_hz as f32,
+    }
+}
+
+/// The hardest punch so far.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SessionBest {
+    best: Option<Punch>,
+}
+
+impl SessionBest {
+    pub const fn new() -> Self {
+        Self { best: None }
+    }
+
+    /// Compare `punch` with the best so far, returning `true` if it's a new best.
+    pub fn record(&mut self, punch: Punch) -> bool {
+        let better = self.best.is_none_or(|best| punch.peak_mg > best.peak_mg);
+        if better {
+            self.best = Some(punch);
+        }
+        better
+    }
+
+    pub fn best(&self) -> Option<Punch> {
+        self.best
+    }
+}
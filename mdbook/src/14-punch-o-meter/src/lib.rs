@@ -0,0 +1,110 @@
+#![no_std]
+
+//! Punch detection and analysis for the punch-o-meter, kept apart from the hardware so it can be
+//! run on recorded readings anywhere.
+
+use libm::sqrtf;
+use sensors::Xyz;
+
+mod analysis;
+
+pub use analysis::{analyse, Punch, SessionBest};
+
+/// What a [`PunchDetector`] saw in a reading.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    /// The acceleration crossed the threshold: a punch has started.
+    Start,
+    /// The measurement window is over.
+    Punch(Punch),
+}
+
+/// Readings of gravity to look back over when a punch starts.
+const LOOKBACK: usize = 16;
+
+/// Length of a 3-axis vector.
+pub fn magnitude((x, y, z): Xyz) -> i32 {
+    let (x, y, z) = (x as f32, y as f32, z as f32);
+    sqrtf(x * x + y * y + z * z) as i32
+}
+
+/// Watches a stream of accelerometer readings for punches, in any direction.
+///
+/// While the board is still, the detector keeps a running average of the readings: that is
+/// gravity, whichever way up the board is. A punch starts when a reading differs from gravity by
+/// more than the threshold. The size of that difference is then recorded for `N` readings and
+/// [analysed](analyse) at the end of the window.
+///
+/// A punch may build up for a few readings before it crosses the threshold, and those readings
+/// pull the running average towards it. So once a punch starts, the difference is measured from
+/// gravity as it was [`LOOKBACK`] readings earlier.
+#[derive(Debug, Clone)]
+pub struct PunchDetector<const N: usize> {
+    threshold_mg: i32,
+    sample_rate_hz: u32,
+    gravity: Option<Xyz>,
+    /// The running average over the last [`LOOKBACK`] readings, oldest at `next`.
+    past: [Xyz; LOOKBACK],
+    next: usize,
+    waveform: [i32; N],
+    len: usize,
+    measuring: bool,
+}
+
+impl<const N: usize> PunchDetector<N> {
+    /// A detector triggering at `threshold_mg` for readings arriving at `sample_rate_hz`.
+    pub const fn new(threshold_mg: i32, sample_rate_hz: u32) -> Self {
+        const { assert!(N > 0, "a punch needs a window of at least one reading") };
+        Self {
+            threshold_mg,
+            sample_rate_hz,
+            gravity: None,
+            past: [(0, 0, 0); LOOKBACK],
+            next: 0,
+            waveform: [0; N],
+            len: 0,
+            measuring: false,
+        }
+    }
+
+    /// Feed the next reading, in mg.
+    pub fn push(&mut self, sample: Xyz) -> Option<Event> {
+        let (gx, gy, gz) = *self.gravity.get_or_insert_with(|| {
+            self.past = [sample; LOOKBACK];
+            sample
+        });
+        let m = magnitude((sample.0 - gx, sample.1 - gy, sample.2 - gz));
+
+        if self.measuring {
+            self.waveform[self.len] = m;
+            self.len += 1;
+            if self.len < N {
+                return None;
+            }
+            self.measuring = false;
+            return Some(Event::Punch(analyse(
+                &self.waveform,
+                self.sample_rate_hz,
+                self.threshold_mg,
+            )));
+        }
+
+        if m > self.threshold_mg {
+            let (gx, gy, gz) = self.past[self.next];
+            self.gravity = Some((gx, gy, gz));
+            self.measuring = true;
+            self.waveform[0] = magnitude((sample.0 - gx, sample.1 - gy, sample.2 - gz));
+            self.len = 1;
+            return Some(Event::Start);
+        }
+        // Follow slow changes in orientation.
+        self.past[self.next] = (gx, gy, gz);
+        self.next = (self.next + 1) % LOOKBACK;
+        self.gravity = Some((
+            gx + (sample.0 - gx) / 16,
+            gy + (sample.1 - gy) / 16,
+            gz + (sample.2 - gz) / 16,
+        ));
+        None
+    }
+}
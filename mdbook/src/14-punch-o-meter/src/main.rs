@@ -2,72 +2,165 @@
 #![no_main]
 #![no_std]
 
-const TICKS_PER_SEC: u32 = 400;
-const THRESHOLD: f32 = 1.5;
+/// Accelerometer readings per second. Low-power mode can go faster still (up to 5376 Hz).
+const SAMPLE_RATE_HZ: u32 = 400;
+/// Measure for one second after the threshold is crossed.
+const WINDOW: usize = SAMPLE_RATE_HZ as usize;
+const THRESHOLD_MG: i32 = 1500;
+/// The accelerometer interrupts when its FIFO holds this many readings, 40 ms worth at 400 Hz.
+/// The FIFO holds 32, so the interrupt handler has plenty of time to empty it.
+const FIFO_WATERMARK: u8 = 16;
+/// How long each display frame lasts.
+const FRAME_MS: u32 = 20;
+/// The bar graph is full at 8 g.
+const FULL_SCALE_MG: i32 = 8000;
+/// How long a punch's bar graph stays up before its value scrolls past, in frames.
+const BAR_FRAMES: u32 = 50;
+/// Frames per column of scrolling.
+const SCROLL_FRAMES: u32 = 5;
+
+use core::fmt::Write;
 
-use cortex_m::asm::nop;
 use cortex_m_rt::entry;
 use panic_rtt_target as _;
 use rtt_target::{rprintln, rtt_init_print};
 
 use microbit::{
+    display::blocking::Display,
     hal::{twim, Timer},
-    pac::twim0::frequency::FREQUENCY_A,
+    pac::{self, interrupt, twim0::frequency::FREQUENCY_A, TWIM0},
+};
+
+use lsm303agr::{AccelMode, AccelOutputDataRate, AccelScale, FifoMode, Interrupt, Lsm303agr};
+use led_matrix::{bar_graph, Frame, Scroller};
+use punch_o_meter::{Event, Punch, PunchDetector, SessionBest};
+use sensors::{
+    fifo::{AccelFifo, Sensitivity},
+    mb2::{int1_active_low, InterruptSampler},
 };
 
-use lsm303agr::{AccelMode, AccelOutputDataRate, AccelScale, Lsm303agr};
+const MODE: AccelMode = AccelMode::Normal;
+// Allow the sensor to measure up to 16 G since human punches
+// can actually be quite fast
+const SCALE: AccelScale = AccelScale::G16;
+
+/// Accelerometer readings, moved out of the FIFO by the watermark interrupt.
+static SAMPLER: InterruptSampler<AccelFifo<twim::Twim<TWIM0>>, 128> = InterruptSampler::new();
+
+#[pac::interrupt]
+fn GPIOTE() {
+    SAMPLER.on_interrupt();
+}
+
+/// What the LED matrix is showing.
+enum Screen {
+    /// The session best as a bar graph.
+    Best,
+    /// The last punch as a bar graph, for this many more frames.
+    Bar(Punch, u32),
+    /// The last punch's peak in g scrolling past, and frames until the next column.
+    Scroll(Scroller, u32),
+}
+
+impl Screen {
+    /// The frame to show now, moving on to the next screen when this one is done.
+    fn frame(&mut self, best: &SessionBest) -> Frame {
+        match self {
+            Screen::Best => best
+                .best()
+                .map_or([[0; 5]; 5], |b| bar_graph(b.peak_mg, FULL_SCALE_MG)),
+            Screen::Bar(punch, 0) => {
+                let mut scroller = Scroller::new();
+                let mg = punch.peak_mg;
+                write!(scroller, "{}.{}", mg / 1000, mg % 1000 / 100).unwrap();
+                *self = Screen::Scroll(scroller, SCROLL_FRAMES);
+                self.frame(best)
+            }
+            Screen::Bar(punch, frames) => {
+                *frames -= 1;
+                bar_graph(punch.peak_mg, FULL_SCALE_MG)
+            }
+            Screen::Scroll(scroller, frames) => {
+                let Some(frame) = scroller.frame() else {
+                    *self = Screen::Best;
+                    return self.frame(best);
+                };
+                *frames -= 1;
+                if *frames == 0 {
+                    scroller.advance();
+                    *frames = SCROLL_FRAMES;
+                }
+                frame
+            }
+        }
+    }
+}
 
 #[entry]
 fn main() -> ! {
     rtt_init_print!();
     let board = microbit::Board::take().unwrap();
 
-    let i2c = { twim::Twim::new(board.TWIM0, board.i2c_internal.into(), FREQUENCY_A::K100) };
+    // A full FIFO is 192 bytes: run the bus at 400 kHz so draining it doesn't take long.
+    let mut i2c = { twim::Twim::new(board.TWIM0, board.i2c_internal.into(), FREQUENCY_A::K400) };
 
-    let mut delay = Timer::new(board.TIMER0);
+    let mut timer = Timer::new(board.TIMER0);
+    let mut display = Display::new(board.display_pins);
+    int1_active_low(&mut i2c).unwrap();
     let mut sensor = Lsm303agr::new_with_i2c(i2c);
     sensor.init().unwrap();
     sensor
-        .set_accel_mode_and_odr(&mut delay, AccelMode::Normal, AccelOutputDataRate::Hz400)
+        .set_accel_mode_and_odr(&mut timer, MODE, AccelOutputDataRate::Hz400)
+        .unwrap();
+    sensor.set_accel_scale(SCALE).unwrap();
+    // In stream mode the FIFO always holds the latest readings, dropping the oldest when full.
+    sensor
+        .acc_set_fifo_mode(FifoMode::Stream, FIFO_WATERMARK)
         .unwrap();
-    // Allow the sensor to measure up to 16 G since human punches
-    // can actually be quite fast
-    sensor.set_accel_scale(AccelScale::G16).unwrap();
+    sensor
+        .acc_enable_interrupt(Interrupt::FifoWatermark)
+        .unwrap();
+    let fifo = AccelFifo::new(sensor.destroy(), Sensitivity::from_mode(MODE, SCALE));
+    let int_pin = board.pins.p0_25.into_floating_input().degrade();
+    SAMPLER.start(board.GPIOTE, int_pin, fifo);
 
-    let mut max_g = 0.;
-    let mut countdown_ticks = None;
+    let mut detector = PunchDetector::<WINDOW>::new(THRESHOLD_MG, SAMPLE_RATE_HZ);
+    let mut best = SessionBest::new();
+    let mut screen = Screen::Best;
+    let mut lost = (0, 0);
 
     loop {
-        while !sensor.accel_status().unwrap().xyz_new_data() {
-            nop();
+        let stats = SAMPLER
+            .with(|sampler| (sampler.overruns(), sampler.dropped()))
+            .unwrap();
+        if stats != lost {
+            rprintln!(
+                "Readings were lost (FIFO overruns {}, queue overflows {})",
+                stats.0,
+                stats.1
+            );
+            lost = stats;
         }
-        // x acceleration in g
-        let (x, _, _) = sensor.acceleration().unwrap().xyz_mg();
-        let g_x = x as f32 / 1000.0;
-
-        if let Some(ticks) = countdown_ticks {
-            if ticks > 0 {
-                // countdown isn't done yet
-                if g_x > max_g {
-                    max_g = g_x;
+        while let Some(sample) = SAMPLER.try_next() {
+            match detector.push(sample) {
+                Some(Event::Start) => rprintln!("START!"),
+                Some(Event::Punch(punch)) => {
+                    rprintln!(
+                        "Peak {}g after {} ms, {} ms above {}g, speed change {} m/s",
+                        punch.peak_mg as f32 / 1000.0,
+                        punch.time_to_peak_ms,
+                        punch.duration_ms,
+                        THRESHOLD_MG as f32 / 1000.0,
+                        punch.delta_v,
+                    );
+                    if best.record(punch) {
+                        rprintln!("New session best!");
+                    }
+                    screen = Screen::Bar(punch, BAR_FRAMES);
                 }
-                countdown_ticks = Some(ticks - 1);
-            } else {
-                // Countdown is done: report max value
-                rprintln!("Max acceleration: {}g", max_g);
-
-                // Reset
-                max_g = 0.;
-                countdown_ticks = None;
-            }
-        } else {
-            // If acceleration goes above a threshold, we start measuring
-            if g_x > THRESHOLD {
-                rprintln!("START!");
-
-                max_g = g_x;
-                countdown_ticks = Some(TICKS_PER_SEC);
+                None => (),
             }
         }
+        display.show(&mut timer, screen.frame(&best), FRAME_MS);
     }
 }
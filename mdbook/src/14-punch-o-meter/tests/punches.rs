@@ -0,0 +1,280 @@
+//! Tests the punch-o-meter's detection and analysis against synthetic punches.
+//!
+//! ```text
+//! cargo test-host -p punch-o-meter
+//! ```
+//!
+//! Each test builds the readings a board would see, resting whichever way up with a little
+//! noise, then punched in some direction, and runs them through a [`PunchDetector`] set up as the
+//! program sets it up. The punches it reports, and the session best it keeps, must match the
+//! punches that were made.
+
+use std::f32::consts::PI;
+
+use punch_o_meter::{analyse, Event, Punch, PunchDetector, SessionBest};
+
+/// As in `src/main.rs`.
+const SAMPLE_RATE_HZ: u32 = 400;
+const WINDOW: usize = SAMPLE_RATE_HZ as usize;
+const THRESHOLD_MG: i32 = 1500;
+
+/// Standard gravity, in m/s² per g.
+const G: f32 = 9.80665;
+
+/// A small linear congruential generator, so every run sees the same noise.
+struct Lcg(u64);
+
+impl Lcg {
+    /// Noise of up to `reach` mg either way.
+    fn noise(&mut self, reach: i32) -> i32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        ((self.0 >> 33) % (2 * reach as u64 + 1)) as i32 - reach
+    }
+}
+
+/// A punch: a half-sine pulse of acceleration along `direction`.
+#[derive(Debug, Clone, Copy)]
+struct Stroke {
+    direction: [f32; 3],
+    peak_mg: f32,
+    /// Readings from start to end of the pulse. Odd, so one falls right on the peak.
+    len: usize,
+}
+
+impl Stroke {
+    fn along(direction: [f32; 3], peak_mg: f32) -> Self {
+        let norm = direction.iter().map(|d| d * d).sum::<f32>().sqrt();
+        Self {
+            direction: direction.map(|d| d / norm),
+            peak_mg,
+            len: 41,
+        }
+    }
+
+    /// Acceleration `i` readings into the pulse.
+    fn at(&self, i: usize) -> f32 {
+        self.peak_mg * (PI * i as f32 / (self.len - 1) as f32).sin()
+    }
+
+    /// The change in speed while the pulse is over the threshold, in m/s.
+    fn delta_v(&self) -> f32 {
+        let above_mg: f32 = (0..self.len)
+            .map(|i| self.at(i))
+            .filter(|a| *a > THRESHOLD_MG as f32)
+            .sum();
+        above_mg / 1000.0 * G / SAMPLE_RATE_HZ as f32
+    }
+}
+
+/// Readings of a board resting with `gravity` (in mg) on it, punched at each of `strokes`' times.
+fn readings(
+    gravity: [f32; 3],
+    len: usize,
+    strokes: &[(usize, Stroke)],
+    seed: u64,
+) -> Vec<(i32, i32, i32)> {
+    let mut rng = Lcg(seed);
+    (0..len)
+        .map(|t| {
+            let mut a = gravity;
+            for (start, stroke) in strokes {
+                if (*start..*start + stroke.len).contains(&t) {
+                    let m = stroke.at(t - start);
+                    for (a, d) in a.iter_mut().zip(stroke.direction) {
+                        *a += m * d;
+                    }
+                }
+            }
+            let [x, y, z] = a.map(|a| a.round() as i32);
+            (x + rng.noise(20), y + rng.noise(20), z + rng.noise(20))
+        })
+        .collect()
+}
+
+/// Feed `readings` to a detector set up as the program's, and collect what it saw, with the
+/// index of the reading that caused it.
+fn detect(readings: &[(i32, i32, i32)]) -> Vec<(usize, Event)> {
+    let mut detector = PunchDetector::<WINDOW>::new(THRESHOLD_MG, SAMPLE_RATE_HZ);
+    readings
+        .iter()
+        .enumerate()
+        .filter_map(|(i, r)| detector.push(*r).map(|e| (i, e)))
+        .collect()
+}
+
+/// The punches among `events`.
+fn punches(events: &[(usize, Event)]) -> Vec<Punch> {
+    events
+        .iter()
+        .filter_map(|(_, e)| match e {
+            Event::Punch(punch) => Some(*punch),
+            Event::Start => None,
+        })
+        .collect()
+}
+
+/// Check that `punch` is the one `stroke` made.
+fn assert_matches(what: &str, punch: &Punch, stroke: &Stroke) {
+    // The noise on three axes can add up to about 35 mg.
+    assert!(
+        (punch.peak_mg as f32 - stroke.peak_mg).abs() <= 40.0,
+        "{}: peak {} mg, expected {}",
+        what,
+        punch.peak_mg,
+        stroke.peak_mg
+    );
+    assert!(
+        (punch.delta_v - stroke.delta_v()).abs() <= 0.1 * stroke.delta_v(),
+        "{}: delta-v {} m/s, expected {}",
+        what,
+        punch.delta_v,
+        stroke.delta_v()
+    );
+}
+
+const FLAT: [f32; 3] = [0.0, 0.0, -1000.0];
+
+#[test]
+fn resting() {
+    for gravity in [FLAT, [1000.0, 0.0, 0.0], [0.0, -707.0, -707.0]] {
+        let events = detect(&readings(gravity, 4 * WINDOW, &[], 1));
+        assert_eq!(events.len(), 0, "{:?}", gravity);
+    }
+}
+
+#[test]
+fn slow_tilt() {
+    // Turning from flat onto an edge over two seconds is well under the threshold at every step.
+    let readings: Vec<_> = (0..2 * WINDOW)
+        .map(|t| {
+            let angle = PI / 2.0 * t as f32 / (2 * WINDOW) as f32;
+            let x = (1000.0 * angle.sin()) as i32;
+            let z = (-1000.0 * angle.cos()) as i32;
+            (x, 0, z)
+        })
+        .collect();
+    assert_eq!(detect(&readings).len(), 0);
+}
+
+#[test]
+fn one_punch() {
+    let stroke = Stroke::along([1.0, 0.0, 0.0], 6000.0);
+    let events = detect(&readings(FLAT, 3 * WINDOW, &[(500, stroke)], 2));
+    let [(start_at, Event::Start), (end_at, Event::Punch(punch))] = events[..] else {
+        panic!("events: {:?}", events);
+    };
+    let crossed = (0..stroke.len)
+        .find(|i| stroke.at(*i) > THRESHOLD_MG as f32)
+        .unwrap();
+    // The window starts with the reading that crossed the threshold.
+    let window_ms = |readings: usize| (readings * 1000 / SAMPLE_RATE_HZ as usize) as u32;
+    let above = (0..stroke.len)
+        .filter(|i| stroke.at(*i) > THRESHOLD_MG as f32)
+        .count();
+    assert!(
+        start_at.abs_diff(500 + crossed) <= 1,
+        "started at {}",
+        start_at
+    );
+    assert_eq!(end_at, start_at + WINDOW - 1);
+    assert_matches("punch", &punch, &stroke);
+    let time_to_peak = window_ms(stroke.len / 2 - crossed);
+    assert!(
+        punch.time_to_peak_ms.abs_diff(time_to_peak) <= 5,
+        "time to peak {} ms, expected {}",
+        punch.time_to_peak_ms,
+        time_to_peak
+    );
+    assert!(
+        punch.duration_ms.abs_diff(window_ms(above)) <= 5,
+        "duration {} ms, expected {}",
+        punch.duration_ms,
+        window_ms(above)
+    );
+}
+
+#[test]
+fn any_direction() {
+    let directions = [
+        [1.0, 0.0, 0.0],
+        [-1.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0],
+        [0.0, 0.0, -1.0],
+        [1.0, -1.0, 1.0],
+    ];
+    let restings = [FLAT, [0.0, 1000.0, 0.0], [-577.0, 577.0, -577.0]];
+    for gravity in restings {
+        for direction in directions {
+            let stroke = Stroke::along(direction, 4000.0);
+            let events = detect(&readings(gravity, 2 * WINDOW, &[(300, stroke)], 3));
+            let what = format!("{:?} resting {:?}", direction, gravity);
+            let [punch] = punches(&events)[..] else {
+                panic!("{}: events {:?}", what, events);
+            };
+            assert_matches(&what, &punch, &stroke);
+        }
+    }
+}
+
+#[test]
+fn session_best() {
+    let strokes = [
+        Stroke::along([1.0, 0.0, 0.0], 3000.0),
+        Stroke::along([0.0, 1.0, 0.0], 7000.0),
+        Stroke::along([0.0, 0.0, 1.0], 5000.0),
+        Stroke::along([-1.0, 0.0, 0.0], 9000.0),
+    ];
+    // A second and a half apart, so each window is over before the next punch.
+    let timed: Vec<_> = strokes
+        .iter()
+        .enumerate()
+        .map(|(i, s)| (200 + i * 600, *s))
+        .collect();
+    let found = punches(&detect(&readings(FLAT, 3000, &timed, 4)));
+    assert_eq!(found.len(), strokes.len(), "punches");
+    let mut best = SessionBest::new();
+    assert_eq!(best.best(), None);
+    let mut records = Vec::new();
+    for (punch, stroke) in found.iter().zip(&strokes) {
+        assert_matches(&format!("{}", stroke.peak_mg), punch, stroke);
+        records.push(best.record(*punch));
+    }
+    assert_eq!(records, vec![true, true, false, true], "new bests");
+    assert_eq!(best.best(), Some(found[3]));
+    // Equalling the best doesn't replace it.
+    let equal = Punch {
+        delta_v: 0.0,
+        ..found[3]
+    };
+    assert!(!best.record(equal), "equal");
+    assert_eq!(best.best(), Some(found[3]));
+}
+
+#[test]
+fn analysis() {
+    let magnitudes = [1600, 2500, 4000, 3000, 1400, 200, 1700, 0];
+    let punch = analyse(&magnitudes, SAMPLE_RATE_HZ, THRESHOLD_MG);
+    let sum = (1600 + 2500 + 4000 + 3000 + 1700) as f32;
+    assert_eq!(punch.peak_mg, 4000);
+    assert_eq!(punch.time_to_peak_ms, 5);
+    assert_eq!(punch.duration_ms, 12);
+    let delta_v = sum / 1000.0 * G / SAMPLE_RATE_HZ as f32;
+    assert!(
+        (punch.delta_v - delta_v).abs() <= 1e-6,
+        "delta-v {}, expected {}",
+        punch.delta_v,
+        delta_v
+    );
+    assert_eq!(
+        analyse(&[0; 4], SAMPLE_RATE_HZ, THRESHOLD_MG),
+        Punch {
+            peak_mg: 0,
+            time_to_peak_ms: 0,
+            duration_ms: 0,
+            delta_v: 0.0,
+        },
+    );
+}
@@ -26,7 +26,7 @@ impl Game {
     pub fn new(rng: &mut Rng) -> Self {
         let mut rng = Prng::seeded(rng);
         let snake = Snake::make_snake();
-        let food_coords = Coords::random(&mut rng, Some(&snake.coord_set));
+        let food_coords = Coords::random(&mut rng, Some(&snake.coord_set)).unwrap();
         Self {
             rng,
             snake,
@@ -46,9 +46,10 @@ impl Game {
         self.score = 0;
     }
 
-    /// Randomly place food on the grid.
+    /// Randomly place food on the grid. There is always somewhere to put it: the game
+    /// is won before the snake can fill the grid.
     fn place_food(&mut self) -> Coords {
-        let coords = Coords::random(&mut self.rng, Some(&self.snake.coord_set));
+        let coords = Coords::random(&mut self.rng, Some(&self.snake.coord_set)).unwrap();
         self.food_coords = coords;
         coords
     }
@@ -1,5 +1,7 @@
 use super::Prng;
 
+use random::Random;
+
 use heapless::FnvIndexSet;
 
 /// A single point on the grid.
@@ -12,20 +14,21 @@ pub struct Coords {
 }
 
 impl Coords {
-    /// Get random coordinates within a grid. `exclude` is an optional set of
-    /// coordinates which should be excluded from the output.
-    pub fn random(rng: &mut Prng, exclude: Option<&FnvIndexSet<Coords, 32>>) -> Self {
-        let mut coords = Coords {
-            row: ((rng.random_u32() as usize) % 5) as i8,
-            col: ((rng.random_u32() as usize) % 5) as i8,
-        };
-        while exclude.is_some_and(|exc| exc.contains(&coords)) {
-            coords = Coords {
-                row: ((rng.random_u32() as usize) % 5) as i8,
-                col: ((rng.random_u32() as usize) % 5) as i8,
-            }
-        }
-        coords
+    /// Get random coordinates within a grid, all equally likely. `exclude` is an
+    /// optional set of coordinates which should be excluded from the output. Returns
+    /// `None` if every point is excluded.
+    pub fn random(rng: &mut Prng, exclude: Option<&FnvIndexSet<Coords, 32>>) -> Option<Self> {
+        rng.free_cell(5, 5, |row, col| {
+            let coords = Coords {
+                row: row as i8,
+                col: col as i8,
+            };
+            !exclude.is_some_and(|exc| exc.contains(&coords))
+        })
+        .map(|(row, col)| Coords {
+            row: row as i8,
+            col: col as i8,
+        })
     }
 
     /// Whether the point is outside the bounds of the grid.
@@ -1,3 +1,5 @@
+use random::Random;
+
 use crate::Rng;
 
 /// A basic pseudo-random number generator.
@@ -28,3 +30,9 @@ impl Prng {
         self.value
     }
 }
+
+impl Random for Prng {
+    fn next_u32(&mut self) -> u32 {
+        self.random_u32()
+    }
+}
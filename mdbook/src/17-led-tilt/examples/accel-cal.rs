@@ -0,0 +1,152 @@
+//! Calibrate the accelerometer and save the result for the tilt game.
+//!
+//! The display asks for each of the board's six positions in turn: an arrow points at the edge
+//! to stand uppermost, a square in the middle means lay it LEDs up, and a square round the edge
+//! LEDs down. Hold the board still in each position until its picture lights up fully. Once all
+//! six are done a tick means the calibration is saved and the game will use it; a cross means the
+//! readings didn't add up, and it starts again.
+
+#![deny(unsafe_code)]
+#![no_main]
+#![no_std]
+
+use cortex_m_rt::entry;
+use panic_rtt_target as _;
+use rtt_target::{rprintln, rtt_init_print};
+
+use microbit::{
+    hal::{twim, Timer},
+    pac::{self, interrupt, twim0::frequency::FREQUENCY_A, TIMER1, TWIM0},
+};
+
+use led_matrix::{mb2::SharedDisplay, Frame, MAX_BRIGHTNESS};
+use lsm303agr::{
+    interface::I2cInterface, mode::MagOneShot, AccelMode, AccelOutputDataRate, Interrupt,
+    Lsm303agr,
+};
+use sensors::{
+    accel_cal::Capture,
+    attitude::Orientation,
+    mb2::{int1_active_low, InterruptSampler},
+};
+use settings::{keys, Settings};
+
+/// Accelerometer readings per second.
+const SAMPLE_RATE_HZ: u32 = 50;
+/// Readings averaged in each position: a second's worth.
+const SAMPLES: u32 = SAMPLE_RATE_HZ;
+/// The prompt blinks on and off for this many readings each.
+const BLINK_TICKS: u32 = 15;
+/// How long the cross stays up, in readings.
+const CROSS_TICKS: u32 = 2 * SAMPLE_RATE_HZ;
+const PROMPT_BRIGHTNESS: u8 = 3;
+
+type Sensor = Lsm303agr<I2cInterface<twim::Twim<TWIM0>>, MagOneShot>;
+
+static SAMPLER: InterruptSampler<Sensor, 8> = InterruptSampler::new();
+static DISPLAY: SharedDisplay<TIMER1> = SharedDisplay::new();
+
+#[pac::interrupt]
+fn GPIOTE() {
+    SAMPLER.on_interrupt();
+}
+
+#[pac::interrupt]
+fn TIMER1() {
+    DISPLAY.on_interrupt();
+}
+
+/// The picture asking for `face`, lit at `brightness`.
+fn prompt(face: Orientation, brightness: u8) -> Frame {
+    let rows: [u8; 5] = match face {
+        Orientation::FaceUp => [0b00000, 0b01110, 0b01110, 0b01110, 0b00000],
+        Orientation::FaceDown => [0b11111, 0b10001, 0b10001, 0b10001, 0b11111],
+        Orientation::LogoUp => [0b00100, 0b01110, 0b10101, 0b00100, 0b00100],
+        Orientation::LogoDown => [0b00100, 0b00100, 0b10101, 0b01110, 0b00100],
+        Orientation::LeftUp => [0b00100, 0b01000, 0b11111, 0b01000, 0b00100],
+        Orientation::RightUp => [0b00100, 0b00010, 0b11111, 0b00010, 0b00100],
+    };
+    picture(rows, brightness)
+}
+
+/// Turn five rows of bits, leftmost LED in the top bit, into a frame.
+fn picture(rows: [u8; 5], brightness: u8) -> Frame {
+    rows.map(|bits| core::array::from_fn(|c| brightness * ((bits >> (4 - c)) & 1)))
+}
+
+const TICK: [u8; 5] = [0b00000, 0b00001, 0b00010, 0b10100, 0b01000];
+const CROSS: [u8; 5] = [0b10001, 0b01010, 0b00100, 0b01010, 0b10001];
+
+#[entry]
+fn main() -> ! {
+    rtt_init_print!();
+    let board = microbit::Board::take().unwrap();
+
+    let mut i2c = { twim::Twim::new(board.TWIM0, board.i2c_internal.into(), FREQUENCY_A::K100) };
+    let mut timer = Timer::new(board.TIMER0);
+    DISPLAY.start(board.TIMER1, board.display_pins);
+    let mut settings = Settings::new(settings::flash(board.NVMC), 0);
+
+    // Calibrate in the same mode the game reads the accelerometer in.
+    int1_active_low(&mut i2c).unwrap();
+    let mut sensor = Lsm303agr::new_with_i2c(i2c);
+    sensor.init().unwrap();
+    sensor
+        .set_accel_mode_and_odr(
+            &mut timer,
+            AccelMode::HighResolution,
+            AccelOutputDataRate::Hz50,
+        )
+        .unwrap();
+    sensor.acc_enable_interrupt(Interrupt::DataReady1).unwrap();
+    let int_pin = board.pins.p0_25.into_floating_input().degrade();
+    SAMPLER.start(board.GPIOTE, int_pin, sensor);
+
+    loop {
+        let mut capture = Capture::new(SAMPLES);
+        let mut ticks = 0;
+        while let Some(next) = capture.next_face() {
+            let accel = SAMPLER.next();
+            ticks += 1;
+            if let Some(face) = capture.update(accel) {
+                rprintln!("{:?}: {:?}", face, capture.average(face).unwrap());
+            }
+            let frame = match capture.holding() {
+                // Fill in the picture of whichever position the board is held in.
+                Some((face, progress)) if !capture.is_captured(face) => {
+                    let brightness = PROMPT_BRIGHTNESS
+                        + ((MAX_BRIGHTNESS - PROMPT_BRIGHTNESS) as f32 * progress) as u8;
+                    prompt(face, brightness)
+                }
+                _ if (ticks / BLINK_TICKS).is_multiple_of(2) => prompt(next, PROMPT_BRIGHTNESS),
+                _ => [[0; 5]; 5],
+            };
+            DISPLAY.show(&frame);
+        }
+
+        match capture.calibration().unwrap() {
+            Ok(calibration) => {
+                rprintln!(
+                    "offset {:?} mg, gain {:?}",
+                    calibration.offset(),
+                    calibration.gain()
+                );
+                settings
+                    .write(keys::ACCEL_CALIBRATION, &calibration.to_bytes())
+                    .unwrap();
+                DISPLAY.show(&picture(TICK, MAX_BRIGHTNESS));
+                break;
+            }
+            Err(e) => {
+                rprintln!("calibration failed: {:?}", e);
+                DISPLAY.show(&picture(CROSS, MAX_BRIGHTNESS));
+                for _ in 0..CROSS_TICKS {
+                    SAMPLER.next();
+                }
+            }
+        }
+    }
+    loop {
+        SAMPLER.next();
+    }
+}
@@ -0,0 +1,10 @@
+#![no_std]
+
+//! Marble physics and mazes for the LED tilt app, kept apart from the hardware so they can be
+//! run anywhere: `cargo test-host -p led-tilt` tries them out on the host.
+
+mod marble;
+pub mod maze;
+
+pub use marble::{Edges, Marble, Physics, Walls};
+pub use maze::{Maze, Viewport};
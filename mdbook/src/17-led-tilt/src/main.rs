@@ -2,30 +2,111 @@
 #![no_main]
 #![no_std]
 
+use core::fmt::Write;
+
 use cortex_m_rt::entry;
-use embedded_hal::{delay::DelayNs, digital::OutputPin};
 use panic_rtt_target as _;
-use rtt_target::rtt_init_print;
+use rtt_target::{rprintln, rtt_init_print};
 
 use microbit::{
-    display::blocking::Display,
-    hal::{gpio, twim, Timer},
-    pac::twim0::frequency::FREQUENCY_A,
+    hal::{
+        gpio,
+        pwm::{Channel, Prescaler, Pwm},
+        twim, Timer,
+    },
+    pac::{self, interrupt, twim0::frequency::FREQUENCY_A, TIMER1, TWIM0},
+};
+
+use led_matrix::{mb2::SharedDisplay, Scroller};
+use led_tilt::{
+    maze::{cell_at, LEVELS},
+    Marble, Maze, Physics, Viewport,
+};
+use lsm303agr::{
+    interface::I2cInterface, mode::MagOneShot, AccelMode, AccelOutputDataRate, Interrupt,
+    Lsm303agr,
 };
+use sensors::{
+    accel_cal::{AccelCalibration, Calibrated},
+    attitude::{AttitudeEstimator, Filter},
+    mb2::{int1_active_low, InterruptSampler},
+};
+use settings::{keys, Settings};
+
+/// Accelerometer readings per second; each one moves the game on by a tick.
+const SAMPLE_RATE_HZ: u32 = 50;
+/// How quickly the tilt the marble feels follows the board, in seconds. Short, so the marble
+/// still answers at once, but long enough to smooth out a knock on the table.
+const TILT_TIME_CONSTANT: f32 = 0.05;
+/// The goal blinks on and off for this many ticks each.
+const BLINK_TICKS: u32 = 15;
+/// Ticks per column when scrolling the time.
+const SCROLL_TICKS: u32 = 4;
+
+// Sound configuration for the goal beep
+const BEEP_FREQUENCY_HZ: u32 = 440; // A4 note
+const BEEP_TICKS: u32 = 10; // 200 ms
+const PWM_MAX_DUTY: u16 = (16_000_000 / BEEP_FREQUENCY_HZ) as u16;
+
+/// The accelerometer, corrected by the calibration the `accel-cal` example saved, if any.
+type Sensor = Calibrated<Lsm303agr<I2cInterface<twim::Twim<TWIM0>>, MagOneShot>>;
+
+/// Accelerometer readings, queued by the data-ready interrupt.
+static SAMPLER: InterruptSampler<Sensor, 8> = InterruptSampler::new();
+
+/// The display, refreshed from its timer interrupt so it can show shades of grey.
+static DISPLAY: SharedDisplay<TIMER1> = SharedDisplay::new();
+
+#[pac::interrupt]
+fn GPIOTE() {
+    SAMPLER.on_interrupt();
+}
+
+#[pac::interrupt]
+fn TIMER1() {
+    DISPLAY.on_interrupt();
+}
 
-use lsm303agr::{AccelMode, AccelOutputDataRate, Lsm303agr};
+/// The accelerometer calibration saved in flash, or none at all if there isn't one.
+fn stored_calibration(nvmc: pac::NVMC) -> AccelCalibration {
+    let mut settings = Settings::new(settings::flash(nvmc), 0);
+    let mut bytes = [0; AccelCalibration::BYTES];
+    let stored = match settings.read(keys::ACCEL_CALIBRATION, &mut bytes).unwrap() {
+        Some(AccelCalibration::BYTES) => AccelCalibration::from_bytes(&bytes),
+        _ => None,
+    };
+    match stored {
+        Some(calibration) => {
+            rprintln!("accelerometer calibration: {:?}", calibration);
+            calibration
+        }
+        None => {
+            rprintln!("accelerometer not calibrated: run the accel-cal example");
+            AccelCalibration::default()
+        }
+    }
+}
 
 #[entry]
 fn main() -> ! {
     rtt_init_print!();
     let board = microbit::Board::take().unwrap();
 
-    let i2c = { twim::Twim::new(board.TWIM0, board.i2c_internal.into(), FREQUENCY_A::K100) };
+    let mut i2c = { twim::Twim::new(board.TWIM0, board.i2c_internal.into(), FREQUENCY_A::K100) };
     let mut timer = Timer::new(board.TIMER0);
-    let mut display = Display::new(board.display_pins);
-    let mut speaker_pin = board.speaker_pin.into_push_pull_output(gpio::Level::Low);
+    DISPLAY.start(board.TIMER1, board.display_pins);
+
+    // A square wave on the speaker, silent until the duty cycle is turned up
+    let speaker_pin = board.speaker_pin.into_push_pull_output(gpio::Level::Low);
+    let speaker = Pwm::new(board.PWM0);
+    speaker.set_prescaler(Prescaler::Div1);
+    speaker.set_max_duty(PWM_MAX_DUTY);
+    speaker.set_output_pin(Channel::C0, speaker_pin.degrade());
+    speaker.set_duty_on(Channel::C0, 0);
+    speaker.enable();
 
     // Initialize accelerometer
+    int1_active_low(&mut i2c).unwrap();
     let mut sensor = Lsm303agr::new_with_i2c(i2c);
     sensor.init().unwrap();
     sensor
@@ -35,71 +116,67 @@ fn main() -> ! {
             AccelOutputDataRate::Hz50,
         )
         .unwrap();
-
-    // LED grid (5x5)
-    let mut leds = [[0u8; 5]; 5];
-
-    // Starting position (center of the grid)
-    let mut led_row: i8 = 2;
-    let mut led_col: i8 = 2;
-
-    // Tilt threshold in milliG (1000 mg = 1g)
-    // Adjust this value to make it more or less sensitive
-    const TILT_THRESHOLD: i32 = 100;
-
-    // LED display refresh time in milliseconds
-    // Lower values make it more responsive, higher values save power
-    const DISPLAY_TIME_MS: u32 = 75;
-
-    // Sound configuration for center position beep
-    const BEEP_FREQUENCY_HZ: u32 = 440; // A4 note
-    const BEEP_DURATION_MS: u32 = 50;   // Short beep
+    // Signal each new reading on INT1, and collect it from the interrupt handler.
+    sensor.acc_enable_interrupt(Interrupt::DataReady1).unwrap();
+    let int_pin = board.pins.p0_25.into_floating_input().degrade();
+    SAMPLER.start(
+        board.GPIOTE,
+        int_pin,
+        Calibrated::new(sensor, stored_calibration(board.NVMC)),
+    );
+
+    let physics = Physics::default();
+    let dt = 1.0 / SAMPLE_RATE_HZ as f32;
+    let mut attitude = AttitudeEstimator::new(Filter {
+        time_constant_s: TILT_TIME_CONSTANT,
+        ..Filter::default()
+    });
+    let mut level = 0;
 
     loop {
-        // Wait for new accelerometer data
-        while !sensor.accel_status().unwrap().xyz_new_data() {
-            timer.delay_ms(1u32);
+        let maze = Maze::parse(LEVELS[level]).unwrap();
+        let (row, col) = maze.start();
+        let mut marble = Marble::new(col as f32, row as f32);
+        let mut view = Viewport::centred(&maze, maze.start());
+        let mut ticks = 0;
+
+        // Roll until the marble reaches the goal
+        while !maze.reached_goal(marble.x, marble.y) {
+            // Sleep until there is new accelerometer data, and tilt by whichever way is down
+            attitude.update(SAMPLER.next(), dt);
+            let (x, y, _z) = attitude.gravity().unwrap();
+            ticks += 1;
+
+            // X axis: positive = tilt right, which rolls the marble right
+            // Y axis: positive = tilt forward (toward top edge), which rolls it up the display
+            marble.step(&physics, (x, -y), dt, &maze);
+
+            view.follow(&maze, cell_at(marble.x, marble.y));
+            let goal_lit = (ticks / BLINK_TICKS).is_multiple_of(2);
+            DISPLAY.show(&maze.draw(&view, &marble, goal_lit));
         }
 
-        // Read acceleration values
-        let (x, y, _z) = sensor.acceleration().unwrap().xyz_mg();
-
-        // Clear current LED position
-        leds[led_row as usize][led_col as usize] = 0u8;
-
-        // Update position based on tilt
-        // X axis: positive = tilt right, negative = tilt left
-        if x > TILT_THRESHOLD {
-            led_col = (led_col + 1).min(4); // Move right, max column is 4
-        } else if x < -TILT_THRESHOLD {
-            led_col = (led_col - 1).max(0); // Move left, min column is 0
-        }
-
-        // Y axis: positive = tilt forward (toward top edge), negative = tilt back
-        // Row 0 is at the top, row 4 is at the bottom
-        if y > TILT_THRESHOLD {
-            led_row = (led_row - 1).max(0); // Move up, min row is 0
-        } else if y < -TILT_THRESHOLD {
-            led_row = (led_row + 1).min(4); // Move down, max row is 4
-        }
-
-        // Set new LED position
-        leds[led_row as usize][led_col as usize] = 255u8;
-
-        // Play a beep if LED is at center position
-        if led_row == 2 && led_col == 2 {
-            let period_us = 1_000_000 / BEEP_FREQUENCY_HZ;
-            let cycles = (BEEP_DURATION_MS * 1000) / period_us;
-
-            for _ in 0..cycles {
-                speaker_pin.set_high().unwrap();
-                timer.delay_us(period_us / 2);
-                speaker_pin.set_low().unwrap();
-                timer.delay_us(period_us / 2);
+        // Beep, and scroll the time taken in seconds
+        let tenths = ticks * 10 / SAMPLE_RATE_HZ;
+        rprintln!("level {} in {}.{} s", level + 1, tenths / 10, tenths % 10);
+        let mut time = Scroller::new();
+        write!(time, "{}.{}", tenths / 10, tenths % 10).unwrap();
+
+        speaker.set_duty_on(Channel::C0, PWM_MAX_DUTY / 2);
+        let mut ticks = 0;
+        while let Some(frame) = time.frame() {
+            SAMPLER.next();
+            ticks += 1;
+            if ticks == BEEP_TICKS {
+                speaker.set_duty_on(Channel::C0, 0);
+            }
+            if ticks % SCROLL_TICKS == 0 {
+                time.advance();
             }
+            DISPLAY.show(&frame);
         }
+        speaker.set_duty_on(Channel::C0, 0);
 
-        // Display the LED grid
-        display.show(&mut timer, leds, DISPLAY_TIME_MS);
+        level = (level + 1) % LEVELS.len();
     }
 }
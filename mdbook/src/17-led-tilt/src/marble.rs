@@ -0,0 +1,135 @@
+//! A marble rolling on a tilted board.
+
+/// How the marble responds to tilt.
+#[derive(Debug, Clone, Copy)]
+pub struct Physics {
+    /// Acceleration, in LEDs/s², when the board is tilted on its side (1 g).
+    pub gain: f32,
+    /// Fraction of its speed the marble loses per second to rolling resistance.
+    pub drag: f32,
+    /// Fraction of its speed the marble keeps when it bounces off a wall.
+    pub restitution: f32,
+    /// Tilts smaller than this, in mg, are ignored, so that a board lying not quite flat holds
+    /// the marble still.
+    pub dead_zone_mg: i32,
+}
+
+impl Default for Physics {
+    fn default() -> Self {
+        Self {
+            gain: 20.0,
+            drag: 1.5,
+            restitution: 0.5,
+            dead_zone_mg: 50,
+        }
+    }
+}
+
+/// The marble's position and velocity, in LEDs and LEDs per second from the top left.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Marble {
+    pub x: f32,
+    pub y: f32,
+    pub vx: f32,
+    pub vy: f32,
+}
+
+/// Hitting something slower than this, in LEDs per second, stops the marble dead rather than
+/// bouncing it, so that a marble resting against a wall stays put.
+const MIN_BOUNCE_SPEED: f32 = 0.5;
+/// The furthest the marble moves in one step, in LEDs, so that it can't jump through a wall.
+const MAX_STEP: f32 = 0.5;
+
+/// Whatever the marble can't roll through.
+pub trait Walls {
+    /// Whether the marble would overlap a wall with its centre at `(x, y)`.
+    fn blocked(&self, x: f32, y: f32) -> bool;
+}
+
+/// Just the edges of the rectangle `0..=max_x` by `0..=max_y`, with a marble too small to matter.
+#[derive(Debug, Clone, Copy)]
+pub struct Edges {
+    pub max_x: f32,
+    pub max_y: f32,
+}
+
+impl Walls for Edges {
+    fn blocked(&self, x: f32, y: f32) -> bool {
+        !(0.0..=self.max_x).contains(&x) || !(0.0..=self.max_y).contains(&y)
+    }
+}
+
+/// Move `p` on by `v` for `dt` seconds unless that would hit something, in which case get as
+/// close as possible and bounce. Returns whether it bounced.
+fn advance(
+    p: &mut f32,
+    v: &mut f32,
+    dt: f32,
+    restitution: f32,
+    blocked: impl Fn(f32) -> bool,
+) -> bool {
+    let mut step = (*v * dt).clamp(-MAX_STEP, MAX_STEP);
+    if !blocked(*p + step) {
+        *p += step;
+        return false;
+    }
+    // Close in on whatever is in the way.
+    for _ in 0..4 {
+        step /= 2.0;
+        if !blocked(*p + step) {
+            *p += step;
+        }
+    }
+    if v.abs() < MIN_BOUNCE_SPEED {
+        *v = 0.0;
+        return false;
+    }
+    *v = -*v * restitution;
+    true
+}
+
+impl Marble {
+    /// A marble at rest at `(x, y)`.
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self {
+            x,
+            y,
+            vx: 0.0,
+            vy: 0.0,
+        }
+    }
+
+    /// Roll for `dt` seconds with the board tilted by `tilt_mg`, in display directions: x to the
+    /// right and y down, without going through `walls`. Returns whether the marble bounced off
+    /// something.
+    pub fn step(
+        &mut self,
+        physics: &Physics,
+        (tilt_x, tilt_y): (i32, i32),
+        dt: f32,
+        walls: &impl Walls,
+    ) -> bool {
+        let accel = |tilt: i32| {
+            if tilt.abs() < physics.dead_zone_mg {
+                0.0
+            } else {
+                tilt as f32 / 1000.0 * physics.gain
+            }
+        };
+        // Semi-implicit Euler: the new velocity moves the marble, which keeps it stable.
+        let keep = (1.0 - physics.drag * dt).max(0.0);
+        self.vx = (self.vx + accel(tilt_x) * dt) * keep;
+        self.vy = (self.vy + accel(tilt_y) * dt) * keep;
+
+        // One axis at a time, so that the marble slides along a wall it hits at an angle.
+        let y = self.y;
+        let bounced_x = advance(&mut self.x, &mut self.vx, dt, physics.restitution, |x| {
+            walls.blocked(x, y)
+        });
+        let x = self.x;
+        let bounced_y = advance(&mut self.y, &mut self.vy, dt, physics.restitution, |y| {
+            walls.blocked(x, y)
+        });
+        bounced_x || bounced_y
+    }
+}
@@ -0,0 +1,224 @@
+//! Mazes to roll the marble through, bigger than the display.
+
+use core::fmt;
+
+use led_matrix::{dot, Frame, MAX_BRIGHTNESS};
+use libm::floorf;
+
+use crate::marble::{Marble, Walls};
+
+/// The marble's radius, in LEDs. It has to line up to within this much of the middle of a
+/// corridor to turn into it.
+pub const MARBLE_RADIUS: f32 = 0.25;
+
+const WALL_BRIGHTNESS: u8 = 2;
+const GOAL_BRIGHTNESS: u8 = 5;
+
+/// The levels, in the order they are played.
+///
+/// `#` is a wall, `S` is where the marble starts, `G` is the goal, and anything else is floor.
+/// Past the edges is all wall.
+pub const LEVELS: &[&[&str]] = &[
+    &[
+        "#########",
+        "#S..#...#",
+        "###.#.#.#",
+        "#...#.#.#",
+        "#.###.#.#",
+        "#.....#G#",
+        "#########",
+    ],
+    &[
+        "###########",
+        "#S....#...#",
+        "#####.#.#.#",
+        "#.....#.#.#",
+        "#.#####.#.#",
+        "#.#.....#.#",
+        "#.#.#####.#",
+        "#...#...#.#",
+        "#####.#.#.#",
+        "#G....#...#",
+        "###########",
+    ],
+    &[
+        "###############",
+        "#S#.....#.....#",
+        "#.#.###.#.###.#",
+        "#.#.#...#...#.#",
+        "#.#.#.#####.#.#",
+        "#...#.....#.#G#",
+        "#####.###.#.###",
+        "#.............#",
+        "###############",
+    ],
+];
+
+/// What is wrong with a level's layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MazeError {
+    /// The level has no rows.
+    Empty,
+    /// A row is a different length from the first.
+    Ragged { row: usize },
+    /// There isn't exactly one start.
+    Start,
+    /// There isn't exactly one goal.
+    Goal,
+}
+
+impl fmt::Display for MazeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MazeError::Empty => write!(f, "no rows"),
+            MazeError::Ragged { row } => write!(f, "row {} is the wrong length", row),
+            MazeError::Start => write!(f, "needs exactly one start"),
+            MazeError::Goal => write!(f, "needs exactly one goal"),
+        }
+    }
+}
+
+/// The cell containing the point `(x, y)`. Cell `(row, col)` is centred on `x = col, y = row`.
+pub fn cell_at(x: f32, y: f32) -> (i32, i32) {
+    (floorf(y + 0.5) as i32, floorf(x + 0.5) as i32)
+}
+
+/// A checked level.
+#[derive(Debug, Clone, Copy)]
+pub struct Maze {
+    rows: &'static [&'static str],
+    start: (i32, i32),
+    goal: (i32, i32),
+}
+
+impl Maze {
+    pub fn parse(rows: &'static [&'static str]) -> Result<Self, MazeError> {
+        let width = rows.first().ok_or(MazeError::Empty)?.len();
+        let (mut start, mut goal) = (None, None);
+        for (r, row) in rows.iter().enumerate() {
+            if row.len() != width {
+                return Err(MazeError::Ragged { row: r });
+            }
+            for (c, b) in row.bytes().enumerate() {
+                let found = match b {
+                    b'S' => (&mut start, MazeError::Start),
+                    b'G' => (&mut goal, MazeError::Goal),
+                    _ => continue,
+                };
+                if found.0.replace((r as i32, c as i32)).is_some() {
+                    return Err(found.1);
+                }
+            }
+        }
+        Ok(Self {
+            rows,
+            start: start.ok_or(MazeError::Start)?,
+            goal: goal.ok_or(MazeError::Goal)?,
+        })
+    }
+
+    pub fn width(&self) -> i32 {
+        self.rows[0].len() as i32
+    }
+
+    pub fn height(&self) -> i32 {
+        self.rows.len() as i32
+    }
+
+    /// The starting cell, as `(row, col)`.
+    pub fn start(&self) -> (i32, i32) {
+        self.start
+    }
+
+    /// The goal cell, as `(row, col)`.
+    pub fn goal(&self) -> (i32, i32) {
+        self.goal
+    }
+
+    pub fn is_wall(&self, row: i32, col: i32) -> bool {
+        let Ok(r) = usize::try_from(row) else {
+            return true;
+        };
+        let Ok(c) = usize::try_from(col) else {
+            return true;
+        };
+        self.rows
+            .get(r)
+            .and_then(|row| row.as_bytes().get(c))
+            .is_none_or(|&b| b == b'#')
+    }
+
+    /// What the display shows through `view`: the walls dimly, the goal when `goal_lit`, so it
+    /// can blink, and the marble at full brightness.
+    pub fn draw(&self, view: &Viewport, marble: &Marble, goal_lit: bool) -> Frame {
+        let mut frame = [[0; 5]; 5];
+        for (r, row) in frame.iter_mut().enumerate() {
+            for (c, led) in row.iter_mut().enumerate() {
+                let cell = (view.row + r as i32, view.col + c as i32);
+                if self.is_wall(cell.0, cell.1) {
+                    *led = WALL_BRIGHTNESS;
+                } else if cell == self.goal && goal_lit {
+                    *led = GOAL_BRIGHTNESS;
+                }
+            }
+        }
+        let (x, y) = view.to_display(marble.x, marble.y);
+        dot(&mut frame, x, y, MAX_BRIGHTNESS);
+        frame
+    }
+
+    /// Whether the marble centred at `(x, y)` is in the goal cell.
+    pub fn reached_goal(&self, x: f32, y: f32) -> bool {
+        cell_at(x, y) == self.goal
+    }
+}
+
+impl Walls for Maze {
+    fn blocked(&self, x: f32, y: f32) -> bool {
+        // The marble is smaller than a cell, so it can only overlap the cells its corners are in.
+        [-MARBLE_RADIUS, MARBLE_RADIUS].into_iter().any(|dx| {
+            [-MARBLE_RADIUS, MARBLE_RADIUS].into_iter().any(|dy| {
+                let (row, col) = cell_at(x + dx, y + dy);
+                self.is_wall(row, col)
+            })
+        })
+    }
+}
+
+/// The part of a maze on the display: the 5×5 cells from `(row, col)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Viewport {
+    pub row: i32,
+    pub col: i32,
+}
+
+impl Viewport {
+    /// How close, in LEDs, the marble can get to the edge of the display before it scrolls.
+    const MARGIN: i32 = 1;
+
+    /// A viewport around the cell `(row, col)` of `maze`.
+    pub fn centred(maze: &Maze, (row, col): (i32, i32)) -> Self {
+        let mut view = Self {
+            row: row - 2,
+            col: col - 2,
+        };
+        view.follow(maze, (row, col));
+        view
+    }
+
+    /// Scroll, if need be, to keep the cell `(row, col)` away from the edges of the display,
+    /// without showing anything past the edges of `maze`.
+    pub fn follow(&mut self, maze: &Maze, (row, col): (i32, i32)) {
+        let scroll = |start: i32, at: i32, size: i32| {
+            let start = start.clamp(at - (4 - Self::MARGIN), at - Self::MARGIN);
+            start.clamp(0, (size - 5).max(0))
+        };
+        self.row = scroll(self.row, row, maze.height());
+        self.col = scroll(self.col, col, maze.width());
+    }
+
+    /// The position `(x, y)` in the maze, relative to the top left of the display.
+    pub fn to_display(&self, x: f32, y: f32) -> (f32, f32) {
+        (x - self.col as f32, y - self.row as f32)
+    }
+}
@@ -0,0 +1,369 @@
+//! Tests the tilt app's marble physics and mazes on the host.
+//!
+//! ```text
+//! cargo test-host -p led-tilt
+//! ```
+//!
+//! Each test rolls a [`Marble`] step by step, at the program's 50 steps a second, on a board
+//! tilted a known way, and compares where it ends up and how fast it is going with what friction
+//! and bouncing should do to it.
+//!
+//! The maze tests make sure every level can be played through, that the marble fits down the
+//! corridors and no further, and that the display scrolls to follow it without showing past
+//! the edges of the maze.
+
+use std::collections::VecDeque;
+
+use led_tilt::{
+    maze::{MazeError, LEVELS, MARBLE_RADIUS},
+    Edges, Marble, Maze, Physics, Viewport, Walls,
+};
+
+/// As in `src/main.rs`.
+const SAMPLE_RATE_HZ: u32 = 50;
+const DT: f32 = 1.0 / SAMPLE_RATE_HZ as f32;
+
+/// The display, as the marble sees it without a maze.
+const DISPLAY: Edges = Edges {
+    max_x: 4.0,
+    max_y: 4.0,
+};
+
+/// The most the accelerometer reads, in mg, at its ±16 g range.
+const MAX_TILT_MG: i32 = 16_000;
+
+fn speed(marble: &Marble) -> f32 {
+    marble.vx.hypot(marble.vy)
+}
+
+/// Roll `marble` for `steps` steps, returning how many times it bounced.
+fn roll(
+    marble: &mut Marble,
+    physics: &Physics,
+    tilt: (i32, i32),
+    steps: usize,
+    walls: &impl Walls,
+) -> usize {
+    (0..steps)
+        .filter(|_| marble.step(physics, tilt, DT, walls))
+        .count()
+}
+
+#[test]
+fn friction() {
+    let physics = Physics::default();
+    // Set rolling to the right on a level board, the marble slows down and stops. Each step
+    // keeps 1 - drag·dt of the speed, so it goes v / (drag·dt) · dt in all: v / drag.
+    let mut marble = Marble {
+        vx: 3.0,
+        ..Marble::new(0.0, 2.0)
+    };
+    let mut last = speed(&marble);
+    for step in 0..SAMPLE_RATE_HZ * 10 {
+        marble.step(&physics, (0, 0), DT, &DISPLAY);
+        assert!(
+            speed(&marble) <= last,
+            "speeding up on the level at step {}",
+            step
+        );
+        last = speed(&marble);
+    }
+    assert!(
+        speed(&marble) <= 1e-3,
+        "still rolling at {}",
+        speed(&marble)
+    );
+    assert!(
+        (marble.x - 3.0 / physics.drag).abs() <= 0.1,
+        "stopped at {}",
+        marble.x
+    );
+    assert_eq!(marble.y, 2.0, "off the line");
+
+    // A board lying not quite flat doesn't move a marble at rest...
+    let mut marble = Marble::new(2.0, 2.0);
+    roll(&mut marble, &physics, (40, -40), 250, &DISPLAY);
+    assert_eq!(marble, Marble::new(2.0, 2.0), "inside the dead zone");
+    // ...and a steady tilt only gets it up to the speed where friction takes away what the tilt
+    // adds.
+    let mut marble = Marble::new(0.0, 0.0);
+    let tilt = 200;
+    let mut physics = physics;
+    physics.dead_zone_mg = 0;
+    let edges = Edges {
+        max_x: 1000.0,
+        max_y: 1000.0,
+    };
+    roll(&mut marble, &physics, (tilt, 0), 250, &edges);
+    let accel = tilt as f32 / 1000.0 * physics.gain;
+    let keep = 1.0 - physics.drag * DT;
+    let top_speed = accel * DT * keep / (1.0 - keep);
+    assert!(
+        (marble.vx - top_speed).abs() <= 0.01,
+        "top speed {}, expected {}",
+        marble.vx,
+        top_speed
+    );
+}
+
+#[test]
+fn bouncing() {
+    // No friction and no tilt, so only the bounce changes the speed.
+    let physics = Physics {
+        drag: 0.0,
+        ..Physics::default()
+    };
+    for (wall, velocity, stop) in [
+        ("left", (-4.0, 0.0), (0.0, 2.0)),
+        ("right", (4.0, 0.0), (4.0, 2.0)),
+        ("top", (0.0, -4.0), (2.0, 0.0)),
+        ("bottom", (0.0, 4.0), (2.0, 4.0)),
+    ] {
+        let mut marble = Marble {
+            vx: velocity.0,
+            vy: velocity.1,
+            ..Marble::new(2.0, 2.0)
+        };
+        let mut steps = 0;
+        while !marble.step(&physics, (0, 0), DT, &DISPLAY) {
+            steps += 1;
+            assert!(steps <= SAMPLE_RATE_HZ, "{}: never got there", wall);
+        }
+        assert_eq!(
+            (marble.vx, marble.vy),
+            (
+                -velocity.0 * physics.restitution,
+                -velocity.1 * physics.restitution,
+            ),
+            "{} bounce",
+            wall
+        );
+        assert!(
+            (marble.x - stop.0).abs() <= 0.05 && (marble.y - stop.1).abs() <= 0.05,
+            "{} wall: stopped at {:?}",
+            wall,
+            (marble.x, marble.y)
+        );
+    }
+
+    // Rolling into a corner bounces off both walls at once.
+    let mut marble = Marble {
+        vx: 4.0,
+        vy: 4.0,
+        ..Marble::new(2.0, 2.0)
+    };
+    let bounces = roll(&mut marble, &physics, (0, 0), 30, &DISPLAY);
+    assert_eq!(bounces, 1, "corner bounces");
+    assert_eq!((marble.vx, marble.vy), (-2.0, -2.0), "off the corner");
+
+    // Held against a wall by the tilt, the marble comes to rest there instead of buzzing.
+    let mut marble = Marble::new(2.0, 2.0);
+    let physics = Physics::default();
+    roll(&mut marble, &physics, (500, 0), 250, &DISPLAY);
+    assert!(
+        (marble.x - 4.0).abs() <= 0.05,
+        "resting at {} rather than on the wall",
+        marble.x
+    );
+    assert_eq!(
+        roll(&mut marble, &physics, (500, 0), 50, &DISPLAY),
+        0,
+        "bounces while resting"
+    );
+}
+
+#[test]
+fn no_tunnelling() {
+    // One cell of wall between the start and the goal.
+    let maze = Maze::parse(&["#######", "#S.#.G#", "#######"]).unwrap();
+    let (row, col) = maze.start();
+    let physics = Physics::default();
+    // As hard as the accelerometer can read, towards the wall and then the far edge, for a few
+    // seconds each and with a step ten times as long as usual thrown in.
+    for (tilt, dt) in [
+        (MAX_TILT_MG, DT),
+        (MAX_TILT_MG, 10.0 * DT),
+        (-MAX_TILT_MG, DT),
+    ] {
+        let mut marble = Marble {
+            vx: tilt.signum() as f32 * 1000.0,
+            ..Marble::new(col as f32, row as f32)
+        };
+        for _ in 0..SAMPLE_RATE_HZ * 3 {
+            marble.step(&physics, (tilt, MAX_TILT_MG), dt, &maze);
+            assert!(
+                !maze.blocked(marble.x, marble.y) && marble.x <= 2.5,
+                "tilted {} mg every {} s: got through to {:?}",
+                tilt,
+                dt,
+                (marble.x, marble.y)
+            );
+        }
+    }
+    // The same, against the edges of the display.
+    for tilt in [(MAX_TILT_MG, MAX_TILT_MG), (-MAX_TILT_MG, -MAX_TILT_MG)] {
+        let mut marble = Marble {
+            vx: 1000.0,
+            vy: -1000.0,
+            ..Marble::new(2.0, 2.0)
+        };
+        for _ in 0..SAMPLE_RATE_HZ * 3 {
+            marble.step(&physics, tilt, DT, &DISPLAY);
+            assert!(
+                !DISPLAY.blocked(marble.x, marble.y),
+                "tilted {:?}: left the display at {:?}",
+                tilt,
+                marble
+            );
+        }
+    }
+}
+
+/// Whether the goal of `maze` can be reached from its start, one cell up, down, left or right
+/// at a time.
+fn solvable(maze: &Maze) -> bool {
+    let (width, height) = (maze.width(), maze.height());
+    let mut seen = vec![false; (width * height) as usize];
+    let mut queue = VecDeque::from([maze.start()]);
+    while let Some((row, col)) = queue.pop_front() {
+        if maze.is_wall(row, col)
+            || std::mem::replace(&mut seen[(row * width + col) as usize], true)
+        {
+            continue;
+        }
+        if (row, col) == maze.goal() {
+            return true;
+        }
+        queue.extend([
+            (row - 1, col),
+            (row + 1, col),
+            (row, col - 1),
+            (row, col + 1),
+        ]);
+    }
+    false
+}
+
+#[test]
+fn levels() {
+    for (i, level) in LEVELS.iter().enumerate() {
+        let maze = Maze::parse(level).unwrap_or_else(|e| panic!("level {}: {}", i + 1, e));
+        assert!(
+            solvable(&maze),
+            "level {}: no way from the start to the goal",
+            i + 1
+        );
+    }
+    // And a few that shouldn't load.
+    for (what, rows, expected) in [
+        ("no rows", &[][..], MazeError::Empty),
+        ("ragged", &["#S#", "#G"][..], MazeError::Ragged { row: 1 }),
+        ("no start", &["#.G#"][..], MazeError::Start),
+        ("two starts", &["SSG"][..], MazeError::Start),
+        ("no goal", &["#S.#"][..], MazeError::Goal),
+        ("two goals", &["GSG"][..], MazeError::Goal),
+    ] {
+        assert_eq!(Maze::parse(rows).err(), Some(expected), "{}", what);
+    }
+    let walled = Maze::parse(&["S#G"]).unwrap();
+    assert!(!solvable(&walled), "walled off");
+}
+
+#[test]
+fn walls() {
+    // Floor right up to the edges, which are all wall past them.
+    let maze = Maze::parse(&["S.", ".G"]).unwrap();
+    let (inside, outside) = (0.49 - MARBLE_RADIUS, 0.51 - MARBLE_RADIUS);
+    for (what, x, y, blocked) in [
+        ("top left", 0.0, 0.0, false),
+        ("bottom right", 1.0, 1.0, false),
+        ("between cells", 0.5, 0.5, false),
+        ("near the left edge", -inside, 0.0, false),
+        ("over the left edge", -outside, 0.0, true),
+        ("near the top edge", 0.0, -inside, false),
+        ("over the top edge", 0.0, -outside, true),
+        ("near the right edge", 1.0 + inside, 1.0, false),
+        ("over the right edge", 1.0 + outside, 1.0, true),
+        ("near the bottom edge", 1.0, 1.0 + inside, false),
+        ("over the bottom edge", 1.0, 1.0 + outside, true),
+        ("over a corner", -outside, -outside, true),
+        ("far away", -10.0, 20.0, true),
+    ] {
+        assert_eq!(maze.blocked(x, y), blocked, "{}", what);
+    }
+    // A wall in the middle keeps the marble a radius away from its cell on every side.
+    let maze = Maze::parse(&["S..", ".#.", "..G"]).unwrap();
+    let (near, far) = (0.5 - MARBLE_RADIUS + 0.01, 0.5 - MARBLE_RADIUS - 0.01);
+    for (what, x, y) in [
+        ("left of the wall", near, 1.0),
+        ("right of the wall", 2.0 - near, 1.0),
+        ("above the wall", 1.0, near),
+        ("below the wall", 1.0, 2.0 - near),
+    ] {
+        assert!(maze.blocked(x, y), "{}", what);
+    }
+    assert!(!(maze.blocked(far, 1.0)), "beside the wall");
+}
+
+#[test]
+fn scrolling() {
+    for level in LEVELS {
+        let maze = Maze::parse(level).unwrap();
+        let limit = |size: i32| (size - 5).max(0);
+        let mut view = Viewport::centred(&maze, maze.start());
+        // Walk every cell of the maze, row by row, as if the marble could go anywhere.
+        for row in 0..maze.height() {
+            for col in (0..maze.width()).chain((0..maze.width()).rev()) {
+                let before = view;
+                view.follow(&maze, (row, col));
+                let inside = |at: i32, start: i32| (0..5).contains(&(at - start));
+                assert!(
+                    (0..=limit(maze.height())).contains(&view.row)
+                        && (0..=limit(maze.width())).contains(&view.col),
+                    "{:?}: shows past the edge of the maze",
+                    view
+                );
+                assert!(
+                    inside(row, view.row) && inside(col, view.col),
+                    "{:?}: lost the marble at {:?}",
+                    view,
+                    (row, col)
+                );
+                // Away from the edges of the maze, it keeps a cell between the marble and the
+                // edge of the display, and doesn't scroll more than it needs to.
+                let clear = |at: i32, start: i32, size: i32| {
+                    (1..4).contains(&(at - start)) || at < 1 || at >= size - 1
+                };
+                assert!(
+                    clear(row, view.row, maze.height()) && clear(col, view.col, maze.width()),
+                    "{:?}: too close to the edge at {:?}",
+                    view,
+                    (row, col)
+                );
+                assert!(
+                    (view.row - before.row).abs() <= 1 && (view.col - before.col).abs() <= 1,
+                    "{:?} to {:?}: jumped at {:?}",
+                    before,
+                    view,
+                    (row, col)
+                );
+            }
+        }
+        // Centred on the far corner, it comes up against the bottom right of the maze.
+        let corner = (maze.height() - 1, maze.width() - 1);
+        assert_eq!(
+            Viewport::centred(&maze, corner),
+            Viewport {
+                row: limit(maze.height()),
+                col: limit(maze.width()),
+            },
+            "far corner"
+        );
+    }
+    // A maze smaller than the display never scrolls.
+    let small = Maze::parse(&["S.G"]).unwrap();
+    let mut view = Viewport::centred(&small, small.goal());
+    assert_eq!(view, Viewport::default(), "small maze");
+    view.follow(&small, (0, 2));
+    assert_eq!(view, Viewport::default(), "following in a small maze");
+}
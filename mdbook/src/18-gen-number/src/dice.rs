@@ -0,0 +1,257 @@
+//! Dice, their faces on the LED matrix, and the roll animation.
+
+use core::fmt::{self, Write};
+
+use led_matrix::{Frame, Scroller};
+
+/// Ticks per column when scrolling text.
+const SCROLL_TICKS: u32 = 4;
+/// Ticks each face of a pair stays up.
+const FACE_TICKS: u32 = 30;
+/// Ticks each face stays up while tumbling: the dice slow down as they settle.
+const TUMBLE_TICKS: [u32; 12] = [1, 1, 1, 2, 2, 2, 3, 3, 4, 5, 6, 8];
+
+/// What is being rolled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dice {
+    D4,
+    D6,
+    D8,
+    D10,
+    D12,
+    D20,
+    /// Two six-sided dice.
+    TwoD6,
+}
+
+impl Dice {
+    /// The next choice, in the order button A steps through them.
+    pub fn next(self) -> Self {
+        match self {
+            Dice::D4 => Dice::D6,
+            Dice::D6 => Dice::D8,
+            Dice::D8 => Dice::D10,
+            Dice::D10 => Dice::D12,
+            Dice::D12 => Dice::D20,
+            Dice::D20 => Dice::TwoD6,
+            Dice::TwoD6 => Dice::D4,
+        }
+    }
+
+    /// Sides on each die.
+    pub fn sides(self) -> u8 {
+        match self {
+            Dice::D4 => 4,
+            Dice::D6 | Dice::TwoD6 => 6,
+            Dice::D8 => 8,
+            Dice::D10 => 10,
+            Dice::D12 => 12,
+            Dice::D20 => 20,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Dice::D4 => "d4",
+            Dice::D6 => "d6",
+            Dice::D8 => "d8",
+            Dice::D10 => "d10",
+            Dice::D12 => "d12",
+            Dice::D20 => "d20",
+            Dice::TwoD6 => "2d6",
+        }
+    }
+}
+
+/// The pips for `value` from 1 to 9, laid out on a 3×3 grid. Anything else is blank.
+pub fn pips(value: u8) -> Frame {
+    let grid: &[(usize, usize)] = match value {
+        1 => &[(1, 1)],
+        2 => &[(0, 2), (2, 0)],
+        3 => &[(0, 2), (1, 1), (2, 0)],
+        4 => &[(0, 0), (0, 2), (2, 0), (2, 2)],
+        5 => &[(0, 0), (0, 2), (1, 1), (2, 0), (2, 2)],
+        6 => &[(0, 0), (0, 2), (1, 0), (1, 2), (2, 0), (2, 2)],
+        7 => &[(0, 0), (0, 2), (1, 0), (1, 1), (1, 2), (2, 0), (2, 2)],
+        8 => &[(0, 0), (0, 1), (0, 2), (1, 0), (1, 2), (2, 0), (2, 1), (2, 2)],
+        9 => &[
+            (0, 0),
+            (0, 1),
+            (0, 2),
+            (1, 0),
+            (1, 1),
+            (1, 2),
+            (2, 0),
+            (2, 1),
+            (2, 2),
+        ],
+        _ => &[],
+    };
+    let mut frame = [[0; 5]; 5];
+    for &(row, col) in grid {
+        frame[row * 2][col * 2] = 1;
+    }
+    frame
+}
+
+fn text(args: fmt::Arguments) -> Scroller {
+    let mut scroller = Scroller::new();
+    // Dice labels and totals are short enough to always fit.
+    let _ = scroller.write_fmt(args);
+    scroller
+}
+
+/// Something for the speaker to play.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sound {
+    /// The dice turned over to a new face.
+    Click,
+    /// The dice came to rest.
+    Landed,
+}
+
+/// Which part of a result is on show.
+enum Part {
+    /// The face of one of the dice.
+    Face(usize),
+    /// A number too big for pips, or the total of a pair.
+    Number(Scroller),
+}
+
+enum State {
+    /// The name of the selected dice, scrolling over and over.
+    Label(Scroller),
+    /// Showing random faces, `step` into [`TUMBLE_TICKS`].
+    Tumbling { step: usize, face: u8 },
+    /// The dice have landed. The second value is only used for a pair.
+    Result { values: [u8; 2], part: Part },
+}
+
+/// The dice, and what the display should show of them.
+///
+/// Call [`tick`](Table::tick) at a steady rate; the animation timings are counted in ticks, and
+/// look right at about 50 per second.
+pub struct Table {
+    dice: Dice,
+    state: State,
+    /// Ticks until the next change.
+    wait: u32,
+}
+
+impl Table {
+    pub fn new(dice: Dice) -> Self {
+        Self {
+            dice,
+            state: State::Label(text(format_args!("{}", dice.label()))),
+            wait: SCROLL_TICKS,
+        }
+    }
+
+    /// Switch to the next kind of dice and show its name.
+    pub fn select_next(&mut self) {
+        *self = Self::new(self.dice.next());
+    }
+
+    /// Throw the dice. The result is decided when they land.
+    pub fn roll(&mut self) {
+        self.state = State::Tumbling { step: 0, face: 0 };
+        self.wait = 0;
+    }
+
+    pub fn is_rolling(&self) -> bool {
+        matches!(self.state, State::Tumbling { .. })
+    }
+
+    pub fn frame(&self) -> Frame {
+        match &self.state {
+            State::Label(scroller)
+            | State::Result {
+                part: Part::Number(scroller),
+                ..
+            } => scroller.frame().unwrap_or_default(),
+            State::Tumbling { face, .. } => pips(*face),
+            State::Result {
+                values,
+                part: Part::Face(i),
+            } => pips(values[*i]),
+        }
+    }
+
+    /// Move the animation on by one tick. `roll(sides)` must return a random number from 1 to
+    /// `sides`.
+    pub fn tick(&mut self, mut roll: impl FnMut(u8) -> u8) -> Option<Sound> {
+        if self.wait > 0 {
+            self.wait -= 1;
+            return None;
+        }
+        let dice = self.dice;
+        match &mut self.state {
+            State::Label(scroller) => {
+                scroller.advance();
+                if scroller.frame().is_none() {
+                    *scroller = text(format_args!("{}", dice.label()));
+                }
+                self.wait = SCROLL_TICKS;
+                None
+            }
+            State::Tumbling { step, .. } if *step == TUMBLE_TICKS.len() => {
+                let values = match dice {
+                    Dice::TwoD6 => [roll(6), roll(6)],
+                    _ => [roll(dice.sides()), 0],
+                };
+                self.show(values);
+                Some(Sound::Landed)
+            }
+            State::Tumbling { step, face } => {
+                // Faces past nine have no pips, so tumble through the ones that do, never
+                // showing the same face twice running.
+                let sides = dice.sides().min(9);
+                *face = loop {
+                    let next = roll(sides);
+                    if next != *face {
+                        break next;
+                    }
+                };
+                self.wait = TUMBLE_TICKS[*step];
+                *step += 1;
+                Some(Sound::Click)
+            }
+            State::Result { values, part } => {
+                let values = *values;
+                match part {
+                    Part::Face(0) if dice == Dice::TwoD6 => {
+                        *part = Part::Face(1);
+                        self.wait = FACE_TICKS;
+                    }
+                    Part::Face(1) => {
+                        *part = Part::Number(text(format_args!("{}", values[0] + values[1])));
+                        self.wait = SCROLL_TICKS;
+                    }
+                    // A single die stays put.
+                    Part::Face(_) => self.wait = FACE_TICKS,
+                    Part::Number(scroller) => {
+                        scroller.advance();
+                        self.wait = SCROLL_TICKS;
+                        if scroller.frame().is_none() {
+                            self.show(values);
+                        }
+                    }
+                }
+                None
+            }
+        }
+    }
+
+    /// Start showing `values` from the beginning: a pair face by face and then their total, a
+    /// single die as pips if it has few enough, and otherwise as a scrolling number.
+    fn show(&mut self, values: [u8; 2]) {
+        let part = if self.dice == Dice::TwoD6 || values[0] <= 9 {
+            self.wait = FACE_TICKS;
+            Part::Face(0)
+        } else {
+            self.wait = SCROLL_TICKS;
+            Part::Number(text(format_args!("{}", values[0])))
+        };
+        self.state = State::Result { values, part };
+    }
+}
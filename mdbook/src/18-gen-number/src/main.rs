@@ -2,30 +2,50 @@
 #![no_std]
 #![allow(static_mut_refs)]
 
-use cortex_m::asm;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use cortex_m::interrupt::free as interrupt_free;
 use cortex_m_rt::entry;
 use panic_rtt_target as _;
 
 use microbit::{
-    display::nonblocking::{Display, BitImage},
+    display::nonblocking::{BitImage, Display},
     hal::{
         gpiote,
-        pac::{self, interrupt, PWM0, TIMER0, TIMER1},
-        pwm::{Pwm, Channel},
+        pac::{self, interrupt, twim0::frequency::FREQUENCY_A, PWM0, TIMER0, TIMER1},
+        pwm::{Channel, Pwm},
         rng::Rng,
-        Timer,
+        twim, Timer,
     },
 };
 
+use lsm303agr::{AccelMode, AccelOutputDataRate, Lsm303agr};
+use random::Random;
+use sensors::{
+    gesture::{Gesture, GestureDetector},
+    Accelerometer,
+};
+
+mod dice;
+
+use dice::{Dice, Sound, Table};
+
 // Global state shared between interrupts and main
 static mut GPIOTE_PERIPHERAL: Option<gpiote::Gpiote> = None;
 static mut DISPLAY: Option<Display<TIMER1>> = None;
 static mut BEEP_PWM: Option<Pwm<PWM0>> = None;
 static mut BEEP_TIMER: Option<Timer<TIMER0>> = None;
-static mut RNG: Option<Rng> = None;
+
+// Button presses, set by the GPIOTE interrupt and cleared by main
+static BUTTON_A_PRESSED: AtomicBool = AtomicBool::new(false);
+static BUTTON_B_PRESSED: AtomicBool = AtomicBool::new(false);
+
+// Accelerometer readings per second. Each reading is one tick of the dice animation.
+const SAMPLE_RATE_HZ: u32 = 50;
 
 // Sound configuration
 const BEEP_DURATION_MS: u32 = 100;
+const CLICK_DURATION_MS: u32 = 5;
 const BEEP_HZ: u32 = 440; // A4 note
 const PWM_MAX_DUTY: u16 = (16_000_000 / BEEP_HZ) as u16; // 36364 for 440Hz
 const PWM_DUTY_BEEP_ON: u16 = PWM_MAX_DUTY / 2; // 50% duty cycle
@@ -37,22 +57,15 @@ fn GPIOTE() {
     // SAFETY: Interrupts are not re-entrant. Interrupts with same priority cannot preempt each other.
     // Sequential execution among interrupts.
     unsafe {
-        let random_byte = RNG.as_mut().unwrap().random_u8();
-        let rand_val = (random_byte % 6) + 1;
-
-        update_display(rand_val);
-
         let gpiote = GPIOTE_PERIPHERAL.as_mut().unwrap();
+        if gpiote.channel0().is_event_triggered() {
+            BUTTON_A_PRESSED.store(true, Ordering::Relaxed);
+        }
+        if gpiote.channel1().is_event_triggered() {
+            BUTTON_B_PRESSED.store(true, Ordering::Relaxed);
+        }
         gpiote.channel0().reset_events();
         gpiote.channel1().reset_events();
-
-        // Turn on beep
-        BEEP_PWM.as_mut().unwrap().set_duty_on(Channel::C0, PWM_DUTY_BEEP_ON);
-
-        // Start beep timer
-        let timer = BEEP_TIMER.as_mut().unwrap();
-        timer.start(BEEP_DURATION_MS * 1000u32);
-        timer.enable_interrupt();
     }
 }
 
@@ -75,13 +88,28 @@ fn TIMER1() {
     }
 }
 
-fn update_display(value: u8) {
-    let pattern = get_dice_pattern(value);
-    let image = BitImage::new(&pattern);
-    // SAFETY: Sequential execution among interrupts.
-    unsafe {
+fn update_display(frame: &[[u8; 5]; 5]) {
+    let image = BitImage::new(frame);
+    // SAFETY: The TIMER1 interrupt can't run inside the critical section.
+    interrupt_free(|_| unsafe {
         DISPLAY.as_mut().unwrap().show(&image);
-    }
+    });
+}
+
+fn beep(duration_ms: u32) {
+    // SAFETY: The TIMER0 interrupt can't run inside the critical section.
+    interrupt_free(|_| unsafe {
+        BEEP_PWM.as_mut().unwrap().set_duty_on(Channel::C0, PWM_DUTY_BEEP_ON);
+
+        let timer = BEEP_TIMER.as_mut().unwrap();
+        timer.start(duration_ms * 1000u32);
+        timer.enable_interrupt();
+    });
+}
+
+/// A random number from 1 to `sides`, each equally likely.
+fn roll(rng: &mut Rng, sides: u8) -> u8 {
+    rng.range(1, sides as i32 + 1) as u8
 }
 
 #[entry]
@@ -107,7 +135,17 @@ fn main() -> ! {
     let beep_timer = Timer::new(board.TIMER0);
 
     // Set up hardware RNG
-    let rng = Rng::new(board.RNG);
+    let mut rng = Rng::new(board.RNG);
+
+    // Set up the accelerometer, to roll the dice with a shake
+    let i2c = twim::Twim::new(board.TWIM0, board.i2c_internal.into(), FREQUENCY_A::K100);
+    let mut delay = Timer::new(board.TIMER2);
+    let mut sensor = Lsm303agr::new_with_i2c(i2c);
+    sensor.init().unwrap();
+    sensor
+        .set_accel_mode_and_odr(&mut delay, AccelMode::Normal, AccelOutputDataRate::Hz50)
+        .unwrap();
+    let mut gestures = GestureDetector::new(SAMPLE_RATE_HZ);
 
     // Set up buttons as floating inputs
     let button_a = board.buttons.button_a.into_floating_input();
@@ -131,25 +169,15 @@ fn main() -> ! {
         .hi_to_lo()
         .enable_interrupt();
     channel1.reset_events();
-    
+
     // SAFETY: One-time initialization before any interrupts are enabled.
     unsafe {
         DISPLAY = Some(display);
         BEEP_PWM = Some(pwm);
         BEEP_TIMER = Some(beep_timer);
-        RNG = Some(rng);
         GPIOTE_PERIPHERAL = Some(gpiote);
     }
 
-    // SAFETY: RNG is initialized above, no interrupts enabled yet.
-    let rand_val = unsafe {
-        let random_byte = RNG.as_mut().unwrap().random_u8();
-        (random_byte % 6) + 1
-    };
-
-    // SAFETY: No interrupts enabled.
-    update_display(rand_val);
-
     // Enable the interrupts
     unsafe {
         pac::NVIC::unmask(pac::Interrupt::TIMER1);
@@ -159,64 +187,29 @@ fn main() -> ! {
 
     pac::NVIC::unpend(pac::Interrupt::GPIOTE);
 
-    loop {
-        // Wait for Interrupt
-        asm::wfi();
-    }
-}
+    // Start with a roll of a single die
+    let mut table = Table::new(Dice::D6);
+    table.roll();
 
-// Get LED pattern for numbers 1-6
-// Returns simple binary: 0 = off, 1 = on
-fn get_dice_pattern(value: u8) -> [[u8; 5]; 5] {
-    match value {
-        1 => [
-            [0, 0, 1, 0, 0],
-            [0, 1, 1, 0, 0],
-            [0, 0, 1, 0, 0],
-            [0, 0, 1, 0, 0],
-            [0, 1, 1, 1, 0],
-        ],
-        2 => [
-            [0, 1, 1, 1, 0],
-            [0, 0, 0, 1, 0],
-            [0, 1, 1, 1, 0],
-            [0, 1, 0, 0, 0],
-            [0, 1, 1, 1, 0],
-        ],
-        3 => [
-            [0, 1, 1, 1, 0],
-            [0, 0, 0, 1, 0],
-            [0, 1, 1, 1, 0],
-            [0, 0, 0, 1, 0],
-            [0, 1, 1, 1, 0],
-        ],
-        4 => [
-            [0, 1, 0, 1, 0],
-            [0, 1, 0, 1, 0],
-            [0, 1, 1, 1, 0],
-            [0, 0, 0, 1, 0],
-            [0, 0, 0, 1, 0],
-        ],
-        5 => [
-            [0, 1, 1, 1, 0],
-            [0, 1, 0, 0, 0],
-            [0, 1, 1, 1, 0],
-            [0, 0, 0, 1, 0],
-            [0, 1, 1, 1, 0],
-        ],
-        6 => [
-            [0, 1, 1, 1, 0],
-            [0, 1, 0, 0, 0],
-            [0, 1, 1, 1, 0],
-            [0, 1, 0, 1, 0],
-            [0, 1, 1, 1, 0],
-        ],
-        _ => [
-            [0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0],
-        ],
+    loop {
+        // Each new reading is one tick
+        let reading = sensor.wait_accel(&mut delay).unwrap();
+
+        // Button A picks the dice; shaking the board or pressing button B rolls them
+        if BUTTON_A_PRESSED.swap(false, Ordering::Relaxed) {
+            table.select_next();
+        }
+        let shaken = gestures.update(reading) == Some(Gesture::Shake);
+        let pressed = BUTTON_B_PRESSED.swap(false, Ordering::Relaxed);
+        if (shaken || pressed) && !table.is_rolling() {
+            table.roll();
+        }
+
+        match table.tick(|sides| roll(&mut rng, sides)) {
+            Some(Sound::Click) => beep(CLICK_DURATION_MS),
+            Some(Sound::Landed) => beep(BEEP_DURATION_MS),
+            None => (),
+        }
+        update_display(&table.frame());
     }
 }
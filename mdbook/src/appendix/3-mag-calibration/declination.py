@@ -0,0 +1,69 @@
+#!/usr/bin/python3
+
+# Generate the declination table in src/declination.rs.
+#
+# How to use this tool:
+# 1. cd to this directory
+# 2. python3 declination.py > table.txt
+# 3. paste table.txt over the DECLINATION table in src/declination.rs
+#
+# The field is the IGRF-13 main field model for epoch 2020, truncated to degree 5. That is
+# good to a degree or two away from the poles, which is plenty for an LED compass.
+
+import math
+
+LAT_MIN, LAT_MAX, LAT_STEP = -60, 80, 10
+LON_MIN, LON_MAX, LON_STEP = -180, 180, 10
+
+# (n, m, g, h) in nT
+COEFFS = [
+    (1, 0, -29404.8, 0.0), (1, 1, -1450.9, 4652.5),
+    (2, 0, -2499.6, 0.0), (2, 1, 2982.0, -2991.6), (2, 2, 1677.0, -734.6),
+    (3, 0, 1363.2, 0.0), (3, 1, -2381.2, -82.1), (3, 2, 1236.2, 241.9),
+    (3, 3, 525.7, -543.4),
+    (4, 0, 903.0, 0.0), (4, 1, 809.5, 281.9), (4, 2, 86.3, -158.4),
+    (4, 3, -309.4, 199.7), (4, 4, 48.0, -349.7),
+    (5, 0, -234.3, 0.0), (5, 1, 363.2, 47.7), (5, 2, 187.8, 208.3),
+    (5, 3, -140.7, -121.2), (5, 4, -151.2, 32.3), (5, 5, 13.5, 98.9),
+]
+NMAX = 5
+
+def legendre(theta):
+    """Schmidt semi-normalized P(n, m) and dP/dtheta at colatitude theta."""
+    c, s = math.cos(theta), math.sin(theta)
+    p = [[0.0] * (NMAX + 1) for _ in range(NMAX + 1)]
+    dp = [[0.0] * (NMAX + 1) for _ in range(NMAX + 1)]
+    p[0][0] = 1.0
+    for n in range(1, NMAX + 1):
+        for m in range(n + 1):
+            if n == m:
+                k = 1.0 if n == 1 else math.sqrt(1 - 1 / (2 * n))
+                p[n][n] = k * s * p[n - 1][n - 1]
+                dp[n][n] = k * (s * dp[n - 1][n - 1] + c * p[n - 1][n - 1])
+            else:
+                a = (2 * n - 1) / math.sqrt(n * n - m * m)
+                b = math.sqrt(((n - 1) ** 2 - m * m) / (n * n - m * m))
+                p2 = p[n - 2][m] if n > 1 else 0.0
+                dp2 = dp[n - 2][m] if n > 1 else 0.0
+                p[n][m] = a * c * p[n - 1][m] - b * p2
+                dp[n][m] = a * (c * dp[n - 1][m] - s * p[n - 1][m]) - b * dp2
+    return p, dp
+
+def declination(lat, lon):
+    theta, phi = math.radians(90 - lat), math.radians(lon)
+    p, dp = legendre(theta)
+    north = east = 0.0
+    for n, m, g, h in COEFFS:
+        cm, sm = math.cos(m * phi), math.sin(m * phi)
+        north += (g * cm + h * sm) * dp[n][m]
+        east += m * (g * sm - h * cm) * p[n][m] / math.sin(theta)
+    return math.degrees(math.atan2(east, north))
+
+print("#[rustfmt::skip]")
+rows = (LAT_MAX - LAT_MIN) // LAT_STEP + 1
+cols = (LON_MAX - LON_MIN) // LON_STEP
+print(f"const DECLINATION: [[i8; {cols}]; {rows}] = [")
+for lat in range(LAT_MIN, LAT_MAX + 1, LAT_STEP):
+    row = [round(declination(lat, lon)) for lon in range(LON_MIN, LON_MAX, LON_STEP)]
+    print(f"    /* {lat:3} */ [" + ", ".join(f"{d:3}" for d in row) + "],")
+print("];")
@@ -0,0 +1,128 @@
+//! Replay a recorded `mag-cal` trace through the calibration and compass code on the host.
+//!
+//! ```text
+//! cargo run -- trace.csv [--declination DEG | --location LAT LON]
+//! ```
+
+use std::{env, fs, process};
+
+use mag_cal::{
+    calibrated_measurement,
+    declination::declination,
+    heading::{magnetic_heading, north_bearing, true_heading},
+    trace::{Replay, Sample, Step, TraceError, MAGIC, RECORD_LEN},
+    Direction, PERIMETER_POINTS,
+};
+
+fn usage() -> ! {
+    eprintln!("usage: mag-cal-replay <trace> [--declination DEG | --location LAT LON]");
+    process::exit(2);
+}
+
+fn parse_arg(arg: Option<String>) -> f32 {
+    arg.and_then(|a| a.parse().ok()).unwrap_or_else(|| usage())
+}
+
+/// Decode a trace in either encoding, reporting where it goes wrong.
+fn load(bytes: &[u8]) -> Result<Vec<Sample>, String> {
+    if let Some(records) = bytes.strip_prefix(&MAGIC) {
+        let chunks = records.chunks_exact(RECORD_LEN);
+        if !chunks.remainder().is_empty() {
+            eprintln!(
+                "warning: ignoring {} trailing bytes",
+                chunks.remainder().len()
+            );
+        }
+        return chunks
+            .enumerate()
+            .map(|(i, record)| {
+                Sample::from_bytes(record.try_into().unwrap())
+                    .map_err(|e: TraceError| format!("record {}: {}", i, e))
+            })
+            .collect();
+    }
+    let text = std::str::from_utf8(bytes).map_err(|e| e.to_string())?;
+    text.lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            Sample::parse_csv(line)
+                .map_err(|e| format!("line {}: {}", i + 1, e))
+                .transpose()
+        })
+        .collect()
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let path = args.next().unwrap_or_else(|| usage());
+    let mut declination_deg = 0.0;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--declination" => declination_deg = parse_arg(args.next()),
+            "--location" => {
+                let lat = parse_arg(args.next());
+                let lon = parse_arg(args.next());
+                declination_deg = declination(lat, lon);
+            }
+            _ => usage(),
+        }
+    }
+
+    let bytes = fs::read(&path).unwrap_or_else(|e| {
+        eprintln!("{}: {}", path, e);
+        process::exit(1);
+    });
+    let samples = load(&bytes).unwrap_or_else(|e| {
+        eprintln!("{}: {}", path, e);
+        process::exit(1);
+    });
+
+    let mut replay = Replay::new();
+    let mut calibration = None;
+    for sample in &samples {
+        match replay.feed(sample) {
+            Step::Tilt(_) | Step::Skipped => (),
+            Step::Recorded((r, c)) => {
+                println!(
+                    "{:>8} ms  sample {:>2}/{} at ({}, {}): {:?}",
+                    sample.t_ms,
+                    replay.sampler().samples().len(),
+                    PERIMETER_POINTS,
+                    r,
+                    c,
+                    sample.xyz(),
+                );
+                if let Some(cal) = replay.sampler().calibration() {
+                    println!("calibration: {:?}", cal);
+                    println!("declination: {}°", declination_deg);
+                    calibration = Some(cal);
+                }
+            }
+            Step::Reading(raw) => {
+                let cal = calibrated_measurement(raw, calibration.as_ref().unwrap());
+                let heading = true_heading(magnetic_heading(cal), declination_deg);
+                println!(
+                    "{:>8} ms  raw {:?} cal {:?} heading {:.1}° arrow {:?}",
+                    sample.t_ms,
+                    (raw.x, raw.y, raw.z),
+                    (cal.x, cal.y, cal.z),
+                    heading,
+                    Direction::from_bearing(north_bearing(heading)),
+                );
+            }
+        }
+    }
+
+    if calibration.is_none() {
+        println!(
+            "trace ended after {} of {} samples; cells still unvisited:",
+            replay.sampler().samples().len(),
+            PERIMETER_POINTS,
+        );
+        for row in replay.sampler().visited() {
+            let row: String = row.iter().map(|&v| if v { '#' } else { '.' }).collect();
+            println!("  {}", row);
+        }
+        process::exit(1);
+    }
+}
@@ -1,17 +1,5 @@
-//! Translated from <https://github.com/lancaster-university/codal-microbit-v2/blob/006abf5566774fbcf674c0c7df27e8a9d20013de/source/MicroBitCompassCalibrator.cpp>
-
-use core::fmt::Debug;
-use embedded_hal::i2c::I2c;
-use embedded_hal::delay::DelayNs;
 use libm::{fabsf, sqrtf};
 
-use lsm303agr::{interface::I2cInterface, mode::MagContinuous, Lsm303agr};
-
-use microbit::display::blocking::Display;
-
-const PERIMETER_POINTS: usize = 25;
-const PIXEL1_THRESHOLD: i32 = 200;
-const PIXEL2_THRESHOLD: i32 = 600;
 const CALIBRATION_INCREMENT: i32 = 200;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -27,11 +15,15 @@ impl Measurement {
     }
 }
 
-#[derive(Debug)]
+/// Hard-iron offset and soft-iron scale for a magnetometer.
+///
+/// The values printed by the calibration demo can be hard-coded with
+/// [`Calibration::new`] to avoid recalibrating on every run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Calibration {
     center: Measurement,
     scale: Measurement,
-    _radius: u32,
+    radius: u32,
 }
 
 impl Default for Calibration {
@@ -43,98 +35,34 @@ impl Default for Calibration {
                 y: 1024,
                 z: 1024,
             },
-            _radius: 0,
+            radius: 0,
         }
     }
 }
 
-pub fn calc_calibration<I, T>(
-    sensor: &mut Lsm303agr<I2cInterface<I>, MagContinuous>,
-    display: &mut Display,
-    timer: &mut T,
-) -> Calibration
-where
-    T: DelayNs,
-    I: I2c,
-{
-    let data = get_data(sensor, display, timer);
-    calibrate(&data)
-}
-
-fn get_data<I, T>(
-    sensor: &mut Lsm303agr<I2cInterface<I>, MagContinuous>,
-    display: &mut Display,
-    timer: &mut T,
-) -> [Measurement; PERIMETER_POINTS]
-where
-    T: DelayNs,
-    I: I2c,
-{
-    let mut leds = [
-        [0, 0, 0, 0, 0],
-        [0, 0, 0, 0, 0],
-        [0, 0, 0, 0, 0],
-        [0, 0, 0, 0, 0],
-        [0, 0, 0, 0, 0],
-    ];
-    let mut cursor = (2, 2);
-    let mut data = [Measurement { x: 0, y: 0, z: 0 }; PERIMETER_POINTS];
-    let mut samples = 0;
-    let mut blink_state = true;
-
-    while samples < PERIMETER_POINTS {
-        while !sensor.accel_status().unwrap().xyz_new_data() {
-            timer.delay_us(10u32);
-        }
-        let (x, y, _) = sensor.acceleration().unwrap().xyz_mg();
-
-        if x < -PIXEL2_THRESHOLD {
-            cursor.1 = 4;
-        } else if x < -PIXEL1_THRESHOLD {
-            cursor.1 = 3;
-        } else if x > PIXEL2_THRESHOLD {
-            cursor.1 = 0;
-        } else if x > PIXEL1_THRESHOLD {
-            cursor.1 = 1;
-        } else {
-            cursor.1 = 2;
+impl Calibration {
+    pub const fn new(center: Measurement, scale: Measurement, radius: u32) -> Self {
+        Self {
+            center,
+            scale,
+            radius,
         }
+    }
 
-        if y < -PIXEL2_THRESHOLD {
-            cursor.0 = 0;
-        } else if y < -PIXEL1_THRESHOLD {
-            cursor.0 = 1;
-        } else if y > PIXEL2_THRESHOLD {
-            cursor.0 = 4;
-        } else if y > PIXEL1_THRESHOLD {
-            cursor.0 = 3;
-        } else {
-            cursor.0 = 2;
-        }
+    /// Center of the fitted sphere, in nT on the ENU axes.
+    pub fn center(&self) -> Measurement {
+        self.center
+    }
 
-        // Turn the y axis properly
-        cursor.0 = 4 - cursor.0;
+    /// Per-axis scale factors, in 1/1024ths.
+    pub fn scale(&self) -> Measurement {
+        self.scale
+    }
 
-        if leds[cursor.0][cursor.1] != 1 {
-            leds[cursor.0][cursor.1] = 1;
-            while !sensor.mag_status().unwrap().xyz_new_data() {
-                timer.delay_us(10u32);
-            }
-            let measurement = Measurement::new(
-                sensor.magnetic_field().unwrap().xyz_nt()
-            );
-            data[samples] = measurement;
-            samples += 1;
-        }
-        let save_cursor = leds[cursor.0][cursor.1];
-        if blink_state {
-            leds[cursor.0][cursor.1] = 1 - leds[cursor.0][cursor.1];
-        }
-        display.show(timer, leds, 200);
-        blink_state = !blink_state;
-        leds[cursor.0][cursor.1] = save_cursor;
+    /// Radius of the fitted sphere, in nT.
+    pub fn radius(&self) -> u32 {
+        self.radius
     }
-    data
 }
 
 fn difference_square(a: Measurement, b: Measurement) -> f32 {
@@ -163,7 +91,9 @@ fn measure_score(center: Measurement, data: &[Measurement]) -> f32 {
     max_d - min_d
 }
 
-fn calibrate(data: &[Measurement]) -> Calibration {
+/// Fit a calibration to magnetometer samples on the ENU axes, as collected by
+/// [`Sampler`](crate::Sampler).
+pub fn calibrate(data: &[Measurement]) -> Calibration {
     // Approximate a center for the data
     let mut center = Measurement { x: 0, y: 0, z: 0 };
     let mut best = center;
@@ -212,11 +142,11 @@ fn calibrate(data: &[Measurement]) -> Calibration {
 }
 
 fn spherify(center: Measurement, data: &[Measurement]) -> Calibration {
-    let mut _radius = 0;
+    let mut radius = 0;
     for point in data {
         let d = sqrtf(difference_square(center, *point)) as u32;
-        if d > _radius {
-            _radius = d;
+        if d > radius {
+            radius = d;
         }
     }
 
@@ -227,7 +157,7 @@ fn spherify(center: Measurement, data: &[Measurement]) -> Calibration {
 
     for point in data {
         let d = sqrtf(difference_square(center, *point));
-        let s = (_radius as f32 / d) - 1.0;
+        let s = (radius as f32 / d) - 1.0;
         scale = scale.max(s);
 
         let dx = point.x - center.x;
@@ -246,7 +176,7 @@ fn spherify(center: Measurement, data: &[Measurement]) -> Calibration {
 
     Calibration {
         center,
-        _radius,
+        radius,
         scale: Measurement {
             x: (1024.0 * scale_x) as i32,
             y: (1024.0 * scale_y) as i32,
@@ -255,10 +185,29 @@ fn spherify(center: Measurement, data: &[Measurement]) -> Calibration {
     }
 }
 
+/// Apply `calibration` to a raw magnetometer reading.
 pub fn calibrated_measurement(measurement: Measurement, calibration: &Calibration) -> Measurement {
+    let mut out = measurement_to_enu(measurement);
+    out = Measurement {
+        x: ((out.x - calibration.center.x) * calibration.scale.x) >> 10,
+        y: ((out.y - calibration.center.y) * calibration.scale.y) >> 10,
+        z: ((out.z - calibration.center.z) * calibration.scale.z) >> 10,
+    };
+    enu_to_cartesian(out)
+}
+
+pub(crate) fn measurement_to_enu(measurement: Measurement) -> Measurement {
+    Measurement {
+        x: -measurement.y,
+        y: -measurement.x,
+        z: measurement.z,
+    }
+}
+
+fn enu_to_cartesian(measurement: Measurement) -> Measurement {
     Measurement {
-        x: ((measurement.x - calibration.center.x) * calibration.scale.x) >> 10,
-        y: ((measurement.y - calibration.center.y) * calibration.scale.y) >> 10,
-        z: ((measurement.z - calibration.center.z) * calibration.scale.z) >> 10,
+        x: -measurement.y,
+        y: measurement.x,
+        z: measurement.z,
     }
 }
@@ -0,0 +1,54 @@
+//! Approximate magnetic declination from latitude and longitude.
+
+use libm::floorf;
+
+use crate::heading::wrap_180;
+
+const LAT_MIN: f32 = -60.0;
+const LAT_MAX: f32 = 80.0;
+const STEP: f32 = 10.0;
+
+// Generated by `declination.py`: degrees east, every 10° of latitude from -60° to 80° and every
+// 10° of longitude from -180° to 170°.
+#[rustfmt::skip]
+const DECLINATION: [[i8; 36]; 15] = [
+    /* -60 */ [ 52,  51,  49,  47,  45,  42,  39,  35,  31,  27,  22,  17,  12,   7,   2,  -3,  -8, -14, -20, -27, -35, -42, -49, -56, -62, -67, -72, -75, -76, -74, -64, -28,  26,  45,  51,  53],
+    /* -50 */ [ 32,  33,  33,  33,  33,  32,  30,  28,  25,  21,  16,  11,   6,   0,  -5, -10, -14, -18, -23, -29, -35, -42, -48, -53, -56, -58, -57, -53, -45, -31, -14,   1,  13,  21,  26,  30],
+    /* -40 */ [ 22,  23,  23,  24,  24,  24,  24,  22,  20,  16,  11,   5,  -2,  -9, -15, -20, -24, -26, -27, -29, -33, -38, -42, -45, -46, -44, -40, -32, -22, -11,  -3,   4,  10,  14,  17,  20],
+    /* -30 */ [ 17,  17,  17,  17,  17,  18,  18,  18,  16,  12,   6,  -1,  -9, -17, -24, -28, -29, -27, -23, -21, -22, -25, -29, -31, -31, -29, -24, -17, -10,  -4,   1,   5,   8,  10,  13,  15],
+    /* -20 */ [ 13,  14,  13,  13,  13,  13,  14,  14,  12,   8,   2,  -6, -14, -21, -26, -27, -25, -20, -14,  -9,  -8, -11, -14, -17, -18, -16, -13,  -8,  -4,   0,   2,   4,   6,   8,  11,  12],
+    /* -10 */ [ 12,  12,  11,  10,  10,  10,  11,  11,   9,   5,  -1,  -9, -16, -21, -23, -22, -19, -13,  -7,  -3,  -2,  -3,  -5,  -8,  -9,  -8,  -6,  -4,  -1,   0,   2,   3,   4,   6,   9,  11],
+    /*   0 */ [ 11,  11,  10,   9,   8,   9,   9,   9,   7,   2,  -4, -10, -16, -19, -19, -17, -13,  -8,  -4,  -1,   1,   1,  -1,  -3,  -4,  -4,  -3,  -2,  -1,   0,   1,   1,   2,   5,   7,   9],
+    /*  10 */ [  9,  10,  10,   9,   9,   9,   9,   8,   5,   1,  -5, -11, -15, -17, -16, -13, -10,  -5,  -2,   1,   2,   2,   1,   0,  -1,  -1,  -1,  -1,  -1,  -1,  -1,  -1,   0,   2,   5,   8],
+    /*  20 */ [  8,   9,  10,  10,  10,  10,  10,   8,   5,   0,  -6, -12, -15, -16, -14, -11,  -7,  -4,  -1,   2,   3,   3,   3,   2,   1,   1,   0,  -1,  -1,  -2,  -3,  -3,  -2,   0,   2,   5],
+    /*  30 */ [  6,   9,  10,  12,  12,  12,  12,   9,   5,  -1,  -8, -13, -15, -15, -13, -10,  -6,  -3,   0,   2,   4,   4,   5,   4,   3,   2,   1,   0,  -2,  -4,  -5,  -6,  -5,  -3,  -1,   3],
+    /*  40 */ [  4,   8,  11,  13,  15,  15,  14,  11,   5,  -2,  -9, -14, -16, -16, -14, -10,  -6,  -3,   1,   3,   5,   6,   7,   7,   6,   5,   3,   0,  -3,  -5,  -8,  -9,  -8,  -7,  -4,   0],
+    /*  50 */ [  2,   7,  11,  14,  17,  18,  17,  13,   6,  -3, -12, -17, -19, -19, -16, -12,  -8,  -3,   0,   4,   7,   9,  10,  11,  10,   9,   6,   2,  -3,  -7, -10, -11, -11,  -9,  -6,  -2],
+    /*  60 */ [  1,   6,  11,  15,  18,  20,  19,  14,   5,  -6, -16, -22, -24, -23, -20, -15, -10,  -5,   0,   4,   9,  12,  15,  16,  17,  15,  11,   6,  -1,  -7, -11, -13, -13, -11,  -8,  -4],
+    /*  70 */ [  1,   6,  11,  15,  19,  20,  19,  12,   0, -15, -26, -31, -31, -29, -25, -20, -14,  -8,  -2,   4,  10,  15,  19,  23,  24,  24,  21,  14,   5,  -4, -10, -14, -14, -12,  -9,  -4],
+    /*  80 */ [  3,   7,  11,  15,  16,  15,   8,  -7, -26, -39, -44, -44, -41, -36, -30, -24, -17, -10,  -3,   4,  10,  17,  23,  28,  32,  35,  36,  33,  26,  16,   4,  -3,  -7,  -7,  -5,  -2],
+];
+
+/// Approximate declination, in degrees east of true north, at `lat`/`lon` degrees (north and east
+/// positive).
+///
+/// This interpolates a coarse table of the 2020 field, so expect a few degrees of error. Latitudes
+/// outside -60°..80° are clamped.
+pub fn declination(lat: f32, lon: f32) -> f32 {
+    let lat = lat.clamp(LAT_MIN, LAT_MAX);
+    let row = (lat - LAT_MIN) / STEP;
+    let col = (wrap_180(lon) + 180.0) / STEP;
+    let (r0, c0) = (floorf(row), floorf(col));
+    let (fr, fc) = (row - r0, col - c0);
+    let r0 = (r0 as usize).min(DECLINATION.len() - 2);
+    let fr = if lat == LAT_MAX { 1.0 } else { fr };
+    let c0 = c0 as usize % DECLINATION[0].len();
+    let c1 = (c0 + 1) % DECLINATION[0].len();
+
+    // Interpolate relative to one corner so cells spanning ±180° near the poles come out right.
+    let base = DECLINATION[r0][c0] as f32;
+    let corner = |r: usize, c: usize| wrap_180(DECLINATION[r][c] as f32 - base);
+    let top = corner(r0, c0) * (1.0 - fc) + corner(r0, c1) * fc;
+    let bottom = corner(r0 + 1, c0) * (1.0 - fc) + corner(r0 + 1, c1) * fc;
+    wrap_180(base + top * (1.0 - fr) + bottom * fr)
+}
@@ -0,0 +1,110 @@
+//! Smoothing for noisy compass headings.
+
+use libm::{atan2f, cosf, fabsf, roundf, sinf, sqrtf};
+
+use crate::heading::{wrap_180, wrap_360};
+
+/// First-order low-pass filter for headings in degrees.
+///
+/// Averaging angles directly goes badly wrong across 0°/360° (the mean of 350° and 10° is not
+/// 180°), so this averages the unit vectors the headings point along instead.
+#[derive(Debug, Clone, Copy)]
+pub struct HeadingFilter {
+    alpha: f32,
+    x: f32,
+    y: f32,
+    primed: bool,
+}
+
+impl HeadingFilter {
+    /// A filter with a `time_constant` for samples arriving every `sample_period`, both in seconds.
+    ///
+    /// Panics unless both are more than zero.
+    pub fn new(time_constant: f32, sample_period: f32) -> Self {
+        assert!(time_constant > 0.0 && sample_period > 0.0);
+        Self {
+            alpha: sample_period / (time_constant + sample_period),
+            x: 0.0,
+            y: 0.0,
+            primed: false,
+        }
+    }
+
+    /// Add a heading and return the filtered heading.
+    pub fn update(&mut self, heading: f32) -> f32 {
+        let (s, c) = (sinf(heading.to_radians()), cosf(heading.to_radians()));
+        if self.primed {
+            self.x += self.alpha * (c - self.x);
+            self.y += self.alpha * (s - self.y);
+        } else {
+            (self.x, self.y) = (c, s);
+            self.primed = true;
+        }
+        self.heading().unwrap_or(heading)
+    }
+
+    /// The filtered heading, or `None` before the first sample or if the recent samples cancel out.
+    pub fn heading(&self) -> Option<f32> {
+        if !self.primed || sqrtf(self.x * self.x + self.y * self.y) < 1e-6 {
+            return None;
+        }
+        Some(wrap_360(atan2f(self.y, self.x).to_degrees()))
+    }
+
+    pub fn reset(&mut self) {
+        self.primed = false;
+    }
+}
+
+/// Splits the circle into equal sectors, the first centered on 0°, and tracks which one an
+/// angle is in. The sector only changes once the angle is more than `band` degrees past the
+/// boundary, so an angle sitting on a boundary doesn't flicker between two sectors.
+#[derive(Debug, Clone, Copy)]
+pub struct SectorHysteresis {
+    sectors: usize,
+    band: f32,
+    current: Option<usize>,
+}
+
+impl SectorHysteresis {
+    /// Panics if there are no `sectors`.
+    pub fn new(sectors: usize, band: f32) -> Self {
+        assert!(sectors >= 1);
+        Self {
+            sectors,
+            band,
+            current: None,
+        }
+    }
+
+    fn width(&self) -> f32 {
+        360.0 / self.sectors as f32
+    }
+
+    /// The sector nearest `angle`, ignoring the current one.
+    pub fn nearest(&self, angle: f32) -> usize {
+        roundf(wrap_360(angle) / self.width()) as usize % self.sectors
+    }
+
+    /// Move to the sector for `angle` (in degrees) if it is clearly out of the current one.
+    pub fn update(&mut self, angle: f32) -> usize {
+        let sector = match self.current {
+            Some(current) => {
+                let center = current as f32 * self.width();
+                let off = fabsf(wrap_180(angle - center));
+                if off <= self.width() / 2.0 + self.band {
+                    current
+                } else {
+                    self.nearest(angle)
+                }
+            }
+            None => self.nearest(angle),
+        };
+        self.current = Some(sector);
+        sector
+    }
+
+    pub fn sector(&self) -> Option<usize> {
+        self.current
+    }
+}
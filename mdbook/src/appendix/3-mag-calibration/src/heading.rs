@@ -0,0 +1,118 @@
+//! Compass headings from calibrated magnetometer readings.
+//!
+//! Headings are in degrees clockwise from north to the logo edge of the board. Bearings are
+//! the other way around: where something lies on the board, in degrees clockwise from the logo
+//! edge.
+
+use core::str::FromStr;
+
+use libm::{atan2f, fabsf, floorf, sqrtf};
+use sensors::Xyz;
+
+use crate::Measurement;
+
+/// Wrap an angle in degrees into `[0, 360)`.
+pub fn wrap_360(deg: f32) -> f32 {
+    let wrapped = deg - 360.0 * floorf(deg / 360.0);
+    if wrapped >= 360.0 {
+        0.0
+    } else {
+        wrapped
+    }
+}
+
+/// Wrap an angle in degrees into `[-180, 180)`.
+pub fn wrap_180(deg: f32) -> f32 {
+    wrap_360(deg + 180.0) - 180.0
+}
+
+/// Magnetic heading of a level board from a [`calibrated_measurement`](crate::calibrated_measurement).
+pub fn magnetic_heading(field: Measurement) -> f32 {
+    // The calibrated axes point (left, forward, down), as the README says: X to the left edge and
+    // Y to the logo. A field along X means magnetic north is off to the left, so the board is
+    // heading east.
+    wrap_360(atan2f(field.x as f32, field.y as f32).to_degrees())
+}
+
+/// Magnetic heading of a board at any tilt, from a calibrated field and the `gravity` the
+/// accelerometer reads, in mg and in its own axes, such as an
+/// [`AttitudeEstimator`](sensors::attitude::AttitudeEstimator)'s.
+pub fn tilt_compensated_heading(field: Measurement, gravity: Xyz) -> f32 {
+    // Gravity in the field's (left, forward, down) axes.
+    let down = [-gravity.0 as f32, gravity.1 as f32, -gravity.2 as f32];
+    let g = sqrtf(down.iter().map(|d| d * d).sum());
+    if g == 0.0 {
+        return magnetic_heading(field);
+    }
+    let down = down.map(|d| d / g);
+    let b = [field.x as f32, field.y as f32, field.z as f32];
+    let cross = |a: [f32; 3], b: [f32; 3]| {
+        [
+            a[1] * b[2] - a[2] * b[1],
+            a[2] * b[0] - a[0] * b[2],
+            a[0] * b[1] - a[1] * b[0],
+        ]
+    };
+    // Level directions to the east and to magnetic north, whichever way the board is tilted;
+    // the heading is how far round from north the logo edge (forward) points.
+    let east = cross(down, b);
+    let north = cross(east, down);
+    wrap_360(atan2f(east[1], north[1]).to_degrees())
+}
+
+/// Correct a magnetic heading by `declination` degrees (east positive) to get a true heading.
+pub fn true_heading(magnetic: f32, declination: f32) -> f32 {
+    wrap_360(magnetic + declination)
+}
+
+/// Bearing of north when the board is on `heading`.
+pub fn north_bearing(heading: f32) -> f32 {
+    wrap_360(-heading)
+}
+
+/// The declination given by the `MAG_DECLINATION` environment variable at build time, in degrees
+/// east, e.g. `MAG_DECLINATION=15.2 cargo embed`.
+pub fn build_declination() -> Option<f32> {
+    option_env!("MAG_DECLINATION").and_then(|d| d.trim().parse().ok())
+}
+
+/// A line typed at the compass: `decl` shows the declination, `decl <degrees east>` sets it and
+/// `loc <lat> <lon>` looks it up from where you are.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeclinationCommand {
+    Show,
+    Set(f32),
+    Locate { lat: f32, lon: f32 },
+}
+
+impl FromStr for DeclinationCommand {
+    type Err = &'static str;
+
+    /// Fails with a message to show if the line isn't a command, or its angles are out of range.
+    fn from_str(line: &str) -> Result<Self, &'static str> {
+        let mut words = line.split_whitespace();
+        let command = match (words.next(), words.next(), words.next(), words.next()) {
+            (Some("decl"), None, _, _) => DeclinationCommand::Show,
+            (Some("decl"), Some(deg), None, _) => DeclinationCommand::Set(angle(
+                deg,
+                180.0,
+                "declination must be a number within ±180°",
+            )?),
+            (Some("loc"), Some(lat), Some(lon), None) => DeclinationCommand::Locate {
+                lat: angle(lat, 90.0, "latitude must be a number within ±90°")?,
+                lon: angle(lon, 180.0, "longitude must be a number within ±180°")?,
+            },
+            _ => return Err("expected decl [degrees east] | loc <lat> <lon>"),
+        };
+        Ok(command)
+    }
+}
+
+/// Parse `word` as a number of degrees no further than `limit` from zero, or fail with `error`.
+fn angle(word: &str, limit: f32, error: &'static str) -> Result<f32, &'static str> {
+    let deg: f32 = word.parse().map_err(|_| error)?;
+    if !deg.is_finite() || fabsf(deg) > limit {
+        return Err(error);
+    }
+    Ok(deg)
+}
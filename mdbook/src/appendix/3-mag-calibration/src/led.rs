@@ -1,5 +1,11 @@
-#[derive(Debug)]
-pub enum Direction{
+//! Arrow images for the eight compass directions.
+
+use libm::roundf;
+
+use crate::heading::wrap_360;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
     North,
     NorthEast,
     East,
@@ -12,68 +18,88 @@ pub enum Direction{
 
 const NORTH: [[u8; 5]; 5] = [
     [0, 0, 1, 0, 0],
-    [0, 1, 1, 1, 0],   
+    [0, 1, 1, 1, 0],
     [1, 0, 1, 0, 1],
     [0, 0, 1, 0, 0],
-    [0, 0, 1, 0, 0],   
+    [0, 0, 1, 0, 0],
 ];
 
 const NORTH_WEST: [[u8; 5]; 5] = [
     [1, 1, 1, 0, 0],
-    [1, 1, 0, 0, 0],   
+    [1, 1, 0, 0, 0],
     [1, 0, 1, 0, 0],
     [0, 0, 0, 1, 0],
-    [0, 0, 0, 0, 1],   
+    [0, 0, 0, 0, 1],
 ];
 
 const WEST: [[u8; 5]; 5] = [
     [0, 0, 1, 0, 0],
-    [0, 1, 0, 0, 0],   
+    [0, 1, 0, 0, 0],
     [1, 1, 1, 1, 1],
     [0, 1, 0, 0, 0],
-    [0, 0, 1, 0, 0],   
+    [0, 0, 1, 0, 0],
 ];
 
 const SOUTH_WEST: [[u8; 5]; 5] = [
     [0, 0, 0, 0, 1],
-    [0, 0, 0, 1, 0],   
+    [0, 0, 0, 1, 0],
     [1, 0, 1, 0, 0],
     [1, 1, 0, 0, 0],
-    [1, 1, 1, 0, 0],   
+    [1, 1, 1, 0, 0],
 ];
 
 const SOUTH: [[u8; 5]; 5] = [
     [0, 0, 1, 0, 0],
-    [0, 0, 1, 0, 0],   
+    [0, 0, 1, 0, 0],
     [1, 0, 1, 0, 1],
     [0, 1, 1, 1, 0],
-    [0, 0, 1, 0, 0],   
+    [0, 0, 1, 0, 0],
 ];
 
 const SOUTH_EAST: [[u8; 5]; 5] = [
     [1, 0, 0, 0, 0],
-    [0, 1, 0, 0, 0],   
+    [0, 1, 0, 0, 0],
     [0, 0, 1, 0, 1],
     [0, 0, 0, 1, 1],
-    [0, 0, 1, 1, 1],   
+    [0, 0, 1, 1, 1],
 ];
 
 const EAST: [[u8; 5]; 5] = [
     [0, 0, 1, 0, 0],
-    [0, 0, 0, 1, 0],   
+    [0, 0, 0, 1, 0],
     [1, 1, 1, 1, 1],
     [0, 0, 0, 1, 0],
-    [0, 0, 1, 0, 0],   
+    [0, 0, 1, 0, 0],
 ];
 
 const NORTH_EAST: [[u8; 5]; 5] = [
     [0, 0, 1, 1, 1],
-    [0, 0, 0, 1, 1],   
+    [0, 0, 0, 1, 1],
     [0, 0, 1, 0, 1],
     [0, 1, 0, 0, 0],
-    [1, 0, 0, 0, 0],   
+    [1, 0, 0, 0, 0],
 ];
 
+impl Direction {
+    const CLOCKWISE: [Direction; 8] = [
+        Direction::North,
+        Direction::NorthEast,
+        Direction::East,
+        Direction::SouthEast,
+        Direction::South,
+        Direction::SouthWest,
+        Direction::West,
+        Direction::NorthWest,
+    ];
+
+    /// The arrow closest to `bearing`, in degrees clockwise from the logo edge.
+    pub fn from_bearing(bearing: f32) -> Direction {
+        let sector = roundf(wrap_360(bearing) / 45.0) as usize;
+        Self::CLOCKWISE[sector % 8]
+    }
+}
+
+/// The 5×5 arrow pointing towards `direction`.
 pub fn direction_to_led(direction: Direction) -> [[u8; 5]; 5] {
     match direction {
         Direction::North => NORTH,
@@ -6,16 +6,78 @@ use cortex_m_rt::entry;
 use panic_rtt_target as _;
 use rtt_target::{rprintln, rtt_init_print};
 
-use embedded_hal::delay::DelayNs;
+use core::fmt::Write;
+
+use embedded_hal::digital::InputPin;
 use microbit::{
     display::blocking::Display,
-    hal::{twim, Timer},
+    hal::{
+        timer::{self, Periodic},
+        twim,
+        uarte::{self, Baudrate, Parity},
+        Timer,
+    },
     pac::twim0::frequency::FREQUENCY_A,
 };
 
 use lsm303agr::{AccelMode, AccelOutputDataRate, Lsm303agr, MagMode, MagOutputDataRate};
 
-use mag_cal::{calc_calibration, calibrated_measurement, Measurement};
+use mag_cal::{
+    calc_calibration_traced, calibrated_measurement, direction_to_led,
+    heading::{build_declination, magnetic_heading, north_bearing, true_heading},
+    trace::{self, Sample, Sensor},
+    Direction, Measurement,
+};
+use sensors::Magnetometer;
+use serial_setup::UartePort;
+use settings::{keys, Settings};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TraceFormat {
+    Csv,
+    Binary,
+}
+
+/// Streams every sensor reading over the serial port, timestamped in ms since reset.
+struct Tracer<T: uarte::Instance, C: timer::Instance> {
+    serial: UartePort<T>,
+    clock: Timer<C, Periodic>,
+    format: TraceFormat,
+}
+
+impl<T: uarte::Instance, C: timer::Instance> Tracer<T, C> {
+    fn new(serial: UartePort<T>, mut clock: Timer<C, Periodic>, format: TraceFormat) -> Self {
+        clock.start(u32::MAX);
+        let mut tracer = Self {
+            serial,
+            clock,
+            format,
+        };
+        match format {
+            TraceFormat::Csv => write!(tracer.serial, "{}\r\n", trace::CSV_HEADER).unwrap(),
+            TraceFormat::Binary => trace::MAGIC
+                .iter()
+                .for_each(|&b| tracer.serial.write(b).unwrap()),
+        }
+        tracer
+    }
+
+    fn emit(&mut self, sensor: Sensor, xyz: (i32, i32, i32)) {
+        let sample = Sample::new(self.clock.read() / 1000, sensor, xyz);
+        match self.format {
+            TraceFormat::Csv => {
+                sample.write_csv(&mut self.serial).unwrap();
+                write!(self.serial, "\r\n").unwrap();
+            }
+            TraceFormat::Binary => {
+                for b in sample.to_bytes() {
+                    self.serial.write(b).unwrap();
+                }
+            }
+        }
+        self.serial.flush().unwrap();
+    }
+}
 
 #[entry]
 fn main() -> ! {
@@ -24,8 +86,37 @@ fn main() -> ! {
 
     let i2c = { twim::Twim::new(board.TWIM0, board.i2c_internal.into(), FREQUENCY_A::K100) };
 
+    // Hold A (CSV) or B (binary) during reset to stream a trace of every reading over serial.
+    let mut buttons = board.buttons;
+    let format = if buttons.button_a.is_low().unwrap() {
+        Some(TraceFormat::Csv)
+    } else if buttons.button_b.is_low().unwrap() {
+        Some(TraceFormat::Binary)
+    } else {
+        None
+    };
+    let mut tracer = format.map(|format| {
+        let serial = uarte::Uarte::new(
+            board.UARTE0,
+            board.uart.into(),
+            Parity::EXCLUDED,
+            Baudrate::BAUD115200,
+        );
+        Tracer::new(
+            UartePort::new(serial),
+            Timer::periodic(board.TIMER1),
+            format,
+        )
+    });
+
     let mut timer0 = Timer::new(board.TIMER0);
     let mut display = Display::new(board.display_pins);
+    let mut settings = Settings::new(settings::flash(board.NVMC), 0);
+    let declination = settings
+        .read_f32(keys::DECLINATION)
+        .unwrap()
+        .or_else(build_declination)
+        .unwrap_or(0.0);
 
     let mut sensor = Lsm303agr::new_with_i2c(i2c);
     sensor.init().unwrap();
@@ -45,23 +136,35 @@ fn main() -> ! {
         .unwrap();
     let mut sensor = sensor.into_mag_continuous().ok().unwrap();
 
-    let calibration = calc_calibration(&mut sensor, &mut display, &mut timer0);
+    let calibration =
+        calc_calibration_traced(&mut sensor, &mut display, &mut timer0, |sensor, xyz| {
+            if let Some(tracer) = tracer.as_mut() {
+                tracer.emit(sensor, xyz);
+            }
+        });
     rprintln!("Calibration: {:?}", calibration);
-    rprintln!("Calibration done, entering busy loop");
+    rprintln!("Calibration done, declination {}°", declination);
     loop {
-        while !sensor.mag_status().unwrap().xyz_new_data() {
-            timer0.delay_ms(1u32);
+        let raw = sensor.wait_mag(&mut timer0).unwrap();
+        if let Some(tracer) = tracer.as_mut() {
+            tracer.emit(Sensor::Mag, raw);
         }
-        let raw_data = Measurement::new(sensor.magnetic_field().unwrap().xyz_nt());
+        let raw_data = Measurement::new(raw);
         let cal_data = calibrated_measurement(raw_data, &calibration);
+        let heading = true_heading(magnetic_heading(cal_data), declination);
         rprintln!(
-            "raw (x {} y {} z {}); cal (x {} y {} z {})",
+            "raw (x {} y {} z {}); cal (x {} y {} z {}); heading {}°",
             raw_data.x,
             raw_data.y,
             raw_data.z,
             cal_data.x,
             cal_data.y,
             cal_data.z,
+            heading,
         );
+
+        // Point the arrow at true north.
+        let arrow = Direction::from_bearing(north_bearing(heading));
+        display.show(&mut timer0, direction_to_led(arrow), 100);
     }
 }
@@ -0,0 +1,83 @@
+use sensors::attitude::Buckets;
+
+use crate::calibration::{calibrate, measurement_to_enu, Calibration, Measurement};
+
+pub const PERIMETER_POINTS: usize = 25;
+
+/// Map an accelerometer reading (x and y in mg) to a `(row, column)` on the 5×5 grid.
+pub fn tilt_cursor(accel: (i32, i32)) -> (usize, usize) {
+    Buckets::default().cell(accel)
+}
+
+/// Collects one magnetometer sample per tilt position.
+///
+/// Feed every accelerometer reading to [`Sampler::tilt`]; when it returns `true` the board has
+/// been tilted into a new grid cell and the next magnetometer reading should be passed to
+/// [`Sampler::record`]. Once every cell has been visited the sampler [`is_complete`].
+///
+/// [`is_complete`]: Sampler::is_complete
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    visited: [[bool; 5]; 5],
+    cursor: (usize, usize),
+    data: [Measurement; PERIMETER_POINTS],
+    samples: usize,
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sampler {
+    pub const fn new() -> Self {
+        Self {
+            visited: [[false; 5]; 5],
+            cursor: (2, 2),
+            data: [Measurement { x: 0, y: 0, z: 0 }; PERIMETER_POINTS],
+            samples: 0,
+        }
+    }
+
+    /// Move the cursor to match the accelerometer reading `(x, y)` in mg. Returns `true` if the
+    /// cursor landed on a cell that still needs a magnetometer sample.
+    pub fn tilt(&mut self, accel: (i32, i32)) -> bool {
+        self.cursor = tilt_cursor(accel);
+        let (r, c) = self.cursor;
+        !self.is_complete() && !self.visited[r][c]
+    }
+
+    /// Record a raw magnetometer reading for the cell under the cursor.
+    pub fn record(&mut self, mag: Measurement) {
+        if self.is_complete() {
+            return;
+        }
+        let (r, c) = self.cursor;
+        self.visited[r][c] = true;
+        self.data[self.samples] = measurement_to_enu(mag);
+        self.samples += 1;
+    }
+
+    pub fn cursor(&self) -> (usize, usize) {
+        self.cursor
+    }
+
+    /// Which grid cells have a sample.
+    pub fn visited(&self) -> [[bool; 5]; 5] {
+        self.visited
+    }
+
+    pub fn samples(&self) -> &[Measurement] {
+        &self.data[..self.samples]
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.samples == PERIMETER_POINTS
+    }
+
+    /// Fit a calibration to the collected samples, or `None` if some cells are still missing.
+    pub fn calibration(&self) -> Option<Calibration> {
+        self.is_complete().then(|| calibrate(&self.data))
+    }
+}
@@ -0,0 +1,222 @@
+//! Recorded sensor traces, for replaying a calibration run somewhere other than the board.
+//!
+//! A trace is the sequence of accelerometer and magnetometer readings a program actually took,
+//! each stamped with the time it was read. It comes in two encodings:
+//!
+//! * CSV: a [`CSV_HEADER`] line followed by one `t_ms,sensor,x,y,z` line per reading, where
+//!   `sensor` is `a` (accelerometer, mg) or `m` (magnetometer, raw nT). Lines starting with `#`
+//!   are comments.
+//! * Binary: the four bytes [`MAGIC`] followed by [`RECORD_LEN`]-byte records: the sensor byte,
+//!   then `t_ms`, `x`, `y` and `z`, each little-endian.
+//!
+//! [`Replay`] feeds a trace back through a [`Sampler`] the same way [`calc_calibration`] does.
+//! Samples also convert into [`Reading`]s, so a trace can be played back by a
+//! [`sensors::mock::Script`] to whole programs written against the `sensors` traits.
+//!
+//! [`calc_calibration`]: crate::calc_calibration
+
+use core::fmt;
+
+use sensors::mock::Reading;
+
+use crate::{Measurement, Sampler};
+
+pub const CSV_HEADER: &str = "t_ms,sensor,x,y,z";
+pub const MAGIC: [u8; 4] = *b"MCT1";
+pub const RECORD_LEN: usize = 17;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sensor {
+    Accel,
+    Mag,
+}
+
+impl Sensor {
+    fn tag(self) -> u8 {
+        match self {
+            Sensor::Accel => b'a',
+            Sensor::Mag => b'm',
+        }
+    }
+
+    fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            b'a' => Some(Sensor::Accel),
+            b'm' => Some(Sensor::Mag),
+            _ => None,
+        }
+    }
+}
+
+/// One timestamped reading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+    pub t_ms: u32,
+    pub sensor: Sensor,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceError {
+    /// A CSV line without exactly five fields.
+    Fields,
+    /// A field that isn't a number.
+    Number,
+    /// A sensor other than `a` or `m`.
+    Sensor,
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TraceError::Fields => write!(f, "expected {} fields", CSV_HEADER),
+            TraceError::Number => write!(f, "bad number"),
+            TraceError::Sensor => write!(f, "unknown sensor"),
+        }
+    }
+}
+
+impl Sample {
+    pub fn new(t_ms: u32, sensor: Sensor, (x, y, z): (i32, i32, i32)) -> Self {
+        Self {
+            t_ms,
+            sensor,
+            x,
+            y,
+            z,
+        }
+    }
+
+    pub fn xyz(&self) -> (i32, i32, i32) {
+        (self.x, self.y, self.z)
+    }
+
+    /// Write this sample as one CSV line, without the line ending.
+    pub fn write_csv<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        write!(
+            w,
+            "{},{},{},{},{}",
+            self.t_ms,
+            self.sensor.tag() as char,
+            self.x,
+            self.y,
+            self.z
+        )
+    }
+
+    /// Parse one CSV line. Blank lines, comments and the header give `Ok(None)`.
+    pub fn parse_csv(line: &str) -> Result<Option<Self>, TraceError> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line == CSV_HEADER {
+            return Ok(None);
+        }
+        let mut fields = line.split(',').map(str::trim);
+        let mut next = || fields.next().ok_or(TraceError::Fields);
+        let t_ms = next()?.parse().map_err(|_| TraceError::Number)?;
+        let sensor = match next()?.as_bytes() {
+            [tag] => Sensor::from_tag(*tag).ok_or(TraceError::Sensor)?,
+            _ => return Err(TraceError::Sensor),
+        };
+        let mut axis =
+            || -> Result<i32, TraceError> { next()?.parse().map_err(|_| TraceError::Number) };
+        let (x, y, z) = (axis()?, axis()?, axis()?);
+        if fields.next().is_some() {
+            return Err(TraceError::Fields);
+        }
+        Ok(Some(Self::new(t_ms, sensor, (x, y, z))))
+    }
+
+    pub fn to_bytes(&self) -> [u8; RECORD_LEN] {
+        let mut record = [0u8; RECORD_LEN];
+        record[0] = self.sensor.tag();
+        record[1..5].copy_from_slice(&self.t_ms.to_le_bytes());
+        record[5..9].copy_from_slice(&self.x.to_le_bytes());
+        record[9..13].copy_from_slice(&self.y.to_le_bytes());
+        record[13..17].copy_from_slice(&self.z.to_le_bytes());
+        record
+    }
+
+    pub fn from_bytes(record: &[u8; RECORD_LEN]) -> Result<Self, TraceError> {
+        let sensor = Sensor::from_tag(record[0]).ok_or(TraceError::Sensor)?;
+        let word = |i: usize| [record[i], record[i + 1], record[i + 2], record[i + 3]];
+        Ok(Self::new(
+            u32::from_le_bytes(word(1)),
+            sensor,
+            (
+                i32::from_le_bytes(word(5)),
+                i32::from_le_bytes(word(9)),
+                i32::from_le_bytes(word(13)),
+            ),
+        ))
+    }
+}
+
+impl From<Sample> for Reading {
+    fn from(sample: Sample) -> Self {
+        match sample.sensor {
+            Sensor::Accel => Reading::Accel(sample.xyz()),
+            Sensor::Mag => Reading::Mag(sample.xyz()),
+        }
+    }
+}
+
+/// Drives a [`Sampler`] from a trace.
+///
+/// Every accelerometer sample moves the cursor. When that lands on a cell still needing a sample,
+/// the next magnetometer sample in the trace is the one recorded, which is exactly what
+/// [`calc_calibration`] would have read. Magnetometer samples that arrive once the sampler is
+/// complete are handed back to the caller, so a trace that carries on into a compass loop can be
+/// replayed through the heading code too.
+///
+/// [`calc_calibration`]: crate::calc_calibration
+#[derive(Debug, Clone, Default)]
+pub struct Replay {
+    sampler: Sampler,
+    want_mag: bool,
+}
+
+/// What [`Replay::feed`] did with a sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    /// The cursor moved to this `(row, column)`.
+    Tilt((usize, usize)),
+    /// The sample was recorded for this `(row, column)`.
+    Recorded((usize, usize)),
+    /// The sampler is complete and this is a reading for the caller.
+    Reading(Measurement),
+    /// Nothing happened: a magnetometer sample nobody asked for.
+    Skipped,
+}
+
+impl Replay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn feed(&mut self, sample: &Sample) -> Step {
+        match sample.sensor {
+            Sensor::Accel => {
+                if self.sampler.is_complete() {
+                    return Step::Skipped;
+                }
+                self.want_mag = self.sampler.tilt((sample.x, sample.y));
+                Step::Tilt(self.sampler.cursor())
+            }
+            Sensor::Mag if self.sampler.is_complete() => {
+                Step::Reading(Measurement::new(sample.xyz()))
+            }
+            Sensor::Mag if self.want_mag => {
+                self.want_mag = false;
+                self.sampler.record(Measurement::new(sample.xyz()));
+                Step::Recorded(self.sampler.cursor())
+            }
+            Sensor::Mag => Step::Skipped,
+        }
+    }
+
+    pub fn sampler(&self) -> &Sampler {
+        &self.sampler
+    }
+}
@@ -0,0 +1,55 @@
+use embedded_hal::delay::DelayNs;
+
+use microbit::display::blocking::Display;
+
+use sensors::{Accelerometer, Magnetometer};
+
+use crate::trace::Sensor;
+use crate::{Calibration, Measurement, Sampler};
+
+/// Run the interactive calibration: the user tilts the board until every LED has lit up.
+pub fn calc_calibration<S, T>(sensor: &mut S, display: &mut Display, timer: &mut T) -> Calibration
+where
+    S: Accelerometer + Magnetometer,
+    T: DelayNs,
+{
+    calc_calibration_traced(sensor, display, timer, |_, _| ())
+}
+
+/// Like [`calc_calibration`], but hands every reading it takes to `trace` as it goes. Accelerometer
+/// readings are in mg and magnetometer readings in nT, as the sensor reports them.
+pub fn calc_calibration_traced<S, T, F>(
+    sensor: &mut S,
+    display: &mut Display,
+    timer: &mut T,
+    mut trace: F,
+) -> Calibration
+where
+    S: Accelerometer + Magnetometer,
+    T: DelayNs,
+    F: FnMut(Sensor, (i32, i32, i32)),
+{
+    let mut sampler = Sampler::new();
+    let mut blink_state = true;
+
+    while !sampler.is_complete() {
+        let accel = sensor.wait_accel(timer).unwrap();
+        trace(Sensor::Accel, accel);
+        let (x, y, _) = accel;
+
+        if sampler.tilt((x, y)) {
+            let mag = sensor.wait_mag(timer).unwrap();
+            trace(Sensor::Mag, mag);
+            sampler.record(Measurement::new(mag));
+        }
+
+        let mut leds = sampler.visited().map(|row| row.map(u8::from));
+        let (r, c) = sampler.cursor();
+        if blink_state {
+            leds[r][c] = 1 - leds[r][c];
+        }
+        display.show(timer, leds, 200);
+        blink_state = !blink_state;
+    }
+    sampler.calibration().unwrap()
+}
@@ -0,0 +1,305 @@
+//! Tests the compass's headings, heading filter, LED hysteresis and declination commands off
+//! the board.
+//!
+//! ```text
+//! cargo test-host -p mag-cal --features declination-table
+//! ```
+//!
+//! Each test feeds headings as the compass would see them, fields and gravity as a tilted board
+//! would read them, or lines as typed at it, and compares what comes out with what should.
+
+use std::panic;
+
+use mag_cal::{
+    filter::{HeadingFilter, SectorHysteresis},
+    heading::{magnetic_heading, tilt_compensated_heading, wrap_180, DeclinationCommand},
+    Measurement,
+};
+
+/// The filter the compass uses: half a second at 10 samples a second.
+fn compass_filter() -> HeadingFilter {
+    HeadingFilter::new(0.5, 0.1)
+}
+
+/// How far apart two headings are, in degrees, the short way round.
+fn apart(a: f32, b: f32) -> f32 {
+    wrap_180(a - b).abs()
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// What a board on `heading`, with its logo edge raised by `pitch` and then its right edge
+/// lowered by `roll` (all in degrees), reads: the calibrated field, in its (left, forward, down)
+/// axes, and gravity in the accelerometer's (right, forward, out of the LEDs) axes.
+fn tilted(heading: f32, pitch: f32, roll: f32) -> (Measurement, (i32, i32, i32)) {
+    let (h, p, r) = (heading.to_radians(), pitch.to_radians(), roll.to_radians());
+    // The board's axes in north, east, down, from which the field dips 60° below north.
+    let forward = [h.cos() * p.cos(), h.sin() * p.cos(), -p.sin()];
+    let level_right = [-h.sin(), h.cos(), 0.0];
+    let below = cross(forward, level_right);
+    let right = level_right.map(|v| v * r.cos());
+    let right = [0, 1, 2].map(|i| right[i] + below[i] * r.sin());
+    let down = cross(forward, right);
+    let field = [250.0, 0.0, 433.0];
+    let gravity = [0.0, 0.0, 1000.0];
+    (
+        Measurement {
+            x: -dot(field, right).round() as i32,
+            y: dot(field, forward).round() as i32,
+            z: dot(field, down).round() as i32,
+        },
+        (
+            dot(gravity, right).round() as i32,
+            dot(gravity, forward).round() as i32,
+            -dot(gravity, down).round() as i32,
+        ),
+    )
+}
+
+#[test]
+fn tilted_headings() {
+    for heading in (0..360).step_by(15).map(|h| h as f32) {
+        // Level, it agrees with the heading that assumes the board is level...
+        let (field, gravity) = tilted(heading, 0.0, 0.0);
+        assert_eq!(gravity, (0, 0, -1000), "level gravity");
+        let level = magnetic_heading(field);
+        assert!(
+            apart(level, heading) <= 0.5
+                && apart(tilt_compensated_heading(field, gravity), level) <= 0.5,
+            "level on {}: got {}",
+            heading,
+            level
+        );
+        // ...and tilted, it still finds the heading, where that one goes astray.
+        for (pitch, roll) in [
+            (30.0, 0.0),
+            (-30.0, 0.0),
+            (0.0, 40.0),
+            (0.0, -40.0),
+            (25.0, -35.0),
+        ] {
+            let (field, gravity) = tilted(heading, pitch, roll);
+            let got = tilt_compensated_heading(field, gravity);
+            assert!(
+                apart(got, heading) <= 0.5,
+                "on {} pitched {} and rolled {}: got {}",
+                heading,
+                pitch,
+                roll,
+                got
+            );
+        }
+    }
+    let (field, _) = tilted(90.0, 30.0, 0.0);
+    assert!(
+        apart(magnetic_heading(field), 90.0) > 5.0,
+        "uncompensated goes astray"
+    );
+    // In free fall there is no down, so it falls back on the level heading.
+    assert_eq!(
+        tilt_compensated_heading(field, (0, 0, 0)),
+        magnetic_heading(field),
+        "weightless"
+    );
+}
+
+#[test]
+fn filter_start() {
+    let mut filter = compass_filter();
+    assert_eq!(filter.heading(), None, "before");
+    assert_eq!(filter.update(123.0), 123.0, "first");
+    filter.reset();
+    assert_eq!(filter.heading(), None, "after reset");
+    assert_eq!(filter.update(-30.0), 330.0, "first after reset");
+}
+
+#[test]
+fn filter_across_north_and_south() {
+    // Jittering either side of north, then of south, and swinging right round through north.
+    let runs: [(&[f32], f32); 3] = [
+        (&[359.0, 1.0], 0.0),
+        (&[179.0, -179.0], 180.0),
+        (&[350.0, 10.0, 355.0, 5.0], 0.0),
+    ];
+    for (headings, mean) in runs {
+        let mut filter = compass_filter();
+        for i in 0..100 {
+            let heading = filter.update(headings[i % headings.len()]);
+            assert!(
+                (0.0..360.0).contains(&heading) && apart(heading, mean) <= 10.0,
+                "{:?}, sample {}: {}",
+                headings,
+                i,
+                heading
+            );
+        }
+        let settled = filter.heading().unwrap();
+        assert!(
+            apart(settled, mean) <= 2.0,
+            "{:?} settled on {}, expected {}",
+            headings,
+            settled,
+            mean
+        );
+    }
+}
+
+#[test]
+fn filter_turning() {
+    let mut filter = compass_filter();
+    filter.update(350.0);
+    let mut last = 350.0;
+    // Turning to 20°: the filter should get there the short way, through north, and not overshoot.
+    for i in 0..50 {
+        let heading = filter.update(20.0);
+        assert!(
+            wrap_180(heading - last) >= 0.0 && wrap_180(heading - 20.0) <= 0.01,
+            "sample {}: {} after {}",
+            i,
+            heading,
+            last
+        );
+        last = heading;
+    }
+    assert!(apart(last, 20.0) < 0.1, "ended on {}", last);
+}
+
+#[test]
+fn sector_hysteresis() {
+    // The compass's 16 LEDs, 22.5° each, with 4° of hysteresis.
+    let mut sectors = SectorHysteresis::new(16, 4.0);
+    assert_eq!(sectors.sector(), None, "before");
+    // Back and forth across ±180°, in both ways of writing it, then further each time.
+    let steps: [(f32, usize); 12] = [
+        (180.0, 8),
+        (-179.0, 8),
+        (179.0, 8),
+        (-170.0, 8),
+        // Past the edge at 191.25°, but not by the 4° it takes.
+        (-166.0, 8),
+        (170.0, 8),
+        (-164.0, 9),
+        (-170.0, 9),
+        (189.0, 9),
+        (186.0, 8),
+        (540.0, 8),
+        (-540.0, 8),
+    ];
+    for (angle, sector) in steps {
+        assert_eq!(sectors.update(angle), sector, "{}", angle);
+    }
+    // And across north.
+    let steps: [(f32, usize); 6] = [
+        (0.0, 0),
+        (-12.0, 0),
+        (359.0, 0),
+        (-16.0, 15),
+        (-8.0, 15),
+        (4.0, 0),
+    ];
+    for (angle, sector) in steps {
+        assert_eq!(sectors.update(angle), sector, "{}", angle);
+    }
+    assert_eq!(sectors.nearest(360.0), 0, "nearest 360");
+    assert_eq!(sectors.nearest(-0.0), 0, "nearest -0");
+    assert_eq!(sectors.nearest(348.74), 15, "nearest 348.75");
+}
+
+#[test]
+fn one_sector() {
+    let mut sectors = SectorHysteresis::new(1, 0.0);
+    for angle in [0.0, 90.0, -180.0, 179.9, 359.9] {
+        assert_eq!(sectors.update(angle), 0, "{}", angle);
+    }
+}
+
+/// Whether `f` panics.
+fn panics(f: impl FnOnce() + panic::UnwindSafe) -> bool {
+    panic::catch_unwind(f).is_err()
+}
+
+#[test]
+fn rejected_filter_settings() {
+    for (time_constant, period, panic) in [
+        (0.0, 0.0, true),
+        (0.0, 0.1, true),
+        (0.5, 0.0, true),
+        (-0.5, 0.1, true),
+        (0.5, 0.1, false),
+    ] {
+        let panicked = panics(|| {
+            HeadingFilter::new(time_constant, period);
+        });
+        assert_eq!(panicked, panic, "filter {}, {}", time_constant, period);
+    }
+    for (sectors, panic) in [(0, true), (1, false)] {
+        let panicked = panics(|| {
+            SectorHysteresis::new(sectors, 4.0);
+        });
+        assert_eq!(panicked, panic, "{} sectors", sectors);
+    }
+}
+
+fn assert_commands(cases: &[(&str, Result<DeclinationCommand, &str>)]) {
+    for (line, expected) in cases {
+        assert_eq!(line.parse::<DeclinationCommand>(), *expected, "{}", line);
+    }
+}
+
+#[test]
+fn declination_commands() {
+    assert_commands(&[
+        ("decl", Ok(DeclinationCommand::Show)),
+        ("  decl  ", Ok(DeclinationCommand::Show)),
+        ("decl 15.2", Ok(DeclinationCommand::Set(15.2))),
+        ("decl -180", Ok(DeclinationCommand::Set(-180.0))),
+        (
+            "loc 45.5 -122.7",
+            Ok(DeclinationCommand::Locate {
+                lat: 45.5,
+                lon: -122.7,
+            }),
+        ),
+        (
+            "loc -90 180",
+            Ok(DeclinationCommand::Locate {
+                lat: -90.0,
+                lon: 180.0,
+            }),
+        ),
+    ]);
+}
+
+#[test]
+fn rejected_commands() {
+    let usage = Err("expected decl [degrees east] | loc <lat> <lon>");
+    let declination = Err("declination must be a number within ±180°");
+    let latitude = Err("latitude must be a number within ±90°");
+    let longitude = Err("longitude must be a number within ±180°");
+    assert_commands(&[
+        ("", usage),
+        ("heading", usage),
+        ("decl 1 2", usage),
+        ("loc 45", usage),
+        ("loc 1 2 3", usage),
+        ("decl east", declination),
+        ("decl 180.5", declination),
+        ("decl -200", declination),
+        ("decl NaN", declination),
+        ("decl inf", declination),
+        ("loc 91 0", latitude),
+        ("loc north 0", latitude),
+        ("loc 0 -181", longitude),
+        ("loc 0 NaN", longitude),
+    ]);
+}
@@ -0,0 +1,68 @@
+//! Running commands on the board's command line, as the book's `shell` crate presents it.
+
+use std::{
+    io::{Read, Write},
+    time::{Duration, Instant},
+};
+
+use crate::{port::Port, Error};
+
+/// How long a command may take to answer.
+const TIMEOUT: Duration = Duration::from_secs(2);
+/// What the board shows when it's ready for a command.
+const PROMPT: &[u8] = b"> ";
+
+/// A connection to a program on the board that reads commands with `shell::Editor`.
+pub struct Console<'a> {
+    port: &'a mut Port,
+}
+
+impl<'a> Console<'a> {
+    /// Get the board's attention: Ctrl-C stops whatever it is doing and clears the line, so
+    /// the next thing it sends is a fresh prompt.
+    pub fn connect(port: &'a mut Port) -> Result<Self, Error> {
+        port.write_all(b"\x03")?;
+        let mut console = Self { port };
+        console.read_until(|received| received.ends_with(PROMPT), true)?;
+        Ok(console)
+    }
+
+    /// Run `line` and return what it printed, with `\n` line endings. A line the board
+    /// answers with `error: ...` is an [`Error::Device`].
+    pub fn command(&mut self, line: &str) -> Result<String, Error> {
+        self.port.write_all(line.as_bytes())?;
+        self.port.write_all(b"\r")?;
+        let received = self.read_until(|received| received.ends_with(b"\r\n> "), false)?;
+        let received = String::from_utf8_lossy(&received).replace("\r\n", "\n");
+        let output = received
+            .strip_prefix(line)
+            .and_then(|rest| rest.strip_prefix('\n'))
+            .ok_or_else(|| Error::Device(format!("the board didn't echo `{}`", line)))?;
+        let output = output.strip_suffix("> ").unwrap_or(output);
+        match output.strip_prefix("error: ") {
+            Some(error) => Err(Error::Device(error.trim_end().to_string())),
+            None => Ok(output.to_string()),
+        }
+    }
+
+    /// Read until what has arrived satisfies `done`, and, if `quiet`, nothing more follows for
+    /// a moment.
+    fn read_until(&mut self, done: impl Fn(&[u8]) -> bool, quiet: bool) -> Result<Vec<u8>, Error> {
+        let deadline = Instant::now() + TIMEOUT;
+        let mut received = Vec::new();
+        let mut buf = [0; 256];
+        loop {
+            let n = self.port.read(&mut buf)?;
+            received.extend_from_slice(&buf[..n]);
+            if done(&received) && (!quiet || n == 0) {
+                return Ok(received);
+            }
+            if Instant::now() > deadline {
+                return Err(Error::Device(
+                    "no prompt from the board; is a program with a command line running?"
+                        .to_string(),
+                ));
+            }
+        }
+    }
+}
@@ -0,0 +1,45 @@
+//! 5x5 pictures for the LED matrix, as text files.
+//!
+//! A picture is five lines of five characters, one per LED: a digit from `0` (off) to `9`
+//! (brightest), `.` for off or `#` for fully on. Blank lines are ignored.
+//!
+//! ```text
+//! .#.#.
+//! #####
+//! #####
+//! .###.
+//! ..#..
+//! ```
+
+/// The picture in `text`, row by row.
+pub fn parse(text: &str) -> Result<[u8; 25], String> {
+    let rows: Vec<&str> = text
+        .lines()
+        .map(str::trim_end)
+        .filter(|l| !l.is_empty())
+        .collect();
+    if rows.len() != 5 {
+        return Err(format!("expected 5 rows, found {}", rows.len()));
+    }
+    let mut image = [0; 25];
+    for (r, row) in rows.iter().enumerate() {
+        if row.chars().count() != 5 {
+            return Err(format!("row {} isn't 5 LEDs long", r + 1));
+        }
+        for (c, led) in row.chars().enumerate() {
+            image[r * 5 + c] = match led {
+                '.' => 0,
+                '#' => 9,
+                '0'..='9' => led as u8 - b'0',
+                _ => return Err(format!("row {}: `{}` isn't a brightness", r + 1, led)),
+            };
+        }
+    }
+    Ok(image)
+}
+
+/// The command that shows `image` on the board: `image` and the 25 brightnesses as digits.
+pub fn command(image: &[u8; 25]) -> String {
+    let digits: String = image.iter().map(|b| char::from(b'0' + b)).collect();
+    format!("image {}", digits)
+}
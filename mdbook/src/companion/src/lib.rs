@@ -0,0 +1,367 @@
+//! Talking to the book's programs from your computer, instead of through minicom or PuTTY.
+//!
+//! [`Port`] opens the board's serial port; [`Console`] runs commands on a program with a
+//! command line, such as the sensor console from the I2C chapter. The `companion` program is
+//! built from the subcommands here.
+
+use std::{
+    fmt,
+    io::{self, BufRead, Read, Write},
+    path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+};
+
+use sensors::{
+    accel_cal::{resting_face, solve, SolveError, FACES},
+    attitude::Orientation,
+    Xyz,
+};
+use telemetry_host::{csv, Decoder, Stats};
+
+mod console;
+pub mod image;
+pub mod port;
+
+pub use console::Console;
+pub use port::Port;
+
+/// Ctrl-], which ends [`monitor`], as in telnet.
+pub const QUIT: u8 = 0x1d;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// The command line doesn't make sense.
+    Usage(String),
+    /// The board answered, but not as hoped.
+    Device(String),
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Usage(why) => write!(f, "{}", why),
+            Error::Device(why) => write!(f, "board: {}", why),
+        }
+    }
+}
+
+pub const USAGE: &str = "\
+usage: companion [--port PATH] [--baud N] COMMAND
+
+The port defaults to $MB2_PORT, or /dev/ttyACM0, at 115200 baud.
+
+commands:
+  monitor                    pass the terminal through to the board; Ctrl-] quits
+  get <acc|mag>              take one reading from the sensor console
+  send-image FILE            show a 5x5 picture on the LED matrix
+  log [--csv] [--count N]    decode telemetry packets, until the board goes away
+  calibrate [--samples N]    six-position accelerometer calibration, saved on the board
+";
+
+/// What to do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Monitor,
+    Get(String),
+    SendImage(PathBuf),
+    Log { csv: bool, count: Option<u64> },
+    Calibrate { samples: u32 },
+}
+
+/// The whole command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    pub port: PathBuf,
+    pub baud: u32,
+    pub command: Command,
+}
+
+fn usage(why: impl Into<String>) -> Error {
+    Error::Usage(why.into())
+}
+
+/// The value after an option.
+fn value<'a, T: std::str::FromStr>(
+    args: &mut impl Iterator<Item = &'a str>,
+    option: &str,
+) -> Result<T, Error> {
+    args.next()
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| usage(format!("{} needs a number", option)))
+}
+
+impl Options {
+    /// Parse the arguments after the program's name, using `default_port` unless `--port` says
+    /// otherwise.
+    pub fn parse(args: &[String], default_port: PathBuf) -> Result<Self, Error> {
+        let mut args = args.iter().map(String::as_str);
+        let mut port = default_port;
+        let mut baud = 115_200;
+        let command = loop {
+            match args.next() {
+                Some("--port") => {
+                    port = args
+                        .next()
+                        .ok_or_else(|| usage("--port needs a path"))?
+                        .into()
+                }
+                Some("--baud") => baud = value(&mut args, "--baud")?,
+                Some(command) => break command,
+                None => return Err(usage("no command")),
+            }
+        };
+        let command = match command {
+            "monitor" => Command::Monitor,
+            "get" => match args.next() {
+                Some(sensor @ ("acc" | "mag")) => Command::Get(sensor.to_string()),
+                _ => return Err(usage("get what? acc or mag")),
+            },
+            "send-image" => {
+                Command::SendImage(args.next().ok_or_else(|| usage("send which file?"))?.into())
+            }
+            "log" => {
+                let (mut csv, mut count) = (false, None);
+                while let Some(option) = args.next() {
+                    match option {
+                        "--csv" => csv = true,
+                        "--count" => count = Some(value(&mut args, "--count")?),
+                        _ => return Err(usage(format!("log: unknown option {}", option))),
+                    }
+                }
+                Command::Log { csv, count }
+            }
+            "calibrate" => {
+                let mut samples = 20;
+                while let Some(option) = args.next() {
+                    match option {
+                        "--samples" => samples = value(&mut args, "--samples")?,
+                        _ => return Err(usage(format!("calibrate: unknown option {}", option))),
+                    }
+                }
+                if samples == 0 {
+                    return Err(usage("--samples must be at least 1"));
+                }
+                Command::Calibrate { samples }
+            }
+            _ => return Err(usage(format!("unknown command {}", command))),
+        };
+        if let Some(extra) = args.next() {
+            return Err(usage(format!("unexpected {}", extra)));
+        }
+        Ok(Self {
+            port,
+            baud,
+            command,
+        })
+    }
+}
+
+/// Copy `input` to the board and what the board sends to `out`, until `input` ends or sends
+/// [`QUIT`]. Once `input` ends, anything the board is still sending is copied until it stops.
+pub fn monitor(
+    port: &mut Port,
+    input: &mut dyn Read,
+    out: &mut (dyn Write + Send),
+) -> Result<(), Error> {
+    let mut reader = port.try_clone()?;
+    let stop = AtomicBool::new(false);
+    thread::scope(|scope| {
+        let receiving = scope.spawn(|| -> io::Result<()> {
+            let mut buf = [0; 256];
+            loop {
+                let n = match reader.read(&mut buf) {
+                    Err(e) if port::is_hangup(&e) => return Ok(()),
+                    result => result?,
+                };
+                if n == 0 && stop.load(Ordering::Relaxed) {
+                    return Ok(());
+                }
+                out.write_all(&buf[..n])?;
+                out.flush()?;
+            }
+        });
+
+        let mut buf = [0; 64];
+        let sent = loop {
+            let n = match input.read(&mut buf) {
+                Ok(0) => break Ok(()),
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => break Err(e),
+            };
+            let (typed, quit) = match buf[..n].iter().position(|b| *b == QUIT) {
+                Some(at) => (&buf[..at], true),
+                None => (&buf[..n], false),
+            };
+            if let Err(e) = port.write_all(typed) {
+                break Err(e);
+            }
+            if quit {
+                break Ok(());
+            }
+        };
+        stop.store(true, Ordering::Relaxed);
+        let received = receiving.join().unwrap();
+        sent.and(received).map_err(Error::from)
+    })
+}
+
+/// Take one reading from the sensor console, and print it the way the console did.
+pub fn get(port: &mut Port, sensor: &str, out: &mut dyn Write) -> Result<(), Error> {
+    let reading = Console::connect(port)?.command(sensor)?;
+    out.write_all(reading.as_bytes())?;
+    Ok(())
+}
+
+/// Show the picture in `text` (see [`image`]) on the board's LED matrix, through the UART
+/// chapter's `remote-display` example.
+pub fn send_image(port: &mut Port, text: &str, out: &mut dyn Write) -> Result<(), Error> {
+    let image = image::parse(text).map_err(Error::Usage)?;
+    let reply = Console::connect(port)?.command(&image::command(&image))?;
+    out.write_all(reply.as_bytes())?;
+    Ok(())
+}
+
+/// Decode telemetry packets from the board and write them to `out`, as CSV or one packet to a
+/// line, until `count` packets have arrived or the board goes away.
+pub fn log(
+    port: &mut Port,
+    csv: bool,
+    count: Option<u64>,
+    out: &mut dyn Write,
+) -> Result<Stats, Error> {
+    if csv {
+        csv::write_header(out)?;
+    }
+    let mut decoder = Decoder::new();
+    let mut buf = [0; 256];
+    loop {
+        let n = match port.read(&mut buf) {
+            Err(e) if port::is_hangup(&e) => break,
+            result => result?,
+        };
+        // A byte at a time, so that the counts stop at the last packet written.
+        for &byte in &buf[..n] {
+            let Some(Ok(packet)) = decoder.push(byte) else {
+                continue;
+            };
+            if csv {
+                csv::write_row(out, &packet)?;
+            } else {
+                writeln!(
+                    out,
+                    "{:>5} {:>8} ms  {:?}",
+                    packet.seq, packet.t_ms, packet.message
+                )?;
+            }
+            if count.is_some_and(|count| decoder.stats().packets >= count) {
+                out.flush()?;
+                return Ok(decoder.stats());
+            }
+        }
+        out.flush()?;
+    }
+    Ok(decoder.stats())
+}
+
+/// How to hold the board for `face`.
+fn describe(face: Orientation) -> &'static str {
+    match face {
+        Orientation::FaceUp => "lay the board flat, LEDs up",
+        Orientation::FaceDown => "lay the board flat, LEDs down",
+        Orientation::LogoUp => "stand the board on the edge opposite the logo",
+        Orientation::LogoDown => "stand the board on its logo edge",
+        Orientation::LeftUp => "stand the board on its right edge",
+        Orientation::RightUp => "stand the board on its left edge",
+    }
+}
+
+/// An accelerometer reading from the sensor console in CSV format, as `,acc,x,y,z,mg`.
+fn parse_acc(line: &str) -> Option<Xyz> {
+    let fields: Vec<&str> = line.trim().split(',').collect();
+    match fields.as_slice() {
+        [_, "acc", x, y, z, "mg"] => Some((x.parse().ok()?, y.parse().ok()?, z.parse().ok()?)),
+        _ => None,
+    }
+}
+
+/// The average of `samples` accelerometer readings.
+fn average(console: &mut Console, samples: u32) -> Result<Xyz, Error> {
+    let mut sum = (0i64, 0i64, 0i64);
+    for _ in 0..samples {
+        let reply = console.command("acc")?;
+        let (x, y, z) = parse_acc(&reply)
+            .ok_or_else(|| Error::Device(format!("unexpected reading `{}`", reply.trim())))?;
+        sum = (sum.0 + x as i64, sum.1 + y as i64, sum.2 + z as i64);
+    }
+    let n = samples as i64;
+    Ok(((sum.0 / n) as i32, (sum.1 / n) as i32, (sum.2 / n) as i32))
+}
+
+/// Work out the accelerometer's offsets and gains through the sensor console, asking on `out`
+/// for the board to be held in each of its six positions in turn and waiting for a line on
+/// `input` before averaging `samples` readings there. The console saves the result with its
+/// `accel-cal` command, for the tilt app to use.
+pub fn calibrate(
+    port: &mut Port,
+    samples: u32,
+    input: &mut dyn BufRead,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    let mut console = Console::connect(port)?;
+    console.command("format csv")?;
+    console.command("unit mg")?;
+
+    let mut averages = [(0, 0, 0); 6];
+    for (i, face) in FACES.into_iter().enumerate() {
+        loop {
+            write!(out, "{}/6: {}, then press Enter ", i + 1, describe(face))?;
+            out.flush()?;
+            if input.read_line(&mut String::new())? == 0 {
+                return Err(usage("calibration abandoned"));
+            }
+            let reading = average(&mut console, samples)?;
+            let (x, y, z) = reading;
+            writeln!(out, "  x {} y {} z {} mg", x, y, z)?;
+            match resting_face(reading) {
+                Some(f) if f == face => {
+                    averages[i] = reading;
+                    break;
+                }
+                _ => writeln!(out, "  that isn't {}; try again", describe(face))?,
+            }
+        }
+    }
+    console.command("format human")?;
+
+    let calibration = solve(&averages).map_err(|SolveError::Span { axis, up, down }| {
+        Error::Device(format!(
+            "the {} axis read {} mg one way up and {} mg the other, which doesn't add up",
+            ["x", "y", "z"][axis],
+            up,
+            down
+        ))
+    })?;
+    let [ox, oy, oz] = calibration.offset();
+    let [gx, gy, gz] = calibration.gain();
+    writeln!(out, "offset: x {:.1} y {:.1} z {:.1} mg", ox, oy, oz)?;
+    writeln!(out, "gain:   x {:.4} y {:.4} z {:.4}", gx, gy, gz)?;
+    let bytes: String = calibration
+        .to_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    writeln!(out, "bytes:  {}", bytes)?;
+    console.command(&format!("accel-cal {}", bytes))?;
+    writeln!(out, "saved on the board")?;
+    Ok(())
+}
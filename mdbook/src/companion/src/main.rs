@@ -0,0 +1,77 @@
+//! A companion for the book's programs, run on your computer.
+//!
+//! ```text
+//! cargo run -- get acc
+//! cargo run -- --port /dev/ttyACM1 monitor
+//! ```
+//!
+//! Run it with no arguments for the list of commands.
+
+use std::{
+    env, fs,
+    io::{self, IsTerminal},
+    path::PathBuf,
+    process,
+};
+
+use companion::{Command, Error, Options, Port, USAGE};
+use nix::sys::termios::{self, SetArg};
+
+/// Run `f` with the terminal sending each key straight through, rather than waiting for a
+/// line and acting on Ctrl-C itself.
+fn with_raw_terminal<T>(f: impl FnOnce() -> T) -> io::Result<T> {
+    let stdin = io::stdin();
+    if !stdin.is_terminal() {
+        return Ok(f());
+    }
+    let saved = termios::tcgetattr(&stdin)?;
+    let mut raw = saved.clone();
+    termios::cfmakeraw(&mut raw);
+    termios::tcsetattr(&stdin, SetArg::TCSANOW, &raw)?;
+    let result = f();
+    termios::tcsetattr(&stdin, SetArg::TCSANOW, &saved)?;
+    Ok(result)
+}
+
+fn run(options: Options) -> Result<(), Error> {
+    let mut port = Port::open(&options.port, options.baud)?;
+    let mut stdout = io::stdout();
+    match options.command {
+        Command::Monitor => {
+            eprintln!("connected to {}; Ctrl-] quits", options.port.display());
+            with_raw_terminal(|| companion::monitor(&mut port, &mut io::stdin(), &mut stdout))?
+        }
+        Command::Get(sensor) => companion::get(&mut port, &sensor, &mut stdout),
+        Command::SendImage(path) => {
+            let text = fs::read_to_string(&path)?;
+            companion::send_image(&mut port, &text, &mut stdout)
+        }
+        Command::Log { csv, count } => {
+            let stats = companion::log(&mut port, csv, count, &mut stdout)?;
+            eprintln!(
+                "{} packets, {} bad frames, {} lost",
+                stats.packets, stats.errors, stats.lost
+            );
+            Ok(())
+        }
+        Command::Calibrate { samples } => {
+            companion::calibrate(&mut port, samples, &mut io::stdin().lock(), &mut stdout)
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let default_port = env::var_os("MB2_PORT").map_or("/dev/ttyACM0".into(), PathBuf::from);
+    let options = match Options::parse(&args, default_port) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("companion: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = run(options) {
+        eprintln!("companion: {}", e);
+        process::exit(1);
+    }
+}
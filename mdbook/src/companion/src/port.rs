@@ -0,0 +1,93 @@
+//! The board's serial port, set up for talking to programs rather than people.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use nix::{
+    errno::Errno,
+    sys::termios::{self, BaudRate, SetArg, SpecialCharacterIndices},
+};
+
+/// Tenths of a second a read waits for the first byte before giving up.
+const READ_TIMEOUT_DS: u8 = 1;
+
+/// A serial port in raw mode, whose reads return 0 bytes if nothing arrives for a tenth of a
+/// second, rather than waiting for ever.
+#[derive(Debug)]
+pub struct Port {
+    file: File,
+}
+
+fn baud_rate(baud: u32) -> Option<BaudRate> {
+    Some(match baud {
+        1200 => BaudRate::B1200,
+        2400 => BaudRate::B2400,
+        4800 => BaudRate::B4800,
+        9600 => BaudRate::B9600,
+        19200 => BaudRate::B19200,
+        38400 => BaudRate::B38400,
+        57600 => BaudRate::B57600,
+        115200 => BaudRate::B115200,
+        230400 => BaudRate::B230400,
+        460800 => BaudRate::B460800,
+        921600 => BaudRate::B921600,
+        1000000 => BaudRate::B1000000,
+        _ => return None,
+    })
+}
+
+impl Port {
+    /// Open the port at `path`, running at `baud` bits per second with no parity and one stop
+    /// bit, the way the book's programs set up the UARTE.
+    pub fn open(path: &Path, baud: u32) -> io::Result<Self> {
+        let speed = baud_rate(baud).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported baud rate {}", baud),
+            )
+        })?;
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let mut settings = termios::tcgetattr(&file)?;
+        termios::cfmakeraw(&mut settings);
+        termios::cfsetspeed(&mut settings, speed)?;
+        settings.control_chars[SpecialCharacterIndices::VMIN as usize] = 0;
+        settings.control_chars[SpecialCharacterIndices::VTIME as usize] = READ_TIMEOUT_DS;
+        termios::tcsetattr(&file, SetArg::TCSANOW, &settings)?;
+        Ok(Self { file })
+    }
+
+    /// Another handle on the same port, for reading on one thread while writing on another.
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(Self {
+            file: self.file.try_clone()?,
+        })
+    }
+}
+
+impl Read for Port {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Write for Port {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Whether a read failed because the other end went away: the board was unplugged, or the
+/// program on the other end of a pseudo-terminal closed it.
+pub fn is_hangup(e: &io::Error) -> bool {
+    matches!(
+        e.raw_os_error().map(Errno::from_raw),
+        Some(Errno::EIO | Errno::ENXIO)
+    )
+}
@@ -0,0 +1,525 @@
+//! Tests the `companion` program's subcommands against fake boards on pseudo-terminals.
+//!
+//! ```text
+//! cargo test
+//! ```
+//!
+//! Each test opens a pseudo-terminal pair, hands one end to the companion as if it were the
+//! board's serial port, and runs a fake board on the other. The fake sensor console is built
+//! from the same `shell` editor and command table, and the same `i2c` output formats, as the
+//! real one, so the companion sees exactly the bytes the board would send.
+
+use std::{
+    fmt::Write as _,
+    fs::File,
+    io::{Read, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use companion::{Command, Console, Error, Options, Port};
+use i2c::{parse_calibration, Output, Reading, Sensor, Unit};
+use nix::{pty::openpty, unistd::ttyname};
+use sensors::{
+    accel_cal::{AccelCalibration, FACES},
+    attitude::Orientation,
+    Xyz,
+};
+use shell::{dispatch, Args, Command as ShellCommand, CommandError, Editor};
+use telemetry::{Encoder, Message};
+use telemetry_host::Stats;
+
+/// The fake accelerometer's errors: it reads `g / gain + offset` on each axis, in mg.
+const OFFSET: [f32; 3] = [40.0, -25.0, 70.0];
+const GAIN: [f32; 3] = [0.98, 1.03, 1.01];
+const MAG: Xyz = (-42, 123_456, 0);
+
+/// A pseudo-terminal with the companion's end open as a [`Port`], and the board's end.
+fn connect() -> (Port, File) {
+    let pty = openpty(None, None).unwrap();
+    let path = ttyname(&pty.slave).unwrap();
+    let port = Port::open(&path, 115_200).unwrap();
+    // Only the port holds the companion's end now, so the board sees it close with the port.
+    drop(pty.slave);
+    (port, File::from(pty.master))
+}
+
+/// What the fake board has been told and is showing.
+#[derive(Default)]
+struct State {
+    /// The commands run, by name.
+    ran: Vec<String>,
+    /// The positions the board is held in, one after another, for `samples` accelerometer
+    /// readings each. It stays in the last.
+    faces: Vec<Orientation>,
+    samples: u32,
+    reads: u32,
+    output: Output,
+    image: Option<String>,
+    /// What `accel-cal` saved.
+    calibration: Option<AccelCalibration>,
+}
+
+impl State {
+    fn accel(&mut self) -> Xyz {
+        let batch = (self.reads / self.samples.max(1)) as usize;
+        let face = self.faces[batch.min(self.faces.len() - 1)];
+        self.reads += 1;
+        let g = match face {
+            Orientation::FaceUp => [0.0, 0.0, -1000.0],
+            Orientation::FaceDown => [0.0, 0.0, 1000.0],
+            Orientation::LogoUp => [0.0, -1000.0, 0.0],
+            Orientation::LogoDown => [0.0, 1000.0, 0.0],
+            Orientation::LeftUp => [1000.0, 0.0, 0.0],
+            Orientation::RightUp => [-1000.0, 0.0, 0.0],
+        };
+        let axis = |i: usize| (g[i] / GAIN[i] + OFFSET[i]).round() as i32;
+        (axis(0), axis(1), axis(2))
+    }
+}
+
+/// The board's end of the line, for the `shell` crate.
+struct Wire(File);
+
+impl embedded_io::ErrorType for Wire {
+    type Error = embedded_io::ErrorKind;
+}
+
+impl embedded_io::Write for Wire {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.0.write(buf).map_err(|_| embedded_io::ErrorKind::Other)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+struct Board {
+    wire: Wire,
+    state: Arc<Mutex<State>>,
+}
+
+impl Board {
+    fn print(&mut self, text: &str) {
+        let _ = self.wire.0.write_all(text.as_bytes());
+    }
+
+    fn reading(&mut self, sensor: Sensor) {
+        let mut state = self.state.lock().unwrap();
+        let xyz = match sensor {
+            Sensor::Acc => state.accel(),
+            Sensor::Mag => MAG,
+        };
+        let mut text = String::new();
+        let reading = Reading {
+            sensor,
+            t_ms: None,
+            xyz,
+        };
+        state.output.write_reading(&mut text, &reading).unwrap();
+        drop(state);
+        self.print(&text);
+    }
+}
+
+fn acc(board: &mut Board, args: &mut Args) -> Result<(), CommandError> {
+    args.end()?;
+    board.reading(Sensor::Acc);
+    Ok(())
+}
+
+fn mag(board: &mut Board, args: &mut Args) -> Result<(), CommandError> {
+    args.end()?;
+    board.reading(Sensor::Mag);
+    Ok(())
+}
+
+fn format(board: &mut Board, args: &mut Args) -> Result<(), CommandError> {
+    board.state.lock().unwrap().output.format = args.parse("<human|csv|json>")?;
+    args.end()
+}
+
+fn unit(board: &mut Board, args: &mut Args) -> Result<(), CommandError> {
+    let unit: Unit = args.parse("<unit>")?;
+    args.end()?;
+    board.state.lock().unwrap().output.set_unit(unit);
+    Ok(())
+}
+
+fn image(board: &mut Board, args: &mut Args) -> Result<(), CommandError> {
+    let leds = args.next_arg("<leds>")?;
+    args.end()?;
+    if leds.len() != 25 || !leds.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(CommandError::Invalid("<leds>"));
+    }
+    board.state.lock().unwrap().image = Some(leds.to_string());
+    Ok(())
+}
+
+fn accel_cal(board: &mut Board, args: &mut Args) -> Result<(), CommandError> {
+    let calibration = parse_calibration(args)?.ok_or(CommandError::Missing("[hex]"))?;
+    board.state.lock().unwrap().calibration = Some(calibration);
+    Ok(())
+}
+
+const COMMANDS: &[ShellCommand<Board>] = &[
+    ShellCommand {
+        name: "acc",
+        usage: "",
+        help: "read the accelerometer",
+        run: acc,
+    },
+    ShellCommand {
+        name: "mag",
+        usage: "",
+        help: "read the magnetometer",
+        run: mag,
+    },
+    ShellCommand {
+        name: "format",
+        usage: "<human|csv|json>",
+        help: "how to write readings",
+        run: format,
+    },
+    ShellCommand {
+        name: "unit",
+        usage: "<mg|m/s2|nT|uT>",
+        help: "what to write readings in",
+        run: unit,
+    },
+    ShellCommand {
+        name: "image",
+        usage: "<leds>",
+        help: "show 25 brightnesses",
+        run: image,
+    },
+    ShellCommand {
+        name: "accel-cal",
+        usage: "[hex]",
+        help: "save an accelerometer calibration",
+        run: accel_cal,
+    },
+];
+
+/// Run a fake sensor console on `line` until the companion's end closes.
+fn console_board(mut line: File, state: Arc<Mutex<State>>) {
+    let mut board = Board {
+        wire: Wire(line.try_clone().unwrap()),
+        state,
+    };
+    let mut editor: Editor<64, 8> = Editor::new("> ");
+    let _ = editor.prompt(&mut board.wire);
+    let mut buf = [0; 64];
+    loop {
+        let n = match line.read(&mut buf) {
+            Ok(0) | Err(_) => return,
+            Ok(n) => n,
+        };
+        for &byte in &buf[..n] {
+            let names = COMMANDS.iter().map(|c| c.name);
+            let Ok(finished) = editor.feed(byte, names, &mut board.wire) else {
+                return;
+            };
+            if let Some(line) = finished {
+                if let Some(name) = line.split_whitespace().next() {
+                    board.state.lock().unwrap().ran.push(name.to_string());
+                }
+                if let Err(e) = dispatch(COMMANDS, line, &mut board) {
+                    board.print(&format!("error: {}\r\n", e));
+                }
+                let _ = editor.prompt(&mut board.wire);
+            }
+        }
+    }
+}
+
+/// Start a fake sensor console holding its accelerometer in `faces`, `samples` readings each.
+fn start_console(faces: &[Orientation], samples: u32) -> (Port, Arc<Mutex<State>>) {
+    let (port, line) = connect();
+    let state = Arc::new(Mutex::new(State {
+        faces: faces.to_vec(),
+        samples,
+        ..State::default()
+    }));
+    let board_state = state.clone();
+    thread::spawn(move || console_board(line, board_state));
+    (port, state)
+}
+
+fn ran(state: &Arc<Mutex<State>>) -> Vec<String> {
+    state.lock().unwrap().ran.clone()
+}
+
+#[test]
+fn options() {
+    let parse = |line: &str| {
+        let args: Vec<String> = line.split_whitespace().map(String::from).collect();
+        Options::parse(&args, "/dev/ttyACM0".into())
+    };
+    let command = |line: &str| parse(line).map(|o| o.command).map_err(|e| e.to_string());
+    assert_eq!(
+        parse("--port /dev/ttyUSB1 --baud 9600 monitor").unwrap(),
+        Options {
+            port: PathBuf::from("/dev/ttyUSB1"),
+            baud: 9600,
+            command: Command::Monitor,
+        },
+        "port and baud"
+    );
+    let cases = [
+        ("get mag", Ok(Command::Get("mag".into()))),
+        (
+            "send-image heart.txt",
+            Ok(Command::SendImage("heart.txt".into())),
+        ),
+        (
+            "log --count 10 --csv",
+            Ok(Command::Log {
+                csv: true,
+                count: Some(10),
+            }),
+        ),
+        ("calibrate", Ok(Command::Calibrate { samples: 20 })),
+        (
+            "calibrate --samples 5",
+            Ok(Command::Calibrate { samples: 5 }),
+        ),
+        ("get gyro", Err("get what? acc or mag".into())),
+        ("log --count many", Err("--count needs a number".into())),
+        ("monitor now", Err("unexpected now".into())),
+        ("--baud 9600", Err("no command".into())),
+        ("dance", Err("unknown command dance".into())),
+    ];
+    for (line, expected) in cases {
+        assert_eq!(command(line), expected, "{}", line);
+    }
+}
+
+#[test]
+fn get() {
+    let (mut port, state) = start_console(&[Orientation::FaceUp], 1);
+    let mut out = Vec::new();
+    let result = companion::get(&mut port, "acc", &mut out);
+    assert_eq!(result.map_err(|e| e.to_string()), Ok(()), "result");
+    assert_eq!(
+        String::from_utf8(out).unwrap().as_str(),
+        "Accelerometer: x 40 y -25 z -920 mg\n",
+        "output"
+    );
+    assert_eq!(ran(&state), vec!["acc".to_string()], "ran");
+}
+
+#[test]
+fn errors_from_the_board() {
+    let (mut port, _state) = start_console(&[Orientation::FaceUp], 1);
+    let mut console = Console::connect(&mut port).unwrap();
+    let unknown = console.command("gyro").map_err(|e| e.to_string());
+    let invalid = console.command("image 12").map_err(|e| e.to_string());
+    // Still in step afterwards.
+    let mag = console.command("mag").map_err(|e| e.to_string());
+    assert_eq!(
+        unknown,
+        Err("board: unknown command `gyro`, try `help`".into()),
+        "unknown"
+    );
+    assert_eq!(
+        invalid,
+        Err("board: image: invalid <leds> (usage: image <leds>)".into()),
+        "invalid"
+    );
+    assert_eq!(
+        mag,
+        Ok("Magnetometer: x -42 y 123456 z 0 nT\n".into()),
+        "mag"
+    );
+}
+
+#[test]
+fn no_board() {
+    // Nothing answers on the other end.
+    let (mut port, _line) = connect();
+    match Console::connect(&mut port) {
+        Err(Error::Device(_)) => {}
+        Err(e) => panic!("expected a device error, got {}", e),
+        Ok(_) => panic!("connected"),
+    }
+}
+
+#[test]
+fn send_image() {
+    let heart = ".#.#.\n#####\n#####\n.###.\n..#..\n";
+    let (mut port, state) = start_console(&[Orientation::FaceUp], 1);
+    let result = companion::send_image(&mut port, heart, &mut Vec::new());
+    assert_eq!(result.map_err(|e| e.to_string()), Ok(()), "result");
+    assert_eq!(
+        state.lock().unwrap().image.clone(),
+        Some("0909099999999990999000900".into()),
+        "image"
+    );
+
+    let dim = "1234\n56789\n00000\n00000\n00000";
+    let result = companion::send_image(&mut port, dim, &mut Vec::new());
+    assert_eq!(
+        result.map_err(|e| e.to_string()),
+        Err("row 1 isn't 5 LEDs long".into()),
+        "short row"
+    );
+    assert_eq!(ran(&state), vec!["image".to_string()], "ran");
+}
+
+/// Run `monitor` with `typed` as the keyboard, returning what it showed.
+fn monitored(port: &mut Port, typed: &[u8]) -> Result<String, Error> {
+    let mut out = Vec::new();
+    companion::monitor(port, &mut &typed[..], &mut out)?;
+    Ok(String::from_utf8(out).unwrap())
+}
+
+#[test]
+fn monitor() {
+    let (mut port, state) = start_console(&[Orientation::FaceUp], 1);
+    let shown = monitored(&mut port, b"mag\r").unwrap();
+    assert_eq!(
+        shown.as_str(),
+        "> mag\r\nMagnetometer: x -42 y 123456 z 0 nT\r\n> ",
+        "shown"
+    );
+    assert_eq!(ran(&state), vec!["mag".to_string()], "ran");
+}
+
+#[test]
+fn monitor_quits_on_ctrl() {
+    let (mut port, state) = start_console(&[Orientation::FaceUp], 1);
+    let result = monitored(&mut port, b"mag\r\x1dacc\r");
+    thread::sleep(Duration::from_millis(200));
+    assert!(result.is_ok(), "result");
+    assert_eq!(ran(&state), vec!["mag".to_string()], "ran");
+}
+
+#[test]
+fn calibrate() {
+    const SAMPLES: u32 = 5;
+    // Held the wrong way up at first.
+    let faces: Vec<_> = [Orientation::FaceDown].into_iter().chain(FACES).collect();
+    let (mut port, state) = start_console(&faces, SAMPLES);
+    let mut out = Vec::new();
+    let result = companion::calibrate(&mut port, SAMPLES, &mut &b"\n".repeat(7)[..], &mut out);
+    let out = String::from_utf8(out).unwrap();
+    assert_eq!(result.map_err(|e| e.to_string()), Ok(()), "result");
+    assert_eq!(out.matches("try again").count(), 1, "retries");
+    assert_eq!(state.lock().unwrap().reads, 7 * SAMPLES, "readings");
+    assert_eq!(
+        state.lock().unwrap().output,
+        Output {
+            acc_unit: Unit::MilliG,
+            ..Output::default()
+        },
+        "format afterwards"
+    );
+    let mut results = String::new();
+    for line in out
+        .lines()
+        .filter(|l| !l.contains("press Enter") && !l.starts_with(' '))
+    {
+        writeln!(results, "{}", line).unwrap();
+    }
+    assert_eq!(
+        results.as_str(),
+        "offset: x 40.0 y -25.0 z 70.0 mg\n\
+         gain:   x 0.9804 y 1.0299 z 1.0101\n\
+         bytes:  000020420000c8c100008c42fbfa7a3fa7d2833ffd4a813f\n\
+         saved on the board\n",
+        "calibration"
+    );
+    let saved = state.lock().unwrap().calibration;
+    assert_eq!(
+        saved.map(|c| {
+            (
+                c.offset().map(f32::round),
+                c.gain().map(|g| (g * 1e4).round()),
+            )
+        }),
+        Some(([40.0, -25.0, 70.0], [9804.0, 10299.0, 10101.0])),
+        "saved"
+    );
+}
+
+/// Send telemetry frames down `line`, corrupting the tenth and dropping the fifteenth, then
+/// close it after a pause.
+fn telemetry_board(mut line: File, packets: u16) {
+    let mut encoder = Encoder::new();
+    for seq in 0..packets {
+        let message = match seq % 3 {
+            0 => Message::Accel {
+                x: seq as i16,
+                y: -1,
+                z: 1000,
+            },
+            1 => Message::MicLevel(seq * 10),
+            _ => Message::Mag { x: 1, y: 2, z: -3 },
+        };
+        let frame = encoder.encode(10 * seq as u32, &message);
+        let mut bytes = frame.as_bytes().to_vec();
+        match seq {
+            9 => bytes[3] ^= 0x10,
+            14 => continue,
+            _ => {}
+        }
+        if line.write_all(&bytes).is_err() {
+            return;
+        }
+    }
+    thread::sleep(Duration::from_millis(300));
+}
+
+#[test]
+fn log() {
+    let (mut port, line) = connect();
+    thread::spawn(move || telemetry_board(line, 20));
+    let mut out = Vec::new();
+    let stats = companion::log(&mut port, true, None, &mut out).map_err(|e| e.to_string());
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(
+        stats,
+        Ok(Stats {
+            packets: 18,
+            errors: 1,
+            lost: 2,
+        }),
+        "stats"
+    );
+    assert_eq!(lines.len(), 19, "rows");
+    assert_eq!(
+        lines[0], "seq,t_ms,type,x,y,z,level,button,pressed",
+        "header"
+    );
+    assert_eq!(lines[1], "0,0,accel,0,-1,1000,,,", "first");
+    assert_eq!(lines[10], "10,100,mic,,,,100,,", "after the bad frame");
+}
+
+#[test]
+fn log_count() {
+    let (mut port, line) = connect();
+    thread::spawn(move || telemetry_board(line, 20));
+    let mut out = Vec::new();
+    let stats = companion::log(&mut port, false, Some(5), &mut out).map_err(|e| e.to_string());
+    let out = String::from_utf8(out).unwrap();
+    assert_eq!(stats.map(|s| s.packets), Ok(5), "packets");
+    assert_eq!(out.lines().count(), 5, "lines");
+    assert_eq!(
+        out.lines().next(),
+        Some("    0        0 ms  Accel { x: 0, y: -1, z: 1000 }"),
+        "first"
+    );
+}
+
+#[test]
+fn port_speeds() {
+    let pty = openpty(None, None).unwrap();
+    let path = ttyname(&pty.slave).unwrap();
+    assert!(Port::open(&path, 9600).is_ok(), "9600");
+    assert!(Port::open(&path, 12345).is_err(), "12345");
+}
@@ -0,0 +1,172 @@
+#![no_std]
+
+//! Drawing numbers, graphs and dots for the MB2's 5×5 LED matrix, independent of the display
+//! driver. With the `mb2` feature, [`mb2`] shares the display with its timer interrupt for
+//! greyscale. [`remote`] takes what to draw from commands on the serial port.
+
+use core::fmt;
+
+use libm::floorf;
+
+#[cfg(feature = "mb2")]
+pub mod mb2;
+pub mod remote;
+
+/// LED brightnesses, as taken by the MB2 display driver.
+pub type Frame = [[u8; 5]; 5];
+
+/// The brightest greyscale level.
+pub const MAX_BRIGHTNESS: u8 = 9;
+
+/// Draw a dot at a position between LEDs, spread over the four nearest in proportion to how
+/// close it is to each, so that it moves smoothly rather than jumping from one LED to the next.
+///
+/// `(x, y)` is in LEDs from the top left, and may be partly or wholly off the display.
+/// `brightness` goes up to [`MAX_BRIGHTNESS`]; the dot is added to what is already there.
+pub fn dot(frame: &mut Frame, x: f32, y: f32, brightness: u8) {
+    let (col, row) = (floorf(x), floorf(y));
+    let (fx, fy) = (x - col, y - row);
+    let (col, row) = (col as i32, row as i32);
+    for (dr, wy) in [(0, 1.0 - fy), (1, fy)] {
+        for (dc, wx) in [(0, 1.0 - fx), (1, fx)] {
+            let (r, c) = (row + dr, col + dc);
+            if !(0..5).contains(&r) || !(0..5).contains(&c) {
+                continue;
+            }
+            let level = (wx * wy * brightness as f32 + 0.5) as u8;
+            let led = &mut frame[r as usize][c as usize];
+            *led = (*led + level).min(MAX_BRIGHTNESS);
+        }
+    }
+}
+
+/// Light `value / full_scale` of the 25 LEDs, filling the bottom row first, left to right.
+pub fn bar_graph(value: i32, full_scale: i32) -> Frame {
+    let lit = (value.max(0) as i64 * 25 / full_scale.max(1) as i64).min(25) as usize;
+    let mut frame = [[0; 5]; 5];
+    for i in 0..lit {
+        frame[4 - i / 5][i % 5] = 1;
+    }
+    frame
+}
+
+/// Glyph columns, top row in bit 0.
+fn glyph(c: char) -> Option<&'static [u8]> {
+    Some(match c {
+        '0' => &[0b11111, 0b10001, 0b11111],
+        '1' => &[0b10010, 0b11111, 0b10000],
+        '2' => &[0b11101, 0b10101, 0b10111],
+        '3' => &[0b10101, 0b10101, 0b11111],
+        '4' => &[0b00111, 0b00100, 0b11111],
+        '5' => &[0b10111, 0b10101, 0b11101],
+        '6' => &[0b11111, 0b10101, 0b11101],
+        '7' => &[0b00001, 0b00001, 0b11111],
+        '8' => &[0b11111, 0b10101, 0b11111],
+        '9' => &[0b10111, 0b10101, 0b11111],
+        'd' => &[0b11100, 0b10100, 0b11111],
+        'A' => &[0b11110, 0b00101, 0b11110],
+        'B' => &[0b11111, 0b10101, 0b01010],
+        'C' => &[0b01110, 0b10001, 0b10001],
+        'D' => &[0b11111, 0b10001, 0b01110],
+        'E' => &[0b11111, 0b10101, 0b10001],
+        'F' => &[0b11111, 0b00101, 0b00001],
+        'G' => &[0b01110, 0b10001, 0b11101],
+        'H' => &[0b11111, 0b00100, 0b11111],
+        'I' => &[0b10001, 0b11111, 0b10001],
+        'J' => &[0b01000, 0b10000, 0b01111],
+        'K' => &[0b11111, 0b00100, 0b11011],
+        'L' => &[0b11111, 0b10000, 0b10000],
+        'M' => &[0b11111, 0b00010, 0b00100, 0b00010, 0b11111],
+        'N' => &[0b11111, 0b00010, 0b00100, 0b11111],
+        'O' => &[0b01110, 0b10001, 0b01110],
+        'P' => &[0b11111, 0b00101, 0b00010],
+        'Q' => &[0b01110, 0b10001, 0b01001, 0b10110],
+        'R' => &[0b11111, 0b00101, 0b11010],
+        'S' => &[0b10010, 0b10101, 0b01001],
+        'T' => &[0b00001, 0b11111, 0b00001],
+        'U' => &[0b11111, 0b10000, 0b11111],
+        'V' => &[0b01111, 0b10000, 0b01111],
+        'W' => &[0b11111, 0b01000, 0b00100, 0b01000, 0b11111],
+        'X' => &[0b11011, 0b00100, 0b11011],
+        'Y' => &[0b00011, 0b11100, 0b00011],
+        'Z' => &[0b11001, 0b10101, 0b10011],
+        '.' => &[0b10000],
+        '-' => &[0b00100, 0b00100],
+        '!' => &[0b10111],
+        '?' => &[0b00001, 0b10101, 0b00010],
+        ':' => &[0b01010],
+        ' ' => &[0b00000],
+        _ => return None,
+    })
+}
+
+/// Longest text a [`Scroller`] can hold, in LED columns.
+pub const MAX_COLUMNS: usize = 128;
+
+/// Scrolls a short number or message across the display from right to left.
+///
+/// Write the text with [`core::fmt::Write`]; digits, capital letters, `d`, `.`, `-`, `!`, `?`,
+/// `:` and spaces are supported. Other small letters are shown as capitals.
+#[derive(Debug, Clone)]
+pub struct Scroller {
+    columns: [u8; MAX_COLUMNS],
+    len: usize,
+    offset: usize,
+}
+
+impl Default for Scroller {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scroller {
+    pub const fn new() -> Self {
+        Self {
+            columns: [0; MAX_COLUMNS],
+            len: 0,
+            offset: 0,
+        }
+    }
+
+    /// The current frame, or `None` once the text has scrolled off the left edge.
+    pub fn frame(&self) -> Option<Frame> {
+        if self.offset >= self.len + 5 {
+            return None;
+        }
+        let mut frame = [[0; 5]; 5];
+        for c in 0..5 {
+            // The text starts just off the right edge.
+            let Some(i) = (self.offset + c).checked_sub(5) else {
+                continue;
+            };
+            let column = self.columns.get(i).filter(|_| i < self.len).copied();
+            for (r, row) in frame.iter_mut().enumerate() {
+                row[c] = column.map_or(0, |bits| (bits >> r) & 1);
+            }
+        }
+        Some(frame)
+    }
+
+    /// Move the text one column to the left.
+    pub fn advance(&mut self) {
+        self.offset += 1;
+    }
+}
+
+impl fmt::Write for Scroller {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            let glyph = glyph(c)
+                .or_else(|| glyph(c.to_ascii_uppercase()))
+                .ok_or(fmt::Error)?;
+            if self.len + glyph.len() + 1 > MAX_COLUMNS {
+                return Err(fmt::Error);
+            }
+            self.columns[self.len..][..glyph.len()].copy_from_slice(glyph);
+            // Leave a blank column between characters.
+            self.len += glyph.len() + 1;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+//! The MB2's non-blocking display, shared with its timer interrupt.
+//!
+//! The non-blocking driver lights one row at a time from a timer interrupt, which is what makes
+//! greyscale possible. [`SharedDisplay`] keeps it in a `static` so both the program and the
+//! interrupt handler can reach it. The program provides the handler itself:
+//!
+//! ```ignore
+//! static DISPLAY: SharedDisplay<TIMER1> = SharedDisplay::new();
+//!
+//! #[interrupt]
+//! fn TIMER1() {
+//!     DISPLAY.on_interrupt();
+//! }
+//! ```
+
+use core::cell::RefCell;
+
+use cortex_m::interrupt::{free as interrupt_free, Mutex};
+use microbit::{
+    display::nonblocking::{Display, GreyscaleImage},
+    gpio::DisplayPins,
+    hal::timer::Instance,
+    pac,
+};
+
+use crate::Frame;
+
+/// A non-blocking [`Display`] driven by timer `T`, shareable as a `static`.
+pub struct SharedDisplay<T: Instance> {
+    display: Mutex<RefCell<Option<Display<T>>>>,
+}
+
+impl<T: Instance> Default for SharedDisplay<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Instance> SharedDisplay<T> {
+    pub const fn new() -> Self {
+        Self {
+            display: Mutex::new(RefCell::new(None)),
+        }
+    }
+
+    /// Take over `timer` and the display pins, and start refreshing the display.
+    pub fn start(&self, timer: T, pins: DisplayPins) {
+        let display = Display::new(timer, pins);
+        interrupt_free(|cs| {
+            *self.display.borrow(cs).borrow_mut() = Some(display);
+        });
+        unsafe { pac::NVIC::unmask(T::INTERRUPT) }
+    }
+
+    /// Call from the timer's interrupt handler.
+    pub fn on_interrupt(&self) {
+        interrupt_free(|cs| {
+            if let Some(display) = self.display.borrow(cs).borrow_mut().as_mut() {
+                display.handle_display_event();
+            }
+        });
+    }
+
+    /// Show `frame` until told otherwise. Brightnesses go from 0 (off) to 9.
+    pub fn show(&self, frame: &Frame) {
+        let image = GreyscaleImage::new(frame);
+        interrupt_free(|cs| {
+            if let Some(display) = self.display.borrow(cs).borrow_mut().as_mut() {
+                display.show(&image);
+            }
+        });
+    }
+
+    pub fn clear(&self) {
+        interrupt_free(|cs| {
+            if let Some(display) = self.display.borrow(cs).borrow_mut().as_mut() {
+                display.clear();
+            }
+        });
+    }
+}
@@ -0,0 +1,344 @@
+//! Drawing on the display from the serial port.
+//!
+//! The parsers here read the arguments of the remote display's commands, and [`Screen`] keeps
+//! track of what those commands have asked to be shown: a picture, scrolling text or one of the
+//! [`Animation`]s. None of it touches the hardware, so it can be tried out on a desktop machine.
+
+use core::{fmt::Write, str::FromStr};
+
+use shell::{Args, CommandError};
+
+use crate::{Frame, Scroller, MAX_BRIGHTNESS};
+
+/// How long scrolling text stays on each column, in ms.
+pub const SCROLL_PERIOD_MS: u32 = 120;
+
+/// The argument to `image`: 25 brightnesses from 0 to 9, a row at a time from the top left.
+pub fn parse_image(args: &mut Args) -> Result<Frame, CommandError> {
+    let leds = args.next_arg("<leds>")?;
+    args.end()?;
+    let digits = leds.as_bytes();
+    if digits.len() != 25 || !digits.iter().all(u8::is_ascii_digit) {
+        return Err(CommandError::Invalid("<leds>"));
+    }
+    let mut frame = [[0; 5]; 5];
+    for (i, digit) in digits.iter().enumerate() {
+        frame[i / 5][i % 5] = digit - b'0';
+    }
+    Ok(frame)
+}
+
+fn coordinate(args: &mut Args, name: &'static str) -> Result<usize, CommandError> {
+    let value: usize = args.parse(name)?;
+    if value >= 5 {
+        return Err(CommandError::Invalid(name));
+    }
+    Ok(value)
+}
+
+/// One LED, counted in columns and rows from the top left, and its brightness.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pixel {
+    pub x: usize,
+    pub y: usize,
+    pub level: u8,
+}
+
+/// The arguments to `set`: `<x> <y> [level]`. The level defaults to the brightest.
+pub fn parse_set(args: &mut Args) -> Result<Pixel, CommandError> {
+    let x = coordinate(args, "<x>")?;
+    let y = coordinate(args, "<y>")?;
+    let level = args.parse_optional("[level]")?.unwrap_or(MAX_BRIGHTNESS);
+    if level > MAX_BRIGHTNESS {
+        return Err(CommandError::Invalid("[level]"));
+    }
+    args.end()?;
+    Ok(Pixel { x, y, level })
+}
+
+/// The arguments to `clear`: the LED to turn off, or `None` for all of them.
+pub fn parse_clear(args: &mut Args) -> Result<Option<(usize, usize)>, CommandError> {
+    if args.clone().next().is_none() {
+        return Ok(None);
+    }
+    let x = coordinate(args, "<x>")?;
+    let y = coordinate(args, "<y>")?;
+    args.end()?;
+    Ok(Some((x, y)))
+}
+
+/// The argument to `brightness`, from 0 to 9, or `None` to ask what it is.
+pub fn parse_brightness(args: &mut Args) -> Result<Option<u8>, CommandError> {
+    let brightness = args.parse_optional("[0-9]")?;
+    if brightness.is_some_and(|b| b > MAX_BRIGHTNESS) {
+        return Err(CommandError::Invalid("[0-9]"));
+    }
+    args.end()?;
+    Ok(brightness)
+}
+
+/// The arguments to `scroll`, laid out ready to go. Words are joined with single spaces; quote
+/// the text to keep more.
+pub fn parse_text(args: &mut Args) -> Result<Scroller, CommandError> {
+    let mut scroller = Scroller::new();
+    let first = args.next_arg("<text>")?;
+    scroller
+        .write_str(first)
+        .map_err(|_| CommandError::Invalid("<text>"))?;
+    for word in args {
+        write!(scroller, " {}", word).map_err(|_| CommandError::Invalid("<text>"))?;
+    }
+    Ok(scroller)
+}
+
+/// The built-in animations, which repeat until stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Animation {
+    /// A dot chasing its tail around the edge.
+    Spin,
+    /// A heart, getting brighter and dimmer.
+    Pulse,
+    /// The columns lighting up from the left, then going out.
+    Wipe,
+}
+
+/// The 16 LEDs around the edge, clockwise from the top left, as `(x, y)`.
+fn edge(i: u32) -> (usize, usize) {
+    let i = (i % 16) as usize;
+    match i {
+        0..=3 => (i, 0),
+        4..=7 => (4, i - 4),
+        8..=11 => (12 - i, 4),
+        _ => (0, 16 - i),
+    }
+}
+
+const HEART: Frame = [
+    [0, 1, 0, 1, 0],
+    [1, 1, 1, 1, 1],
+    [1, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0],
+    [0, 0, 1, 0, 0],
+];
+
+impl Animation {
+    pub fn name(self) -> &'static str {
+        match self {
+            Animation::Spin => "spin",
+            Animation::Pulse => "pulse",
+            Animation::Wipe => "wipe",
+        }
+    }
+
+    /// How many frames before it starts again.
+    pub fn frames(self) -> u32 {
+        match self {
+            Animation::Spin | Animation::Pulse => 16,
+            Animation::Wipe => 10,
+        }
+    }
+
+    /// How long each frame stays, in ms.
+    pub fn period_ms(self) -> u32 {
+        match self {
+            Animation::Spin => 60,
+            Animation::Pulse => 50,
+            Animation::Wipe => 100,
+        }
+    }
+
+    /// Frame `step`, counting from 0; it carries on past [`frames`](Self::frames) by starting
+    /// again.
+    pub fn frame(self, step: u32) -> Frame {
+        let step = step % self.frames();
+        let mut frame = [[0; 5]; 5];
+        match self {
+            Animation::Spin => {
+                for (back, level) in [(2, 1), (1, 4), (0, MAX_BRIGHTNESS)] {
+                    let (x, y) = edge(step + 16 - back);
+                    frame[y][x] = level;
+                }
+            }
+            Animation::Pulse => {
+                // 1 up to 9, then back down to 2.
+                let level = if step < 9 { step + 1 } else { 17 - step } as u8;
+                for (row, mask) in frame.iter_mut().zip(HEART) {
+                    for (led, lit) in row.iter_mut().zip(mask) {
+                        *led = lit * level;
+                    }
+                }
+            }
+            Animation::Wipe => {
+                let lit = if step < 5 { 0..step + 1 } else { step - 4..5 };
+                for row in frame.iter_mut() {
+                    for x in lit.clone() {
+                        row[x as usize] = MAX_BRIGHTNESS;
+                    }
+                }
+            }
+        }
+        frame
+    }
+}
+
+impl FromStr for Animation {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "spin" => Ok(Animation::Spin),
+            "pulse" => Ok(Animation::Pulse),
+            "wipe" => Ok(Animation::Wipe),
+            _ => Err(()),
+        }
+    }
+}
+
+/// `frame` at `brightness` out of 9, keeping every lit LED lit unless the brightness is 0.
+pub fn dim(frame: &Frame, brightness: u8) -> Frame {
+    frame.map(|row| row.map(|led| (led * brightness).div_ceil(MAX_BRIGHTNESS)))
+}
+
+#[derive(Debug, Clone)]
+enum Playing {
+    Picture,
+    Text(Scroller),
+    Animation(Animation, u32),
+}
+
+/// What the display should be showing.
+///
+/// The commands change it, and the program calls [`tick`](Screen::tick) as often as it can to
+/// find out what to put on the display, and when.
+#[derive(Debug, Clone)]
+pub struct Screen {
+    /// What `image`, `set` and `clear` have drawn, shown when nothing is playing.
+    picture: Frame,
+    brightness: u8,
+    playing: Playing,
+    /// The frame last shown, at full brightness.
+    shown: Frame,
+    /// When to move on to the next frame of text or animation.
+    due_ms: Option<u32>,
+    /// Whether what is playing has changed, so the next tick should start it.
+    fresh: bool,
+    /// Whether the brightness has changed, so the next tick should show the same frame again.
+    redraw: bool,
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Screen {
+    /// A blank picture at full brightness.
+    pub const fn new() -> Self {
+        Self {
+            picture: [[0; 5]; 5],
+            brightness: MAX_BRIGHTNESS,
+            playing: Playing::Picture,
+            shown: [[0; 5]; 5],
+            due_ms: None,
+            fresh: true,
+            redraw: false,
+        }
+    }
+
+    pub fn picture(&self) -> &Frame {
+        &self.picture
+    }
+
+    /// Show `picture` in place of whatever was there.
+    pub fn show(&mut self, picture: Frame) {
+        self.picture = picture;
+        self.stop();
+    }
+
+    /// Change one LED of the picture, and show it.
+    pub fn set(&mut self, pixel: Pixel) {
+        self.picture[pixel.y][pixel.x] = pixel.level;
+        self.stop();
+    }
+
+    /// Scroll `text` across once, then go back to the picture.
+    pub fn scroll(&mut self, text: Scroller) {
+        self.start(Playing::Text(text));
+    }
+
+    pub fn play(&mut self, animation: Animation) {
+        self.start(Playing::Animation(animation, 0));
+    }
+
+    /// Stop any text or animation, and go back to the picture.
+    pub fn stop(&mut self) {
+        self.start(Playing::Picture);
+    }
+
+    fn start(&mut self, playing: Playing) {
+        self.playing = playing;
+        self.fresh = true;
+    }
+
+    pub fn brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    /// Dim everything shown, from 0 (off) to 9 (full brightness).
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness.min(MAX_BRIGHTNESS);
+        self.redraw = true;
+    }
+
+    /// What is showing: `picture`, `text`, or the animation's name.
+    pub fn activity(&self) -> &'static str {
+        match self.playing {
+            Playing::Picture => "picture",
+            Playing::Text(_) => "text",
+            Playing::Animation(animation, _) => animation.name(),
+        }
+    }
+
+    /// What should be on the display at `now_ms`, if it isn't there already. The time may wrap
+    /// around.
+    pub fn tick(&mut self, now_ms: u32) -> Option<Frame> {
+        let due = self
+            .due_ms
+            .is_some_and(|due| now_ms.wrapping_sub(due) < u32::MAX / 2);
+        if self.fresh || due {
+            self.fresh = false;
+            let (frame, period) = self.next_frame();
+            self.shown = frame;
+            self.due_ms = period.map(|period| now_ms.wrapping_add(period));
+        } else if !self.redraw {
+            return None;
+        }
+        self.redraw = false;
+        Some(dim(&self.shown, self.brightness))
+    }
+
+    /// The next frame of whatever is playing, and how long until the one after, if there is
+    /// one.
+    fn next_frame(&mut self) -> (Frame, Option<u32>) {
+        match &mut self.playing {
+            Playing::Picture => (self.picture, None),
+            Playing::Text(scroller) => match scroller.frame() {
+                Some(frame) => {
+                    scroller.advance();
+                    let frame = frame.map(|row| row.map(|led| led * MAX_BRIGHTNESS));
+                    (frame, Some(SCROLL_PERIOD_MS))
+                }
+                None => {
+                    self.playing = Playing::Picture;
+                    (self.picture, None)
+                }
+            },
+            Playing::Animation(animation, step) => {
+                let frame = animation.frame(*step);
+                *step = (*step + 1) % animation.frames();
+                (frame, Some(animation.period_ms()))
+            }
+        }
+    }
+}
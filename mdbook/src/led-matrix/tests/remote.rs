@@ -0,0 +1,334 @@
+//! Tests the remote display's command parsing and timing from `led-matrix`'s `remote` module.
+//!
+//! ```text
+//! cargo test-host -p led-matrix
+//! ```
+//!
+//! Each test runs argument lines through one of the parsers, or drives a [`Screen`] through a
+//! made-up stretch of time, and compares the result with what the display should do. Frames are
+//! written as five rows of digits, top first.
+
+use std::fmt::Debug;
+
+use led_matrix::{
+    remote::{
+        dim, parse_brightness, parse_clear, parse_image, parse_set, parse_text, Animation, Pixel,
+        Screen, SCROLL_PERIOD_MS,
+    },
+    Frame,
+};
+use shell::{Args, CommandError};
+
+/// `frame` as five rows of digits, separated by spaces.
+fn draw(frame: &Frame) -> String {
+    let rows: Vec<String> = frame
+        .iter()
+        .map(|row| row.iter().map(|led| char::from(b'0' + led)).collect())
+        .collect();
+    rows.join(" ")
+}
+
+/// Run each argument line through `parse`, and compare with what it should give.
+fn assert_parses<T: Debug + PartialEq>(
+    parse: fn(&mut Args) -> Result<T, CommandError>,
+    cases: &[(&str, Result<T, CommandError>)],
+) {
+    for (line, expected) in cases {
+        assert_eq!(parse(&mut Args::new(line)), *expected, "{:?}", line);
+    }
+}
+
+#[test]
+fn image_arguments() {
+    let image = |frame: Frame| Ok(draw(&frame));
+    assert_parses(
+        |args| parse_image(args).map(|frame| draw(&frame)),
+        &[
+            (
+                "0909099999999990999000900",
+                image([
+                    [0, 9, 0, 9, 0],
+                    [9, 9, 9, 9, 9],
+                    [9, 9, 9, 9, 9],
+                    [0, 9, 9, 9, 0],
+                    [0, 0, 9, 0, 0],
+                ]),
+            ),
+            (
+                "  0123456789012345678901234 ",
+                Ok("01234 56789 01234 56789 01234".into()),
+            ),
+            ("", Err(CommandError::Missing("<leds>"))),
+            (
+                "012345678901234567890123",
+                Err(CommandError::Invalid("<leds>")),
+            ),
+            (
+                "01234567890123456789012345",
+                Err(CommandError::Invalid("<leds>")),
+            ),
+            (
+                "0123456789012345678901-34",
+                Err(CommandError::Invalid("<leds>")),
+            ),
+            ("01234 56789 01234 56789 01234", Err(CommandError::TooMany)),
+        ],
+    );
+}
+
+#[test]
+fn set_clear_and_brightness_arguments() {
+    let pixel = |x, y, level| Ok(Pixel { x, y, level });
+    assert_parses(
+        parse_set,
+        &[
+            ("0 0", pixel(0, 0, 9)),
+            ("4 2 3", pixel(4, 2, 3)),
+            ("1 1 0", pixel(1, 1, 0)),
+            ("", Err(CommandError::Missing("<x>"))),
+            ("1", Err(CommandError::Missing("<y>"))),
+            ("5 0", Err(CommandError::Invalid("<x>"))),
+            ("0 -1", Err(CommandError::Invalid("<y>"))),
+            ("0 0 10", Err(CommandError::Invalid("[level]"))),
+            ("0 0 9 9", Err(CommandError::TooMany)),
+        ],
+    );
+    assert_parses(
+        parse_clear,
+        &[
+            ("", Ok(None)),
+            ("3 4", Ok(Some((3, 4)))),
+            ("3", Err(CommandError::Missing("<y>"))),
+            ("3 4 5", Err(CommandError::TooMany)),
+            ("x y", Err(CommandError::Invalid("<x>"))),
+        ],
+    );
+    assert_parses(
+        parse_brightness,
+        &[
+            ("", Ok(None)),
+            ("0", Ok(Some(0))),
+            ("9", Ok(Some(9))),
+            ("10", Err(CommandError::Invalid("[0-9]"))),
+            ("dim", Err(CommandError::Invalid("[0-9]"))),
+            ("5 6", Err(CommandError::TooMany)),
+        ],
+    );
+}
+
+/// The frames `text` scrolls through, as the display shows them.
+fn scrolled(text: &str) -> Result<Vec<String>, CommandError> {
+    let mut screen = Screen::new();
+    screen.scroll(parse_text(&mut Args::new(text))?);
+    let mut frames = Vec::new();
+    let mut now = 0;
+    while screen.activity() == "text" {
+        if let Some(frame) = screen.tick(now) {
+            frames.push(draw(&frame));
+        }
+        now += SCROLL_PERIOD_MS;
+    }
+    Ok(frames)
+}
+
+#[test]
+fn scrolling_text() {
+    let hi = scrolled("HI").unwrap();
+    // Two three-column letters, each followed by a blank column, scroll in from the right and off
+    // the left, then the picture comes back.
+    assert_eq!(hi.len(), 8 + 5 + 1, "frames");
+    assert_eq!(hi[5].as_str(), "90909 90900 99900 90900 90909", "all on");
+    assert_eq!(
+        hi[0].as_str(),
+        "00000 00000 00000 00000 00000",
+        "blank at first"
+    );
+    assert_eq!(scrolled("hi"), Ok(hi), "small letters");
+    assert_eq!(
+        scrolled("\"1  2\"").map(|frames| frames.len()),
+        Ok(4 + 4 + 4 + 5 + 1),
+        "spaces"
+    );
+    assert_eq!(
+        scrolled("1 2").map(|frames| frames.len()),
+        Ok(4 + 2 + 4 + 5 + 1),
+        "words"
+    );
+    assert_eq!(
+        scrolled(""),
+        Err(CommandError::Missing("<text>")),
+        "missing"
+    );
+    assert_eq!(
+        scrolled("café"),
+        Err(CommandError::Invalid("<text>")),
+        "accents"
+    );
+    assert_eq!(
+        scrolled(&"W".repeat(30)),
+        Err(CommandError::Invalid("<text>")),
+        "too long"
+    );
+}
+
+#[test]
+fn animations() {
+    let frame = |animation: Animation, step| draw(&animation.frame(step));
+    assert_eq!(
+        ["spin", "pulse", "wipe", "dance"].map(|name| name.parse().ok()),
+        [
+            Some(Animation::Spin),
+            Some(Animation::Pulse),
+            Some(Animation::Wipe),
+            None,
+        ],
+        "names"
+    );
+    let cases = [
+        (Animation::Spin, 0, "90000 40000 10000 00000 00000"),
+        (Animation::Spin, 5, "00014 00009 00000 00000 00000"),
+        (Animation::Spin, 9, "00000 00000 00000 00001 00094"),
+        (Animation::Pulse, 0, "01010 11111 11111 01110 00100"),
+        (Animation::Pulse, 8, "09090 99999 99999 09990 00900"),
+        (Animation::Pulse, 15, "02020 22222 22222 02220 00200"),
+        (Animation::Wipe, 0, "90000 90000 90000 90000 90000"),
+        (Animation::Wipe, 4, "99999 99999 99999 99999 99999"),
+        (Animation::Wipe, 6, "00999 00999 00999 00999 00999"),
+    ];
+    for (animation, step, expected) in cases {
+        let what = format!("{} {}", animation.name(), step);
+        assert_eq!(frame(animation, step), expected.to_string(), "{}", what);
+    }
+    for animation in [Animation::Spin, Animation::Pulse, Animation::Wipe] {
+        assert_eq!(
+            frame(animation, animation.frames()),
+            frame(animation, 0),
+            "{}",
+            animation.name()
+        );
+    }
+}
+
+#[test]
+fn brightness() {
+    let levels: Frame = [[0, 1, 2, 3, 4], [5, 6, 7, 8, 9], [0; 5], [0; 5], [0; 5]];
+    assert_eq!(
+        draw(&dim(&levels, 5)),
+        "01223 34455 00000 00000 00000",
+        "half"
+    );
+    assert_eq!(
+        draw(&dim(&levels, 1)),
+        "01111 11111 00000 00000 00000",
+        "dimmest"
+    );
+    assert_eq!(dim(&levels, 9), levels, "full");
+    assert_eq!(dim(&levels, 0), [[0; 5]; 5], "off");
+}
+
+#[test]
+fn screen_timing() {
+    let mut screen = Screen::new();
+    let tick = |screen: &mut Screen, now| screen.tick(now).map(|frame| draw(&frame));
+    assert_eq!(
+        tick(&mut screen, 0),
+        Some("00000 ".repeat(4) + "00000"),
+        "blank"
+    );
+    assert_eq!(tick(&mut screen, 1), None, "unchanged");
+
+    screen.set(Pixel {
+        x: 2,
+        y: 1,
+        level: 7,
+    });
+    assert_eq!(
+        tick(&mut screen, 2),
+        Some("00000 00700 00000 00000 00000".into()),
+        "set"
+    );
+
+    screen.play(Animation::Wipe);
+    assert_eq!(screen.activity(), "wipe", "activity");
+    assert_eq!(
+        tick(&mut screen, 1000),
+        Some(frame_of(Animation::Wipe, 0)),
+        "started"
+    );
+    assert_eq!(tick(&mut screen, 1099), None, "too soon");
+    assert_eq!(
+        tick(&mut screen, 1100),
+        Some(frame_of(Animation::Wipe, 1)),
+        "next"
+    );
+    screen.set_brightness(1);
+    assert_eq!(
+        tick(&mut screen, 1150),
+        Some("11000 11000 11000 11000 11000".into()),
+        "dimmed"
+    );
+    assert!(tick(&mut screen, 1200).is_some(), "dimmed, then on time");
+    assert_eq!(tick(&mut screen, 1299), None, "still on time");
+    screen.set_brightness(9);
+
+    // Late ticks carry on from when they came.
+    assert_eq!(
+        tick(&mut screen, 1700),
+        Some(frame_of(Animation::Wipe, 3)),
+        "late"
+    );
+    assert_eq!(tick(&mut screen, 1750), None, "after late");
+
+    screen.stop();
+    assert_eq!(screen.activity(), "picture", "stopped");
+    assert_eq!(
+        tick(&mut screen, 1760),
+        Some("00000 00700 00000 00000 00000".into()),
+        "picture"
+    );
+
+    screen.show([[3; 5]; 5]);
+    assert_eq!(
+        tick(&mut screen, 1770),
+        Some("33333 ".repeat(4) + "33333"),
+        "image"
+    );
+    assert_eq!(*screen.picture(), [[3; 5]; 5], "picture kept");
+}
+
+fn frame_of(animation: Animation, step: u32) -> String {
+    draw(&animation.frame(step))
+}
+
+#[test]
+fn clock_wrapping() {
+    let mut screen = Screen::new();
+    screen.play(Animation::Spin);
+    let start = u32::MAX - 10;
+    assert!(screen.tick(start).is_some(), "start");
+    assert_eq!(screen.tick(u32::MAX), None, "before wrap");
+    assert_eq!(screen.tick(48), None, "after wrap");
+    assert_eq!(
+        screen.tick(49).map(|f| draw(&f)),
+        Some(frame_of(Animation::Spin, 1)),
+        "due"
+    );
+}
+
+/// Every character `scroll` takes can be scrolled, on its own and in any case.
+#[test]
+fn font() {
+    let text = "0123456789 ABCDEFGHIJKLMNOPQRSTUVWXYZ .-!?:";
+    for c in text.chars().chain(text.to_lowercase().chars()) {
+        if c == ' ' {
+            continue;
+        }
+        let frames = scrolled(&c.to_string());
+        assert!(
+            frames.as_ref().is_ok_and(|frames| frames.len() >= 7),
+            "{:?}: {:?}",
+            c,
+            frames
+        );
+    }
+}
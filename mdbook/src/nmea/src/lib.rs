@@ -0,0 +1,417 @@
+#![no_std]
+
+//! Reading the sentences GPS modules send, in the NMEA 0183 format.
+//!
+//! A GPS module talks in lines of text, usually at 9600 baud, several times a second:
+//!
+//! ```text
+//! $GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47
+//! ```
+//!
+//! Each starts with `$`, then a two-letter talker (`GP` for GPS, `GN` for a mix of satellite
+//! systems) and the sentence type, then comma-separated fields, some of them empty. After the `*`
+//! comes a checksum: every byte between the `$` and the `*` XORed together, in hex. Lines end
+//! with `\r\n` and are at most 82 bytes long.
+//!
+//! [`Sentences`] picks whole sentences out of the bytes as they arrive, and [`parse`] checks them
+//! and reads the two that say where the module is: [`Gga`], the fix, and [`Rmc`], the position
+//! with the date, speed and heading. Nothing here touches the serial port, so it can be tried out
+//! on a desktop machine against recorded sentences.
+
+use core::{fmt, str::FromStr};
+
+/// The longest sentence, from the `$` to the `\n`.
+pub const MAX_SENTENCE: usize = 82;
+
+/// Collects received bytes into sentences.
+pub struct Sentences {
+    buf: [u8; MAX_SENTENCE - 1],
+    len: usize,
+    /// Whether a `$` has been seen, and the sentence hasn't overflowed since.
+    in_sentence: bool,
+}
+
+impl Default for Sentences {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sentences {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; MAX_SENTENCE - 1],
+            len: 0,
+            in_sentence: false,
+        }
+    }
+
+    /// Add a byte. Returns a whole sentence, from its `$` up to but not including the `\r\n`,
+    /// once the `\n` arrives.
+    ///
+    /// Anything before the first `$`, and any sentence too long to be one, is dropped; a `$`
+    /// always starts afresh, so a sentence cut short by lost bytes can't swallow the next one.
+    pub fn push(&mut self, byte: u8) -> Option<&str> {
+        match byte {
+            b'$' => {
+                self.buf[0] = byte;
+                self.len = 1;
+                self.in_sentence = true;
+                None
+            }
+            _ if !self.in_sentence => None,
+            b'\n' => {
+                self.in_sentence = false;
+                let line = &self.buf[..self.len];
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                core::str::from_utf8(line).ok()
+            }
+            // Leave room for the `\n`.
+            _ if self.len == MAX_SENTENCE - 1 => {
+                self.in_sentence = false;
+                None
+            }
+            _ => {
+                self.buf[self.len] = byte;
+                self.len += 1;
+                None
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// Not a `$`, two letters of talker and a sentence type.
+    NotASentence,
+    /// No `*` and two hex digits at the end.
+    NoChecksum,
+    /// The checksum doesn't match, so the sentence was damaged on the way.
+    BadChecksum,
+    /// The field with this name can't be read.
+    Invalid(&'static str),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NotASentence => f.write_str("not a sentence"),
+            Error::NoChecksum => f.write_str("no checksum"),
+            Error::BadChecksum => f.write_str("bad checksum"),
+            Error::Invalid(field) => write!(f, "invalid {}", field),
+        }
+    }
+}
+
+/// Time of day, UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Time {
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    pub millis: u16,
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}:{:02}.{:03}",
+            self.hour, self.minute, self.second, self.millis
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Date {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// Where on Earth, in degrees: north and east are positive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ns = if self.latitude < 0.0 { 'S' } else { 'N' };
+        let ew = if self.longitude < 0.0 { 'W' } else { 'E' };
+        write!(
+            f,
+            "{:.5}{} {:.5}{}",
+            self.latitude.abs(),
+            ns,
+            self.longitude.abs(),
+            ew
+        )
+    }
+}
+
+/// How the module worked out its position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quality {
+    /// It hasn't, yet.
+    NoFix,
+    Gps,
+    /// With corrections from a base station or satellite.
+    Differential,
+    /// Any of the rarer kinds: RTK, dead reckoning, manual input or simulation.
+    Other(u8),
+}
+
+/// `GGA`: the fix.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gga {
+    pub time: Option<Time>,
+    pub position: Option<Position>,
+    pub quality: Quality,
+    /// How many satellites the fix uses.
+    pub satellites: Option<u8>,
+    /// Horizontal dilution of precision: 1 is ideal, and above 5 the position is rough.
+    pub hdop: Option<f32>,
+    /// Height above mean sea level, in metres.
+    pub altitude: Option<f32>,
+}
+
+/// `RMC`: the recommended minimum of position, speed and heading, with the date.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rmc {
+    pub time: Option<Time>,
+    /// Whether the module has a fix; if not, the rest is stale or missing.
+    pub valid: bool,
+    pub position: Option<Position>,
+    /// Speed over the ground, in knots.
+    pub speed: Option<f32>,
+    /// Direction of travel, in degrees clockwise from true north.
+    pub course: Option<f32>,
+    pub date: Option<Date>,
+}
+
+/// A checked sentence.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sentence<'a> {
+    Gga(Gga),
+    Rmc(Rmc),
+    /// Any other type, such as `GSV` (the satellites in view), given by its name.
+    Other(&'a str),
+}
+
+/// Check `line`, one sentence without its `\r\n`, and read it.
+pub fn parse(line: &str) -> Result<Sentence<'_>, Error> {
+    let body = line.strip_prefix('$').ok_or(Error::NotASentence)?;
+    let (body, checksum) = body.rsplit_once('*').ok_or(Error::NoChecksum)?;
+    if checksum.len() != 2 {
+        return Err(Error::NoChecksum);
+    }
+    let checksum = u8::from_str_radix(checksum, 16).map_err(|_| Error::NoChecksum)?;
+    if body.bytes().fold(0, |sum, b| sum ^ b) != checksum {
+        return Err(Error::BadChecksum);
+    }
+
+    let mut fields = Fields(body.split(','));
+    let address = fields.next();
+    if address.len() != 5 || !address.bytes().all(|b| b.is_ascii_uppercase()) {
+        return Err(Error::NotASentence);
+    }
+    match &address[2..] {
+        "GGA" => parse_gga(&mut fields).map(Sentence::Gga),
+        "RMC" => parse_rmc(&mut fields).map(Sentence::Rmc),
+        kind => Ok(Sentence::Other(kind)),
+    }
+}
+
+/// The fields of a sentence. Modules leave trailing fields off, so running out reads as empty.
+struct Fields<'a>(core::str::Split<'a, char>);
+
+impl<'a> Fields<'a> {
+    fn next(&mut self) -> &'a str {
+        self.0.next().unwrap_or("")
+    }
+
+    /// A number, or `None` if the field is empty.
+    fn number<T: FromStr>(&mut self, name: &'static str) -> Result<Option<T>, Error> {
+        match self.next() {
+            "" => Ok(None),
+            field => field.parse().map(Some).map_err(|_| Error::Invalid(name)),
+        }
+    }
+}
+
+fn parse_gga(fields: &mut Fields) -> Result<Gga, Error> {
+    let time = parse_time(fields.next())?;
+    let position = parse_position(fields)?;
+    let quality = match fields.number::<u8>("quality")? {
+        None | Some(0) => Quality::NoFix,
+        Some(1) => Quality::Gps,
+        Some(2) => Quality::Differential,
+        Some(other) => Quality::Other(other),
+    };
+    let satellites = fields.number("satellites")?;
+    let hdop = fields.number("hdop")?;
+    let altitude = fields.number("altitude")?;
+    Ok(Gga {
+        time,
+        position,
+        quality,
+        satellites,
+        hdop,
+        altitude,
+    })
+}
+
+fn parse_rmc(fields: &mut Fields) -> Result<Rmc, Error> {
+    let time = parse_time(fields.next())?;
+    let valid = match fields.next() {
+        "A" => true,
+        "V" | "" => false,
+        _ => return Err(Error::Invalid("status")),
+    };
+    let position = parse_position(fields)?;
+    let speed = fields.number("speed")?;
+    let course = fields.number("course")?;
+    let date = parse_date(fields.next())?;
+    Ok(Rmc {
+        time,
+        valid,
+        position,
+        speed,
+        course,
+        date,
+    })
+}
+
+/// Two digits starting at `at`.
+fn two_digits(field: &str, at: usize, name: &'static str) -> Result<u8, Error> {
+    field
+        .get(at..at + 2)
+        .filter(|digits| digits.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|digits| digits.parse().ok())
+        .ok_or(Error::Invalid(name))
+}
+
+/// `hhmmss`, maybe with a fraction of a second.
+fn parse_time(field: &str) -> Result<Option<Time>, Error> {
+    if field.is_empty() {
+        return Ok(None);
+    }
+    let hour = two_digits(field, 0, "time")?;
+    let minute = two_digits(field, 2, "time")?;
+    let second = two_digits(field, 4, "time")?;
+    let millis = match &field[6..] {
+        "" => 0,
+        fraction => {
+            let digits = fraction.strip_prefix('.').unwrap_or("");
+            if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(Error::Invalid("time"));
+            }
+            // Milliseconds are plenty; modules give two or three digits.
+            (0..3).fold(0, |millis, i| {
+                let digit = digits.as_bytes().get(i).map_or(0, |b| b - b'0');
+                millis * 10 + digit as u16
+            })
+        }
+    };
+    if hour > 23 || minute > 59 || second > 60 {
+        return Err(Error::Invalid("time"));
+    }
+    Ok(Some(Time {
+        hour,
+        minute,
+        second,
+        millis,
+    }))
+}
+
+/// `ddmmyy`. Two-digit years are taken to be this century.
+fn parse_date(field: &str) -> Result<Option<Date>, Error> {
+    if field.is_empty() {
+        return Ok(None);
+    }
+    if field.len() != 6 {
+        return Err(Error::Invalid("date"));
+    }
+    let day = two_digits(field, 0, "date")?;
+    let month = two_digits(field, 2, "date")?;
+    let year = two_digits(field, 4, "date")?;
+    if !(1..=31).contains(&day) || !(1..=12).contains(&month) {
+        return Err(Error::Invalid("date"));
+    }
+    Ok(Some(Date {
+        year: 2000 + year as u16,
+        month,
+        day,
+    }))
+}
+
+/// Latitude and longitude, each as degrees and minutes (`ddmm.mmmm` and `dddmm.mmmm`) followed
+/// by a field for the hemisphere. All four are empty without a fix.
+fn parse_position(fields: &mut Fields) -> Result<Option<Position>, Error> {
+    let latitude = parse_angle(
+        fields.next(),
+        fields.next(),
+        2,
+        90.0,
+        ('N', 'S'),
+        "latitude",
+    )?;
+    let longitude = parse_angle(
+        fields.next(),
+        fields.next(),
+        3,
+        180.0,
+        ('E', 'W'),
+        "longitude",
+    )?;
+    match (latitude, longitude) {
+        (Some(latitude), Some(longitude)) => Ok(Some(Position {
+            latitude,
+            longitude,
+        })),
+        (None, None) => Ok(None),
+        (None, _) => Err(Error::Invalid("latitude")),
+        (_, None) => Err(Error::Invalid("longitude")),
+    }
+}
+
+/// One of those, up to `max_degrees` either way.
+fn parse_angle(
+    field: &str,
+    hemisphere: &str,
+    degree_digits: usize,
+    max_degrees: f64,
+    (positive, negative): (char, char),
+    name: &'static str,
+) -> Result<Option<f64>, Error> {
+    if field.is_empty() && hemisphere.is_empty() {
+        return Ok(None);
+    }
+    let invalid = Error::Invalid(name);
+    let (degrees, minutes) = field.split_at_checked(degree_digits).ok_or(invalid)?;
+    // Only digits, and a point in the minutes: `str::parse` would take "inf" or "1e1" as well.
+    let (whole, fraction) = minutes.split_once('.').unwrap_or((minutes, ""));
+    let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if !digits(degrees) || whole.is_empty() || !digits(whole) || !digits(fraction) {
+        return Err(invalid);
+    }
+    let degrees: f64 = degrees.parse().map_err(|_| invalid)?;
+    let minutes: f64 = minutes.parse().map_err(|_| invalid)?;
+    let angle = degrees + minutes / 60.0;
+    if minutes >= 60.0 || angle > max_degrees {
+        return Err(invalid);
+    }
+    match hemisphere.chars().next() {
+        Some(c) if c == positive && hemisphere.len() == 1 => Ok(Some(angle)),
+        Some(c) if c == negative && hemisphere.len() == 1 => Ok(Some(-angle)),
+        _ => Err(invalid),
+    }
+}
@@ -0,0 +1,248 @@
+//! Tests the `nmea` crate's sentence splitting and parsing against sentences as GPS modules
+//! send them.
+//!
+//! ```text
+//! cargo test-host -p nmea
+//! ```
+//!
+//! Each test feeds bytes to [`Sentences`] or lines to [`parse`], and compares what comes out with
+//! what a module meant.
+
+use nmea::{parse, Date, Error, Gga, Position, Quality, Rmc, Sentence, Sentences, Time};
+
+/// Feed `bytes` to a [`Sentences`] one at a time, and collect the sentences it finds.
+fn split(bytes: &[u8]) -> Vec<String> {
+    let mut sentences = Sentences::new();
+    bytes
+        .iter()
+        .filter_map(|b| sentences.push(*b).map(String::from))
+        .collect()
+}
+
+#[test]
+fn splitting_sentences() {
+    let cases: &[(&[u8], &[&str])] = &[
+        (b"$GPGSV,1*00\r\n", &["$GPGSV,1*00"]),
+        // Without the `\r`, as some modules do.
+        (b"$GPGSV,1*00\n", &["$GPGSV,1*00"]),
+        // Joining part way through a sentence.
+        (b"1,E,1*47\r\n$GPGSV,1*00\r\n", &["$GPGSV,1*00"]),
+        (
+            b"$GPGSV,1*00\r\n$GPGSV,2*00\r\n",
+            &["$GPGSV,1*00", "$GPGSV,2*00"],
+        ),
+        // Bytes lost, end of line and all: the next `$` starts again.
+        (b"$GPGGA,1235$GPGSV,1*00\r\n", &["$GPGSV,1*00"]),
+        (b"$GPGSV,1*00", &[]),
+    ];
+    for (bytes, expected) in cases {
+        assert_eq!(
+            split(bytes),
+            *expected,
+            "{:?}",
+            String::from_utf8_lossy(bytes)
+        );
+    }
+}
+
+#[test]
+fn sentences_too_long() {
+    // 82 bytes with the `\r\n`.
+    let longest = format!("${}", "A".repeat(79));
+    let too_long = format!("${}", "A".repeat(80));
+    let stream = format!("{}\r\n{}\r\n$GPGSV,1*00\r\n", longest, too_long);
+    assert_eq!(
+        split(stream.as_bytes()),
+        [longest, "$GPGSV,1*00".to_string()]
+    );
+}
+
+/// Parse each line, and compare with what it should give.
+fn assert_lines(cases: &[(&str, Result<Sentence, Error>)]) {
+    for (line, expected) in cases {
+        assert_eq!(parse(line), *expected, "{}", line);
+    }
+}
+
+fn time(hour: u8, minute: u8, second: u8, millis: u16) -> Option<Time> {
+    Some(Time {
+        hour,
+        minute,
+        second,
+        millis,
+    })
+}
+
+#[test]
+fn gga() {
+    assert_lines(&[
+        (
+            "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47",
+            Ok(Sentence::Gga(Gga {
+                time: time(12, 35, 19, 0),
+                position: Some(Position {
+                    latitude: 48.0 + 7.038 / 60.0,
+                    longitude: 11.0 + 31.0 / 60.0,
+                }),
+                quality: Quality::Gps,
+                satellites: Some(8),
+                hdop: Some(0.9),
+                altitude: Some(545.4),
+            })),
+        ),
+        (
+            "$GPGGA,001043.5,3352.1234,S,15112.5678,W,2,12,0.8,10.0,M,20.0,M,,*62",
+            Ok(Sentence::Gga(Gga {
+                time: time(0, 10, 43, 500),
+                position: Some(Position {
+                    latitude: -(33.0 + 52.1234 / 60.0),
+                    longitude: -(151.0 + 12.5678 / 60.0),
+                }),
+                quality: Quality::Differential,
+                satellites: Some(12),
+                hdop: Some(0.8),
+                altitude: Some(10.0),
+            })),
+        ),
+        // Straight after switching on.
+        (
+            "$GPGGA,,,,,,0,00,99.99,,,,,,*48",
+            Ok(Sentence::Gga(Gga {
+                time: None,
+                position: None,
+                quality: Quality::NoFix,
+                satellites: Some(0),
+                hdop: Some(99.99),
+                altitude: None,
+            })),
+        ),
+    ])
+}
+
+#[test]
+fn rmc() {
+    assert_lines(&[
+        (
+            "$GNRMC,083559.00,A,4717.11437,N,00833.91522,E,0.004,77.52,091202,,,A*49",
+            Ok(Sentence::Rmc(Rmc {
+                time: time(8, 35, 59, 0),
+                valid: true,
+                position: Some(Position {
+                    latitude: 47.0 + 17.11437 / 60.0,
+                    longitude: 8.0 + 33.91522 / 60.0,
+                }),
+                speed: Some(0.004),
+                course: Some(77.52),
+                date: Some(Date {
+                    year: 2002,
+                    month: 12,
+                    day: 9,
+                }),
+            })),
+        ),
+        (
+            "$GPRMC,,V,,,,,,,,,,N*53",
+            Ok(Sentence::Rmc(Rmc {
+                time: None,
+                valid: false,
+                position: None,
+                speed: None,
+                course: None,
+                date: None,
+            })),
+        ),
+        (
+            "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,311326*1A",
+            Err(Error::Invalid("date")),
+        ),
+    ])
+}
+
+#[test]
+fn other_sentences() {
+    assert_lines(&[(
+        "$GPGSV,3,1,11,03,03,111,00,04,15,270,00,06,01,010,00,13,06,292,00*74",
+        Ok(Sentence::Other("GSV")),
+    )])
+}
+
+#[test]
+fn damaged_sentences() {
+    assert_lines(&[
+        // One digit changed on the way.
+        (
+            "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*46",
+            Err(Error::BadChecksum),
+        ),
+        (
+            "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,",
+            Err(Error::NoChecksum),
+        ),
+        ("$GPGSV,1*7", Err(Error::NoChecksum)),
+        ("$GPGSV,1*zz", Err(Error::NoChecksum)),
+        ("GPGSV,1*00", Err(Error::NotASentence)),
+        ("$GP*17", Err(Error::NotASentence)),
+    ])
+}
+
+#[test]
+fn invalid_fields() {
+    assert_lines(&[
+        (
+            "$GPGGA,123519,4807.038,X,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*51",
+            Err(Error::Invalid("latitude")),
+        ),
+        // 67 minutes.
+        (
+            "$GPGGA,123519,4867.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*41",
+            Err(Error::Invalid("latitude")),
+        ),
+        (
+            "$GPGGA,123519,48nan,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*34",
+            Err(Error::Invalid("latitude")),
+        ),
+        (
+            "$GPGGA,123519,4807.0.38,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*69",
+            Err(Error::Invalid("latitude")),
+        ),
+        // Past the poles, and more than halfway round.
+        (
+            "$GPGGA,123519,9500.000,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*4B",
+            Err(Error::Invalid("latitude")),
+        ),
+        (
+            "$GPGGA,123519,4807.038,N,18100.000,E,1,08,0.9,545.4,M,46.9,M,,*4D",
+            Err(Error::Invalid("longitude")),
+        ),
+        (
+            "$GPGGA,256000,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*4B",
+            Err(Error::Invalid("time")),
+        ),
+        (
+            "$GPGGA,123519,4807.038,N,01131.000,E,1,eight,0.9,545.4,M,46.9,M,,*38",
+            Err(Error::Invalid("satellites")),
+        ),
+    ])
+}
+
+#[test]
+fn display() {
+    let position = Position {
+        latitude: -(33.0 + 52.1234 / 60.0),
+        longitude: 11.0 + 31.0 / 60.0,
+    };
+    let date = Date {
+        year: 2026,
+        month: 2,
+        day: 3,
+    };
+    let cases = [
+        (position.to_string(), "33.86872S 11.51667E"),
+        (time(9, 5, 7, 40).unwrap().to_string(), "09:05:07.040"),
+        (date.to_string(), "2026-02-03"),
+        (Error::Invalid("date").to_string(), "invalid date"),
+    ];
+    for (got, expected) in cases {
+        assert_eq!(got, expected);
+    }
+}
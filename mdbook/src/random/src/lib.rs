@@ -0,0 +1,108 @@
+#![no_std]
+
+//! Unbiased random choices on top of a source of random `u32`s.
+//!
+//! Reducing a random number with `%` favours the smaller results whenever the range doesn't
+//! divide evenly into the generator's: `random_u8() % 6` gives 0 to 3 with probability 43/256
+//! each but 4 and 5 with only 42/256. [`Random::below`] rejects the few values that cause this
+//! and draws again, so every result is equally likely. Everything else here is built on it.
+//!
+//! Implement [`Random::next_u32`] for a generator to get the rest. With the `mb2` feature the
+//! MB2's hardware generator implements it.
+
+/// A source of random numbers, and unbiased choices made with them.
+pub trait Random {
+    /// A random `u32`, every value equally likely.
+    fn next_u32(&mut self) -> u32;
+
+    /// A random number from 0 up to but not including `n`.
+    ///
+    /// # Panics
+    ///
+    /// If `n` is 0.
+    fn below(&mut self, n: u32) -> u32 {
+        assert!(n > 0, "empty range");
+        // 2³² mod n: rejecting the values below this leaves a whole number of copies of 0..n.
+        let reject = n.wrapping_neg() % n;
+        loop {
+            let x = self.next_u32();
+            if x >= reject {
+                return x % n;
+            }
+        }
+    }
+
+    /// A random number from `lo` up to but not including `hi`.
+    ///
+    /// # Panics
+    ///
+    /// If the range is empty.
+    fn range(&mut self, lo: i32, hi: i32) -> i32 {
+        assert!(lo < hi, "empty range");
+        let span = hi.abs_diff(lo);
+        lo.wrapping_add_unsigned(self.below(span))
+    }
+
+    /// Put `items` in a random order, every order equally likely.
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        // Fisher-Yates: pick the last item from all of them, then the one before from the rest...
+        for i in (1..items.len()).rev() {
+            let j = self.below(i as u32 + 1) as usize;
+            items.swap(i, j);
+        }
+    }
+
+    /// The index of a random entry in `weights`, each chosen in proportion to its weight.
+    /// `None` if all the weights are zero.
+    ///
+    /// # Panics
+    ///
+    /// If the weights add up to more than `u32::MAX`.
+    fn weighted(&mut self, weights: &[u32]) -> Option<usize> {
+        let total = weights
+            .iter()
+            .try_fold(0u32, |sum, &w| sum.checked_add(w))
+            .expect("weights too large");
+        if total == 0 {
+            return None;
+        }
+        let mut pick = self.below(total);
+        weights.iter().position(|&w| {
+            if pick < w {
+                return true;
+            }
+            pick -= w;
+            false
+        })
+    }
+
+    /// A random `(row, column)` among the cells of a `rows` × `cols` grid for which `is_free`
+    /// returns `true`, each equally likely. `None` if there are none.
+    ///
+    /// Unlike drawing cells until a free one turns up, this takes the same time however full the
+    /// grid is: one pass to count the free cells and one to find the chosen one.
+    fn free_cell(
+        &mut self,
+        rows: usize,
+        cols: usize,
+        mut is_free: impl FnMut(usize, usize) -> bool,
+    ) -> Option<(usize, usize)> {
+        let cells = (0..rows).flat_map(|row| (0..cols).map(move |col| (row, col)));
+        let free = cells
+            .clone()
+            .filter(|&(row, col)| is_free(row, col))
+            .count();
+        if free == 0 {
+            return None;
+        }
+        let pick = self.below(free as u32) as usize;
+        cells.filter(|&(row, col)| is_free(row, col)).nth(pick)
+    }
+}
+
+#[cfg(feature = "mb2")]
+impl Random for microbit::hal::rng::Rng {
+    fn next_u32(&mut self) -> u32 {
+        self.random_u32()
+    }
+}
@@ -0,0 +1,144 @@
+//! Chi-square checks that the choices made by the `random` crate are uniform.
+//!
+//! ```text
+//! cargo run --release
+//! ```
+//!
+//! Each check draws many samples from a good host generator, counts how often each outcome
+//! comes up and compares the counts with what a fair choice would give. As a control, the same
+//! test is run on the biased `random_u8() % 6`, which it must catch. Exits with status 1 if any
+//! check fails.
+
+use std::process;
+
+use random::Random;
+
+/// The chance of a fair choice failing a check by bad luck.
+const P_VALUE: f64 = 0.001;
+/// The standard normal quantile for [`P_VALUE`], one-sided.
+const Z: f64 = 3.090;
+
+/// SplitMix64, a fast generator that passes the usual statistical test batteries.
+struct SplitMix64(u64);
+
+impl Random for SplitMix64 {
+    fn next_u32(&mut self) -> u32 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        ((z ^ (z >> 31)) >> 32) as u32
+    }
+}
+
+/// The chi-square value beyond which a fair choice lands with probability [`P_VALUE`], by the
+/// Wilson-Hilferty approximation.
+fn critical(degrees_of_freedom: usize) -> f64 {
+    let k = degrees_of_freedom as f64;
+    let a = 2.0 / (9.0 * k);
+    k * (1.0 - a + Z * a.sqrt()).powi(3)
+}
+
+/// Chi-square statistic for `counts` against `expected` proportions, ignoring outcomes that
+/// should never happen. Returns `None` if one of those happened anyway.
+fn chi_square(counts: &[u64], expected: &[f64]) -> Option<(f64, usize)> {
+    let n: u64 = counts.iter().sum();
+    let total: f64 = expected.iter().sum();
+    let mut chi2 = 0.0;
+    let mut categories = 0;
+    for (&count, &p) in counts.iter().zip(expected) {
+        if p == 0.0 {
+            if count != 0 {
+                return None;
+            }
+            continue;
+        }
+        let e = n as f64 * p / total;
+        chi2 += (count as f64 - e).powi(2) / e;
+        categories += 1;
+    }
+    Some((chi2, categories - 1))
+}
+
+/// Run one check, printing the result. Returns whether it passed.
+fn check(name: &str, counts: &[u64], expected: &[f64]) -> bool {
+    match chi_square(counts, expected) {
+        Some((chi2, df)) => {
+            let limit = critical(df);
+            let pass = chi2 < limit;
+            println!(
+                "{:<28} chi2 = {:>9.2}  limit = {:>7.2}  (df {:>3})  {}",
+                name,
+                chi2,
+                limit,
+                df,
+                if pass { "ok" } else { "FAIL" }
+            );
+            pass
+        }
+        None => {
+            println!("{:<28} an impossible outcome came up  FAIL", name);
+            false
+        }
+    }
+}
+
+fn count(outcomes: usize, draws: usize, mut draw: impl FnMut() -> usize) -> Vec<u64> {
+    let mut counts = vec![0; outcomes];
+    for _ in 0..draws {
+        counts[draw()] += 1;
+    }
+    counts
+}
+
+fn main() {
+    let mut rng = SplitMix64(0x5eed);
+    let mut ok = true;
+
+    for n in [2, 3, 5, 6, 7, 10, 12, 20, 25, 100] {
+        let counts = count(n, 100_000 * n.min(10), || rng.below(n as u32) as usize);
+        ok &= check(&format!("below({})", n), &counts, &vec![1.0; n]);
+    }
+
+    let counts = count(7, 700_000, || (rng.range(-3, 4) + 3) as usize);
+    ok &= check("range(-3, 4)", &counts, &[1.0; 7]);
+
+    // A shuffle of four items: each of the 24 orders, numbered by the order itself.
+    let counts = count(24, 480_000, || {
+        let mut items = [0, 1, 2, 3];
+        rng.shuffle(&mut items);
+        let mut remaining = vec![0, 1, 2, 3];
+        items.iter().fold(0, |index, item| {
+            let position = remaining.iter().position(|r| r == item).unwrap();
+            remaining.remove(position);
+            index * (remaining.len() + 1) + position
+        })
+    });
+    ok &= check("shuffle of 4", &counts, &[1.0; 24]);
+
+    let weights = [1, 2, 3, 0, 4];
+    let counts = count(weights.len(), 500_000, || rng.weighted(&weights).unwrap());
+    let expected = weights.map(f64::from);
+    ok &= check("weighted([1, 2, 3, 0, 4])", &counts, &expected);
+
+    // A nearly full 5×5 grid, with only the cells on one diagonal free.
+    let counts = count(25, 500_000, || {
+        let (row, col) = rng.free_cell(5, 5, |row, col| row == col).unwrap();
+        row * 5 + col
+    });
+    let expected: Vec<f64> = (0..25)
+        .map(|i| if i / 5 == i % 5 { 1.0 } else { 0.0 })
+        .collect();
+    ok &= check("free_cell, 5 of 25 free", &counts, &expected);
+    ok &= rng.free_cell(5, 5, |_, _| false).is_none();
+
+    // The control: the test must see the bias in reducing a byte with `%`.
+    println!("control, expected to fail:");
+    let counts = count(6, 1_000_000, || rng.next_u32() as u8 as usize % 6);
+    ok &= !check("random_u8() % 6", &counts, &[1.0; 6]);
+
+    if !ok {
+        process::exit(1);
+    }
+    println!("all checks passed at p = {}", P_VALUE);
+}
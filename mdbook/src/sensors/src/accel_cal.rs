@@ -0,0 +1,330 @@
+//! Six-position accelerometer calibration.
+//!
+//! Each axis of an accelerometer reads a little off zero when it should read nothing, and a
+//! little more or less than 1 g when it should read 1 g. Resting the board on each of its six
+//! faces and edges in turn points each axis straight up and straight down, and the two readings
+//! give that axis's offset (halfway between them) and gain (what makes them 2 g apart).
+//!
+//! [`Capture`] collects the six averages from a stream of readings, waiting for the board to be
+//! held still in each position, and [`solve`] turns them into an [`AccelCalibration`].
+//! [`Calibrated`] applies one to a sensor, so the rest of a program reads corrected values
+//! without knowing. [`AccelCalibration::to_bytes`] packs a calibration up for storing in flash.
+
+use crate::{attitude::Orientation, Accelerometer, ErrorType, Magnetometer, Xyz};
+
+/// The positions a [`Capture`] asks for, in the order it asks for them.
+pub const FACES: [Orientation; 6] = [
+    Orientation::FaceUp,
+    Orientation::FaceDown,
+    Orientation::LogoUp,
+    Orientation::LogoDown,
+    Orientation::LeftUp,
+    Orientation::RightUp,
+];
+
+/// An axis reading at least this much, in mg, with the others reading no more than
+/// [`OFF_AXIS_MG`], is taken to be pointing straight up or down.
+const ON_AXIS_MG: i32 = 700;
+const OFF_AXIS_MG: i32 = 300;
+/// Readings further than this, in mg, from the first of a run mean the board moved.
+const STEADY_MG: i32 = 60;
+/// An axis whose up and down readings are further from 2 g apart than this, in mg, can't be
+/// calibrated: the board wasn't where the calibration thought it was.
+const SPAN_TOLERANCE_MG: i32 = 400;
+
+/// Where `face` is in [`FACES`].
+fn index(face: Orientation) -> usize {
+    FACES.iter().position(|f| *f == face).unwrap()
+}
+
+/// The position a reading, in mg, says the board is resting in, if it is close to any of them.
+pub fn resting_face((x, y, z): Xyz) -> Option<Orientation> {
+    let [ax, ay, az] = [x.abs(), y.abs(), z.abs()];
+    let on = |a: i32, b: i32, c: i32| a >= ON_AXIS_MG && b <= OFF_AXIS_MG && c <= OFF_AXIS_MG;
+    if on(ax, ay, az) {
+        Some(if x > 0 {
+            Orientation::LeftUp
+        } else {
+            Orientation::RightUp
+        })
+    } else if on(ay, ax, az) {
+        Some(if y > 0 {
+            Orientation::LogoDown
+        } else {
+            Orientation::LogoUp
+        })
+    } else if on(az, ax, ay) {
+        Some(if z > 0 {
+            Orientation::FaceDown
+        } else {
+            Orientation::FaceUp
+        })
+    } else {
+        None
+    }
+}
+
+/// Why [`solve`] couldn't calibrate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SolveError {
+    /// The up and down readings of axis 0 (x), 1 (y) or 2 (z), in mg, aren't about 2 g apart.
+    Span { axis: usize, up: i32, down: i32 },
+}
+
+/// Offset and gain for each axis of an accelerometer.
+///
+/// A raw reading `r` on an axis is corrected to `(r - offset) * gain`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AccelCalibration {
+    offset: [f32; 3],
+    gain: [f32; 3],
+}
+
+impl Default for AccelCalibration {
+    /// No correction at all.
+    fn default() -> Self {
+        Self {
+            offset: [0.0; 3],
+            gain: [1.0; 3],
+        }
+    }
+}
+
+impl AccelCalibration {
+    /// The length of [`to_bytes`](Self::to_bytes).
+    pub const BYTES: usize = 24;
+
+    pub const fn new(offset: [f32; 3], gain: [f32; 3]) -> Self {
+        Self { offset, gain }
+    }
+
+    /// Per-axis offsets, in mg.
+    pub fn offset(&self) -> [f32; 3] {
+        self.offset
+    }
+
+    /// Per-axis gains.
+    pub fn gain(&self) -> [f32; 3] {
+        self.gain
+    }
+
+    /// Correct a raw reading in mg.
+    pub fn apply(&self, (x, y, z): Xyz) -> Xyz {
+        let axis = |i: usize, r: i32| {
+            let v = (r as f32 - self.offset[i]) * self.gain[i];
+            if v < 0.0 {
+                (v - 0.5) as i32
+            } else {
+                (v + 0.5) as i32
+            }
+        };
+        (axis(0, x), axis(1, y), axis(2, z))
+    }
+
+    /// The offsets then the gains, each as a little-endian `f32`.
+    pub fn to_bytes(&self) -> [u8; Self::BYTES] {
+        let mut bytes = [0; Self::BYTES];
+        let values = self.offset.iter().chain(self.gain.iter());
+        for (chunk, value) in bytes.chunks_exact_mut(4).zip(values) {
+            chunk.copy_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// The inverse of [`to_bytes`](Self::to_bytes). Returns `None` if `bytes` is the wrong length
+    /// or doesn't hold a believable calibration, such as one left behind by an older format.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != Self::BYTES {
+            return None;
+        }
+        let mut values = [0.0; 6];
+        for (value, chunk) in values.iter_mut().zip(bytes.chunks_exact(4)) {
+            *value = f32::from_le_bytes(chunk.try_into().unwrap());
+        }
+        let [ox, oy, oz, gx, gy, gz] = values;
+        let offset_ok = |o: f32| o.is_finite() && o.abs() < 1000.0;
+        let gain_ok = |g: f32| g.is_finite() && (0.5..2.0).contains(&g);
+        if [ox, oy, oz].into_iter().all(offset_ok) && [gx, gy, gz].into_iter().all(gain_ok) {
+            Some(Self::new([ox, oy, oz], [gx, gy, gz]))
+        } else {
+            None
+        }
+    }
+}
+
+/// Work out a calibration from the average reading, in mg, in each of the [`FACES`], in order.
+pub fn solve(averages: &[Xyz; 6]) -> Result<AccelCalibration, SolveError> {
+    let reading = |face: Orientation| averages[index(face)];
+    // The reading of each axis when it points down, so reads +1 g, and when it points up.
+    let spans = [
+        (
+            reading(Orientation::LeftUp).0,
+            reading(Orientation::RightUp).0,
+        ),
+        (
+            reading(Orientation::LogoDown).1,
+            reading(Orientation::LogoUp).1,
+        ),
+        (
+            reading(Orientation::FaceDown).2,
+            reading(Orientation::FaceUp).2,
+        ),
+    ];
+    let mut calibration = AccelCalibration::default();
+    for (axis, (down, up)) in spans.into_iter().enumerate() {
+        if (down - up - 2000).abs() > SPAN_TOLERANCE_MG {
+            return Err(SolveError::Span { axis, up, down });
+        }
+        calibration.offset[axis] = (down + up) as f32 / 2.0;
+        calibration.gain[axis] = 2000.0 / (down - up) as f32;
+    }
+    Ok(calibration)
+}
+
+/// Averages readings in each of the six [`FACES`] as the board is turned from one to the next.
+///
+/// A position counts once the board has rested in it, without moving, for the number of
+/// readings given to [`new`](Self::new). Positions can be visited in any order, and visiting one
+/// again replaces its average.
+#[derive(Debug, Clone)]
+pub struct Capture {
+    samples: u32,
+    averages: [Option<Xyz>; 6],
+    /// The position being held, its first reading, how many readings so far, and their sum.
+    run: Option<(Orientation, Xyz, u32, [i32; 3])>,
+}
+
+impl Capture {
+    /// Average `samples` readings in each position.
+    pub const fn new(samples: u32) -> Self {
+        Self {
+            samples,
+            averages: [None; 6],
+            run: None,
+        }
+    }
+
+    /// Feed a raw reading in mg. Returns the position just captured, if this reading finished one.
+    pub fn update(&mut self, accel: Xyz) -> Option<Orientation> {
+        let Some(face) = resting_face(accel) else {
+            self.run = None;
+            return None;
+        };
+        let steady = |first: Xyz| {
+            (accel.0 - first.0).abs() <= STEADY_MG
+                && (accel.1 - first.1).abs() <= STEADY_MG
+                && (accel.2 - first.2).abs() <= STEADY_MG
+        };
+        let (_, _, count, sum) = match &mut self.run {
+            Some(run) if run.0 == face && steady(run.1) => run,
+            run => run.insert((face, accel, 0, [0; 3])),
+        };
+        *count += 1;
+        sum[0] += accel.0;
+        sum[1] += accel.1;
+        sum[2] += accel.2;
+        if *count < self.samples {
+            return None;
+        }
+        let n = *count as i32;
+        self.averages[index(face)] = Some((sum[0] / n, sum[1] / n, sum[2] / n));
+        self.run = None;
+        Some(face)
+    }
+
+    /// The position being held still, and how far through capturing it, from 0 to 1.
+    pub fn holding(&self) -> Option<(Orientation, f32)> {
+        self.run
+            .map(|(face, _, count, _)| (face, count as f32 / self.samples as f32))
+    }
+
+    pub fn is_captured(&self, face: Orientation) -> bool {
+        self.averages[index(face)].is_some()
+    }
+
+    /// The first of the [`FACES`] still to be captured.
+    pub fn next_face(&self) -> Option<Orientation> {
+        FACES.into_iter().find(|face| !self.is_captured(*face))
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.next_face().is_none()
+    }
+
+    /// The average reading in `face`, once it has been captured.
+    pub fn average(&self, face: Orientation) -> Option<Xyz> {
+        self.averages[index(face)]
+    }
+
+    /// The calibration, once every position has been captured.
+    pub fn calibration(&self) -> Option<Result<AccelCalibration, SolveError>> {
+        let mut averages = [(0, 0, 0); 6];
+        for (average, captured) in averages.iter_mut().zip(self.averages) {
+            *average = captured?;
+        }
+        Some(solve(&averages))
+    }
+
+    /// Forget `face`, so that it has to be captured again.
+    pub fn forget(&mut self, face: Orientation) {
+        self.averages[index(face)] = None;
+    }
+}
+
+/// A sensor whose acceleration readings are corrected by an [`AccelCalibration`]. Anything else
+/// it measures passes straight through.
+#[derive(Debug)]
+pub struct Calibrated<S> {
+    sensor: S,
+    calibration: AccelCalibration,
+}
+
+impl<S> Calibrated<S> {
+    pub const fn new(sensor: S, calibration: AccelCalibration) -> Self {
+        Self {
+            sensor,
+            calibration,
+        }
+    }
+
+    pub fn calibration(&self) -> &AccelCalibration {
+        &self.calibration
+    }
+
+    pub fn set_calibration(&mut self, calibration: AccelCalibration) {
+        self.calibration = calibration;
+    }
+
+    /// The sensor itself, for reading it uncorrected or changing its settings.
+    pub fn inner_mut(&mut self) -> &mut S {
+        &mut self.sensor
+    }
+
+    pub fn free(self) -> S {
+        self.sensor
+    }
+}
+
+impl<S: ErrorType> ErrorType for Calibrated<S> {
+    type Error = S::Error;
+}
+
+impl<S: Accelerometer> Accelerometer for Calibrated<S> {
+    fn accel_ready(&mut self) -> Result<bool, Self::Error> {
+        self.sensor.accel_ready()
+    }
+
+    fn read_accel(&mut self) -> Result<Xyz, Self::Error> {
+        Ok(self.calibration.apply(self.sensor.read_accel()?))
+    }
+}
+
+impl<S: Magnetometer> Magnetometer for Calibrated<S> {
+    fn mag_ready(&mut self) -> Result<bool, Self::Error> {
+        self.sensor.mag_ready()
+    }
+
+    fn read_mag(&mut self) -> Result<Xyz, Self::Error> {
+        self.sensor.read_mag()
+    }
+}
@@ -0,0 +1,217 @@
+//! Which way up the board is, from accelerometer readings.
+//!
+//! At rest an accelerometer measures only gravity, so the direction of its readings gives the
+//! board's roll and pitch. Movement adds to the readings, though, so [`AttitudeEstimator`]
+//! smooths them with a complementary filter: short-term changes come from a gyroscope if there
+//! is one (the MB2 has none) and the long-term direction from the accelerometer.
+//!
+//! All angles are in degrees, and readings in the LSM303AGR's axes as mounted on the MB2: x to
+//! the right, y towards the logo and z out of the LEDs. The readings point the way gravity pulls,
+//! so z reads -1 g with the LEDs facing up and x reads +1 g with the right edge down.
+
+use libm::{atan2f, sqrtf};
+
+use crate::Xyz;
+
+const RAD_TO_DEG: f32 = 180.0 / core::f32::consts::PI;
+
+/// Which face or edge of the board points up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    /// The LEDs face up.
+    FaceUp,
+    /// The LEDs face down.
+    FaceDown,
+    /// Standing on the edge opposite the logo.
+    LogoUp,
+    /// Standing on the logo edge.
+    LogoDown,
+    /// Standing on the right edge.
+    LeftUp,
+    /// Standing on the left edge.
+    RightUp,
+}
+
+/// Tuning for an [`AttitudeEstimator`].
+#[derive(Debug, Clone, Copy)]
+pub struct Filter {
+    /// How long, in seconds, the accelerometer takes to pull the estimate most of the way (63%)
+    /// to a new attitude. Longer is steadier but slower; zero follows every reading.
+    pub time_constant_s: f32,
+    /// Readings further than this from 1 g, in mg, are taken to be movement rather than
+    /// gravity and don't correct the estimate.
+    pub jolt_tolerance_mg: i32,
+    /// A new face or edge must point up more than the current one by this much, in mg, before
+    /// the [`Orientation`] changes.
+    pub hysteresis_mg: i32,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self {
+            time_constant_s: 0.2,
+            jolt_tolerance_mg: 300,
+            hysteresis_mg: 150,
+        }
+    }
+}
+
+/// Smoothed roll, pitch and orientation from a stream of accelerometer readings.
+#[derive(Debug, Clone)]
+pub struct AttitudeEstimator {
+    filter: Filter,
+    /// The estimated gravity reading, in mg.
+    gravity: Option<[f32; 3]>,
+    orientation: Option<Orientation>,
+}
+
+impl AttitudeEstimator {
+    pub const fn new(filter: Filter) -> Self {
+        Self {
+            filter,
+            gravity: None,
+            orientation: None,
+        }
+    }
+
+    /// Feed an accelerometer reading in mg, taken `dt` seconds after the last one.
+    pub fn update(&mut self, accel: Xyz, dt: f32) {
+        self.update_with_gyro(accel, (0.0, 0.0, 0.0), dt);
+    }
+
+    /// Feed an accelerometer reading in mg along with the rotation rate in degrees per second
+    /// about the same axes, both taken `dt` seconds after the last ones. Readings taken no later
+    /// than the last, with a `dt` of zero or less, are ignored.
+    pub fn update_with_gyro(&mut self, accel: Xyz, (gx, gy, gz): (f32, f32, f32), dt: f32) {
+        let measured = [accel.0 as f32, accel.1 as f32, accel.2 as f32];
+        let Some(g) = self.gravity.as_mut() else {
+            self.gravity = Some(measured);
+            self.update_orientation();
+            return;
+        };
+        // No time has passed, so nothing has turned; and with no time constant either, the
+        // weight below would be 0 / 0.
+        if dt <= 0.0 {
+            return;
+        }
+
+        // Turning the board at ω turns gravity, as seen from the board, at -ω.
+        let (wx, wy, wz) = (
+            gx / RAD_TO_DEG * dt,
+            gy / RAD_TO_DEG * dt,
+            gz / RAD_TO_DEG * dt,
+        );
+        *g = [
+            g[0] - (wy * g[2] - wz * g[1]),
+            g[1] - (wz * g[0] - wx * g[2]),
+            g[2] - (wx * g[1] - wy * g[0]),
+        ];
+
+        let magnitude = sqrtf(measured.iter().map(|v| v * v).sum());
+        if (magnitude - 1000.0).abs() <= self.filter.jolt_tolerance_mg as f32 {
+            let weight = dt / (self.filter.time_constant_s + dt);
+            for (g, m) in g.iter_mut().zip(measured) {
+                *g += (m - *g) * weight;
+            }
+        }
+        self.update_orientation();
+    }
+
+    fn update_orientation(&mut self) {
+        let Some(g) = self.gravity else {
+            return;
+        };
+        let up = |o: Orientation| match o {
+            Orientation::FaceUp => -g[2],
+            Orientation::FaceDown => g[2],
+            Orientation::LogoUp => -g[1],
+            Orientation::LogoDown => g[1],
+            Orientation::LeftUp => g[0],
+            Orientation::RightUp => -g[0],
+        };
+        let best = [
+            Orientation::FaceUp,
+            Orientation::FaceDown,
+            Orientation::LogoUp,
+            Orientation::LogoDown,
+            Orientation::LeftUp,
+            Orientation::RightUp,
+        ]
+        .into_iter()
+        .max_by(|a, b| up(*a).total_cmp(&up(*b)))
+        .unwrap();
+        let change = match self.orientation {
+            None => true,
+            Some(current) => up(best) > up(current) + self.filter.hysteresis_mg as f32,
+        };
+        if change {
+            self.orientation = Some(best);
+        }
+    }
+
+    /// The estimated gravity reading, in mg, once there has been a reading.
+    pub fn gravity(&self) -> Option<Xyz> {
+        self.gravity.map(|[x, y, z]| (x as i32, y as i32, z as i32))
+    }
+
+    /// Rotation about the y axis, positive with the right edge down, from -180 to 180. Standing
+    /// on the logo edge or the one opposite, roll means nothing and is 0.
+    pub fn roll(&self) -> Option<f32> {
+        self.gravity.map(|[x, _, z]| {
+            if x == 0.0 && z == 0.0 {
+                0.0
+            } else {
+                atan2f(x, -z) * RAD_TO_DEG
+            }
+        })
+    }
+
+    /// Rotation about the x axis, positive with the logo edge down, from -90 to 90.
+    pub fn pitch(&self) -> Option<f32> {
+        self.gravity
+            .map(|[x, y, z]| atan2f(y, sqrtf(x * x + z * z)) * RAD_TO_DEG)
+    }
+
+    pub fn orientation(&self) -> Option<Orientation> {
+        self.orientation
+    }
+}
+
+/// Splits tilt readings into five steps, for moving a cursor around the 5×5 display.
+///
+/// Readings within `inner_mg` of level are the middle step, those between `inner_mg` and
+/// `outer_mg` either side the next ones out, and anything beyond that the outermost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Buckets {
+    pub inner_mg: i32,
+    pub outer_mg: i32,
+}
+
+impl Default for Buckets {
+    /// About 12° and 37° of tilt.
+    fn default() -> Self {
+        Self {
+            inner_mg: 200,
+            outer_mg: 600,
+        }
+    }
+}
+
+impl Buckets {
+    /// The step, from 0 for the most negative readings to 4 for the most positive.
+    pub fn index(&self, mg: i32) -> usize {
+        match mg {
+            mg if mg < -self.outer_mg => 0,
+            mg if mg < -self.inner_mg => 1,
+            mg if mg > self.outer_mg => 4,
+            mg if mg > self.inner_mg => 3,
+            _ => 2,
+        }
+    }
+
+    /// The `(row, column)` on the display for a reading with `x` and `y` in mg: tilting right
+    /// moves right and tilting the logo down moves up.
+    pub fn cell(&self, (x, y): (i32, i32)) -> (usize, usize) {
+        (4 - self.index(y), self.index(x))
+    }
+}
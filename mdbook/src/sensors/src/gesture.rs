@@ -0,0 +1,255 @@
+//! Gestures recognised from accelerometer readings.
+//!
+//! [`GestureDetector`] works in software on readings from any [`Accelerometer`](crate::Accelerometer).
+
+use heapless::Deque;
+use libm::sqrtf;
+
+use crate::Xyz;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gesture {
+    Shake,
+    Tap,
+    DoubleTap,
+    FreeFall,
+    /// The LEDs are facing up.
+    FaceUp,
+    /// The LEDs are facing down.
+    FaceDown,
+}
+
+/// Tuning for a [`GestureDetector`]. Accelerations are in mg and times in ms.
+#[derive(Debug, Clone, Copy)]
+pub struct Thresholds {
+    /// A tap is a jolt away from gravity bigger than this...
+    pub tap_mg: i32,
+    /// ...that is over within this long.
+    pub tap_max_ms: u32,
+    /// A second tap within this long makes a double tap.
+    pub double_tap_ms: u32,
+    /// Readings this far from gravity along an axis count towards a shake...
+    pub shake_mg: i32,
+    /// ...when the direction changes this many times...
+    pub shake_reversals: u32,
+    /// ...with no more than this long between changes.
+    pub shake_gap_ms: u32,
+    /// No more shakes or taps are reported for this long after a shake.
+    pub shake_holdoff_ms: u32,
+    /// In free fall the accelerometer reads less than this...
+    pub freefall_mg: i32,
+    /// ...for at least this long.
+    pub freefall_ms: u32,
+    /// Face up or down is within this much of 1 g along z...
+    pub face_tolerance_mg: i32,
+    /// ...held for this long.
+    pub face_ms: u32,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            tap_mg: 1200,
+            tap_max_ms: 60,
+            double_tap_ms: 300,
+            shake_mg: 900,
+            shake_reversals: 4,
+            shake_gap_ms: 250,
+            shake_holdoff_ms: 1000,
+            freefall_mg: 400,
+            freefall_ms: 80,
+            face_tolerance_mg: 250,
+            face_ms: 200,
+        }
+    }
+}
+
+fn magnitude((x, y, z): Xyz) -> i32 {
+    let (x, y, z) = (x as f32, y as f32, z as f32);
+    sqrtf(x * x + y * y + z * z) as i32
+}
+
+fn sign(v: i32, threshold: i32) -> i8 {
+    if v > threshold {
+        1
+    } else if v < -threshold {
+        -1
+    } else {
+        0
+    }
+}
+
+/// Turns a stream of accelerometer readings, in mg, into debounced [`Gesture`]s.
+///
+/// Orientation gestures are reported once each time the board settles into a new orientation,
+/// free fall once per fall, and taps only after the double-tap window has passed, so a double tap
+/// isn't also reported as a tap.
+#[derive(Debug, Clone)]
+pub struct GestureDetector {
+    /// Readings per second, to turn times into reading counts.
+    sample_rate_hz: u32,
+    thresholds: Thresholds,
+    gravity: Option<Xyz>,
+    /// Readings since the current jolt started, while it lasts.
+    jolt: Option<u32>,
+    /// Readings since an unreported tap.
+    tap: Option<u32>,
+    last_sign: [i8; 3],
+    reversals: u32,
+    since_reversal: u32,
+    holdoff: u32,
+    falling: u32,
+    fell: bool,
+    face: Option<Gesture>,
+    face_count: u32,
+    reported_face: Option<Gesture>,
+    events: Deque<Gesture, 4>,
+}
+
+impl GestureDetector {
+    pub fn new(sample_rate_hz: u32) -> Self {
+        Self::with_thresholds(sample_rate_hz, Thresholds::default())
+    }
+
+    pub fn with_thresholds(sample_rate_hz: u32, thresholds: Thresholds) -> Self {
+        Self {
+            sample_rate_hz,
+            thresholds,
+            gravity: None,
+            jolt: None,
+            tap: None,
+            last_sign: [0; 3],
+            reversals: 0,
+            since_reversal: 0,
+            holdoff: 0,
+            falling: 0,
+            fell: false,
+            face: None,
+            face_count: 0,
+            reported_face: None,
+            events: Deque::new(),
+        }
+    }
+
+    /// Number of readings in `ms` milliseconds, at least one.
+    fn samples(&self, ms: u32) -> u32 {
+        (ms * self.sample_rate_hz / 1000).max(1)
+    }
+
+    fn emit(&mut self, gesture: Gesture) {
+        if self.events.is_full() {
+            self.events.pop_front();
+        }
+        let _ = self.events.push_back(gesture);
+    }
+
+    /// Feed the next reading. Returns the next gesture recognised, if any.
+    pub fn update(&mut self, sample: Xyz) -> Option<Gesture> {
+        let (gx, gy, gz) = *self.gravity.get_or_insert(sample);
+        let dynamic = (sample.0 - gx, sample.1 - gy, sample.2 - gz);
+
+        self.update_shake(dynamic);
+        self.update_tap(magnitude(dynamic));
+        self.update_freefall(magnitude(sample));
+        self.update_face(sample.2);
+
+        // Gravity only changes slowly; let jolts pass it by.
+        if self.jolt.is_none() {
+            self.gravity = Some((
+                gx + (sample.0 - gx) / 16,
+                gy + (sample.1 - gy) / 16,
+                gz + (sample.2 - gz) / 16,
+            ));
+        }
+        self.events.pop_front()
+    }
+
+    fn update_shake(&mut self, (x, y, z): Xyz) {
+        self.holdoff = self.holdoff.saturating_sub(1);
+        self.since_reversal += 1;
+        if self.since_reversal > self.samples(self.thresholds.shake_gap_ms) {
+            self.reversals = 0;
+            self.last_sign = [0; 3];
+            self.since_reversal = 0;
+        }
+        for (axis, v) in [x, y, z].into_iter().enumerate() {
+            let s = sign(v, self.thresholds.shake_mg);
+            if s == 0 {
+                continue;
+            }
+            if self.last_sign[axis] == -s {
+                self.reversals += 1;
+                self.since_reversal = 0;
+            }
+            self.last_sign[axis] = s;
+        }
+        if self.reversals >= self.thresholds.shake_reversals {
+            if self.holdoff == 0 {
+                self.emit(Gesture::Shake);
+            }
+            self.holdoff = self.samples(self.thresholds.shake_holdoff_ms);
+            self.reversals = 0;
+            self.tap = None;
+        }
+    }
+
+    fn update_tap(&mut self, jolt_mg: i32) {
+        if let Some(age) = self.tap.as_mut() {
+            *age += 1;
+            if *age > self.samples(self.thresholds.double_tap_ms) {
+                self.tap = None;
+                self.emit(Gesture::Tap);
+            }
+        }
+        match self.jolt {
+            _ if jolt_mg > self.thresholds.tap_mg => {
+                self.jolt = Some(self.jolt.map_or(1, |n| n + 1))
+            }
+            Some(len) => {
+                self.jolt = None;
+                if len <= self.samples(self.thresholds.tap_max_ms) && self.holdoff == 0 {
+                    if self.tap.take().is_some() {
+                        self.emit(Gesture::DoubleTap);
+                    } else {
+                        self.tap = Some(0);
+                    }
+                }
+            }
+            None => (),
+        }
+    }
+
+    fn update_freefall(&mut self, magnitude_mg: i32) {
+        if magnitude_mg >= self.thresholds.freefall_mg {
+            self.falling = 0;
+            self.fell = false;
+            return;
+        }
+        self.falling += 1;
+        if self.falling >= self.samples(self.thresholds.freefall_ms) && !self.fell {
+            self.fell = true;
+            self.emit(Gesture::FreeFall);
+        }
+    }
+
+    fn update_face(&mut self, z: i32) {
+        // The accelerometer is on the back of the board, so z reads -1 g with the LEDs up.
+        let limit = 1000 - self.thresholds.face_tolerance_mg;
+        let face = match z {
+            z if z < -limit => Some(Gesture::FaceUp),
+            z if z > limit => Some(Gesture::FaceDown),
+            _ => None,
+        };
+        if face != self.face {
+            self.face = face;
+            self.face_count = 0;
+        }
+        self.face_count += 1;
+        if self.face_count >= self.samples(self.thresholds.face_ms) && self.reported_face != face {
+            self.reported_face = face;
+            if let Some(face) = face {
+                self.emit(face);
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+#![no_std]
+
+//! Traits for the 3-axis motion sensors used in this book.
+//!
+//! Programs written against [`Accelerometer`] and [`Magnetometer`] rather than a particular
+//! driver can run on any sensor that implements them. With the `lsm303agr` feature (on by
+//! default) the MB2's LSM303AGR does; [`mock::Script`] plays back canned readings instead, so the
+//! same program logic can be exercised on a desktop machine.
+//!
+//! Both sensors latch their newest reading: the `*_ready` methods report whether a reading has
+//! arrived since the last one was read, much like the data-ready bits of a real sensor.
+//!
+//! [`fifo`] reads the LSM303AGR accelerometer's FIFO in bursts, for sample rates too high to keep
+//! up with one reading at a time. [`sampler`] queues readings as they arrive so they can be
+//! processed later; with the `mb2` feature, [`mb2`] fills that queue from the sensor's interrupt.
+//! [`gesture`] recognises shakes, taps, free fall and which way up the board is, and
+//! [`attitude`] tracks the board's roll and pitch. [`accel_cal`] calibrates the accelerometer
+//! by resting the board on each of its faces and corrects its readings from then on.
+
+use core::fmt::Debug;
+
+use embedded_hal::delay::DelayNs;
+
+pub mod accel_cal;
+pub mod attitude;
+pub mod fifo;
+pub mod gesture;
+#[cfg(feature = "lsm303agr")]
+mod lsm303;
+#[cfg(feature = "mb2")]
+pub mod mb2;
+pub mod mock;
+pub mod sampler;
+
+/// A reading along the sensor's x, y and z axes.
+pub type Xyz = (i32, i32, i32);
+
+/// How long the `wait_*` methods sleep between polls.
+const POLL_US: u32 = 100;
+
+/// The error type shared by a sensor's traits.
+pub trait ErrorType {
+    type Error: Debug;
+}
+
+pub trait Accelerometer: ErrorType {
+    /// Whether a new acceleration reading is waiting to be read.
+    fn accel_ready(&mut self) -> Result<bool, Self::Error>;
+
+    /// The latest acceleration, in mg.
+    fn read_accel(&mut self) -> Result<Xyz, Self::Error>;
+
+    /// Wait for a new acceleration reading and return it.
+    fn wait_accel<D: DelayNs>(&mut self, delay: &mut D) -> Result<Xyz, Self::Error> {
+        while !self.accel_ready()? {
+            delay.delay_us(POLL_US);
+        }
+        self.read_accel()
+    }
+}
+
+pub trait Magnetometer: ErrorType {
+    /// Whether a new magnetic field reading is waiting to be read.
+    fn mag_ready(&mut self) -> Result<bool, Self::Error>;
+
+    /// The latest magnetic field, in nT.
+    fn read_mag(&mut self) -> Result<Xyz, Self::Error>;
+
+    /// Wait for a new magnetic field reading and return it.
+    fn wait_mag<D: DelayNs>(&mut self, delay: &mut D) -> Result<Xyz, Self::Error> {
+        while !self.mag_ready()? {
+            delay.delay_us(POLL_US);
+        }
+        self.read_mag()
+    }
+}
@@ -0,0 +1,29 @@
+use embedded_hal::i2c::I2c;
+use lsm303agr::{interface::I2cInterface, mode::MagContinuous, Error, Lsm303agr};
+
+use crate::{Accelerometer, ErrorType, Magnetometer, Xyz};
+
+impl<I: I2c, MODE> ErrorType for Lsm303agr<I2cInterface<I>, MODE> {
+    type Error = Error<I::Error>;
+}
+
+impl<I: I2c, MODE> Accelerometer for Lsm303agr<I2cInterface<I>, MODE> {
+    fn accel_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.accel_status()?.xyz_new_data())
+    }
+
+    fn read_accel(&mut self) -> Result<Xyz, Self::Error> {
+        Ok(self.acceleration()?.xyz_mg())
+    }
+}
+
+/// The magnetometer only produces new readings on its own in continuous mode.
+impl<I: I2c> Magnetometer for Lsm303agr<I2cInterface<I>, MagContinuous> {
+    fn mag_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.mag_status()?.xyz_new_data())
+    }
+
+    fn read_mag(&mut self) -> Result<Xyz, Self::Error> {
+        Ok(self.magnetic_field()?.xyz_nt())
+    }
+}
@@ -0,0 +1,137 @@
+//! Interrupt-driven sampling on the MB2.
+//!
+//! The LSM303AGR accelerometer can signal on its INT1 pin, which the MB2 wires to P0.25, when it
+//! has data ready (or when its FIFO reaches the watermark). [`InterruptSampler`] watches that pin
+//! with GPIOTE channel 0 and queues readings from the interrupt handler, so the main program can
+//! sleep until there is something to do.
+//!
+//! The program provides the interrupt handler itself:
+//!
+//! ```ignore
+//! static SAMPLER: InterruptSampler<MySensor, 32> = InterruptSampler::new();
+//!
+//! #[interrupt]
+//! fn GPIOTE() {
+//!     SAMPLER.on_interrupt();
+//! }
+//! ```
+
+use core::cell::RefCell;
+
+use cortex_m::interrupt::{free as interrupt_free, Mutex};
+use embedded_hal::i2c::I2c;
+use microbit::{
+    hal::{
+        gpio::{Floating, Input, Pin},
+        gpiote::Gpiote,
+    },
+    pac,
+};
+
+use crate::{
+    fifo::ACCEL_ADDR,
+    sampler::{Pending, Sampler},
+    Xyz,
+};
+
+const CTRL_REG6_A: u8 = 0x25;
+const H_LACTIVE: u8 = 0b0000_0010;
+
+/// Make the accelerometer's interrupt pins active low, as the MB2's shared interrupt line
+/// expects. The `lsm303agr` driver leaves this register alone, so do it directly on the bus
+/// before handing it to the driver.
+pub fn int1_active_low<I: I2c>(i2c: &mut I) -> Result<(), I::Error> {
+    i2c.write(ACCEL_ADDR, &[CTRL_REG6_A, H_LACTIVE])
+}
+
+struct State<S, const N: usize> {
+    gpiote: Gpiote,
+    sampler: Sampler<S, N>,
+}
+
+/// A [`Sampler`] fed from the GPIOTE interrupt, shareable as a `static`.
+pub struct InterruptSampler<S, const N: usize> {
+    state: Mutex<RefCell<Option<State<S, N>>>>,
+}
+
+impl<S, const N: usize> Default for InterruptSampler<S, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S, const N: usize> InterruptSampler<S, N> {
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(None)),
+        }
+    }
+}
+
+impl<S: Pending, const N: usize> InterruptSampler<S, N> {
+    /// Start sampling from `source`, whose INT1 output (active low) is on `int_pin`.
+    pub fn start(&self, gpiote: pac::GPIOTE, int_pin: Pin<Input<Floating>>, source: S) {
+        let gpiote = Gpiote::new(gpiote);
+        let channel = gpiote.channel0();
+        channel.input_pin(&int_pin).hi_to_lo().enable_interrupt();
+        channel.reset_events();
+
+        interrupt_free(|cs| {
+            let mut sampler = Sampler::new(source);
+            // The line may already be asserted, in which case there will be no edge until the
+            // waiting data has been read.
+            sampler.service().unwrap();
+            *self.state.borrow(cs).borrow_mut() = Some(State { gpiote, sampler });
+
+            unsafe {
+                pac::NVIC::unmask(pac::Interrupt::GPIOTE);
+            }
+            pac::NVIC::unpend(pac::Interrupt::GPIOTE);
+        });
+    }
+
+    /// Call from the `GPIOTE` interrupt handler.
+    pub fn on_interrupt(&self) {
+        interrupt_free(|cs| {
+            if let Some(state) = self.state.borrow(cs).borrow_mut().as_mut() {
+                if state.gpiote.channel0().is_event_triggered() {
+                    state.gpiote.channel0().reset_events();
+                    state.sampler.service().unwrap();
+                }
+            }
+        });
+    }
+
+    /// The oldest queued reading, if any.
+    pub fn try_next(&self) -> Option<Xyz> {
+        self.with(|sampler| sampler.pop()).flatten()
+    }
+
+    /// The oldest queued reading, sleeping until one arrives.
+    pub fn next(&self) -> Xyz {
+        loop {
+            let sample = interrupt_free(|cs| {
+                let mut state = self.state.borrow(cs).borrow_mut();
+                let state = state.as_mut().expect("sampler not started");
+                let sample = state.sampler.pop();
+                if sample.is_none() {
+                    // With interrupts masked a pending interrupt still wakes us, but can't run
+                    // until we leave the critical section, so none can slip in unnoticed.
+                    cortex_m::asm::wfi();
+                }
+                sample
+            });
+            if let Some(sample) = sample {
+                return sample;
+            }
+        }
+    }
+
+    /// Run `f` on the sampler, or return `None` if it hasn't been started.
+    pub fn with<R>(&self, f: impl FnOnce(&mut Sampler<S, N>) -> R) -> Option<R> {
+        interrupt_free(|cs| {
+            let mut state = self.state.borrow(cs).borrow_mut();
+            state.as_mut().map(|state| f(&mut state.sampler))
+        })
+    }
+}
@@ -0,0 +1,89 @@
+//! A sensor that plays back a script of readings.
+
+use crate::{Accelerometer, ErrorType, Magnetometer, Xyz};
+
+/// One reading in a [`Script`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reading {
+    Accel(Xyz),
+    Mag(Xyz),
+}
+
+/// The script has run out of readings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Exhausted;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Latch {
+    value: Xyz,
+    new: bool,
+}
+
+impl Latch {
+    fn set(&mut self, value: Xyz) {
+        *self = Latch { value, new: true };
+    }
+
+    fn take(&mut self) -> Xyz {
+        self.new = false;
+        self.value
+    }
+}
+
+/// A combined accelerometer and magnetometer that produces the readings of a script, in order.
+///
+/// Readings arrive only when asked for: each `*_ready` call that finds nothing new steps through
+/// the script to the next reading for its sensor. Readings for the other sensor that are stepped
+/// over are latched the way real data registers are, so a newer one overwrites an older unread
+/// one. Once the script runs out the ready methods return [`Exhausted`], which ends any
+/// `wait_*` loop rather than spinning forever.
+pub struct Script<I> {
+    readings: I,
+    accel: Latch,
+    mag: Latch,
+}
+
+impl<I: Iterator<Item = Reading>> Script<I> {
+    pub fn new<R: IntoIterator<IntoIter = I>>(readings: R) -> Self {
+        Self {
+            readings: readings.into_iter(),
+            accel: Latch::default(),
+            mag: Latch::default(),
+        }
+    }
+
+    /// Step through the script until `done` says the wanted reading has arrived.
+    fn advance(&mut self, done: fn(&Self) -> bool) -> Result<bool, Exhausted> {
+        while !done(self) {
+            match self.readings.next().ok_or(Exhausted)? {
+                Reading::Accel(xyz) => self.accel.set(xyz),
+                Reading::Mag(xyz) => self.mag.set(xyz),
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl<I> ErrorType for Script<I> {
+    type Error = Exhausted;
+}
+
+impl<I: Iterator<Item = Reading>> Accelerometer for Script<I> {
+    fn accel_ready(&mut self) -> Result<bool, Exhausted> {
+        self.advance(|s| s.accel.new)
+    }
+
+    fn read_accel(&mut self) -> Result<Xyz, Exhausted> {
+        Ok(self.accel.take())
+    }
+}
+
+impl<I: Iterator<Item = Reading>> Magnetometer for Script<I> {
+    fn mag_ready(&mut self) -> Result<bool, Exhausted> {
+        self.advance(|s| s.mag.new)
+    }
+
+    fn read_mag(&mut self) -> Result<Xyz, Exhausted> {
+        Ok(self.mag.take())
+    }
+}
@@ -0,0 +1,108 @@
+//! Queueing readings as they arrive, to be processed later.
+
+use heapless::Deque;
+
+use crate::{fifo::AccelFifo, Accelerometer, ErrorType, Xyz};
+use embedded_hal::i2c::I2c;
+
+/// Something holding accelerometer readings that haven't been read yet.
+pub trait Pending: ErrorType {
+    /// Hand every waiting reading to `f`, oldest first. Returns `true` if the sensor had to throw
+    /// readings away before these because nobody collected them in time.
+    fn take_pending<F: FnMut(Xyz)>(&mut self, f: F) -> Result<bool, Self::Error>;
+}
+
+/// A plain accelerometer only ever has its newest reading waiting.
+impl<A: Accelerometer> Pending for A {
+    fn take_pending<F: FnMut(Xyz)>(&mut self, mut f: F) -> Result<bool, Self::Error> {
+        while self.accel_ready()? {
+            f(self.read_accel()?);
+        }
+        Ok(false)
+    }
+}
+
+impl<I: I2c> Pending for AccelFifo<I> {
+    fn take_pending<F: FnMut(Xyz)>(&mut self, f: F) -> Result<bool, Self::Error> {
+        let mut samples = [(0, 0, 0); crate::fifo::FIFO_DEPTH];
+        let burst = self.drain(&mut samples)?;
+        samples[..burst.len].iter().copied().for_each(f);
+        Ok(burst.overrun)
+    }
+}
+
+/// A queue of up to `N` readings from `S`.
+///
+/// Call [`service`](Sampler::service) whenever the sensor signals that data is ready, typically
+/// from its interrupt handler, and take the readings out with [`pop`](Sampler::pop) at leisure. If
+/// the queue fills up, the oldest readings are dropped to make room.
+pub struct Sampler<S, const N: usize> {
+    source: S,
+    queue: Deque<Xyz, N>,
+    dropped: u32,
+    overruns: u32,
+}
+
+impl<S: Pending, const N: usize> Sampler<S, N> {
+    pub const fn new(source: S) -> Self {
+        Self {
+            source,
+            queue: Deque::new(),
+            dropped: 0,
+            overruns: 0,
+        }
+    }
+
+    /// Move every waiting reading from the sensor into the queue.
+    pub fn service(&mut self) -> Result<(), S::Error> {
+        let Self {
+            source,
+            queue,
+            dropped,
+            ..
+        } = self;
+        let overrun = source.take_pending(|sample| {
+            if queue.is_full() {
+                queue.pop_front();
+                *dropped += 1;
+            }
+            // Can't fail: there is room now.
+            let _ = queue.push_back(sample);
+        })?;
+        if overrun {
+            self.overruns += 1;
+        }
+        Ok(())
+    }
+
+    /// The oldest queued reading.
+    pub fn pop(&mut self) -> Option<Xyz> {
+        self.queue.pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Readings dropped because the queue was full.
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    /// Times the sensor itself reported losing readings.
+    pub fn overruns(&self) -> u32 {
+        self.overruns
+    }
+
+    pub fn source_mut(&mut self) -> &mut S {
+        &mut self.source
+    }
+
+    pub fn free(self) -> S {
+        self.source
+    }
+}
@@ -0,0 +1,789 @@
+//! Tests the `sensors` crate off the board, against simulated sensors.
+//!
+//! ```text
+//! cargo test-host -p sensors --features lsm303agr
+//! ```
+//!
+//! The accelerometer calibration tests build an accelerometer with known offsets and gains, turn
+//! it through the six positions with some noise and some wobbling in between, and run the
+//! readings through [`Capture`]. The calibration it finds must match the sensor's errors and
+//! correct readings taken at any angle back to 1 g. A run with a position held the wrong way
+//! round must be refused.
+//!
+//! The FIFO tests decode status bits and raw readings as the accelerometer sends them, and
+//! drain a pretend FIFO over a pretend bus, with and without readings lost to an overrun.
+//!
+//! The gesture tests shake, tap, drop and turn over a pretend board at 50 readings a second, as
+//! the dice program reads it, and compare the gestures reported with the ones made.
+//!
+//! The attitude tests rest the board on each face and edge, and tip it nearly onto its end, and
+//! compare the roll, pitch and orientation worked out with the real ones.
+
+use std::collections::VecDeque;
+
+use embedded_hal::i2c::{ErrorKind, ErrorType, I2c, Operation};
+use lsm303agr::{AccelMode, AccelScale};
+use sensors::{
+    accel_cal::{solve, AccelCalibration, Capture, SolveError, FACES},
+    attitude::{AttitudeEstimator, Filter, Orientation},
+    fifo::{AccelFifo, Burst, FifoStatus, Sensitivity, ACCEL_ADDR, FIFO_DEPTH, SAMPLE_LEN},
+    gesture::{Gesture, GestureDetector},
+    sampler::Sampler,
+    Xyz,
+};
+
+/// Readings averaged in each position.
+const SAMPLES: u32 = 50;
+/// How far, in mg, the simulated sensor's noise reaches either way.
+const NOISE_MG: f32 = 8.0;
+
+/// A small linear congruential generator, so every run sees the same noise.
+struct Lcg(u64);
+
+impl Lcg {
+    /// Uniform in `-1.0..1.0`.
+    fn next(&mut self) -> f32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        ((self.0 >> 40) as f32 / (1u64 << 24) as f32) * 2.0 - 1.0
+    }
+}
+
+/// An accelerometer that reads `g / gain + offset` on each axis, plus noise.
+struct Sensor {
+    offset: [f32; 3],
+    gain: [f32; 3],
+    rng: Lcg,
+}
+
+impl Sensor {
+    fn read(&mut self, g: [f32; 3]) -> Xyz {
+        let mut axis = |i: usize| {
+            (g[i] / self.gain[i] + self.offset[i] + self.rng.next() * NOISE_MG).round() as i32
+        };
+        (axis(0), axis(1), axis(2))
+    }
+}
+
+/// The true acceleration, in mg, with the board resting in `face`.
+fn gravity(face: Orientation) -> [f32; 3] {
+    match face {
+        Orientation::FaceUp => [0.0, 0.0, -1000.0],
+        Orientation::FaceDown => [0.0, 0.0, 1000.0],
+        Orientation::LogoUp => [0.0, -1000.0, 0.0],
+        Orientation::LogoDown => [0.0, 1000.0, 0.0],
+        Orientation::LeftUp => [1000.0, 0.0, 0.0],
+        Orientation::RightUp => [-1000.0, 0.0, 0.0],
+    }
+}
+
+/// Turn `sensor` through `faces`, wobbling it on the way into each, and return what was captured.
+fn capture(sensor: &mut Sensor, faces: &[(Orientation, [f32; 3])]) -> Capture {
+    let mut capture = Capture::new(SAMPLES);
+    for &(face, g) in faces {
+        for i in 0..20 {
+            let wobble = 300.0 * (i as f32 * 0.7).sin();
+            capture.update(sensor.read([g[0] + wobble, g[1] - wobble, g[2] + wobble]));
+        }
+        let mut captured = None;
+        for _ in 0..SAMPLES {
+            captured = captured.or(capture.update(sensor.read(g)));
+        }
+        assert_eq!(captured, Some(face), "captured");
+    }
+    capture
+}
+
+/// Calibrate a sensor with `offset` and `gain`, and check that the calibration undoes them.
+fn assert_recovers(offset: [f32; 3], gain: [f32; 3]) {
+    let mut sensor = Sensor {
+        offset,
+        gain,
+        rng: Lcg(0x5eed),
+    };
+    // Visit the positions out of order, the way people do.
+    let order = [5, 2, 0, 4, 1, 3];
+    let faces: Vec<_> = order
+        .iter()
+        .map(|&i| (FACES[i], gravity(FACES[i])))
+        .collect();
+    let calibration = match capture(&mut sensor, &faces).calibration() {
+        Some(Ok(calibration)) => calibration,
+        other => panic!("no calibration: {:?}", other),
+    };
+    for axis in 0..3 {
+        let offset_error = (calibration.offset()[axis] - offset[axis]).abs();
+        let gain_error = (calibration.gain()[axis] / gain[axis] - 1.0).abs();
+        assert!(
+            offset_error <= 3.0 && gain_error <= 0.005,
+            "axis {}: offset {} (want {}), gain {} (want {})",
+            axis,
+            calibration.offset()[axis],
+            offset[axis],
+            calibration.gain()[axis],
+            gain[axis]
+        );
+    }
+
+    // Anywhere on the sphere, a corrected reading at rest should come to 1 g.
+    let mut worst = 0.0f32;
+    for i in 0..200 {
+        let (theta, phi) = (i as f32 * 0.61, i as f32 * 0.17);
+        let g = [
+            1000.0 * theta.cos() * phi.sin(),
+            1000.0 * theta.sin() * phi.sin(),
+            1000.0 * phi.cos(),
+        ];
+        let (x, y, z) = calibration.apply(sensor.read(g));
+        let magnitude = ((x * x + y * y + z * z) as f32).sqrt();
+        worst = worst.max((magnitude - 1000.0).abs());
+    }
+    assert!(
+        worst <= 2.0 * NOISE_MG,
+        "corrected readings up to {:.1} mg from 1 g",
+        worst
+    );
+
+    let bytes = calibration.to_bytes();
+    assert_eq!(
+        AccelCalibration::from_bytes(&bytes),
+        Some(calibration),
+        "round trip through bytes"
+    );
+}
+
+#[test]
+fn perfect_sensor() {
+    assert_recovers([0.0; 3], [1.0; 3]);
+}
+
+#[test]
+fn typical_sensor() {
+    assert_recovers([40.0, -25.0, 70.0], [0.98, 1.03, 1.01]);
+}
+
+#[test]
+fn poor_sensor() {
+    assert_recovers([-150.0, 120.0, -90.0], [1.12, 0.9, 1.08]);
+}
+
+#[test]
+fn upside_down_refused() {
+    let mut sensor = Sensor {
+        offset: [20.0, -35.0, 60.0],
+        gain: [1.02, 0.97, 1.01],
+        rng: Lcg(0xbad),
+    };
+    let mut capture = capture(&mut sensor, &FACES.map(|face| (face, gravity(face))));
+    // Hold the board face up again but record it as face down, as a confused solver might.
+    let mut averages = FACES.map(|face| capture.average(face).unwrap());
+    averages[1] = averages[0];
+    let solved = solve(&averages);
+    assert!(
+        matches!(solved, Err(SolveError::Span { axis: 2, .. })),
+        "solved anyway: {:?}",
+        solved
+    );
+    capture.forget(Orientation::FaceDown);
+    assert!(
+        capture.calibration().is_none(),
+        "calibrated after forgetting"
+    );
+    assert_eq!(
+        capture.next_face(),
+        Some(Orientation::FaceDown),
+        "next face"
+    );
+}
+
+#[test]
+fn bad_bytes_refused() {
+    let mut bytes = AccelCalibration::default().to_bytes();
+    assert_eq!(AccelCalibration::from_bytes(&bytes[..20]), None, "short");
+    bytes[12..16].copy_from_slice(&f32::NAN.to_le_bytes());
+    assert_eq!(AccelCalibration::from_bytes(&bytes), None, "NaN gain");
+}
+
+#[test]
+fn fifo_status() {
+    let status = |len, overrun| FifoStatus { len, overrun };
+    let cases = [
+        // EMPTY set.
+        (0b0010_0000, status(0, false)),
+        (0b0000_0001, status(1, false)),
+        // WTM set: the watermark doesn't change how many there are.
+        (0b1001_0000, status(16, false)),
+        (0b0001_1111, status(31, false)),
+        // OVRN set: the FIFO is full, whatever FSS says.
+        (0b0101_1111, status(FIFO_DEPTH, true)),
+        (0b1100_0000, status(FIFO_DEPTH, true)),
+    ];
+    for (bits, expected) in cases {
+        assert_eq!(FifoStatus::from_bits(bits), expected, "{:08b}", bits);
+    }
+}
+
+/// A reading of `digits` at the resolution of a `bits`-bit mode, left-justified as the
+/// accelerometer sends it.
+fn left_justified(digits: i16, bits: u32) -> [u8; 2] {
+    (digits << (16 - bits)).to_le_bytes()
+}
+
+/// µg per digit at each full scale.
+type Sensitivities = [(AccelScale, i32); 4];
+
+#[test]
+fn sample_decoding() {
+    // µg per digit, from table 3 of the datasheet.
+    let modes: [(AccelMode, u32, Sensitivities); 3] = [
+        (
+            AccelMode::LowPower,
+            8,
+            [
+                (AccelScale::G2, 15_630),
+                (AccelScale::G4, 31_260),
+                (AccelScale::G8, 62_520),
+                (AccelScale::G16, 187_580),
+            ],
+        ),
+        (
+            AccelMode::Normal,
+            10,
+            [
+                (AccelScale::G2, 3_900),
+                (AccelScale::G4, 7_820),
+                (AccelScale::G8, 15_630),
+                (AccelScale::G16, 46_900),
+            ],
+        ),
+        (
+            AccelMode::HighResolution,
+            12,
+            [
+                (AccelScale::G2, 980),
+                (AccelScale::G4, 1_950),
+                (AccelScale::G8, 3_900),
+                (AccelScale::G16, 11_720),
+            ],
+        ),
+    ];
+    for (mode, bits, scales) in modes {
+        for (scale, ug) in scales {
+            let sensitivity = Sensitivity::from_mode(mode, scale);
+            // 1 g, as near as the mode gets, then the most negative and positive readings.
+            let one_g = ((1_000_000 + ug / 2) / ug) as i16;
+            let largest = (1 << (bits - 1)) - 1;
+            let mut raw = [0; SAMPLE_LEN];
+            raw[0..2].copy_from_slice(&left_justified(one_g, bits));
+            raw[2..4].copy_from_slice(&left_justified(-largest - 1, bits));
+            raw[4..6].copy_from_slice(&left_justified(largest, bits));
+            let mg = |digits: i16| digits as i32 * ug / 1000;
+            let what = format!("{:?} {:?}", mode, scale);
+            let got = sensitivity.decode(&raw);
+            assert_eq!(got, (mg(one_g), mg(-largest - 1), mg(largest)), "{}", what);
+            assert!(
+                (got.0 - 1000).abs() <= ug / 2000 + 1,
+                "{}: 1 g reads {} mg",
+                what,
+                got.0
+            );
+        }
+    }
+    // Not quite a 16-bit reading: the bits below the mode's resolution don't count.
+    let sensitivity = Sensitivity::from_mode(AccelMode::Normal, AccelScale::G2);
+    assert_eq!(sensitivity.to_mg(-64 + 0x3f), -3, "low bits");
+    assert_eq!(
+        Sensitivity::from_mode(AccelMode::PowerDown, AccelScale::G2).to_mg(i16::MAX),
+        0,
+        "powered down"
+    );
+}
+
+#[test]
+fn back_to_back_samples() {
+    let sensitivity = Sensitivity::from_mode(AccelMode::HighResolution, AccelScale::G2);
+    let one_g = left_justified(1020, 12);
+    let raw: Vec<u8> = [[0; 2], [0; 2], one_g, one_g, [0; 2], [0; 2]]
+        .concat()
+        .into_iter()
+        // Half a reading more, as if the transfer stopped short.
+        .chain([0; 3])
+        .collect();
+    let mut out = [(0, 0, 0); 4];
+    assert_eq!(sensitivity.decode_all(&raw, &mut out), 2, "count");
+    assert_eq!(&out[..2], &[(0, 0, 999), (999, 0, 0)], "readings");
+    let mut short = [(0, 0, 0); 1];
+    assert_eq!(sensitivity.decode_all(&raw, &mut short), 1, "room for one");
+}
+
+const FIFO_SRC_REG_A: u8 = 0x2f;
+/// `OUT_X_L_A` with the auto-increment bit.
+const OUT_BURST: u8 = 0x28 | 0x80;
+
+/// The accelerometer's FIFO behind a pretend I²C bus.
+#[derive(Default)]
+struct FifoBus {
+    samples: VecDeque<[u8; SAMPLE_LEN]>,
+    overrun: bool,
+    /// Burst reads of the output registers so far.
+    bursts: usize,
+}
+
+impl FifoBus {
+    /// The sensor takes a reading, throwing away the oldest if the FIFO is full.
+    fn sample(&mut self, (x, y, z): (i16, i16, i16)) {
+        if self.samples.len() == FIFO_DEPTH {
+            self.samples.pop_front();
+            self.overrun = true;
+        }
+        let mut raw = [0; SAMPLE_LEN];
+        for (bytes, digits) in raw.chunks_mut(2).zip([x, y, z]) {
+            bytes.copy_from_slice(&left_justified(digits, 12));
+        }
+        self.samples.push_back(raw);
+    }
+
+    fn src(&self) -> u8 {
+        match (self.samples.len(), self.overrun) {
+            (0, _) => 0b0010_0000,
+            (_, true) => 0b0101_1111,
+            (len, false) => len as u8,
+        }
+    }
+}
+
+impl ErrorType for FifoBus {
+    type Error = ErrorKind;
+}
+
+impl I2c for FifoBus {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), ErrorKind> {
+        if address != ACCEL_ADDR {
+            return Err(ErrorKind::Other);
+        }
+        let mut register = None;
+        for operation in operations {
+            match operation {
+                Operation::Write(bytes) => register = bytes.first().copied(),
+                Operation::Read(buf) => match register {
+                    Some(FIFO_SRC_REG_A) => buf.fill(self.src()),
+                    Some(OUT_BURST) => {
+                        for chunk in buf.chunks_mut(SAMPLE_LEN) {
+                            let sample = self.samples.pop_front().ok_or(ErrorKind::Other)?;
+                            chunk.copy_from_slice(&sample);
+                        }
+                        self.overrun = false;
+                        self.bursts += 1;
+                    }
+                    _ => return Err(ErrorKind::Other),
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What a [`FifoBus`] holds: ±2 g high-resolution readings.
+const FIFO_SENSITIVITY: Sensitivity =
+    Sensitivity::from_mode(AccelMode::HighResolution, AccelScale::G2);
+
+fn fifo() -> AccelFifo<FifoBus> {
+    AccelFifo::new(FifoBus::default(), FIFO_SENSITIVITY)
+}
+
+/// The readings `first..first + count`, as [`fill`] has the sensor take them, in mg.
+fn numbered(first: i32, count: usize) -> Vec<Xyz> {
+    (first..first + count as i32)
+        .map(|i| (i * 980 / 1000, -i * 980 / 1000, 1020 * 980 / 1000))
+        .collect()
+}
+
+/// Have the sensor behind `fifo` take `count` numbered readings, starting from `first`.
+fn fill(fifo: &mut AccelFifo<FifoBus>, first: i16, count: usize) {
+    let mut bus = std::mem::replace(fifo, self::fifo()).free();
+    for i in first..first + count as i16 {
+        bus.sample((i, -i, 1020));
+    }
+    *fifo = AccelFifo::new(bus, FIFO_SENSITIVITY);
+}
+
+#[test]
+fn draining_the_fifo() {
+    let mut fifo = fifo();
+    let mut out = [(0, 0, 0); FIFO_DEPTH];
+    fill(&mut fifo, 100, 5);
+    let burst = fifo.drain(&mut out).unwrap();
+    assert_eq!(
+        burst,
+        Burst {
+            len: 5,
+            overrun: false,
+        },
+        "five"
+    );
+    assert_eq!(out[..5].to_vec(), numbered(100, 5), "five readings");
+    // Nothing waiting: no burst read at all.
+    assert_eq!(
+        fifo.drain(&mut out).unwrap(),
+        Burst {
+            len: 0,
+            overrun: false,
+        },
+        "empty"
+    );
+    // 40 readings into 32 places: the first 8 are gone.
+    fill(&mut fifo, 200, 40);
+    let burst = fifo.drain(&mut out).unwrap();
+    assert_eq!(
+        burst,
+        Burst {
+            len: FIFO_DEPTH,
+            overrun: true,
+        },
+        "overrun"
+    );
+    assert_eq!(out.to_vec(), numbered(208, FIFO_DEPTH), "newest 32");
+    assert_eq!(fifo.free().bursts, 2, "bursts");
+}
+
+#[test]
+fn queueing_fifo_bursts() {
+    let mut sampler: Sampler<_, 48> = Sampler::new(fifo());
+    fill(sampler.source_mut(), 0, 16);
+    sampler.service().unwrap();
+    fill(sampler.source_mut(), 16, 16);
+    sampler.service().unwrap();
+    // Overrunning the FIFO, then the queue.
+    fill(sampler.source_mut(), 32, 40);
+    sampler.service().unwrap();
+    assert_eq!(sampler.overruns(), 1, "overruns");
+    assert_eq!(sampler.dropped(), 16, "dropped");
+    assert_eq!(sampler.len(), 48, "queued");
+    let queued: Vec<Xyz> = std::iter::from_fn(|| sampler.pop()).collect();
+    // 0..32 came through; 32..40 were lost in the FIFO and 16..32 dropped from the queue.
+    let expected = [numbered(16, 16), numbered(40, 32)].concat();
+    assert_eq!(queued, expected, "readings");
+    assert!(sampler.is_empty(), "empty");
+}
+
+/// Readings per second, as the dice program takes them: each one is 20 ms.
+const GESTURE_RATE_HZ: u32 = 50;
+/// Lying still with the LEDs up.
+const FACE_UP: Xyz = (0, 0, -1000);
+const FACE_DOWN: Xyz = (0, 0, 1000);
+const ON_EDGE: Xyz = (1000, 0, 0);
+
+/// `count` readings of `reading`.
+fn hold(reading: Xyz, count: usize) -> Vec<Xyz> {
+    vec![reading; count]
+}
+
+/// A jolt along x lasting `count` readings, on a board lying face up.
+fn jolt(count: usize) -> Vec<Xyz> {
+    hold((2000, 0, -1000), count)
+}
+
+/// Shaking along x, turning round every `every` readings, for `count` readings.
+fn shaking(every: usize, count: usize) -> Vec<Xyz> {
+    (0..count)
+        .map(|i| {
+            let x = if (i / every).is_multiple_of(2) {
+                1500
+            } else {
+                -1500
+            };
+            (x, 0, -1000)
+        })
+        .collect()
+}
+
+/// Feed `readings` to a new detector and collect the gestures, with the index of the reading
+/// that brought each one.
+fn gestures(readings: &[Xyz]) -> Vec<(usize, Gesture)> {
+    let mut detector = GestureDetector::new(GESTURE_RATE_HZ);
+    readings
+        .iter()
+        .enumerate()
+        .filter_map(|(i, r)| detector.update(*r).map(|g| (i, g)))
+        .collect()
+}
+
+/// Just the gestures, for when the timing doesn't matter.
+fn names(readings: &[Xyz]) -> Vec<Gesture> {
+    gestures(readings).into_iter().map(|(_, g)| g).collect()
+}
+
+#[test]
+fn orientation() {
+    use Gesture::{FaceDown, FaceUp};
+    // Face up is reported once the board has been still for 200 ms: 10 readings.
+    assert_eq!(gestures(&hold(FACE_UP, 200)), vec![(9, FaceUp)], "resting");
+    // Turning over briefly, or tilting a little, doesn't count.
+    let wobble = [
+        hold(FACE_UP, 20),
+        hold(FACE_DOWN, 5),
+        hold(FACE_UP, 20),
+        hold((300, 0, -950), 30),
+        hold(FACE_UP, 20),
+    ]
+    .concat();
+    assert_eq!(names(&wobble), vec![FaceUp], "wobble");
+    let turned = [hold(FACE_UP, 20), hold(FACE_DOWN, 20), hold(FACE_UP, 20)].concat();
+    assert_eq!(
+        gestures(&turned),
+        vec![(9, FaceUp), (29, FaceDown), (49, FaceUp)],
+        "turned over"
+    );
+    // On its edge for long enough, face up counts again when it comes back.
+    let edge = [hold(FACE_UP, 20), hold(ON_EDGE, 20), hold(FACE_UP, 20)].concat();
+    assert_eq!(names(&edge), vec![FaceUp, FaceUp], "on edge");
+}
+
+#[test]
+fn taps() {
+    let still = hold(FACE_UP, 20);
+    let tap = [still.clone(), jolt(2), hold(FACE_UP, 40)].concat();
+    // The jolt ends with reading 22, and the tap is reported once more than 300 ms have passed
+    // without a second one.
+    assert_eq!(
+        gestures(&tap),
+        vec![(9, Gesture::FaceUp), (22 + 16, Gesture::Tap)],
+        "tap"
+    );
+    let double = [
+        still.clone(),
+        jolt(2),
+        hold(FACE_UP, 8),
+        jolt(1),
+        hold(FACE_UP, 40),
+    ]
+    .concat();
+    assert_eq!(
+        names(&double),
+        vec![Gesture::FaceUp, Gesture::DoubleTap],
+        "double tap"
+    );
+    let apart = [
+        still.clone(),
+        jolt(2),
+        hold(FACE_UP, 20),
+        jolt(2),
+        hold(FACE_UP, 40),
+    ]
+    .concat();
+    assert_eq!(
+        names(&apart),
+        vec![Gesture::FaceUp, Gesture::Tap, Gesture::Tap],
+        "two taps"
+    );
+    // Longer than 60 ms is a push, not a tap.
+    let push = [still, jolt(4), hold(FACE_UP, 40)].concat();
+    assert_eq!(names(&push), vec![Gesture::FaceUp], "push");
+}
+
+#[test]
+fn shakes() {
+    let still = hold(FACE_UP, 20);
+    // Back and forth every 100 ms for a second: one shake, and no taps on the way.
+    let shake = [still.clone(), shaking(5, 50), hold(FACE_UP, 40)].concat();
+    assert_eq!(
+        names(&shake),
+        vec![Gesture::FaceUp, Gesture::Shake],
+        "shake"
+    );
+    // Shaking on and on is still one shake...
+    let long = [still.clone(), shaking(5, 200), hold(FACE_UP, 40)].concat();
+    assert_eq!(
+        names(&long),
+        vec![Gesture::FaceUp, Gesture::Shake],
+        "long shake"
+    );
+    // ...but a second one after a rest is another.
+    let again = [
+        still.clone(),
+        shaking(5, 50),
+        hold(FACE_UP, 60),
+        shaking(5, 50),
+        hold(FACE_UP, 40),
+    ]
+    .concat();
+    assert_eq!(
+        names(&again),
+        vec![Gesture::FaceUp, Gesture::Shake, Gesture::Shake],
+        "shaken again"
+    );
+    // Too slow to be a shake: 400 ms each way. Each turn is long enough not to be a tap either.
+    let slow = [still, shaking(20, 160), hold(FACE_UP, 40)].concat();
+    assert_eq!(names(&slow), vec![Gesture::FaceUp], "slow");
+}
+
+#[test]
+fn free_fall() {
+    let falling = (30, -20, 10);
+    let drop = [hold(FACE_UP, 20), hold(falling, 10), hold(FACE_UP, 20)].concat();
+    // Reported once, 80 ms in.
+    assert_eq!(
+        gestures(&drop)
+            .into_iter()
+            .filter(|(_, g)| *g == Gesture::FreeFall)
+            .collect::<Vec<_>>(),
+        vec![(23, Gesture::FreeFall)],
+        "drop"
+    );
+    let blip = [hold(FACE_UP, 20), hold(falling, 3), hold(FACE_UP, 20)].concat();
+    assert_eq!(names(&blip), vec![Gesture::FaceUp], "blip");
+    let twice = [
+        hold(FACE_UP, 20),
+        hold(falling, 10),
+        hold(FACE_UP, 2),
+        hold(falling, 10),
+        hold(FACE_UP, 20),
+    ]
+    .concat();
+    let falls = names(&twice)
+        .into_iter()
+        .filter(|g| *g == Gesture::FreeFall)
+        .count();
+    assert_eq!(falls, 2, "two drops");
+}
+
+/// Readings per second, as the tilt game takes them.
+const ATTITUDE_RATE_HZ: f32 = 50.0;
+
+/// What the accelerometer reads with the logo edge lowered by `pitch` and then the right edge
+/// by `roll`, in degrees.
+fn tipped(pitch: f32, roll: f32) -> Xyz {
+    let (p, r) = (pitch.to_radians(), roll.to_radians());
+    let level = 1000.0 * p.cos();
+    (
+        (level * r.sin()).round() as i32,
+        (1000.0 * p.sin()).round() as i32,
+        (-level * r.cos()).round() as i32,
+    )
+}
+
+/// An estimator that has had `reading` for a second.
+fn settled(reading: Xyz) -> AttitudeEstimator {
+    let mut attitude = AttitudeEstimator::new(Filter::default());
+    for _ in 0..ATTITUDE_RATE_HZ as usize {
+        attitude.update(reading, 1.0 / ATTITUDE_RATE_HZ);
+    }
+    attitude
+}
+
+#[test]
+fn attitude_at_rest() {
+    for (face, roll, pitch) in [
+        (Orientation::FaceUp, 0.0, 0.0),
+        (Orientation::FaceDown, 180.0, 0.0),
+        (Orientation::LogoUp, 0.0, -90.0),
+        (Orientation::LogoDown, 0.0, 90.0),
+        (Orientation::LeftUp, 90.0, 0.0),
+        (Orientation::RightUp, -90.0, 0.0),
+    ] {
+        let [x, y, z] = gravity(face);
+        let attitude = settled((x as i32, y as i32, z as i32));
+        assert_eq!(attitude.orientation(), Some(face), "{:?}", face);
+        // Upside down, roll is as much 180° as -180°.
+        let got = attitude.roll().unwrap();
+        let got = if roll == 180.0 { got.abs() } else { got };
+        assert!((got - roll).abs() <= 0.01, "{:?} roll: got {}", face, got);
+        let got = attitude.pitch().unwrap();
+        assert!((got - pitch).abs() <= 0.01, "{:?} pitch: got {}", face, got);
+    }
+}
+
+#[test]
+fn attitude_on_end() {
+    // Tipping the logo edge down, or up, towards and onto the vertical: pitch keeps up all the
+    // way, and roll doesn't spin round when there's nothing left to measure it by.
+    for pitch in [80.0, 85.0, 89.0, 89.9, 90.0] {
+        for pitch in [pitch, -pitch] {
+            let attitude = settled(tipped(pitch, 0.0));
+            let got = attitude.pitch().unwrap();
+            assert!(
+                (got - pitch).abs() <= 0.1,
+                "pitched {}: pitch {}",
+                pitch,
+                got
+            );
+            let got = attitude.roll().unwrap();
+            assert!(got.abs() <= 0.01, "pitched {}: roll {}", pitch, got);
+        }
+    }
+    // Rolled as well, roll is still there to be had until the last few degrees.
+    for (pitch, roll) in [(80.0, 30.0), (85.0, -60.0), (-85.0, 120.0), (-80.0, -150.0)] {
+        let attitude = settled(tipped(pitch, roll));
+        let what = format!("pitched {} and rolled {}", pitch, roll);
+        let got = attitude.pitch().unwrap();
+        assert!((got - pitch).abs() <= 0.1, "{}: pitch {}", what, got);
+        let got = attitude.roll().unwrap();
+        assert!((got - roll).abs() <= 1.0, "{}: roll {}", what, got);
+    }
+}
+
+#[test]
+fn attitude_smoothing() {
+    let dt = 1.0 / ATTITUDE_RATE_HZ;
+    let mut attitude = settled(tipped(0.0, 0.0));
+    // Knocks don't move it...
+    for _ in 0..5 {
+        attitude.update((0, 2000, -1000), dt);
+    }
+    let pitch = attitude.pitch().unwrap();
+    assert!(pitch.abs() <= 0.01, "knocked: pitch {}", pitch);
+    // ...but turning does, most of the way in the time constant.
+    let steps = (Filter::default().time_constant_s / dt) as usize;
+    for _ in 0..steps {
+        attitude.update(tipped(0.0, 90.0), dt);
+    }
+    let (x, _, _) = attitude.gravity().unwrap();
+    assert!((x - 630).abs() <= 30, "one time constant on: x {}", x);
+    for _ in 0..ATTITUDE_RATE_HZ as usize {
+        attitude.update(tipped(0.0, 90.0), dt);
+    }
+    let roll = attitude.roll().unwrap();
+    assert!((roll - 90.0).abs() <= 1.0, "a second on: roll {}", roll);
+
+    // Halfway between lying flat and standing on an edge, it stays the way it was.
+    for (from, to) in [(Orientation::FaceUp, 0.0), (Orientation::LeftUp, 90.0)] {
+        let mut attitude = settled(tipped(0.0, to));
+        for _ in 0..ATTITUDE_RATE_HZ as usize {
+            attitude.update(tipped(0.0, 45.0), dt);
+        }
+        assert_eq!(attitude.orientation(), Some(from), "halfway");
+    }
+    let mut attitude = settled(tipped(0.0, 0.0));
+    for _ in 0..ATTITUDE_RATE_HZ as usize {
+        attitude.update(tipped(0.0, 60.0), dt);
+    }
+    assert_eq!(
+        attitude.orientation(),
+        Some(Orientation::LeftUp),
+        "mostly on edge"
+    );
+}
+
+#[test]
+fn attitude_repeated_timestamp() {
+    let dt = 1.0 / ATTITUDE_RATE_HZ;
+    let mut attitude = AttitudeEstimator::new(Filter {
+        time_constant_s: 0.0,
+        ..Filter::default()
+    });
+    attitude.update(tipped(0.0, 0.0), dt);
+    // The same time again: ignored, rather than spoiling the estimate for good.
+    attitude.update(tipped(0.0, 30.0), 0.0);
+    let roll = attitude.roll().unwrap();
+    assert!(roll.abs() <= 0.01, "repeated: roll {}", roll);
+    // With no smoothing, the next reading is taken as it is.
+    attitude.update(tipped(0.0, 30.0), dt);
+    let roll = attitude.roll().unwrap();
+    assert!((roll - 30.0).abs() <= 0.1, "next: roll {}", roll);
+}
@@ -0,0 +1,274 @@
+//! Interrupt-driven UARTE with ring buffers.
+//!
+//! A plain [`UartePort`] only receives while the program is sitting in `read()`, and the
+//! UARTE holds on to just a few bytes by itself, so anything more that arrives while the program
+//! is busy elsewhere is lost. [`BufferedUarte`] instead receives and transmits from the UARTE's
+//! interrupt, one byte at a time in, and in chunks out, through ring buffers of `RX` and `TX`
+//! bytes. Reading and writing only touch the buffers, so they never have to wait for the line.
+//!
+//! The program provides the interrupt handler itself:
+//!
+//! ```ignore
+//! static SERIAL: BufferedUarte<UARTE0, 64, 256> = BufferedUarte::new();
+//!
+//! #[interrupt]
+//! fn UARTE0_UART0() {
+//!     SERIAL.on_interrupt();
+//! }
+//! ```
+
+use core::{
+    cell::RefCell,
+    sync::atomic::{compiler_fence, Ordering::SeqCst},
+};
+
+use cortex_m::interrupt::{free as interrupt_free, Mutex};
+use heapless::Deque;
+use microbit::{
+    hal::uarte::{Instance, Uarte},
+    pac::{self, uarte0::RegisterBlock, UARTE0, UARTE1},
+};
+
+use crate::port::{Port, UartePort};
+
+/// Most bytes handed to the UARTE in one transmission.
+const TX_CHUNK: usize = 32;
+
+/// A UARTE with its own interrupt.
+pub trait Interrupt: Instance {
+    const INTERRUPT: pac::Interrupt;
+}
+
+impl Interrupt for UARTE0 {
+    const INTERRUPT: pac::Interrupt = pac::Interrupt::UARTE0_UART0;
+}
+
+impl Interrupt for UARTE1 {
+    const INTERRUPT: pac::Interrupt = pac::Interrupt::UARTE1;
+}
+
+/// What has gone wrong on a [`BufferedUarte`] since it started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Bytes received with the receive buffer full, and so thrown away.
+    pub rx_overruns: u32,
+    /// Framing, parity, break or overrun errors reported by the UARTE itself.
+    pub rx_errors: u32,
+}
+
+struct State<T: Instance, const RX: usize, const TX: usize> {
+    /// Held only so that nothing else can use the UARTE.
+    _serial: Uarte<T>,
+    rx: Deque<u8, RX>,
+    tx: Deque<u8, TX>,
+    rx_dma: [u8; 1],
+    tx_dma: [u8; TX_CHUNK],
+    sending: bool,
+    stats: Stats,
+}
+
+pub(crate) fn registers<T: Instance>() -> &'static RegisterBlock {
+    // SAFETY: the registers are only touched by a `BufferedUarte` holding the `Uarte`, from
+    // inside a critical section.
+    unsafe { &*T::ptr() }
+}
+
+impl<T: Instance, const RX: usize, const TX: usize> State<T, RX, TX> {
+    /// Receive the next byte into `rx_dma`.
+    fn start_rx(&mut self) {
+        let uarte = registers::<T>();
+        compiler_fence(SeqCst);
+        // SAFETY: `rx_dma` is in RAM, one byte long, and lives in a `static` for as long as the
+        // UARTE runs.
+        uarte
+            .rxd
+            .ptr
+            .write(|w| unsafe { w.ptr().bits(self.rx_dma.as_mut_ptr() as u32) });
+        uarte.rxd.maxcnt.write(|w| unsafe { w.maxcnt().bits(1) });
+        uarte.tasks_startrx.write(|w| unsafe { w.bits(1) });
+    }
+
+    /// Send the next chunk of the transmit buffer, unless a chunk is already going.
+    fn start_tx(&mut self) {
+        let uarte = registers::<T>();
+        if self.sending {
+            return;
+        }
+        let mut len = 0;
+        while len < TX_CHUNK {
+            let Some(b) = self.tx.pop_front() else {
+                break;
+            };
+            self.tx_dma[len] = b;
+            len += 1;
+        }
+        if len == 0 {
+            return;
+        }
+        compiler_fence(SeqCst);
+        // SAFETY: as for `rx_dma`, and `len` is at most `TX_CHUNK`.
+        uarte
+            .txd
+            .ptr
+            .write(|w| unsafe { w.ptr().bits(self.tx_dma.as_ptr() as u32) });
+        uarte
+            .txd
+            .maxcnt
+            .write(|w| unsafe { w.maxcnt().bits(len as _) });
+        uarte.tasks_starttx.write(|w| unsafe { w.bits(1) });
+        self.sending = true;
+    }
+}
+
+/// A UARTE with `RX` and `TX` byte ring buffers fed from its interrupt, shareable as a `static`.
+pub struct BufferedUarte<T: Instance, const RX: usize, const TX: usize> {
+    state: Mutex<RefCell<Option<State<T, RX, TX>>>>,
+}
+
+impl<T: Instance, const RX: usize, const TX: usize> Default for BufferedUarte<T, RX, TX> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Instance, const RX: usize, const TX: usize> BufferedUarte<T, RX, TX> {
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(None)),
+        }
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&mut State<T, RX, TX>) -> R) -> Option<R> {
+        interrupt_free(|cs| self.state.borrow(cs).borrow_mut().as_mut().map(f))
+    }
+
+    /// Call from the UARTE's interrupt handler.
+    pub fn on_interrupt(&self) {
+        self.with(|state| {
+            let uarte = registers::<T>();
+            if uarte.events_error.read().bits() != 0 {
+                uarte.events_error.reset();
+                // Writing the error bits back clears them.
+                let errors = uarte.errorsrc.read().bits();
+                uarte.errorsrc.write(|w| unsafe { w.bits(errors) });
+                state.stats.rx_errors += 1;
+            }
+            if uarte.events_endrx.read().bits() != 0 {
+                uarte.events_endrx.reset();
+                compiler_fence(SeqCst);
+                if uarte.rxd.amount.read().bits() > 0
+                    && state.rx.push_back(state.rx_dma[0]).is_err()
+                {
+                    state.stats.rx_overruns += 1;
+                }
+                // The UARTE keeps anything arriving meanwhile in its own small FIFO.
+                state.start_rx();
+            }
+            if uarte.events_endtx.read().bits() != 0 {
+                uarte.events_endtx.reset();
+                state.sending = false;
+                state.start_tx();
+            }
+        });
+    }
+
+    /// The next received byte, if there is one.
+    pub fn try_read(&self) -> Option<u8> {
+        self.with(|state| state.rx.pop_front()).flatten()
+    }
+
+    /// Queue `byte` for sending. Returns `false`, and drops it, if the transmit buffer is full.
+    pub fn try_write(&self, byte: u8) -> bool {
+        self.with(|state| {
+            let queued = state.tx.push_back(byte).is_ok();
+            state.start_tx();
+            queued
+        })
+        .unwrap_or(false)
+    }
+
+    /// How many received bytes are waiting to be read.
+    pub fn rx_len(&self) -> usize {
+        self.with(|state| state.rx.len()).unwrap_or(0)
+    }
+
+    /// How many more bytes the transmit buffer can take.
+    pub fn tx_room(&self) -> usize {
+        self.with(|state| TX - state.tx.len()).unwrap_or(0)
+    }
+
+    /// Whether everything written has been sent.
+    pub fn is_flushed(&self) -> bool {
+        self.with(|state| state.tx.is_empty() && !state.sending)
+            .unwrap_or(true)
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.with(|state| state.stats).unwrap_or_default()
+    }
+}
+
+impl<T: Interrupt, const RX: usize, const TX: usize> BufferedUarte<T, RX, TX> {
+    /// Take over `serial` and start receiving. The returned port reads and writes through the
+    /// buffers; so can this `BufferedUarte`, from anywhere.
+    pub fn start(&'static self, serial: Uarte<T>) -> UartePort<T> {
+        interrupt_free(|cs| {
+            let mut state = self.state.borrow(cs).borrow_mut();
+            let state = state.insert(State {
+                _serial: serial,
+                rx: Deque::new(),
+                tx: Deque::new(),
+                rx_dma: [0],
+                tx_dma: [0; TX_CHUNK],
+                sending: false,
+                stats: Stats::default(),
+            });
+            let uarte = registers::<T>();
+            uarte.events_endrx.reset();
+            uarte.events_endtx.reset();
+            uarte.events_error.reset();
+            uarte
+                .intenset
+                .write(|w| w.endrx().set().endtx().set().error().set());
+            state.start_rx();
+        });
+        unsafe { pac::NVIC::unmask(T::INTERRUPT) }
+        UartePort(Port::Buffered(self))
+    }
+}
+
+/// The parts of a [`BufferedUarte`] a [`UartePort`] uses, without its buffer sizes.
+pub(crate) trait Buffers {
+    fn try_read(&self) -> Option<u8>;
+    fn try_write(&self, byte: u8) -> bool;
+    fn rx_len(&self) -> usize;
+    fn tx_room(&self) -> usize;
+    fn is_flushed(&self) -> bool;
+    fn stats(&self) -> Stats;
+}
+
+impl<T: Instance, const RX: usize, const TX: usize> Buffers for BufferedUarte<T, RX, TX> {
+    fn try_read(&self) -> Option<u8> {
+        BufferedUarte::try_read(self)
+    }
+
+    fn try_write(&self, byte: u8) -> bool {
+        BufferedUarte::try_write(self, byte)
+    }
+
+    fn rx_len(&self) -> usize {
+        BufferedUarte::rx_len(self)
+    }
+
+    fn tx_room(&self) -> usize {
+        BufferedUarte::tx_room(self)
+    }
+
+    fn is_flushed(&self) -> bool {
+        BufferedUarte::is_flushed(self)
+    }
+
+    fn stats(&self) -> Stats {
+        BufferedUarte::stats(self)
+    }
+}
@@ -1,41 +1,32 @@
 #![no_std]
 
-use core::fmt;
-use embedded_io::{Read, Write};
-use microbit::hal::uarte::{self, Instance, Uarte, UarteRx, UarteTx};
+//! The MB2's serial port, as used throughout the book.
+//!
+//! With the `uarte` feature (on by default), [`UartePort::new`] wraps a UARTE for blocking
+//! byte-at-a-time reads and writes. For a port that keeps receiving while the program is busy,
+//! start a [`BufferedUarte`] instead: it hands back a [`UartePort`] with the same methods, plus
+//! non-blocking [`try_read`](UartePort::try_read) and [`try_write`](UartePort::try_write).
+//!
+//! [`UartePort`] also implements the `embedded-io` traits, and with the `async` feature their
+//! `embedded-io-async` counterparts, so protocol code written against those runs over it
+//! unchanged. The same code can be tried out on a desktop machine over a [`Loopback`].
+//!
+//! Either UARTE will do: `UARTE0` on `board.uart` talks to the computer, and `UARTE1` can be
+//! wired to another serial device through [`edge_pins`]. [`LineSettings`] picks the baud rate and
+//! parity, when the UARTE is made or later with [`UartePort::set_line`], and can be kept in the
+//! `settings` store so they survive a reset. [`UartePort::switch_line`] checks that the other end
+//! followed before they are kept.
 
-#[allow(unused)]
-pub struct UartePort<T: Instance>(UarteTx<T>, UarteRx<T>);
+#[cfg(feature = "uarte")]
+mod buffered;
+mod line;
+mod loopback;
+#[cfg(feature = "uarte")]
+mod port;
 
-impl<T: Instance> UartePort<T> {
-    pub fn new(serial: Uarte<T>) -> UartePort<T> {
-        let tx_buf = cortex_m::singleton!(TX_BUF: [u8; 1] = [0u8; 1]).unwrap();
-        let rx_buf = cortex_m::singleton!(RX_BUF: [u8; 1] = [0u8; 1]).unwrap();
-        let (tx, rx) = serial.split(tx_buf, rx_buf).unwrap();
-        UartePort(tx, rx)
-    }
-}
-
-impl<T: Instance> fmt::Write for UartePort<T> {
-    fn write_str(&mut self, s: &str) -> fmt::Result {
-        self.0.write_str(s)
-    }
-}
-
-impl<T: Instance> UartePort<T> {
-    pub fn write(&mut self, b: u8) -> Result<(), uarte::Error> {
-        self.0.write(&[b])?;
-        Ok(())
-    }
-
-    pub fn flush(&mut self) -> Result<(), uarte::Error> {
-        self.0.flush()
-    }
-
-    #[allow(unused)]
-    pub fn read(&mut self) -> Result<u8, uarte::Error> {
-        let mut buf = [0u8; 1];
-        self.1.read(&mut buf)?;
-        Ok(buf[0])
-    }
-}
+#[cfg(feature = "uarte")]
+pub use buffered::{BufferedUarte, Interrupt, Stats};
+pub use line::{parse_line, LineSettings, Parity, BAUD_RATES};
+pub use loopback::{Loopback, LoopbackFull};
+#[cfg(feature = "uarte")]
+pub use port::{edge_pins, DmaBuffers, UartePort, CONFIRM_SECS};
@@ -0,0 +1,156 @@
+//! Baud rate and parity, chosen at run time and kept in the settings store.
+
+use core::{fmt, str::FromStr};
+
+use shell::{Args, CommandError};
+
+/// The baud rates the nRF52833's UARTEs can run at.
+pub const BAUD_RATES: &[u32] = &[
+    1_200, 2_400, 4_800, 9_600, 14_400, 19_200, 28_800, 31_250, 38_400, 56_000, 57_600, 76_800,
+    115_200, 230_400, 250_000, 460_800, 921_600, 1_000_000,
+];
+
+/// The UARTEs can only send an even parity bit, or none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parity {
+    None,
+    Even,
+}
+
+impl FromStr for Parity {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "none" | "n" => Ok(Parity::None),
+            "even" | "e" => Ok(Parity::Even),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How fast the line runs and whether it has a parity bit. There are always 8 data bits and 1
+/// stop bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineSettings {
+    baud: u32,
+    parity: Parity,
+}
+
+impl Default for LineSettings {
+    /// 115200 baud without parity, which the MB2's interface chip expects unless told otherwise.
+    fn default() -> Self {
+        Self {
+            baud: 115_200,
+            parity: Parity::None,
+        }
+    }
+}
+
+impl LineSettings {
+    /// `None` if `baud` isn't one of [`BAUD_RATES`].
+    pub fn new(baud: u32, parity: Parity) -> Option<Self> {
+        BAUD_RATES.contains(&baud).then_some(Self { baud, parity })
+    }
+
+    pub fn baud(&self) -> u32 {
+        self.baud
+    }
+
+    pub fn parity(&self) -> Parity {
+        self.parity
+    }
+
+    /// The baud rate, little-endian, then 1 for even parity or 0 for none; for
+    /// `settings::keys::SERIAL_LINE` and the like.
+    pub fn to_bytes(&self) -> [u8; 5] {
+        let mut bytes = [0; 5];
+        bytes[..4].copy_from_slice(&self.baud.to_le_bytes());
+        bytes[4] = (self.parity == Parity::Even) as u8;
+        bytes
+    }
+
+    /// Undo [`to_bytes`](Self::to_bytes). `None` if `bytes` don't hold settings the UARTEs can
+    /// use.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes: &[u8; 5] = bytes.try_into().ok()?;
+        let parity = match bytes[4] {
+            0 => Parity::None,
+            1 => Parity::Even,
+            _ => return None,
+        };
+        Self::new(
+            u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            parity,
+        )
+    }
+}
+
+/// The usual shorthand, like `115200 8N1`.
+impl fmt::Display for LineSettings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parity = match self.parity {
+            Parity::None => 'N',
+            Parity::Even => 'E',
+        };
+        write!(f, "{} 8{}1", self.baud, parity)
+    }
+}
+
+/// The arguments to a command showing or changing line settings: `[baud] [none|even]`. `None`
+/// if there are none, meaning show `current`; the parity stays as it is unless given.
+pub fn parse_line(
+    args: &mut Args,
+    current: LineSettings,
+) -> Result<Option<LineSettings>, CommandError> {
+    let Some(baud) = args.parse_optional("[baud]")? else {
+        return Ok(None);
+    };
+    let parity = args
+        .parse_optional("[none|even]")?
+        .unwrap_or(current.parity);
+    args.end()?;
+    LineSettings::new(baud, parity)
+        .map(Some)
+        .ok_or(CommandError::Invalid("[baud]"))
+}
+
+#[cfg(feature = "uarte")]
+mod hal {
+    use microbit::hal::uarte::{Baudrate, Parity as HalParity};
+
+    use super::{LineSettings, Parity};
+
+    impl LineSettings {
+        pub fn baudrate(&self) -> Baudrate {
+            match self.baud {
+                1_200 => Baudrate::BAUD1200,
+                2_400 => Baudrate::BAUD2400,
+                4_800 => Baudrate::BAUD4800,
+                9_600 => Baudrate::BAUD9600,
+                14_400 => Baudrate::BAUD14400,
+                19_200 => Baudrate::BAUD19200,
+                28_800 => Baudrate::BAUD28800,
+                31_250 => Baudrate::BAUD31250,
+                38_400 => Baudrate::BAUD38400,
+                56_000 => Baudrate::BAUD56000,
+                57_600 => Baudrate::BAUD57600,
+                76_800 => Baudrate::BAUD76800,
+                115_200 => Baudrate::BAUD115200,
+                230_400 => Baudrate::BAUD230400,
+                250_000 => Baudrate::BAUD250000,
+                460_800 => Baudrate::BAUD460800,
+                921_600 => Baudrate::BAUD921600,
+                // `new` only lets through the rates above.
+                _ => Baudrate::BAUD1M,
+            }
+        }
+
+        pub fn hal_parity(&self) -> HalParity {
+            match self.parity {
+                Parity::None => HalParity::EXCLUDED,
+                Parity::Even => HalParity::INCLUDED,
+            }
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! An in-memory serial port that reads back whatever was written to it.
+
+use heapless::Deque;
+
+/// Writing to a full [`Loopback`], which nothing else is going to empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoopbackFull;
+
+impl embedded_io::Error for LoopbackFull {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        embedded_io::ErrorKind::OutOfMemory
+    }
+}
+
+/// A serial port with its transmit line wired to its receive line, holding up to `N` bytes in
+/// between.
+///
+/// Code written against the `embedded-io` traits can be run over it on a desktop machine:
+/// [`inject`](Self::inject) what the other end would send, and [`drain`](Self::drain) what was
+/// sent back. Nothing arrives while a read waits, so reading an empty loopback returns 0 bytes,
+/// as at the end of a file, rather than blocking forever.
+#[derive(Debug, Clone, Default)]
+pub struct Loopback<const N: usize> {
+    bytes: Deque<u8, N>,
+}
+
+impl<const N: usize> Loopback<N> {
+    pub const fn new() -> Self {
+        Self {
+            bytes: Deque::new(),
+        }
+    }
+
+    /// Queue `bytes` to be read, as if they had been received. Returns how many fitted.
+    pub fn inject(&mut self, bytes: &[u8]) -> usize {
+        bytes
+            .iter()
+            .take_while(|&&b| self.bytes.push_back(b).is_ok())
+            .count()
+    }
+
+    /// Take everything waiting to be read.
+    pub fn drain(&mut self) -> impl Iterator<Item = u8> + '_ {
+        core::iter::from_fn(|| self.bytes.pop_front())
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+impl<const N: usize> embedded_io::ErrorType for Loopback<N> {
+    type Error = LoopbackFull;
+}
+
+impl<const N: usize> embedded_io::Read for Loopback<N> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let mut len = 0;
+        for (b, byte) in buf.iter_mut().zip(self.drain()) {
+            *b = byte;
+            len += 1;
+        }
+        Ok(len)
+    }
+}
+
+impl<const N: usize> embedded_io::Write for Loopback<N> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        match self.inject(buf) {
+            0 if !buf.is_empty() => Err(LoopbackFull),
+            len => Ok(len),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl<const N: usize> embedded_io::ReadReady for Loopback<N> {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.bytes.is_empty())
+    }
+}
+
+impl<const N: usize> embedded_io::WriteReady for Loopback<N> {
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.bytes.is_full())
+    }
+}
+
+/// Never has to wait, so the same as the blocking traits.
+#[cfg(feature = "async")]
+mod nonblocking {
+    use super::Loopback;
+
+    impl<const N: usize> embedded_io_async::Read for Loopback<N> {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            embedded_io::Read::read(self, buf)
+        }
+    }
+
+    impl<const N: usize> embedded_io_async::Write for Loopback<N> {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            embedded_io::Write::write(self, buf)
+        }
+
+        async fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,334 @@
+//! A UARTE as a byte stream.
+
+use core::fmt;
+use embedded_io::{Read, ReadReady, Write};
+use microbit::{
+    hal::{
+        gpio::{Disconnected, Level, Pin},
+        uarte::{self, Instance, Pins, Uarte, UarteRx, UarteTx},
+    },
+    pac::{UARTE0, UARTE1},
+};
+
+use crate::{
+    buffered::{self, registers, Stats},
+    LineSettings,
+};
+
+/// How long [`UartePort::switch_line`] waits, in seconds, to hear back at the new settings.
+pub const CONFIRM_SECS: u32 = 10;
+
+pub struct UartePort<T: Instance>(pub(crate) Port<T>);
+
+pub(crate) enum Port<T: Instance> {
+    /// Blocking transfers straight to and from the UARTE.
+    Direct(UarteTx<T>, UarteRx<T>),
+    /// Reads and writes through a [`BufferedUarte`](crate::BufferedUarte)'s buffers.
+    Buffered(&'static dyn buffered::Buffers),
+}
+
+/// A UARTE with one-byte DMA buffers of its own, so that each can have an unbuffered
+/// [`UartePort`].
+pub trait DmaBuffers: Instance {
+    /// The transmit and receive buffers, the first time only.
+    fn take_buffers() -> Option<(&'static mut [u8; 1], &'static mut [u8; 1])>;
+}
+
+impl DmaBuffers for UARTE0 {
+    fn take_buffers() -> Option<(&'static mut [u8; 1], &'static mut [u8; 1])> {
+        let tx_buf = cortex_m::singleton!(TX_BUF0: [u8; 1] = [0u8; 1])?;
+        let rx_buf = cortex_m::singleton!(RX_BUF0: [u8; 1] = [0u8; 1])?;
+        Some((tx_buf, rx_buf))
+    }
+}
+
+impl DmaBuffers for UARTE1 {
+    fn take_buffers() -> Option<(&'static mut [u8; 1], &'static mut [u8; 1])> {
+        let tx_buf = cortex_m::singleton!(TX_BUF1: [u8; 1] = [0u8; 1])?;
+        let rx_buf = cortex_m::singleton!(RX_BUF1: [u8; 1] = [0u8; 1])?;
+        Some((tx_buf, rx_buf))
+    }
+}
+
+impl<T: DmaBuffers> UartePort<T> {
+    pub fn new(serial: Uarte<T>) -> UartePort<T> {
+        let (tx_buf, rx_buf) = T::take_buffers().unwrap();
+        let (tx, rx) = serial.split(tx_buf, rx_buf).unwrap();
+        UartePort(Port::Direct(tx, rx))
+    }
+}
+
+/// Serial pins on two of the edge connector's pins, such as `board.edge.e00.degrade()`, for
+/// talking to something other than the computer on `UARTE1`. `txd` goes to the other side's
+/// receive pin and `rxd` to its transmit pin.
+pub fn edge_pins(txd: Pin<Disconnected>, rxd: Pin<Disconnected>) -> Pins {
+    Pins {
+        // The line idles high.
+        txd: txd.into_push_pull_output(Level::High),
+        rxd: rxd.into_floating_input(),
+        cts: None,
+        rts: None,
+    }
+}
+
+impl<T: Instance> fmt::Write for UartePort<T> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match &mut self.0 {
+            Port::Direct(tx, _) => tx.write_str(s),
+            Port::Buffered(_) => s
+                .bytes()
+                .try_for_each(|b| self.write(b))
+                .map_err(|_| fmt::Error),
+        }
+    }
+}
+
+impl<T: Instance> UartePort<T> {
+    /// Send `b`, waiting for room in the transmit buffer if it is buffered.
+    pub fn write(&mut self, b: u8) -> Result<(), uarte::Error> {
+        match &mut self.0 {
+            Port::Direct(tx, _) => {
+                tx.write(&[b])?;
+            }
+            Port::Buffered(port) => {
+                while !port.try_write(b) {
+                    core::hint::spin_loop();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Queue `b` for sending if there is room, returning whether there was. An unbuffered port
+    /// always has room, but may block.
+    pub fn try_write(&mut self, b: u8) -> Result<bool, uarte::Error> {
+        match &mut self.0 {
+            Port::Direct(..) => self.write(b).map(|()| true),
+            Port::Buffered(port) => Ok(port.try_write(b)),
+        }
+    }
+
+    pub fn flush(&mut self) -> Result<(), uarte::Error> {
+        match &mut self.0 {
+            Port::Direct(tx, _) => tx.flush(),
+            Port::Buffered(port) => {
+                while !port.is_flushed() {
+                    core::hint::spin_loop();
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Send everything written so far, then switch to `line`. Whatever is at the other end has to
+    /// switch too before the two can understand each other again.
+    pub fn set_line(&mut self, line: &LineSettings) -> Result<(), uarte::Error> {
+        self.flush()?;
+        // The last byte can still be on its way out once the UARTE says it is done: wait long
+        // enough for it to go even at 1200 baud.
+        cortex_m::asm::delay(64_000_000 / 100);
+        let uarte = registers::<T>();
+        uarte
+            .baudrate
+            .write(|w| w.baudrate().variant(line.baudrate()));
+        uarte
+            .config
+            .modify(|_, w| w.parity().variant(line.hal_parity()));
+        Ok(())
+    }
+
+    /// Switch from `from` to `to`, asking for `y` to be typed at the new settings within
+    /// [`CONFIRM_SECS`]. Without it, switch back, so that a terminal which can't follow isn't
+    /// left unable to reach the board. Returns whether `to` was kept.
+    ///
+    /// Only save `to` once this has returned `true`.
+    pub fn switch_line(
+        &mut self,
+        from: &LineSettings,
+        to: &LineSettings,
+    ) -> Result<bool, uarte::Error> {
+        write!(
+            self,
+            "switching to {}: change the terminal to match and press y within {} s\r\n",
+            to, CONFIRM_SECS
+        )
+        .map_err(|_| uarte::Error::Transmit)?;
+        self.set_line(to)?;
+        // Anything else that arrives, or a byte that can't be made out, is most likely the
+        // terminal still at the old settings.
+        for _ in 0..CONFIRM_SECS * 100 {
+            if let Ok(Some(b'y' | b'Y')) = self.try_read() {
+                write!(self, "\r\nkeeping {}\r\n", to).map_err(|_| uarte::Error::Transmit)?;
+                return Ok(true);
+            }
+            cortex_m::asm::delay(64_000_000 / 100);
+        }
+        self.set_line(from)?;
+        write!(self, "no answer, so back to {}\r\n", from).map_err(|_| uarte::Error::Transmit)?;
+        Ok(false)
+    }
+
+    /// Whether a received byte is waiting, so that `read()` will not block.
+    pub fn read_ready(&mut self) -> Result<bool, uarte::Error> {
+        match &mut self.0 {
+            Port::Direct(_, rx) => rx.read_ready(),
+            Port::Buffered(port) => Ok(port.rx_len() > 0),
+        }
+    }
+
+    /// Wait for a byte and return it.
+    pub fn read(&mut self) -> Result<u8, uarte::Error> {
+        match &mut self.0 {
+            Port::Direct(_, rx) => {
+                let mut buf = [0u8; 1];
+                rx.read(&mut buf)?;
+                Ok(buf[0])
+            }
+            Port::Buffered(port) => loop {
+                if let Some(b) = port.try_read() {
+                    return Ok(b);
+                }
+                core::hint::spin_loop();
+            },
+        }
+    }
+
+    /// The next received byte, if there is one.
+    pub fn try_read(&mut self) -> Result<Option<u8>, uarte::Error> {
+        if self.read_ready()? {
+            self.read().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Whether everything written has been sent. An unbuffered port sends as it goes.
+    pub fn is_flushed(&self) -> bool {
+        match &self.0 {
+            Port::Direct(..) => true,
+            Port::Buffered(port) => port.is_flushed(),
+        }
+    }
+
+    /// Bytes lost and line errors so far. An unbuffered port doesn't count them, and always
+    /// reports none.
+    pub fn stats(&self) -> Stats {
+        match &self.0 {
+            Port::Direct(..) => Stats::default(),
+            Port::Buffered(port) => port.stats(),
+        }
+    }
+}
+
+impl<T: Instance> embedded_io::ErrorType for UartePort<T> {
+    type Error = uarte::Error;
+}
+
+/// Waits for at least one byte, then returns as many as are waiting.
+impl<T: Instance> embedded_io::Read for UartePort<T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let Some((first, rest)) = buf.split_first_mut() else {
+            return Ok(0);
+        };
+        *first = UartePort::read(self)?;
+        let mut len = 1;
+        for b in rest {
+            let Some(next) = self.try_read()? else {
+                break;
+            };
+            *b = next;
+            len += 1;
+        }
+        Ok(len)
+    }
+}
+
+/// Waits for room for at least one byte, then queues as many as there is room for.
+impl<T: Instance> embedded_io::Write for UartePort<T> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let Some((&first, rest)) = buf.split_first() else {
+            return Ok(0);
+        };
+        UartePort::write(self, first)?;
+        let mut len = 1;
+        for &b in rest {
+            if !self.try_write(b)? {
+                break;
+            }
+            len += 1;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        UartePort::flush(self)
+    }
+}
+
+impl<T: Instance> embedded_io::ReadReady for UartePort<T> {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        UartePort::read_ready(self)
+    }
+}
+
+impl<T: Instance> embedded_io::WriteReady for UartePort<T> {
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        match &mut self.0 {
+            Port::Direct(tx, _) => tx.write_ready(),
+            Port::Buffered(port) => Ok(port.tx_room() > 0),
+        }
+    }
+}
+
+/// The same as the blocking traits, except that instead of spinning while they wait they yield,
+/// so other tasks can run. There is no interrupt to wake them, so they are polled again at once.
+#[cfg(feature = "async")]
+mod nonblocking {
+    use core::{future::poll_fn, task::Poll};
+
+    use embedded_io::WriteReady;
+    use microbit::hal::uarte::{Error, Instance};
+
+    use super::UartePort;
+
+    /// Yield to the executor until `ready` says to go ahead.
+    async fn wait<T: Instance>(
+        port: &mut UartePort<T>,
+        mut ready: impl FnMut(&mut UartePort<T>) -> Result<bool, Error>,
+    ) -> Result<(), Error> {
+        poll_fn(|cx| match ready(port) {
+            Ok(false) => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+            Ok(true) => Poll::Ready(Ok(())),
+            Err(e) => Poll::Ready(Err(e)),
+        })
+        .await
+    }
+
+    impl<T: Instance> embedded_io_async::Read for UartePort<T> {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            if buf.is_empty() {
+                return Ok(0);
+            }
+            wait(self, |port| port.read_ready()).await?;
+            embedded_io::Read::read(self, buf)
+        }
+    }
+
+    impl<T: Instance> embedded_io_async::Write for UartePort<T> {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            if buf.is_empty() {
+                return Ok(0);
+            }
+            wait(self, |port| port.write_ready()).await?;
+            embedded_io::Write::write(self, buf)
+        }
+
+        async fn flush(&mut self) -> Result<(), Self::Error> {
+            wait(self, |port| Ok(port.is_flushed())).await?;
+            UartePort::flush(self)
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! Tests the `serial-setup` crate's line settings: parsing them from a command, storing them
+//! and showing them.
+//!
+//! ```text
+//! cargo test-host -p serial-setup
+//! ```
+
+use serial_setup::{parse_line, LineSettings, Parity, BAUD_RATES};
+use shell::{Args, CommandError};
+
+fn line(baud: u32, parity: Parity) -> LineSettings {
+    LineSettings::new(baud, parity).unwrap()
+}
+
+#[test]
+fn baud_rates() {
+    assert!(BAUD_RATES
+        .iter()
+        .all(|baud| LineSettings::new(*baud, Parity::Even).is_some()));
+    assert_eq!(LineSettings::new(9601, Parity::None), None);
+    assert_eq!(LineSettings::new(0, Parity::None), None);
+    assert_eq!(LineSettings::default(), line(115_200, Parity::None));
+}
+
+#[test]
+fn line_arguments() {
+    let current = line(115_200, Parity::Even);
+    let cases = [
+        ("", Ok(None)),
+        ("9600", Ok(Some(line(9_600, Parity::Even)))),
+        ("9600 none", Ok(Some(line(9_600, Parity::None)))),
+        ("1000000 n", Ok(Some(line(1_000_000, Parity::None)))),
+        ("57600 e", Ok(Some(line(57_600, Parity::Even)))),
+        ("9601", Err(CommandError::Invalid("[baud]"))),
+        ("fast", Err(CommandError::Invalid("[baud]"))),
+        ("9600 odd", Err(CommandError::Invalid("[none|even]"))),
+        ("9600 none now", Err(CommandError::TooMany)),
+    ];
+    for (args, expected) in cases {
+        assert_eq!(
+            parse_line(&mut Args::new(args), current),
+            expected,
+            "{:?}",
+            args
+        );
+    }
+}
+
+#[test]
+fn stored_settings() {
+    for line in [line(9_600, Parity::None), line(921_600, Parity::Even)] {
+        assert_eq!(LineSettings::from_bytes(&line.to_bytes()), Some(line));
+    }
+    assert_eq!(
+        line(9_600, Parity::Even).to_bytes(),
+        [0x80, 0x25, 0, 0, 1],
+        "9600 8E1"
+    );
+    assert_eq!(
+        LineSettings::from_bytes(&[0x81, 0x25, 0, 0, 1]),
+        None,
+        "bad rate"
+    );
+    assert_eq!(
+        LineSettings::from_bytes(&[0x80, 0x25, 0, 0, 2]),
+        None,
+        "bad parity"
+    );
+    assert_eq!(
+        LineSettings::from_bytes(&[0x80, 0x25, 0, 0]),
+        None,
+        "too short"
+    );
+}
+
+#[test]
+fn display() {
+    assert_eq!(line(115_200, Parity::None).to_string(), "115200 8N1");
+    assert_eq!(line(9_600, Parity::Even).to_string(), "9600 8E1");
+}
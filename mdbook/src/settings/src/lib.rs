@@ -0,0 +1,335 @@
+#![no_std]
+
+//! Small persistent settings store for the MB2.
+//!
+//! Settings live in a flash page as an append-only log of `(key, value)` records: writing a key
+//! appends a new record and reading a key returns the newest one. When the page fills up the live
+//! records are compacted into a second page, which takes over once it is complete; until then the
+//! first is left alone, so a reset part way through loses nothing. The two pages take turns. The
+//! store only needs [`NorFlash`], so it works just as well on a RAM stand-in as on the nRF52833's
+//! NVMC.
+//!
+//! With the `nvmc` feature (on by default) [`flash`] hands out the NVMC driver for the last two
+//! pages of flash, which is where the MB2 programs in this book keep their settings. The
+//! programs are linked with all 512K of flash, so nothing stops one growing into those pages;
+//! instead [`flash`] checks with [`image_end`] that the program stops short of them, and panics if
+//! it doesn't.
+
+use embedded_storage::nor_flash::NorFlash;
+
+#[cfg(feature = "nvmc")]
+mod nvmc;
+#[cfg(feature = "nvmc")]
+pub use nvmc::{flash, image_end, SETTINGS_ADDR, SETTINGS_LEN};
+
+/// Identifies one stored value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key(pub u8);
+
+/// The keys used by the programs in this book. Keep them all here so they can't collide.
+pub mod keys {
+    use super::Key;
+
+    /// Magnetic declination in degrees east of true north, as an `f32`.
+    pub const DECLINATION: Key = Key(0x01);
+    /// Accelerometer offsets and gains, as `sensors::accel_cal::AccelCalibration::to_bytes`.
+    pub const ACCEL_CALIBRATION: Key = Key(0x02);
+    /// Baud rate and parity of the serial port to the computer, as
+    /// `serial_setup::LineSettings::to_bytes`.
+    pub const SERIAL_LINE: Key = Key(0x03);
+    /// Baud rate and parity of the GPS module on the edge connector, as for `SERIAL_LINE`.
+    pub const GPS_LINE: Key = Key(0x04);
+}
+
+/// Largest value that can be stored under one key.
+pub const MAX_VALUE_LEN: usize = 60;
+
+const MAGIC: [u8; 4] = *b"SET2";
+/// A page starts with its generation, counting up with each compaction, then [`MAGIC`].
+const PAGE_HEADER_LEN: u32 = 8;
+const HEADER_LEN: usize = 4;
+const RECORD_MARK: u8 = 0x5a;
+const ERASED: u8 = 0xff;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<E> {
+    Flash(E),
+    /// The value is longer than [`MAX_VALUE_LEN`], or the key is reserved.
+    Invalid,
+    /// Even after compaction there is no room for the value.
+    Full,
+}
+
+impl<E> From<E> for Error<E> {
+    fn from(e: E) -> Self {
+        Error::Flash(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Record {
+    key: u8,
+    len: usize,
+    offset: u32,
+    valid: bool,
+}
+
+impl Record {
+    fn size(&self) -> usize {
+        record_size(self.len)
+    }
+}
+
+fn record_size(len: usize) -> usize {
+    HEADER_LEN + len.next_multiple_of(4)
+}
+
+fn checksum(key: u8, value: &[u8]) -> u8 {
+    let sum = value
+        .iter()
+        .fold(key.wrapping_add(value.len() as u8), |s, b| {
+            s.wrapping_add(*b)
+        });
+    !sum
+}
+
+/// One of the store's two pages, holding the settings.
+#[derive(Debug, Clone, Copy)]
+struct Page {
+    base: u32,
+    generation: u32,
+}
+
+/// A settings store occupying two erase pages of `F`, starting at `offset`.
+pub struct Settings<F> {
+    flash: F,
+    offset: u32,
+}
+
+impl<F: NorFlash> Settings<F> {
+    pub fn new(flash: F, offset: u32) -> Self {
+        assert!(4usize.is_multiple_of(F::WRITE_SIZE));
+        assert!((offset as usize).is_multiple_of(F::ERASE_SIZE));
+        assert!(offset as usize + 2 * F::ERASE_SIZE <= flash.capacity());
+        Self { flash, offset }
+    }
+
+    pub fn free(self) -> F {
+        self.flash
+    }
+
+    /// Copy the value stored under `key` into `buf`, returning its length.
+    pub fn read(&mut self, key: Key, buf: &mut [u8]) -> Result<Option<usize>, Error<F::Error>> {
+        let Some(page) = self.active()? else {
+            return Ok(None);
+        };
+        let Some(record) = self.find(page.base, key.0)? else {
+            return Ok(None);
+        };
+        if record.len == 0 {
+            return Ok(None);
+        }
+        let len = record.len.min(buf.len());
+        self.flash.read(
+            page.base + record.offset + HEADER_LEN as u32,
+            &mut buf[..len],
+        )?;
+        Ok(Some(record.len))
+    }
+
+    /// Store `value` under `key`, replacing any earlier value.
+    pub fn write(&mut self, key: Key, value: &[u8]) -> Result<(), Error<F::Error>> {
+        if key.0 == ERASED || value.len() > MAX_VALUE_LEN {
+            return Err(Error::Invalid);
+        }
+        let mut record = [ERASED; HEADER_LEN + MAX_VALUE_LEN];
+        record[0] = key.0;
+        record[1] = value.len() as u8;
+        record[2] = checksum(key.0, value);
+        record[3] = RECORD_MARK;
+        record[HEADER_LEN..][..value.len()].copy_from_slice(value);
+        let record = &record[..record_size(value.len())];
+
+        if let Some(page) = self.active()? {
+            match self.end(page.base)? {
+                Some(end) if end as usize + record.len() <= F::ERASE_SIZE => {
+                    self.flash.write(page.base + end, record)?;
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+        self.compact(key.0, record)
+    }
+
+    /// Forget the value stored under `key`.
+    pub fn remove(&mut self, key: Key) -> Result<(), Error<F::Error>> {
+        let Some(page) = self.active()? else {
+            return Ok(());
+        };
+        match self.find(page.base, key.0)? {
+            Some(record) if record.len > 0 => self.write(key, &[]),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn read_f32(&mut self, key: Key) -> Result<Option<f32>, Error<F::Error>> {
+        let mut buf = [0u8; 4];
+        Ok(match self.read(key, &mut buf)? {
+            Some(4) => Some(f32::from_le_bytes(buf)),
+            _ => None,
+        })
+    }
+
+    pub fn write_f32(&mut self, key: Key, value: f32) -> Result<(), Error<F::Error>> {
+        self.write(key, &value.to_le_bytes())
+    }
+
+    /// The page holding the settings: of the pages with a whole header, the newer one.
+    fn active(&mut self) -> Result<Option<Page>, Error<F::Error>> {
+        let mut active: Option<Page> = None;
+        for base in [self.offset, self.offset + F::ERASE_SIZE as u32] {
+            let mut header = [0u8; PAGE_HEADER_LEN as usize];
+            self.flash.read(base, &mut header)?;
+            if header[4..] != MAGIC {
+                continue;
+            }
+            let generation = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+            let newer = active.is_none_or(|page| {
+                // Counting on, with wrapping, rather than simply larger.
+                generation.wrapping_sub(page.generation) as i32 > 0
+            });
+            if newer {
+                active = Some(Page { base, generation });
+            }
+        }
+        Ok(active)
+    }
+
+    /// Read the record starting at offset `at` in the page at `base`, or `None` at the end of the
+    /// log.
+    fn record_at(&mut self, base: u32, at: u32) -> Result<Option<Record>, Error<F::Error>> {
+        if at as usize + HEADER_LEN > F::ERASE_SIZE {
+            return Ok(None);
+        }
+        let mut header = [0u8; HEADER_LEN];
+        self.flash.read(base + at, &mut header)?;
+        if header == [ERASED; HEADER_LEN] {
+            return Ok(None);
+        }
+        let len = header[1] as usize;
+        if header[3] != RECORD_MARK || len > MAX_VALUE_LEN {
+            // A torn header: nothing after it can be trusted.
+            return Ok(None);
+        }
+        let mut value = [0u8; MAX_VALUE_LEN];
+        self.flash
+            .read(base + at + HEADER_LEN as u32, &mut value[..len])?;
+        Ok(Some(Record {
+            key: header[0],
+            len,
+            offset: at,
+            valid: header[2] == checksum(header[0], &value[..len]),
+        }))
+    }
+
+    /// Offset just past the last record in the page at `base`, or `None` if what follows isn't
+    /// erased, so the page needs compacting before anything can be appended.
+    fn end(&mut self, base: u32) -> Result<Option<u32>, Error<F::Error>> {
+        let mut at = PAGE_HEADER_LEN;
+        while let Some(record) = self.record_at(base, at)? {
+            at += record.size() as u32;
+        }
+        if at as usize + HEADER_LEN <= F::ERASE_SIZE {
+            let mut header = [0u8; HEADER_LEN];
+            self.flash.read(base + at, &mut header)?;
+            if header != [ERASED; HEADER_LEN] {
+                return Ok(None);
+            }
+        }
+        Ok(Some(at))
+    }
+
+    fn find(&mut self, base: u32, key: u8) -> Result<Option<Record>, Error<F::Error>> {
+        let mut found = None;
+        let mut at = PAGE_HEADER_LEN;
+        while let Some(record) = self.record_at(base, at)? {
+            if record.valid && record.key == key {
+                found = Some(record);
+            }
+            at += record.size() as u32;
+        }
+        Ok(found)
+    }
+
+    /// The first record from `at` on in the page at `base` holding the newest value of a key
+    /// other than `replacing`.
+    fn next_live(
+        &mut self,
+        base: u32,
+        mut at: u32,
+        replacing: u8,
+    ) -> Result<Option<Record>, Error<F::Error>> {
+        while let Some(record) = self.record_at(base, at)? {
+            let live = record.valid
+                && record.key != replacing
+                && record.len > 0
+                && self.find(base, record.key)?.is_some_and(|r| r.offset == at);
+            if live {
+                return Ok(Some(record));
+            }
+            at += record.size() as u32;
+        }
+        Ok(None)
+    }
+
+    /// Copy the newest value of each key other than `replacing` into the other page, followed by
+    /// `record` unless it removes its key, and make that page the active one.
+    ///
+    /// The new page's header is written last, so until the very end the old page stays active
+    /// and untouched: a reset part way through loses only the value being written.
+    fn compact(&mut self, replacing: u8, record: &[u8]) -> Result<(), Error<F::Error>> {
+        let active = self.active()?;
+        let new_len = if record[1] > 0 { record.len() } else { 0 };
+
+        // Make sure it all fits before erasing anything.
+        let mut len = PAGE_HEADER_LEN as usize + new_len;
+        if let Some(page) = active {
+            let mut at = PAGE_HEADER_LEN;
+            while let Some(live) = self.next_live(page.base, at, replacing)? {
+                len += live.size();
+                at = live.offset + live.size() as u32;
+            }
+        }
+        if len > F::ERASE_SIZE {
+            return Err(Error::Full);
+        }
+
+        let target = match active {
+            Some(page) if page.base == self.offset => self.offset + F::ERASE_SIZE as u32,
+            _ => self.offset,
+        };
+        self.flash.erase(target, target + F::ERASE_SIZE as u32)?;
+        let mut end = PAGE_HEADER_LEN;
+        if let Some(page) = active {
+            let mut at = PAGE_HEADER_LEN;
+            while let Some(live) = self.next_live(page.base, at, replacing)? {
+                let mut copy = [0u8; HEADER_LEN + MAX_VALUE_LEN];
+                let copy = &mut copy[..live.size()];
+                self.flash.read(page.base + live.offset, copy)?;
+                self.flash.write(target + end, copy)?;
+                end += live.size() as u32;
+                at = live.offset + live.size() as u32;
+            }
+        }
+        if new_len > 0 {
+            self.flash.write(target + end, record)?;
+        }
+
+        let generation = active.map_or(0, |page| page.generation.wrapping_add(1));
+        self.flash.write(target, &generation.to_le_bytes())?;
+        // The magic goes in after the generation, so a page with it has a whole header.
+        self.flash.write(target + 4, &MAGIC)?;
+        Ok(())
+    }
+}
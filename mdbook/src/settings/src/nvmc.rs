@@ -0,0 +1,39 @@
+use core::ptr::addr_of;
+
+use microbit::hal::nvmc::Nvmc;
+use microbit::pac::NVMC;
+
+/// Address of the flash pages holding the settings: the last two 4K pages of the nRF52833's 512K.
+pub const SETTINGS_ADDR: usize = 0x7_e000;
+/// Size of the two pages.
+pub const SETTINGS_LEN: usize = 2 * 4 * 1024;
+
+extern "C" {
+    // From cortex-m-rt's linker script: where the initial values of `.data` are kept in flash,
+    // and where `.data` is in RAM. Those values are the last thing in the program image.
+    static __sidata: u8;
+    static __sdata: u8;
+    static __edata: u8;
+}
+
+/// The first address in flash past the program image, for checking that a program has left the
+/// flash it keeps data in alone.
+pub fn image_end() -> usize {
+    let data_len = addr_of!(__edata) as usize - addr_of!(__sdata) as usize;
+    addr_of!(__sidata) as usize + data_len
+}
+
+/// Take the NVMC and return a flash driver for the settings pages, for use with
+/// `Settings::new(flash, 0)`. Panics if the program is too big to leave the pages free.
+pub fn flash(nvmc: NVMC) -> Nvmc<NVMC> {
+    // The chip's linker script gives the program all 512K, so nothing else stops it growing
+    // into the settings pages.
+    assert!(
+        image_end() <= SETTINGS_ADDR,
+        "the program runs into the settings pages"
+    );
+    // SAFETY: the settings pages are outside the program image, and `nvmc` is the only NVMC, so
+    // this is the only reference to them.
+    let pages = unsafe { core::slice::from_raw_parts_mut(SETTINGS_ADDR as *mut u8, SETTINGS_LEN) };
+    Nvmc::new(nvmc, pages)
+}
@@ -0,0 +1,347 @@
+//! Tests the settings store against a pretend flash that can lose power at any moment.
+//!
+//! ```text
+//! cargo test-host -p settings
+//! ```
+//!
+//! Each test writes, overwrites and removes settings, sometimes cutting the power part way
+//! through, then opens the store afresh as a reset would and compares what it reads back with
+//! what should have survived.
+
+use embedded_storage::nor_flash::{ErrorType, NorFlash, NorFlashErrorKind, ReadNorFlash};
+use settings::{Error, Key, Settings, MAX_VALUE_LEN};
+
+const PAGE: usize = 4096;
+
+/// Flash in RAM, behaving like the NVMC: erasing sets whole pages to `0xff`, and writing can only
+/// clear bits, a word at a time.
+struct RamFlash {
+    bytes: Vec<u8>,
+    erases: usize,
+    /// Words that can still be written, and pages erased, before the power goes. Once it has gone
+    /// nothing more changes.
+    power: Option<usize>,
+}
+
+impl RamFlash {
+    fn new() -> Self {
+        Self {
+            bytes: vec![0xff; 2 * PAGE],
+            erases: 0,
+            power: None,
+        }
+    }
+
+    /// Use up one word or page of what's left before the power goes, if it hasn't gone already.
+    fn spend(&mut self) -> Result<(), NorFlashErrorKind> {
+        match &mut self.power {
+            Some(0) => Err(NorFlashErrorKind::Other),
+            Some(left) => {
+                *left -= 1;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl ErrorType for RamFlash {
+    type Error = NorFlashErrorKind;
+}
+
+impl ReadNorFlash for RamFlash {
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        let start = offset as usize;
+        let source = self
+            .bytes
+            .get(start..start + bytes.len())
+            .ok_or(NorFlashErrorKind::OutOfBounds)?;
+        bytes.copy_from_slice(source);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.bytes.len()
+    }
+}
+
+impl NorFlash for RamFlash {
+    const WRITE_SIZE: usize = 4;
+    const ERASE_SIZE: usize = PAGE;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        let (from, to) = (from as usize, to as usize);
+        if !from.is_multiple_of(Self::ERASE_SIZE) || !to.is_multiple_of(Self::ERASE_SIZE) {
+            return Err(NorFlashErrorKind::NotAligned);
+        }
+        for page in (from..to).step_by(Self::ERASE_SIZE) {
+            self.spend()?;
+            self.bytes
+                .get_mut(page..page + Self::ERASE_SIZE)
+                .ok_or(NorFlashErrorKind::OutOfBounds)?
+                .fill(0xff);
+            self.erases += 1;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        let start = offset as usize;
+        if !start.is_multiple_of(Self::WRITE_SIZE) || !bytes.len().is_multiple_of(Self::WRITE_SIZE)
+        {
+            return Err(NorFlashErrorKind::NotAligned);
+        }
+        if start + bytes.len() > self.bytes.len() {
+            return Err(NorFlashErrorKind::OutOfBounds);
+        }
+        for (i, word) in bytes.chunks(Self::WRITE_SIZE).enumerate() {
+            self.spend()?;
+            let at = start + i * Self::WRITE_SIZE;
+            for (old, new) in self.bytes[at..at + Self::WRITE_SIZE].iter_mut().zip(word) {
+                *old &= new;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The value stored under `key`, if there is one.
+fn read(settings: &mut Settings<RamFlash>, key: u8) -> Option<Vec<u8>> {
+    let mut buf = [0; MAX_VALUE_LEN];
+    let len = settings.read(Key(key), &mut buf).unwrap()?;
+    Some(buf[..len].to_vec())
+}
+
+/// Open the store afresh on the same flash, as after a reset, with the power back on.
+fn reset(settings: Settings<RamFlash>) -> Settings<RamFlash> {
+    let mut flash = settings.free();
+    flash.power = None;
+    Settings::new(flash, 0)
+}
+
+#[test]
+fn empty_store() {
+    let mut settings = Settings::new(RamFlash::new(), 0);
+    assert_eq!(read(&mut settings, 1), None);
+    assert_eq!(settings.remove(Key(1)), Ok(()));
+    assert_eq!(settings.free().erases, 0);
+}
+
+#[test]
+fn appending() {
+    let mut settings = Settings::new(RamFlash::new(), 0);
+    settings.write(Key(1), b"one").unwrap();
+    settings.write(Key(2), b"").unwrap();
+    settings.write_f32(Key(3), -2.5).unwrap();
+    let mut settings = reset(settings);
+    let mut short = [0; 2];
+    assert_eq!(read(&mut settings, 1), Some(b"one".to_vec()));
+    assert_eq!(read(&mut settings, 2), None);
+    assert_eq!(settings.read_f32(Key(3)), Ok(Some(-2.5)));
+    assert_eq!(settings.read_f32(Key(1)), Ok(None));
+    // The whole length, though only what fits is copied.
+    assert_eq!(settings.read(Key(1), &mut short), Ok(Some(3)));
+    assert_eq!(&short, b"on");
+    assert_eq!(read(&mut settings, 4), None);
+}
+
+#[test]
+fn overwriting_and_removing() {
+    let mut settings = Settings::new(RamFlash::new(), 0);
+    settings.write(Key(1), b"first").unwrap();
+    settings.write(Key(2), b"other").unwrap();
+    settings.write(Key(1), b"second").unwrap();
+    assert_eq!(read(&mut settings, 1), Some(b"second".to_vec()));
+    settings.remove(Key(1)).unwrap();
+    let mut settings = reset(settings);
+    assert_eq!(read(&mut settings, 1), None);
+    assert_eq!(read(&mut settings, 2), Some(b"other".to_vec()));
+    settings.write(Key(1), b"third").unwrap();
+    assert_eq!(read(&mut settings, 1), Some(b"third".to_vec()));
+}
+
+#[test]
+fn invalid_writes() {
+    let mut settings = Settings::new(RamFlash::new(), 0);
+    assert_eq!(
+        settings.write(Key(1), &[0; MAX_VALUE_LEN + 1]),
+        Err(Error::Invalid),
+        "too long"
+    );
+    assert_eq!(
+        settings.write(Key(0xff), b"x"),
+        Err(Error::Invalid),
+        "reserved key"
+    );
+    assert_eq!(settings.write(Key(1), &[7; MAX_VALUE_LEN]), Ok(()));
+}
+
+#[test]
+fn compaction() {
+    let mut settings = Settings::new(RamFlash::new(), 0);
+    settings.write(Key(1), b"kept").unwrap();
+    settings.write(Key(2), b"removed").unwrap();
+    settings.remove(Key(2)).unwrap();
+    // 8 bytes a record, so about four pages' worth.
+    for i in 0..2000u32 {
+        settings.write(Key(3), &i.to_le_bytes()).unwrap();
+    }
+    let mut settings = reset(settings);
+    assert_eq!(read(&mut settings, 1), Some(b"kept".to_vec()));
+    assert_eq!(read(&mut settings, 2), None);
+    assert_eq!(read(&mut settings, 3), Some(1999u32.to_le_bytes().to_vec()));
+    let erases = settings.free().erases;
+    // One to start the first page, then one each time a page fills.
+    assert!((4..=5).contains(&erases), "{} erases", erases);
+}
+
+#[test]
+fn full_store() {
+    let mut settings = Settings::new(RamFlash::new(), 0);
+    let mut key = 0;
+    let full = loop {
+        key += 1;
+        match settings.write(Key(key), &[key; MAX_VALUE_LEN]) {
+            Ok(()) => continue,
+            Err(e) => break e,
+        }
+    };
+    // 64 bytes each after the page's 8-byte header.
+    assert_eq!(full, Error::Full);
+    assert_eq!(key, 64);
+    let mut settings = reset(settings);
+    for key in 1..64 {
+        assert_eq!(read(&mut settings, key), Some(vec![key; MAX_VALUE_LEN]));
+    }
+    // Replacing a value still fits, and so does a shorter one once another is gone.
+    assert_eq!(settings.write(Key(1), &[0; 4]), Ok(()));
+    assert_eq!(settings.remove(Key(2)), Ok(()));
+    assert_eq!(settings.write(Key(64), &[0; 4]), Ok(()));
+}
+
+#[test]
+fn torn_records() {
+    // A 12-byte record takes 4 words: cut the power after each of them.
+    for words in 0..4 {
+        let mut settings = Settings::new(RamFlash::new(), 0);
+        settings.write(Key(1), b"old").unwrap();
+        settings.write(Key(2), b"other").unwrap();
+        let mut flash = settings.free();
+        flash.power = Some(words);
+        let mut settings = Settings::new(flash, 0);
+        let _ = settings.write(Key(1), b"new value");
+        let mut settings = reset(settings);
+        assert_eq!(
+            read(&mut settings, 1),
+            Some(b"old".to_vec()),
+            "{} words",
+            words
+        );
+        assert_eq!(
+            read(&mut settings, 2),
+            Some(b"other".to_vec()),
+            "{} words",
+            words
+        );
+        // The half-written record is skipped over by compacting, not appended after.
+        settings.write(Key(3), b"next").unwrap();
+        assert_eq!(
+            read(&mut settings, 3),
+            Some(b"next".to_vec()),
+            "{} words",
+            words
+        );
+        assert_eq!(
+            read(&mut settings, 2),
+            Some(b"other".to_vec()),
+            "{} words",
+            words
+        );
+    }
+}
+
+/// Write `others`, then key 9 over and over until the next write would have to compact.
+fn fill_to_compaction(others: &[(u8, &[u8])]) -> Settings<RamFlash> {
+    let mut settings = Settings::new(RamFlash::new(), 0);
+    for (key, value) in others {
+        settings.write(Key(*key), value).unwrap();
+    }
+    let mut i = 0u32;
+    loop {
+        let before = settings.free();
+        let erases = before.erases;
+        let saved = before.bytes.clone();
+        settings = Settings::new(before, 0);
+        settings.write(Key(9), &i.to_le_bytes()).unwrap();
+        let mut flash = settings.free();
+        if flash.erases > erases {
+            flash.bytes = saved;
+            flash.erases = erases;
+            return Settings::new(flash, 0);
+        }
+        settings = Settings::new(flash, 0);
+        i += 1;
+    }
+}
+
+#[test]
+fn reset_while_compacting() {
+    let others: &[(u8, &[u8])] = &[(1, b"declination"), (2, &[0xab; MAX_VALUE_LEN]), (3, b"")];
+    let mut power = 0;
+    loop {
+        let mut settings = fill_to_compaction(others);
+        let last = read(&mut settings, 9);
+        let mut flash = settings.free();
+        flash.power = Some(power);
+        let mut settings = Settings::new(flash, 0);
+        let finished = settings.write(Key(9), b"new").is_ok();
+        let mut settings = reset(settings);
+        for (key, value) in others {
+            let expected = (!value.is_empty()).then(|| value.to_vec());
+            assert_eq!(
+                read(&mut settings, *key),
+                expected,
+                "power for {}, key {}",
+                power,
+                key
+            );
+        }
+        let got = read(&mut settings, 9);
+        if finished {
+            assert_eq!(got, Some(b"new".to_vec()));
+            return;
+        }
+        assert!(
+            got == last || got == Some(b"new".to_vec()),
+            "power for {}: got {:?}",
+            power,
+            got
+        );
+        // And the store still works afterwards.
+        settings.write(Key(9), b"again").unwrap();
+        assert_eq!(
+            read(&mut settings, 9),
+            Some(b"again".to_vec()),
+            "power for {}",
+            power
+        );
+        power += 1;
+    }
+}
+
+#[test]
+fn pages_taking_turns() {
+    let mut settings = Settings::new(RamFlash::new(), 0);
+    settings.write(Key(1), b"kept").unwrap();
+    // Many compactions, so the pages take turns again and again.
+    for i in 0..20_000u32 {
+        settings.write(Key(2), &i.to_le_bytes()).unwrap();
+        if i.is_multiple_of(997) {
+            settings = reset(settings);
+            assert_eq!(read(&mut settings, 2), Some(i.to_le_bytes().to_vec()));
+        }
+    }
+    assert_eq!(read(&mut settings, 1), Some(b"kept".to_vec()));
+}
@@ -0,0 +1,181 @@
+//! A table of commands, and running them.
+
+use core::{fmt, str::FromStr};
+
+/// One entry in a command table.
+///
+/// `C` is whatever the commands work on, typically a struct holding the serial port and the
+/// peripherals the commands use.
+pub struct Command<C> {
+    pub name: &'static str,
+    /// The arguments, as shown by [`write_help`], such as `"<reg> [value]"`.
+    pub usage: &'static str,
+    /// What the command does, in a line.
+    pub help: &'static str,
+    pub run: fn(&mut C, &mut Args) -> Result<(), CommandError>,
+}
+
+/// What was wrong with a command's arguments, or why it couldn't do what was asked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandError {
+    /// The named argument is missing.
+    Missing(&'static str),
+    /// The named argument isn't something the command understands.
+    Invalid(&'static str),
+    /// There were more arguments than the command takes.
+    TooMany,
+    /// The command ran but failed.
+    Failed(&'static str),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommandError::Missing(arg) => write!(f, "missing {}", arg),
+            CommandError::Invalid(arg) => write!(f, "invalid {}", arg),
+            CommandError::TooMany => write!(f, "too many arguments"),
+            CommandError::Failed(why) => write!(f, "{}", why),
+        }
+    }
+}
+
+/// Why [`dispatch`] couldn't run a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DispatchError<'a> {
+    /// No command has this name.
+    Unknown(&'a str),
+    /// The command went wrong.
+    Command {
+        name: &'static str,
+        usage: &'static str,
+        error: CommandError,
+    },
+}
+
+impl fmt::Display for DispatchError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DispatchError::Unknown(name) => write!(f, "unknown command `{}`, try `help`", name),
+            DispatchError::Command { name, usage, error } => {
+                write!(f, "{}: {}", name, error)?;
+                if !matches!(error, CommandError::Failed(_)) {
+                    write!(f, " (usage: {} {})", name, usage)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The words of a command line after the command's name.
+///
+/// Words are separated by spaces or tabs. Double quotes group words, spaces and all, into one.
+#[derive(Debug, Clone)]
+pub struct Args<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Args<'a> {
+    pub fn new(line: &'a str) -> Self {
+        Self { rest: line }
+    }
+
+    /// The next word, named `name` in the error if there isn't one.
+    pub fn next_arg(&mut self, name: &'static str) -> Result<&'a str, CommandError> {
+        self.next().ok_or(CommandError::Missing(name))
+    }
+
+    /// The next word, as a `T`.
+    pub fn parse<T: FromStr>(&mut self, name: &'static str) -> Result<T, CommandError> {
+        self.next_arg(name)?
+            .parse()
+            .map_err(|_| CommandError::Invalid(name))
+    }
+
+    /// The next word as a `T`, or `None` if there are no more.
+    pub fn parse_optional<T: FromStr>(
+        &mut self,
+        name: &'static str,
+    ) -> Result<Option<T>, CommandError> {
+        match self.next() {
+            None => Ok(None),
+            Some(word) => word
+                .parse()
+                .map(Some)
+                .map_err(|_| CommandError::Invalid(name)),
+        }
+    }
+
+    /// Check that every word has been used.
+    pub fn end(&mut self) -> Result<(), CommandError> {
+        match self.next() {
+            None => Ok(()),
+            Some(_) => Err(CommandError::TooMany),
+        }
+    }
+}
+
+impl<'a> Iterator for Args<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let rest = self.rest.trim_start_matches([' ', '\t']);
+        if rest.is_empty() {
+            self.rest = rest;
+            return None;
+        }
+        let (word, rest) = match rest.strip_prefix('"') {
+            // An unclosed quote runs to the end of the line.
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => rest.split_once([' ', '\t']).unwrap_or((rest, "")),
+        };
+        self.rest = rest;
+        Some(word)
+    }
+}
+
+/// Run the command `line` names from `commands`, handing it the rest of the line. A blank line
+/// does nothing.
+pub fn dispatch<'a, C>(
+    commands: &[Command<C>],
+    line: &'a str,
+    context: &mut C,
+) -> Result<(), DispatchError<'a>> {
+    let mut args = Args::new(line);
+    let Some(name) = args.next() else {
+        return Ok(());
+    };
+    let command = commands
+        .iter()
+        .find(|c| c.name == name)
+        .ok_or(DispatchError::Unknown(name))?;
+    (command.run)(context, &mut args).map_err(|error| DispatchError::Command {
+        name: command.name,
+        usage: command.usage,
+        error,
+    })
+}
+
+/// List `commands` with their usage and help, one to a line.
+pub fn write_help<C, W: embedded_io::Write>(
+    commands: &[Command<C>],
+    out: &mut W,
+) -> Result<(), W::Error> {
+    let width = commands
+        .iter()
+        .map(|c| c.name.len() + 1 + c.usage.len())
+        .max()
+        .unwrap_or(0);
+    for command in commands {
+        let used = command.name.len() + 1 + command.usage.len();
+        out.write_all(command.name.as_bytes())?;
+        out.write_all(b" ")?;
+        out.write_all(command.usage.as_bytes())?;
+        for _ in used..width + 2 {
+            out.write_all(b" ")?;
+        }
+        out.write_all(command.help.as_bytes())?;
+        out.write_all(b"\r\n")?;
+    }
+    Ok(())
+}
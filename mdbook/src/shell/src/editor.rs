@@ -0,0 +1,212 @@
+//! Line editing, the way a terminal user expects it.
+
+use embedded_io::Write;
+use heapless::{Deque, Vec};
+
+const CTRL_C: u8 = 0x03;
+const BACKSPACE: u8 = 0x08;
+const TAB: u8 = b'\t';
+const CTRL_U: u8 = 0x15;
+const ESCAPE: u8 = 0x1b;
+const DELETE: u8 = 0x7f;
+const BELL: &[u8] = b"\x07";
+/// Back to the start of the line, and clear it.
+const CLEAR_LINE: &[u8] = b"\r\x1b[K";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Normal,
+    /// After an escape.
+    Escape,
+    /// Inside an escape sequence, such as the `ESC [ A` an up arrow sends.
+    Sequence,
+}
+
+/// Collects a line of up to `N` bytes at a time, remembering the last `H` lines (at least one).
+pub struct Editor<const N: usize, const H: usize> {
+    prompt: &'static str,
+    line: Vec<u8, N>,
+    /// Whether `line` was finished by the last byte, and should be cleared by the next.
+    finished: bool,
+    /// Whether the last byte was a carriage return, so that a line feed after it isn't another
+    /// line.
+    after_cr: bool,
+    state: State,
+    history: Deque<Vec<u8, N>, H>,
+    /// How many lines back through the history `line` came from; 0 while typing a new line.
+    recalled: usize,
+    /// The new line, put aside while looking through the history.
+    draft: Vec<u8, N>,
+}
+
+impl<const N: usize, const H: usize> Editor<N, H> {
+    pub const fn new(prompt: &'static str) -> Self {
+        Self {
+            prompt,
+            line: Vec::new(),
+            finished: false,
+            after_cr: false,
+            state: State::Normal,
+            history: Deque::new(),
+            recalled: 0,
+            draft: Vec::new(),
+        }
+    }
+
+    /// Show the prompt, ready for a new line.
+    pub fn prompt<W: Write>(&self, out: &mut W) -> Result<(), W::Error> {
+        out.write_all(self.prompt.as_bytes())
+    }
+
+    /// Handle a byte from the terminal, echoing whatever it changes to `out`. Returns the line
+    /// once enter is pressed; the next call starts a new one, but doesn't show the prompt.
+    ///
+    /// Tab completes the first word of the line from `names`.
+    pub fn feed<'n, W, I>(
+        &mut self,
+        byte: u8,
+        names: I,
+        out: &mut W,
+    ) -> Result<Option<&str>, W::Error>
+    where
+        W: Write,
+        I: IntoIterator<Item = &'n str>,
+        I::IntoIter: Clone,
+    {
+        if self.finished {
+            self.finished = false;
+            self.line.clear();
+        }
+        let after_cr = core::mem::replace(&mut self.after_cr, byte == b'\r');
+
+        match (self.state, byte) {
+            (State::Normal, ESCAPE) => self.state = State::Escape,
+            (State::Escape, b'[' | b'O') => self.state = State::Sequence,
+            (State::Escape, _) => self.state = State::Normal,
+            // Parameters, as in `ESC [ 1 ; 5 A`.
+            (State::Sequence, 0x20..=0x3f) => {}
+            (State::Sequence, _) => {
+                self.state = State::Normal;
+                match byte {
+                    b'A' => self.recall(1, out)?,
+                    b'B' => self.recall(-1, out)?,
+                    _ => {}
+                }
+            }
+            (State::Normal, b'\n') if after_cr => {}
+            (State::Normal, b'\r' | b'\n') => {
+                out.write_all(b"\r\n")?;
+                self.remember();
+                self.finished = true;
+                // Only printable ASCII is ever added.
+                return Ok(Some(core::str::from_utf8(&self.line).unwrap()));
+            }
+            (State::Normal, BACKSPACE | DELETE) => match self.line.pop() {
+                Some(_) => out.write_all(b"\x08 \x08")?,
+                None => out.write_all(BELL)?,
+            },
+            (State::Normal, CTRL_C) => {
+                out.write_all(b"^C\r\n")?;
+                self.line.clear();
+                self.recalled = 0;
+                self.prompt(out)?;
+            }
+            (State::Normal, CTRL_U) => {
+                self.line.clear();
+                self.redraw(out)?;
+            }
+            (State::Normal, TAB) => self.complete(names.into_iter(), out)?,
+            (State::Normal, b' '..=b'~') => match self.line.push(byte) {
+                Ok(()) => out.write_all(&[byte])?,
+                Err(_) => out.write_all(BELL)?,
+            },
+            (State::Normal, _) => {}
+        }
+        Ok(None)
+    }
+
+    fn redraw<W: Write>(&self, out: &mut W) -> Result<(), W::Error> {
+        out.write_all(CLEAR_LINE)?;
+        self.prompt(out)?;
+        out.write_all(&self.line)
+    }
+
+    /// Add the finished line to the history, unless it is blank or the same as the last one.
+    fn remember(&mut self) {
+        self.recalled = 0;
+        if self.line.iter().all(|b| *b == b' ') || self.history.back() == Some(&self.line) {
+            return;
+        }
+        if self.history.is_full() {
+            self.history.pop_front();
+        }
+        // Can't fail: there is room now.
+        let _ = self.history.push_back(self.line.clone());
+    }
+
+    /// Step `by` lines back through the history, or forward if negative.
+    fn recall<W: Write>(&mut self, by: isize, out: &mut W) -> Result<(), W::Error> {
+        let Some(to) = self
+            .recalled
+            .checked_add_signed(by)
+            .filter(|to| *to <= self.history.len())
+        else {
+            return out.write_all(BELL);
+        };
+        if self.recalled == 0 {
+            self.draft = self.line.clone();
+        }
+        self.recalled = to;
+        self.line = match to {
+            0 => self.draft.clone(),
+            _ => self.history.iter().rev().nth(to - 1).unwrap().clone(),
+        };
+        self.redraw(out)
+    }
+
+    /// Complete the command name being typed, as far as `names` agree.
+    fn complete<'n, W: Write>(
+        &mut self,
+        names: impl Iterator<Item = &'n str> + Clone,
+        out: &mut W,
+    ) -> Result<(), W::Error> {
+        if self.line.contains(&b' ') {
+            return out.write_all(BELL);
+        }
+        let typed = core::str::from_utf8(&self.line).unwrap();
+        let mut matches = names.filter(|name| name.starts_with(typed));
+        let Some(first) = matches.clone().next() else {
+            return out.write_all(BELL);
+        };
+        // The longest prefix all the matches share.
+        let common = matches.clone().fold(first, |common, name| {
+            let len = common
+                .bytes()
+                .zip(name.bytes())
+                .take_while(|(a, b)| a == b)
+                .count();
+            &common[..len]
+        });
+
+        let unique = matches.clone().nth(1).is_none();
+        if common.len() > typed.len() || unique {
+            let mut line = Vec::new();
+            let fits = line.extend_from_slice(common.as_bytes()).is_ok()
+                && (!unique || line.push(b' ').is_ok());
+            if !fits {
+                return out.write_all(BELL);
+            }
+            self.line = line;
+            return self.redraw(out);
+        }
+
+        // Nothing more in common: show the choices.
+        out.write_all(b"\r\n")?;
+        for name in matches.by_ref() {
+            out.write_all(name.as_bytes())?;
+            out.write_all(b"  ")?;
+        }
+        out.write_all(b"\r\n")?;
+        self.redraw(out)
+    }
+}
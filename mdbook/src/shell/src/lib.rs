@@ -0,0 +1,17 @@
+#![no_std]
+
+//! A small command shell for the serial port.
+//!
+//! [`Editor`] turns the bytes typed into a terminal into lines, echoing them back as it goes. It
+//! understands backspace, Ctrl-C (abandon the line), Ctrl-U (clear it), the up and down arrows
+//! (step through earlier lines) and tab (complete a command name). [`dispatch`] splits a line into
+//! words and runs the matching [`Command`] from a table, and [`write_help`] lists the table.
+//!
+//! Both only need `embedded-io`'s [`Write`](embedded_io::Write) to talk back, so they work the
+//! same over the MB2's `UartePort` as over an in-memory port on a desktop machine.
+
+mod command;
+mod editor;
+
+pub use command::{dispatch, write_help, Args, Command, CommandError, DispatchError};
+pub use editor::Editor;
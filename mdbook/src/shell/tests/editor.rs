@@ -0,0 +1,230 @@
+//! Byte-stream fixtures for the `shell` crate's line editor and command table.
+//!
+//! ```text
+//! cargo test-host -p shell
+//! ```
+//!
+//! Each fixture types some bytes at an [`Editor`] over an in-memory serial port, running any
+//! finished lines through a small command table, and compares what was echoed back and which
+//! commands ran with what a terminal user should see.
+
+use serial_setup::Loopback;
+use shell::{dispatch, write_help, Args, Command, CommandError, Editor};
+
+/// What the commands saw.
+#[derive(Default)]
+struct Context {
+    ran: Vec<String>,
+}
+
+fn set(context: &mut Context, args: &mut Args) -> Result<(), CommandError> {
+    let name = args.next_arg("<name>")?;
+    let value: i32 = args.parse("<value>")?;
+    args.end()?;
+    context.ran.push(format!("set {}={}", name, value));
+    Ok(())
+}
+
+fn say(context: &mut Context, args: &mut Args) -> Result<(), CommandError> {
+    let words: Vec<_> = args.collect();
+    context.ran.push(format!("say {:?}", words));
+    Ok(())
+}
+
+fn fail(_: &mut Context, _: &mut Args) -> Result<(), CommandError> {
+    Err(CommandError::Failed("sensor not responding"))
+}
+
+const COMMANDS: &[Command<Context>] = &[
+    Command {
+        name: "set",
+        usage: "<name> <value>",
+        help: "set a value",
+        run: set,
+    },
+    Command {
+        name: "say",
+        usage: "[words...]",
+        help: "say something",
+        run: say,
+    },
+    Command {
+        name: "status",
+        usage: "",
+        help: "fail",
+        run: fail,
+    },
+    Command {
+        name: "stats",
+        usage: "",
+        help: "fail",
+        run: fail,
+    },
+];
+
+struct Fixture {
+    name: &'static str,
+    input: &'static [u8],
+    /// Everything echoed back, with `ESC` written as `^[` and the prompt as `> `.
+    output: &'static str,
+    /// What the commands recorded, and the errors reported, in order.
+    ran: &'static [&'static str],
+}
+
+const FIXTURES: &[Fixture] = &[
+    Fixture {
+        name: "typing a command",
+        input: b"set x 5\r",
+        output: "set x 5\r\n> ",
+        ran: &["set x=5"],
+    },
+    Fixture {
+        name: "CRLF is one line",
+        input: b"say hi\r\nsay there\n",
+        output: "say hi\r\n> say there\r\n> ",
+        ran: &["say [\"hi\"]", "say [\"there\"]"],
+    },
+    Fixture {
+        name: "quotes and extra spaces",
+        input: b"say  \"two words\"   three \"unclosed quote\r",
+        output: "say  \"two words\"   three \"unclosed quote\r\n> ",
+        ran: &["say [\"two words\", \"three\", \"unclosed quote\"]"],
+    },
+    Fixture {
+        name: "backspace and delete",
+        input: b"sez\x08\x08ay hi\x7f\x7fho\r\x08",
+        output: "sez\x08 \x08\x08 \x08ay hi\x08 \x08\x08 \x08ho\r\n> \x07",
+        ran: &["say [\"ho\"]"],
+    },
+    Fixture {
+        name: "ctrl-c abandons the line",
+        input: b"set x\x03say ok\r",
+        output: "set x^C\r\n> say ok\r\n> ",
+        ran: &["say [\"ok\"]"],
+    },
+    Fixture {
+        name: "ctrl-u clears the line",
+        input: b"set x\x15say ok\r",
+        output: "set x\r^[[K> say ok\r\n> ",
+        ran: &["say [\"ok\"]"],
+    },
+    Fixture {
+        name: "line too long",
+        input: b"say 0123456789abcdefghijklmnopqrstuvwxyz!!\r",
+        output: "say 0123456789abcdefghijklmnopqrstuvwxyz\x07\x07\r\n> ",
+        ran: &["say [\"0123456789abcdefghijklmnopqrstuvwxyz\"]"],
+    },
+    Fixture {
+        name: "history up and down",
+        input: b"say a\rsay b\rsa\x1b[A\x1b[A\x1b[A\x1b[B\x1b[B\r",
+        output:
+            "say a\r\n> say b\r\n> sa\r^[[K> say b\r^[[K> say a\x07\r^[[K> say b\r^[[K> sa\r\n> ",
+        ran: &[
+            "say [\"a\"]",
+            "say [\"b\"]",
+            "unknown command `sa`, try `help`",
+        ],
+    },
+    Fixture {
+        name: "history skips repeats and blanks",
+        input: b"say a\rsay a\r\r  \r\x1bOA\x1bOA\r",
+        output: "say a\r\n> say a\r\n> \r\n>   \r\n> \r^[[K> say a\x07\r\n> ",
+        ran: &["say [\"a\"]", "say [\"a\"]", "say [\"a\"]"],
+    },
+    Fixture {
+        name: "history forgets the oldest",
+        input: b"say 1\rsay 2\rsay 3\rsay 4\rsay 5\r\x1b[A\x1b[A\x1b[A\x1b[A\x1b[A\r",
+        output: "say 1\r\n> say 2\r\n> say 3\r\n> say 4\r\n> say 5\r\n> \
+                 \r^[[K> say 5\r^[[K> say 4\r^[[K> say 3\r^[[K> say 2\x07\r\n> ",
+        ran: &[
+            "say [\"1\"]",
+            "say [\"2\"]",
+            "say [\"3\"]",
+            "say [\"4\"]",
+            "say [\"5\"]",
+            "say [\"2\"]",
+        ],
+    },
+    Fixture {
+        name: "other escape sequences are ignored",
+        input: b"s\x1b[1;5C\x1b[Day\r",
+        output: "say\r\n> ",
+        ran: &["say []"],
+    },
+    Fixture {
+        name: "tab completes a unique name",
+        input: b"se\t1 2\r",
+        output: "se\r^[[K> set 1 2\r\n> ",
+        ran: &["set 1=2"],
+    },
+    Fixture {
+        name: "tab lists the choices",
+        input: b"s\t",
+        output: "s\r\nset  say  status  stats  \r\n\r^[[K> s",
+        ran: &[],
+    },
+    Fixture {
+        name: "tab extends to the common prefix",
+        input: b"st\t\tu\t\t",
+        output: "st\r^[[K> stat\r\nstatus  stats  \r\n\r^[[K> statu\r^[[K> status \x07",
+        ran: &[],
+    },
+    Fixture {
+        name: "tab with no match, or after the name",
+        input: b"x\tset \t",
+        output: "x\x07set \x07",
+        ran: &[],
+    },
+    Fixture {
+        name: "argument errors show usage",
+        input: b"set x\rset x y\rset x 1 2\rstatus\r",
+        output: "set x\r\n> set x y\r\n> set x 1 2\r\n> status\r\n> ",
+        ran: &[
+            "set: missing <value> (usage: set <name> <value>)",
+            "set: invalid <value> (usage: set <name> <value>)",
+            "set: too many arguments (usage: set <name> <value>)",
+            "status: sensor not responding",
+        ],
+    },
+];
+
+fn printable(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).replace('\x1b', "^[")
+}
+
+fn run(fixture: &Fixture) {
+    let mut editor: Editor<40, 4> = Editor::new("> ");
+    let mut port: Loopback<4096> = Loopback::new();
+    let mut context = Context::default();
+    for &byte in fixture.input {
+        let names = COMMANDS.iter().map(|c| c.name);
+        if let Some(line) = editor.feed(byte, names, &mut port).unwrap() {
+            if let Err(e) = dispatch(COMMANDS, line, &mut context) {
+                context.ran.push(e.to_string());
+            }
+            editor.prompt(&mut port).unwrap();
+        }
+    }
+    let output = printable(&port.drain().collect::<Vec<_>>());
+    assert_eq!(output, fixture.output, "{}: echoed", fixture.name);
+    assert_eq!(context.ran, fixture.ran, "{}: ran", fixture.name);
+}
+
+#[test]
+fn fixtures() {
+    for fixture in FIXTURES {
+        run(fixture);
+    }
+}
+
+#[test]
+fn help() {
+    let mut port: Loopback<4096> = Loopback::new();
+    write_help(COMMANDS, &mut port).unwrap();
+    let help = printable(&port.drain().collect::<Vec<_>>());
+    let expected = "set <name> <value>  set a value\r\n\
+                    say [words...]      say something\r\n\
+                    status              fail\r\n\
+                    stats               fail\r\n";
+    assert_eq!(help, expected);
+}
@@ -0,0 +1,71 @@
+//! Decode the board's telemetry into CSV.
+//!
+//! ```text
+//! telemetry-csv [FILE]
+//! ```
+//!
+//! Reads frames from `FILE`, or standard input if there is none, and writes a CSV row for each
+//! packet to standard output. To read straight from the board, set its serial port to raw mode
+//! at the right speed first, for example on Linux:
+//!
+//! ```text
+//! stty -F /dev/ttyACM0 115200 raw -echo
+//! telemetry-csv /dev/ttyACM0 > readings.csv
+//! ```
+//!
+//! Once the input ends, how many packets were decoded, broken or lost goes to standard error.
+
+use std::{
+    env,
+    fs::File,
+    io::{self, BufWriter, Read, Write},
+    process,
+};
+
+use telemetry_host::{csv, Decoder};
+
+fn run(mut input: impl Read) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    csv::write_header(&mut out)?;
+    let mut decoder = Decoder::new();
+    let mut buf = [0; 256];
+    loop {
+        let n = match input.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        for result in decoder.push_all(&buf[..n]) {
+            match result {
+                Ok(packet) => csv::write_row(&mut out, &packet)?,
+                Err(e) => eprintln!("bad frame: {:?}", e),
+            }
+        }
+        // Keep the output up to date while reading from a live port.
+        out.flush()?;
+    }
+    let stats = decoder.stats();
+    eprintln!(
+        "{} packets, {} bad frames, {} lost",
+        stats.packets, stats.errors, stats.lost
+    );
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.as_slice() {
+        [] => run(io::stdin().lock()),
+        [path] => File::open(path).and_then(run),
+        _ => {
+            eprintln!("usage: telemetry-csv [FILE]");
+            process::exit(2);
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("telemetry-csv: {}", e);
+        process::exit(1);
+    }
+}
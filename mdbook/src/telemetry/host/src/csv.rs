@@ -0,0 +1,31 @@
+//! Packets as CSV, one row each.
+//!
+//! Every message type shares the same columns; the ones a message doesn't use are left empty.
+
+use std::io::{self, Write};
+
+use telemetry::{Button, Message, Packet};
+
+/// The first line of the file.
+pub const HEADER: &str = "seq,t_ms,type,x,y,z,level,button,pressed";
+
+pub fn write_header<W: Write + ?Sized>(out: &mut W) -> io::Result<()> {
+    writeln!(out, "{}", HEADER)
+}
+
+/// Write `packet` as a row.
+pub fn write_row<W: Write + ?Sized>(out: &mut W, packet: &Packet) -> io::Result<()> {
+    write!(out, "{},{},", packet.seq, packet.t_ms)?;
+    match packet.message {
+        Message::Accel { x, y, z } => writeln!(out, "accel,{},{},{},,,", x, y, z),
+        Message::Mag { x, y, z } => writeln!(out, "mag,{},{},{},,,", x, y, z),
+        Message::MicLevel(level) => writeln!(out, "mic,,,,{},,", level),
+        Message::Button { button, pressed } => {
+            let button = match button {
+                Button::A => "A",
+                Button::B => "B",
+            };
+            writeln!(out, "button,,,,,{},{}", button, pressed as u8)
+        }
+    }
+}
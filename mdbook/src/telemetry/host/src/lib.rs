@@ -0,0 +1,82 @@
+//! Decoding the board's telemetry on your computer.
+//!
+//! [`Decoder`] splits a byte stream from the serial port into frames and decodes them, keeping
+//! count of what was lost on the way; [`csv`] writes the packets out for a spreadsheet. The
+//! `telemetry-csv` program puts the two together.
+
+pub mod csv;
+
+pub use telemetry::{Button, DecodeError, Message, Packet};
+
+/// What a [`Decoder`] has seen so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Packets decoded.
+    pub packets: u64,
+    /// Frames that couldn't be decoded.
+    pub errors: u64,
+    /// Packets missing, going by the gaps in the sequence numbers.
+    pub lost: u64,
+}
+
+/// Turns the bytes from the serial port back into packets.
+#[derive(Debug, Default)]
+pub struct Decoder {
+    frame: Vec<u8>,
+    /// Whether the current frame grew longer than any frame can be.
+    overlong: bool,
+    last_seq: Option<u16>,
+    stats: Stats,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take the next byte. Returns the packet, or why it couldn't be decoded, when `byte` ends a
+    /// frame.
+    ///
+    /// If the stream was picked up part way through a frame, the first result is an error.
+    pub fn push(&mut self, byte: u8) -> Option<Result<Packet, DecodeError>> {
+        if byte != 0 {
+            if self.frame.len() < telemetry::MAX_FRAME {
+                self.frame.push(byte);
+            } else {
+                self.overlong = true;
+            }
+            return None;
+        }
+        let overlong = std::mem::take(&mut self.overlong);
+        if self.frame.is_empty() && !overlong {
+            // Nothing between two zeros.
+            return None;
+        }
+        let result = if overlong {
+            Err(DecodeError::Framing)
+        } else {
+            Packet::decode(&self.frame)
+        };
+        self.frame.clear();
+        match &result {
+            Ok(packet) => {
+                if let Some(last) = self.last_seq {
+                    self.stats.lost += packet.seq.wrapping_sub(last.wrapping_add(1)) as u64;
+                }
+                self.last_seq = Some(packet.seq);
+                self.stats.packets += 1;
+            }
+            Err(_) => self.stats.errors += 1,
+        }
+        Some(result)
+    }
+
+    /// Take a run of bytes, returning what each frame they end decodes to.
+    pub fn push_all(&mut self, bytes: &[u8]) -> Vec<Result<Packet, DecodeError>> {
+        bytes.iter().filter_map(|&b| self.push(b)).collect()
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+}
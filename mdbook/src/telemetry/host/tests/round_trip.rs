@@ -0,0 +1,304 @@
+//! Round-trip tests for the `telemetry` protocol and the `telemetry-host` decoder.
+//!
+//! ```text
+//! cargo test
+//! ```
+//!
+//! Each test encodes packets the way the board does, sometimes damages the byte stream the way
+//! a serial line might, and decodes it again on this side, comparing what comes out with what
+//! went in.
+
+use telemetry::{cobs, crc16, Button, DecodeError, Encoder, Message, Packet, MAX_FRAME};
+use telemetry_host::{csv, Decoder, Stats};
+
+/// One of each message, with zeros and extremes in the payloads to exercise the framing.
+const MESSAGES: [Message; 7] = [
+    Message::Accel { x: 0, y: 0, z: 0 },
+    Message::Accel {
+        x: -12,
+        y: 980,
+        z: i16::MIN,
+    },
+    Message::Mag {
+        x: i32::MAX,
+        y: -45_000,
+        z: 256,
+    },
+    Message::MicLevel(0),
+    Message::MicLevel(4095),
+    Message::Button {
+        button: Button::A,
+        pressed: true,
+    },
+    Message::Button {
+        button: Button::B,
+        pressed: false,
+    },
+];
+
+/// Encode `MESSAGES` into one stream, 10 ms apart.
+fn stream(encoder: &mut Encoder) -> (Vec<u8>, Vec<Packet>) {
+    let mut bytes = Vec::new();
+    let mut packets = Vec::new();
+    for (i, message) in MESSAGES.iter().enumerate() {
+        let t_ms = 10 * i as u32;
+        let frame = encoder.encode(t_ms, message);
+        bytes.extend_from_slice(frame.as_bytes());
+        packets.push(Packet {
+            seq: i as u16,
+            t_ms,
+            message: *message,
+        });
+    }
+    (bytes, packets)
+}
+
+#[test]
+fn crc() {
+    // The standard check value for CRC-16/CCITT-FALSE.
+    assert_eq!(crc16(b"123456789"), 0x29b1, "crc of \"123456789\"");
+}
+
+#[test]
+fn cobs_framing() {
+    let run_of = |n: usize| (1..=n).map(|i| i as u8).collect::<Vec<_>>();
+    let cases: [(Vec<u8>, Vec<u8>); 6] = [
+        (vec![], vec![1]),
+        (vec![0], vec![1, 1]),
+        (vec![0, 0], vec![1, 1, 1]),
+        (vec![0x11, 0x22, 0, 0x33], vec![3, 0x11, 0x22, 2, 0x33]),
+        (vec![0x11, 0, 0, 0], vec![2, 0x11, 1, 1, 1]),
+        (run_of(254), [vec![0xff], run_of(254), vec![1]].concat()),
+    ];
+    for (packet, encoded) in cases {
+        let mut out = vec![0; cobs::max_encoded_len(packet.len())];
+        let len = cobs::encode(&packet, &mut out);
+        assert_eq!(&out[..len], &encoded[..], "encoding");
+        let mut back = vec![0; packet.len()];
+        assert_eq!(
+            cobs::decode(&encoded, &mut back),
+            Ok(packet.len()),
+            "decoding"
+        );
+        assert_eq!(&back, &packet, "decoded");
+    }
+    let mut out = [0; 8];
+    assert_eq!(
+        cobs::decode(&[3, 1, 0], &mut out),
+        Err(cobs::Error::Malformed),
+        "zero in frame"
+    );
+    assert_eq!(
+        cobs::decode(&[4, 1, 2], &mut out),
+        Err(cobs::Error::Malformed),
+        "truncated frame"
+    );
+    assert_eq!(
+        cobs::decode(&[3, 1, 2], &mut out[..1]),
+        Err(cobs::Error::TooLong),
+        "too long"
+    );
+}
+
+#[test]
+fn round_trip() {
+    let (bytes, packets) = stream(&mut Encoder::new());
+    assert_eq!(bytes.iter().filter(|b| **b == 0).count(), 7, "zeros");
+    let mut decoder = Decoder::new();
+    let decoded: Vec<_> = decoder.push_all(&bytes);
+    assert_eq!(
+        decoded,
+        packets.into_iter().map(Ok).collect::<Vec<_>>(),
+        "packets"
+    );
+    assert_eq!(
+        decoder.stats(),
+        Stats {
+            packets: 7,
+            errors: 0,
+            lost: 0,
+        },
+        "stats"
+    );
+}
+
+#[test]
+fn largest_frame() {
+    let mut encoder = Encoder::new();
+    let longest = Message::Mag {
+        x: -1,
+        y: -1,
+        z: -1,
+    };
+    assert_eq!(
+        encoder.encode(u32::MAX, &longest).as_bytes().len(),
+        MAX_FRAME,
+        "frame"
+    );
+}
+
+#[test]
+fn byte_at_a_time() {
+    let (bytes, packets) = stream(&mut Encoder::new());
+    let mut decoder = Decoder::new();
+    let mut decoded = Vec::new();
+    for (i, byte) in bytes.iter().enumerate() {
+        if let Some(result) = decoder.push(*byte) {
+            // Only the zero ending a frame finishes a packet.
+            assert_eq!(*byte, 0, "packet finished at byte {}", i);
+            decoded.push(result);
+        }
+    }
+    assert_eq!(
+        decoded,
+        packets.into_iter().map(Ok).collect::<Vec<_>>(),
+        "packets"
+    );
+}
+
+#[test]
+fn corrupted_packet() {
+    let (mut bytes, packets) = stream(&mut Encoder::new());
+    // Flip a bit in the second packet, without making it a zero.
+    let second = bytes.iter().position(|b| *b == 0).unwrap() + 3;
+    bytes[second] ^= 0x40;
+    if bytes[second] == 0 {
+        bytes[second] = 0x40;
+    }
+    let mut decoder = Decoder::new();
+    let decoded = decoder.push_all(&bytes);
+    let mut expected: Vec<_> = packets.into_iter().map(Ok).collect();
+    expected[1] = Err(DecodeError::Crc);
+    assert_eq!(decoded, expected, "packets");
+    assert_eq!(
+        decoder.stats(),
+        Stats {
+            packets: 6,
+            errors: 1,
+            lost: 1,
+        },
+        "stats"
+    );
+}
+
+#[test]
+fn joined_mid_stream() {
+    let (bytes, packets) = stream(&mut Encoder::new());
+    let decoded = Decoder::new().push_all(&bytes[5..]);
+    let mut expected: Vec<_> = packets.into_iter().map(Ok).collect();
+    // The tail of the first frame can't be decoded; everything after it can.
+    expected[0] = Err(decoded[0].unwrap_err());
+    assert_eq!(decoded, expected, "packets");
+}
+
+#[test]
+fn lost_packets_and_wrapping() {
+    let mut encoder = Encoder::new();
+    let mut bytes = Vec::new();
+    let mut seqs = Vec::new();
+    let message = Message::MicLevel(7);
+    for seq in 0..=u16::MAX as u32 + 3 {
+        let frame = encoder.encode(seq, &message);
+        // Drop every packet from 100 to 109, and the one just before the sequence wraps.
+        if (100..110).contains(&seq) || seq == u16::MAX as u32 {
+            continue;
+        }
+        if seq > u16::MAX as u32 - 3 {
+            seqs.push(seq as u16);
+        }
+        bytes.extend_from_slice(frame.as_bytes());
+    }
+    let mut decoder = Decoder::new();
+    let decoded = decoder.push_all(&bytes);
+    let tail: Vec<_> = decoded[decoded.len() - seqs.len()..]
+        .iter()
+        .map(|p| p.unwrap().seq)
+        .collect();
+    assert_eq!(tail, seqs, "sequence numbers");
+    assert_eq!(
+        decoder.stats(),
+        Stats {
+            packets: 65_528,
+            errors: 0,
+            lost: 11,
+        },
+        "stats"
+    );
+}
+
+#[test]
+fn bad_packets_refused() {
+    let mut encoder = Encoder::new();
+    let encoded = encoder.encode(0, &Message::MicLevel(1));
+    let frame = &encoded.as_bytes()[..encoded.as_bytes().len() - 1];
+    let mut packet = [0; 16];
+    let len = cobs::decode(frame, &mut packet).unwrap();
+
+    // Re-encode `packet` after `change`, fixing up the CRC.
+    let reframe = |change: &dyn Fn(&mut Vec<u8>)| {
+        let mut body = packet[..len - 2].to_vec();
+        change(&mut body);
+        let crc = crc16(&body);
+        body.extend_from_slice(&crc.to_le_bytes());
+        let mut out = vec![0; cobs::max_encoded_len(body.len())];
+        let n = cobs::encode(&body, &mut out);
+        Packet::decode(&out[..n])
+    };
+    assert_eq!(
+        reframe(&|p| p[0] = 2),
+        Err(DecodeError::Version(2)),
+        "version 2"
+    );
+    assert_eq!(
+        reframe(&|p| p[1] = 9),
+        Err(DecodeError::UnknownKind(9)),
+        "type 9"
+    );
+    assert_eq!(
+        reframe(&|p| p.push(0)),
+        Err(DecodeError::Length),
+        "long payload"
+    );
+    assert_eq!(
+        reframe(&|p| {
+            p[1] = Message::BUTTON;
+            p[8] = 2;
+        }),
+        Err(DecodeError::Invalid),
+        "button 2"
+    );
+    assert_eq!(Packet::decode(&[3, 1, 1]), Err(DecodeError::Short), "short");
+    assert_eq!(
+        Decoder::new().push_all(
+            &[1; MAX_FRAME + 4]
+                .iter()
+                .chain(&[0])
+                .copied()
+                .collect::<Vec<_>>(),
+        ),
+        vec![Err(DecodeError::Framing)],
+        "overlong"
+    );
+}
+
+#[test]
+fn csv_rows() {
+    let (bytes, _) = stream(&mut Encoder::new());
+    let mut out = Vec::new();
+    csv::write_header(&mut out).unwrap();
+    for packet in Decoder::new().push_all(&bytes) {
+        csv::write_row(&mut out, &packet.unwrap()).unwrap();
+    }
+    assert_eq!(
+        String::from_utf8(out).unwrap().as_str(),
+        "seq,t_ms,type,x,y,z,level,button,pressed\n\
+         0,0,accel,0,0,0,,,\n\
+         1,10,accel,-12,980,-32768,,,\n\
+         2,20,mag,2147483647,-45000,256,,,\n\
+         3,30,mic,,,,0,,\n\
+         4,40,mic,,,,4095,,\n\
+         5,50,button,,,,,A,1\n\
+         6,60,button,,,,,B,0\n",
+        "csv"
+    );
+}
@@ -0,0 +1,71 @@
+//! Consistent Overhead Byte Stuffing.
+//!
+//! COBS rewrites a packet so that it contains no zero bytes, at a cost of one byte in every 254,
+//! plus one. A zero can then mark the end of each frame: a receiver that starts listening part
+//! way through, or loses a byte, is back in step at the next zero.
+//!
+//! Each run of non-zero bytes is sent after a code byte one more than its length; the zero that
+//! ended the run is implied. A code of 0xff means 254 bytes with no zero after them.
+
+/// The most bytes encoding `len` bytes can take, not counting the zero that ends the frame.
+pub const fn max_encoded_len(len: usize) -> usize {
+    len + len / 254 + 1
+}
+
+/// Why a frame couldn't be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The frame contains a zero, or a code runs past its end.
+    Malformed,
+    /// The decoded packet doesn't fit in the output.
+    TooLong,
+}
+
+/// Encode `packet` into `out`, which must hold at least [`max_encoded_len`] bytes. Returns the
+/// length of the encoding.
+pub fn encode(packet: &[u8], out: &mut [u8]) -> usize {
+    let mut code_at = 0;
+    let mut len = 1;
+    let mut code = 1u8;
+    for &byte in packet {
+        if byte != 0 {
+            out[len] = byte;
+            len += 1;
+            code += 1;
+        }
+        if byte == 0 || code == 0xff {
+            out[code_at] = code;
+            code_at = len;
+            len += 1;
+            code = 1;
+        }
+    }
+    out[code_at] = code;
+    len
+}
+
+/// Decode `frame`, without the zero that ended it, into `out`. Returns the packet's length.
+pub fn decode(frame: &[u8], out: &mut [u8]) -> Result<usize, Error> {
+    let mut len = 0;
+    let mut i = 0;
+    while i < frame.len() {
+        let code = frame[i];
+        let run = frame
+            .get(i + 1..i + code as usize)
+            .ok_or(Error::Malformed)?;
+        if code == 0 || run.contains(&0) {
+            return Err(Error::Malformed);
+        }
+        out.get_mut(len..len + run.len())
+            .ok_or(Error::TooLong)?
+            .copy_from_slice(run);
+        len += run.len();
+        i += code as usize;
+        // Every run but the last, and the 254-byte ones, ended in a zero.
+        if code != 0xff && i < frame.len() {
+            *out.get_mut(len).ok_or(Error::TooLong)? = 0;
+            len += 1;
+        }
+    }
+    Ok(len)
+}
@@ -0,0 +1,20 @@
+//! CRC-16/CCITT-FALSE: polynomial 0x1021, starting from 0xffff, no reflection, no final XOR.
+
+const POLY: u16 = 0x1021;
+const INIT: u16 = 0xffff;
+
+/// The CRC of `bytes`.
+pub fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc = INIT;
+    for &byte in bytes {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ POLY
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
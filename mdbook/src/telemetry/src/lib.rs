@@ -0,0 +1,243 @@
+#![no_std]
+
+//! A binary protocol for streaming sensor readings over the serial port.
+//!
+//! Text is easy to read on a terminal, but at hundreds of readings a second it takes too long to
+//! send, and a receiver that starts part way through a line can't tell where the numbers begin.
+//! Instead each reading is sent as a small packet:
+//!
+//! | bytes | field                                                 |
+//! |-------|-------------------------------------------------------|
+//! | 1     | protocol [`VERSION`]                                  |
+//! | 1     | message type, as [`Message::kind`]                    |
+//! | 2     | sequence number, counting up from 0 and wrapping      |
+//! | 4     | milliseconds since the program started                |
+//! | ...   | the message's payload, see [`Message`]                |
+//! | 2     | CRC-16 of everything before it, see [`crc16`]         |
+//!
+//! Numbers are little-endian. The packet is then [COBS](cobs) encoded and followed by a zero
+//! byte, so that a zero always means the end of a packet.
+//!
+//! [`Encoder`] builds the frames on the board; [`Packet::decode`] takes them apart again, and the
+//! `telemetry-host` crate (in `host`) uses it to turn a recording into CSV on your computer.
+
+pub mod cobs;
+mod crc;
+
+pub use crc::crc16;
+
+/// The version of the protocol described here. Decoders refuse packets of any other version.
+pub const VERSION: u8 = 1;
+
+const HEADER_LEN: usize = 8;
+const CRC_LEN: usize = 2;
+/// The longest payload, the magnetometer's.
+const MAX_PAYLOAD: usize = 12;
+/// The longest packet, before encoding.
+pub const MAX_PACKET: usize = HEADER_LEN + MAX_PAYLOAD + CRC_LEN;
+/// The longest frame, encoded and with its closing zero.
+pub const MAX_FRAME: usize = cobs::max_encoded_len(MAX_PACKET) + 1;
+
+/// One of the board's two buttons.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    A,
+    B,
+}
+
+/// What a packet carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    /// An accelerometer reading in mg: three `i16`s.
+    Accel { x: i16, y: i16, z: i16 },
+    /// A magnetometer reading in nT: three `i32`s.
+    Mag { x: i32, y: i32, z: i32 },
+    /// How loud the microphone heard things: the peak-to-peak swing of its samples, a `u16`.
+    MicLevel(u16),
+    /// A button was pressed or let go: the button, 0 for A or 1 for B, then 1 for pressed or 0
+    /// for released.
+    Button { button: Button, pressed: bool },
+}
+
+impl Message {
+    pub const ACCEL: u8 = 0x01;
+    pub const MAG: u8 = 0x02;
+    pub const MIC_LEVEL: u8 = 0x03;
+    pub const BUTTON: u8 = 0x04;
+
+    /// The message type, as sent in the packet.
+    pub fn kind(&self) -> u8 {
+        match self {
+            Message::Accel { .. } => Self::ACCEL,
+            Message::Mag { .. } => Self::MAG,
+            Message::MicLevel(_) => Self::MIC_LEVEL,
+            Message::Button { .. } => Self::BUTTON,
+        }
+    }
+
+    /// Write the payload to the start of `out`, returning its length.
+    fn write_payload(&self, out: &mut [u8]) -> usize {
+        let mut put = |at: usize, bytes: &[u8]| {
+            out[at..at + bytes.len()].copy_from_slice(bytes);
+            at + bytes.len()
+        };
+        match *self {
+            Message::Accel { x, y, z } => {
+                let at = put(0, &x.to_le_bytes());
+                let at = put(at, &y.to_le_bytes());
+                put(at, &z.to_le_bytes())
+            }
+            Message::Mag { x, y, z } => {
+                let at = put(0, &x.to_le_bytes());
+                let at = put(at, &y.to_le_bytes());
+                put(at, &z.to_le_bytes())
+            }
+            Message::MicLevel(level) => put(0, &level.to_le_bytes()),
+            Message::Button { button, pressed } => {
+                put(0, &[(button == Button::B) as u8, pressed as u8])
+            }
+        }
+    }
+
+    /// Read a message of type `kind` from its payload.
+    pub fn from_payload(kind: u8, payload: &[u8]) -> Result<Self, DecodeError> {
+        let i16_at = |i: usize| i16::from_le_bytes([payload[i], payload[i + 1]]);
+        let i32_at = |i: usize| {
+            i32::from_le_bytes([payload[i], payload[i + 1], payload[i + 2], payload[i + 3]])
+        };
+        let expected = match kind {
+            Self::ACCEL => 6,
+            Self::MAG => 12,
+            Self::MIC_LEVEL => 2,
+            Self::BUTTON => 2,
+            _ => return Err(DecodeError::UnknownKind(kind)),
+        };
+        if payload.len() != expected {
+            return Err(DecodeError::Length);
+        }
+        Ok(match kind {
+            Self::ACCEL => Message::Accel {
+                x: i16_at(0),
+                y: i16_at(2),
+                z: i16_at(4),
+            },
+            Self::MAG => Message::Mag {
+                x: i32_at(0),
+                y: i32_at(4),
+                z: i32_at(8),
+            },
+            Self::MIC_LEVEL => Message::MicLevel(u16::from_le_bytes([payload[0], payload[1]])),
+            _ => Message::Button {
+                button: match payload[0] {
+                    0 => Button::A,
+                    1 => Button::B,
+                    _ => return Err(DecodeError::Invalid),
+                },
+                pressed: match payload[1] {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(DecodeError::Invalid),
+                },
+            },
+        })
+    }
+}
+
+/// Why a frame couldn't be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The COBS encoding is broken, or the packet is longer than any this protocol sends.
+    Framing,
+    /// The packet is too short to hold a header and CRC.
+    Short,
+    /// The CRC doesn't match: some of the packet was lost or changed on the way.
+    Crc,
+    /// The packet is from another version of the protocol.
+    Version(u8),
+    /// No message has this type.
+    UnknownKind(u8),
+    /// The payload is the wrong length for its message type.
+    Length,
+    /// The payload holds a value its message type doesn't allow.
+    Invalid,
+}
+
+/// A decoded packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Packet {
+    pub seq: u16,
+    pub t_ms: u32,
+    pub message: Message,
+}
+
+impl Packet {
+    /// Decode a frame, without the zero that ended it.
+    pub fn decode(frame: &[u8]) -> Result<Self, DecodeError> {
+        let mut packet = [0; MAX_PACKET];
+        let len = cobs::decode(frame, &mut packet).map_err(|_| DecodeError::Framing)?;
+        let packet = &packet[..len];
+        if len < HEADER_LEN + CRC_LEN {
+            return Err(DecodeError::Short);
+        }
+        let (body, crc) = packet.split_at(len - CRC_LEN);
+        if crc16(body) != u16::from_le_bytes([crc[0], crc[1]]) {
+            return Err(DecodeError::Crc);
+        }
+        if body[0] != VERSION {
+            return Err(DecodeError::Version(body[0]));
+        }
+        Ok(Packet {
+            seq: u16::from_le_bytes([body[2], body[3]]),
+            t_ms: u32::from_le_bytes([body[4], body[5], body[6], body[7]]),
+            message: Message::from_payload(body[1], &body[HEADER_LEN..])?,
+        })
+    }
+}
+
+/// An encoded frame, ready to send.
+#[derive(Debug, Clone, Copy)]
+pub struct Frame {
+    bytes: [u8; MAX_FRAME],
+    len: usize,
+}
+
+impl Frame {
+    /// The bytes to send, closing zero included.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+/// Numbers packets and encodes them into frames.
+#[derive(Debug, Default)]
+pub struct Encoder {
+    seq: u16,
+}
+
+impl Encoder {
+    pub const fn new() -> Self {
+        Self { seq: 0 }
+    }
+
+    /// Encode `message`, taken `t_ms` milliseconds after the program started, as the next
+    /// packet.
+    pub fn encode(&mut self, t_ms: u32, message: &Message) -> Frame {
+        let mut packet = [0; MAX_PACKET];
+        packet[0] = VERSION;
+        packet[1] = message.kind();
+        packet[2..4].copy_from_slice(&self.seq.to_le_bytes());
+        packet[4..8].copy_from_slice(&t_ms.to_le_bytes());
+        let len = HEADER_LEN + message.write_payload(&mut packet[HEADER_LEN..]);
+        let crc = crc16(&packet[..len]);
+        packet[len..len + CRC_LEN].copy_from_slice(&crc.to_le_bytes());
+        self.seq = self.seq.wrapping_add(1);
+
+        let mut frame = Frame {
+            bytes: [0; MAX_FRAME],
+            len: 0,
+        };
+        // The frame is already zeroed, so the closing zero is in place.
+        frame.len = cobs::encode(&packet[..len + CRC_LEN], &mut frame.bytes) + 1;
+        frame
+    }
+}
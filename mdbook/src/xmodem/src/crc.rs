@@ -0,0 +1,19 @@
+//! CRC-16/XMODEM: polynomial 0x1021, starting from 0, no reflection, no final XOR.
+
+const POLY: u16 = 0x1021;
+
+/// The CRC of `bytes`, as sent after each block.
+pub fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc = 0;
+    for &byte in bytes {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ POLY
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
@@ -0,0 +1,270 @@
+#![no_std]
+
+//! Receiving files over the serial port with XMODEM, straight into flash.
+//!
+//! XMODEM is old, but nearly every terminal program can still send with it (minicom's "Send
+//! files" menu, or `sx` from the lrzsz tools), which makes it an easy way to get a level map, a
+//! melody or a calibration blob onto the board without flashing a new program. The sender splits
+//! the file into numbered blocks and waits for the receiver to answer each one:
+//!
+//! | bytes       | field                                                     |
+//! |-------------|-----------------------------------------------------------|
+//! | 1           | [`SOH`] for a 128-byte block, or [`STX`] for 1024 bytes   |
+//! | 1           | block number, counting up from 1 and wrapping             |
+//! | 1           | the block number with every bit flipped                   |
+//! | 128 or 1024 | data                                                      |
+//! | 2           | CRC-16 of the data, big-endian, see [`crc16`]             |
+//!
+//! The receiver answers [`ACK`] to move on, or [`NAK`] to have the block sent again; [`EOT`]
+//! ends the file. It starts things off by sending `C` every few seconds, which asks for CRCs
+//! rather than the original one-byte checksums; this receiver only understands CRCs. Either side
+//! can give up by sending [`CAN`] twice.
+//!
+//! Blocks are always whole, so the end of the file is padded out, usually with `0x1a`.
+//!
+//! [`Receiver`] doesn't touch the serial port itself: it is handed each byte as it arrives, and
+//! the time, and says what to send back. That way it can be tried out on a desktop machine
+//! against a pretend sender. It writes the file through [`NorFlash`]; with the `nvmc` feature
+//! (on by default) [`flash`] hands out the NVMC driver for the MB2's file area, once it has
+//! checked that the program doesn't reach into it.
+
+use embedded_storage::nor_flash::NorFlash;
+
+mod crc;
+#[cfg(feature = "nvmc")]
+mod nvmc;
+
+pub use crc::crc16;
+#[cfg(feature = "nvmc")]
+pub use nvmc::{flash, FILES_ADDR, FILES_LEN};
+
+/// Starts a 128-byte block.
+pub const SOH: u8 = 0x01;
+/// Starts a 1024-byte block.
+pub const STX: u8 = 0x02;
+/// The end of the file.
+pub const EOT: u8 = 0x04;
+/// Block received.
+pub const ACK: u8 = 0x06;
+/// Block damaged or missing, send it again.
+pub const NAK: u8 = 0x15;
+/// Give up.
+pub const CAN: u8 = 0x18;
+/// Asks the sender to start, with CRCs.
+pub const CRC_MODE: u8 = b'C';
+
+/// How often to ask the sender to start, in ms.
+pub const START_PERIOD_MS: u32 = 3_000;
+/// How many times to ask before giving up.
+pub const START_TRIES: u32 = 20;
+/// Longest wait for the next byte of a block, in ms.
+pub const BYTE_TIMEOUT_MS: u32 = 1_000;
+/// Longest wait for the next block, in ms.
+pub const BLOCK_TIMEOUT_MS: u32 = 10_000;
+/// Most damaged or missing blocks in a row before giving up.
+pub const MAX_RETRIES: u32 = 10;
+
+const LONGEST_BLOCK: usize = 1024;
+/// The block number and its complement, then the data, then the CRC.
+const BUF_LEN: usize = 2 + LONGEST_BLOCK + 2;
+
+const NOTHING: &[u8] = &[];
+const ACK_REPLY: &[u8] = &[ACK];
+const NAK_REPLY: &[u8] = &[NAK];
+const START_REPLY: &[u8] = &[CRC_MODE];
+const CANCEL_REPLY: &[u8] = &[CAN, CAN, CAN];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<E> {
+    Flash(E),
+    /// The sender never started.
+    Timeout,
+    /// The sender gave up.
+    Cancelled,
+    /// [`MAX_RETRIES`] damaged or missing blocks in a row.
+    TooManyErrors,
+    /// The file doesn't fit in the flash.
+    TooLarge,
+    /// A block arrived out of order, so part of the file is missing.
+    OutOfSequence,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Asking the sender to start.
+    Starting { tries: u32 },
+    /// Between blocks.
+    Waiting,
+    /// Part way through a block of `size` data bytes, with `got` bytes of it so far.
+    Block { size: usize, got: usize },
+    Finished,
+}
+
+/// Receives one file into `F`, from its start.
+pub struct Receiver<F: NorFlash> {
+    flash: F,
+    state: State,
+    buf: [u8; BUF_LEN],
+    /// The number of the block wanted next.
+    next_block: u8,
+    /// Bytes written so far.
+    len: u32,
+    /// Flash erased so far, ready to write.
+    erased: u32,
+    /// Damaged or missing blocks in a row.
+    errors: u32,
+    /// When to act if nothing arrives.
+    deadline_ms: u32,
+    /// Whether the last byte between blocks was a [`CAN`].
+    cancelling: bool,
+    outcome: Option<Result<u32, Error<F::Error>>>,
+}
+
+impl<F: NorFlash> Receiver<F> {
+    /// Get ready to receive into `flash`. The first [`tick`](Self::tick) asks the sender to
+    /// start.
+    pub fn new(flash: F, now_ms: u32) -> Self {
+        Self {
+            flash,
+            state: State::Starting { tries: 0 },
+            buf: [0; BUF_LEN],
+            next_block: 1,
+            len: 0,
+            erased: 0,
+            errors: 0,
+            deadline_ms: now_ms,
+            cancelling: false,
+            outcome: None,
+        }
+    }
+
+    /// How the transfer ended, with the length written, padding and all; or `None` if it is still
+    /// going.
+    pub fn outcome(&self) -> Option<&Result<u32, Error<F::Error>>> {
+        self.outcome.as_ref()
+    }
+
+    /// Give back the flash, to read the file from.
+    pub fn into_flash(self) -> F {
+        self.flash
+    }
+
+    /// Call as often as possible while no bytes are arriving, to keep asking the sender to start
+    /// and to notice when it goes quiet. Returns what to send it.
+    pub fn tick(&mut self, now_ms: u32) -> &'static [u8] {
+        if self.state == State::Finished || now_ms.wrapping_sub(self.deadline_ms) > u32::MAX / 2 {
+            return NOTHING;
+        }
+        match self.state {
+            State::Starting { tries } if tries == START_TRIES => self.fail(Error::Timeout),
+            State::Starting { tries } => {
+                self.state = State::Starting { tries: tries + 1 };
+                self.deadline_ms = now_ms.wrapping_add(START_PERIOD_MS);
+                START_REPLY
+            }
+            State::Waiting | State::Block { .. } => self.retry(now_ms),
+            State::Finished => NOTHING,
+        }
+    }
+
+    /// Handle a byte from the sender. Returns what to send back.
+    pub fn receive(&mut self, byte: u8, now_ms: u32) -> &'static [u8] {
+        match self.state {
+            State::Starting { .. } | State::Waiting => {
+                let cancelling = self.cancelling;
+                self.cancelling = byte == CAN;
+                match byte {
+                    SOH => self.begin(128, now_ms),
+                    STX => self.begin(LONGEST_BLOCK, now_ms),
+                    EOT => {
+                        self.state = State::Finished;
+                        self.outcome = Some(Ok(self.len));
+                        ACK_REPLY
+                    }
+                    CAN if cancelling => {
+                        self.state = State::Finished;
+                        self.outcome = Some(Err(Error::Cancelled));
+                        NOTHING
+                    }
+                    // Anything else is line noise.
+                    _ => NOTHING,
+                }
+            }
+            State::Block { size, got } => {
+                self.buf[got] = byte;
+                if got + 1 < size + 4 {
+                    self.state = State::Block { size, got: got + 1 };
+                    self.deadline_ms = now_ms.wrapping_add(BYTE_TIMEOUT_MS);
+                    return NOTHING;
+                }
+                self.end_block(size, now_ms)
+            }
+            State::Finished => NOTHING,
+        }
+    }
+
+    fn begin(&mut self, size: usize, now_ms: u32) -> &'static [u8] {
+        self.state = State::Block { size, got: 0 };
+        self.deadline_ms = now_ms.wrapping_add(BYTE_TIMEOUT_MS);
+        NOTHING
+    }
+
+    /// Check a whole block, and write it if it is the next one.
+    fn end_block(&mut self, size: usize, now_ms: u32) -> &'static [u8] {
+        let (number, check) = (self.buf[0], self.buf[1]);
+        let crc = u16::from_be_bytes([self.buf[2 + size], self.buf[3 + size]]);
+        if number != !check || crc16(&self.buf[2..2 + size]) != crc {
+            return self.retry(now_ms);
+        }
+        if self.len > 0 && number == self.next_block.wrapping_sub(1) {
+            // Our ACK was lost, so the sender has sent the last block again.
+            return self.wait(now_ms, ACK_REPLY);
+        }
+        if number != self.next_block {
+            return self.fail(Error::OutOfSequence);
+        }
+        if self.len as usize + size > self.flash.capacity() {
+            return self.fail(Error::TooLarge);
+        }
+        if let Err(e) = self.write(size) {
+            return self.fail(Error::Flash(e));
+        }
+        self.len += size as u32;
+        self.next_block = self.next_block.wrapping_add(1);
+        self.errors = 0;
+        self.wait(now_ms, ACK_REPLY)
+    }
+
+    /// Write the block's `size` data bytes after the rest, erasing as much more flash as that
+    /// needs.
+    fn write(&mut self, size: usize) -> Result<(), F::Error> {
+        let end = self.len + size as u32;
+        while self.erased < end {
+            let next = self.erased + F::ERASE_SIZE as u32;
+            self.flash.erase(self.erased, next)?;
+            self.erased = next;
+        }
+        self.flash.write(self.len, &self.buf[2..2 + size])
+    }
+
+    fn wait(&mut self, now_ms: u32, reply: &'static [u8]) -> &'static [u8] {
+        self.state = State::Waiting;
+        self.deadline_ms = now_ms.wrapping_add(BLOCK_TIMEOUT_MS);
+        reply
+    }
+
+    /// Ask for the block again, unless that has already been done too often.
+    fn retry(&mut self, now_ms: u32) -> &'static [u8] {
+        self.errors += 1;
+        if self.errors > MAX_RETRIES {
+            return self.fail(Error::TooManyErrors);
+        }
+        self.wait(now_ms, NAK_REPLY)
+    }
+
+    fn fail(&mut self, error: Error<F::Error>) -> &'static [u8] {
+        self.state = State::Finished;
+        self.outcome = Some(Err(error));
+        CANCEL_REPLY
+    }
+}
@@ -0,0 +1,24 @@
+use microbit::hal::nvmc::Nvmc;
+use microbit::pac::NVMC;
+use settings::image_end;
+
+/// Address of the flash kept for received files: the 64K just below the settings pages at the
+/// top of the nRF52833's 512K, well clear of any program in this book.
+pub const FILES_ADDR: usize = 0x6_e000;
+/// Size of the file area, and so the largest file that can be received.
+pub const FILES_LEN: usize = 64 * 1024;
+
+/// Take the NVMC and return a flash driver for the file area, for use with `Receiver::new`.
+/// Panics if the program is too big to leave the area free.
+pub fn flash(nvmc: NVMC) -> Nvmc<NVMC> {
+    // The chip's linker script gives the program all 512K, so nothing else stops it growing
+    // into the file area.
+    assert!(
+        image_end() <= FILES_ADDR,
+        "the program runs into the file area"
+    );
+    // SAFETY: the file area is outside the program image, and `nvmc` is the only NVMC, so this
+    // is the only reference to it.
+    let area = unsafe { core::slice::from_raw_parts_mut(FILES_ADDR as *mut u8, FILES_LEN) };
+    Nvmc::new(nvmc, area)
+}
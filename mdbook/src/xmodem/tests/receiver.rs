@@ -0,0 +1,446 @@
+//! Tests the `xmodem` receiver against a pretend sender and a pretend flash.
+//!
+//! ```text
+//! cargo test-host -p xmodem
+//! ```
+//!
+//! Each test sends a file the way `sx` would, sometimes damaging or dropping blocks or answers
+//! on the way, and compares what the receiver answered and what ended up in the flash with what
+//! should have. Time only passes when the line is quiet, a tenth of a second at a time.
+
+use std::collections::VecDeque;
+
+use embedded_storage::nor_flash::{ErrorType, NorFlash, NorFlashErrorKind, ReadNorFlash};
+use xmodem::{crc16, Error, Receiver, ACK, CAN, CRC_MODE, EOT, NAK, SOH, STX};
+
+/// Flash in RAM, behaving like the NVMC: erasing sets whole pages to `0xff`, and writing can only
+/// clear bits.
+struct RamFlash {
+    bytes: Vec<u8>,
+    erases: usize,
+    /// Writes from here on fail.
+    broken_from: Option<u32>,
+}
+
+impl RamFlash {
+    fn new(len: usize) -> Self {
+        Self {
+            // Not erased to begin with, so that writing unerased flash shows up.
+            bytes: vec![0; len],
+            erases: 0,
+            broken_from: None,
+        }
+    }
+}
+
+impl ErrorType for RamFlash {
+    type Error = NorFlashErrorKind;
+}
+
+impl ReadNorFlash for RamFlash {
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        let start = offset as usize;
+        let source = self
+            .bytes
+            .get(start..start + bytes.len())
+            .ok_or(NorFlashErrorKind::OutOfBounds)?;
+        bytes.copy_from_slice(source);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.bytes.len()
+    }
+}
+
+impl NorFlash for RamFlash {
+    const WRITE_SIZE: usize = 4;
+    const ERASE_SIZE: usize = 4096;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        let (from, to) = (from as usize, to as usize);
+        if !from.is_multiple_of(Self::ERASE_SIZE) || !to.is_multiple_of(Self::ERASE_SIZE) {
+            return Err(NorFlashErrorKind::NotAligned);
+        }
+        self.bytes
+            .get_mut(from..to)
+            .ok_or(NorFlashErrorKind::OutOfBounds)?
+            .fill(0xff);
+        self.erases += (to - from) / Self::ERASE_SIZE;
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        if self.broken_from.is_some_and(|from| offset >= from) {
+            return Err(NorFlashErrorKind::Other);
+        }
+        let start = offset as usize;
+        if !start.is_multiple_of(Self::WRITE_SIZE) || !bytes.len().is_multiple_of(Self::WRITE_SIZE)
+        {
+            return Err(NorFlashErrorKind::NotAligned);
+        }
+        let target = self
+            .bytes
+            .get_mut(start..start + bytes.len())
+            .ok_or(NorFlashErrorKind::OutOfBounds)?;
+        for (old, new) in target.iter_mut().zip(bytes) {
+            *old &= new;
+        }
+        Ok(())
+    }
+}
+
+/// Changes a block's frame, given the block's index and how many times it has been sent before.
+type Damage = fn(usize, u32, &mut Vec<u8>);
+
+/// Sends a file a block at a time, as `sx` does, answering the receiver's replies.
+struct Sender {
+    blocks: Vec<Vec<u8>>,
+    current: usize,
+    attempt: u32,
+    started: bool,
+    eot_sent: bool,
+    damage: Damage,
+    /// Whether the receiver's ACK of a block goes missing, given the same as `damage`.
+    lose_ack: fn(usize, u32) -> bool,
+    /// Every byte the receiver sent.
+    heard: Vec<u8>,
+}
+
+fn no_damage(_: usize, _: u32, _: &mut Vec<u8>) {}
+
+fn no_loss(_: usize, _: u32) -> bool {
+    false
+}
+
+impl Sender {
+    /// Send `file` in 1024-byte blocks while there is that much left, then 128-byte ones if
+    /// `long` is set; otherwise all in 128-byte blocks.
+    fn new(file: &[u8], long: bool) -> Self {
+        let mut blocks = Vec::new();
+        let mut rest = file;
+        while !rest.is_empty() {
+            let size = if long && rest.len() >= 1024 {
+                1024
+            } else {
+                128
+            };
+            let take = size.min(rest.len());
+            let mut data = rest[..take].to_vec();
+            data.resize(size, 0x1a);
+            blocks.push(data);
+            rest = &rest[take..];
+        }
+        Self {
+            blocks,
+            current: 0,
+            attempt: 0,
+            started: false,
+            eot_sent: false,
+            damage: no_damage,
+            lose_ack: no_loss,
+            heard: Vec::new(),
+        }
+    }
+
+    fn frame(&self) -> Vec<u8> {
+        let data = &self.blocks[self.current];
+        let number = (self.current + 1) as u8;
+        let mut frame = vec![if data.len() == 1024 { STX } else { SOH }, number, !number];
+        frame.extend_from_slice(data);
+        frame.extend_from_slice(&crc16(data).to_be_bytes());
+        (self.damage)(self.current, self.attempt, &mut frame);
+        frame
+    }
+
+    /// The next thing to send.
+    fn send(&mut self) -> Vec<u8> {
+        if self.current == self.blocks.len() {
+            self.eot_sent = true;
+            vec![EOT]
+        } else {
+            self.frame()
+        }
+    }
+
+    fn hear(&mut self, reply: &[u8]) -> Vec<u8> {
+        self.heard.extend_from_slice(reply);
+        match reply.first() {
+            Some(&CRC_MODE) if !self.started => {
+                self.started = true;
+                self.send()
+            }
+            Some(&ACK) if self.eot_sent => Vec::new(),
+            Some(&ACK) if (self.lose_ack)(self.current, self.attempt) => {
+                // Sent again once the sender gives up waiting.
+                self.attempt += 1;
+                self.send()
+            }
+            Some(&ACK) => {
+                self.current += 1;
+                self.attempt = 0;
+                self.send()
+            }
+            Some(&NAK) => {
+                self.attempt += 1;
+                self.send()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// How a transfer went.
+struct Transfer {
+    outcome: Result<u32, Error<NorFlashErrorKind>>,
+    heard: Vec<u8>,
+    flash: RamFlash,
+    ms: u32,
+}
+
+/// Run `sender` against a receiver writing to `flash`, starting at `start_ms`.
+fn transfer_from(mut sender: Sender, flash: RamFlash, start_ms: u32) -> Transfer {
+    let mut receiver = Receiver::new(flash, start_ms);
+    let mut now = start_ms;
+    let mut line = VecDeque::new();
+    // Long enough for the receiver to give up on anything.
+    for _ in 0..10_000 {
+        let reply = receiver.tick(now);
+        line.extend(sender.hear(reply));
+        while let Some(byte) = line.pop_front() {
+            let reply = receiver.receive(byte, now);
+            line.extend(sender.hear(reply));
+        }
+        if let Some(outcome) = receiver.outcome() {
+            return Transfer {
+                outcome: *outcome,
+                heard: sender.heard,
+                ms: now.wrapping_sub(start_ms),
+                flash: receiver.into_flash(),
+            };
+        }
+        now = now.wrapping_add(100);
+    }
+    panic!("the transfer never finished");
+}
+
+fn transfer(sender: Sender, flash: RamFlash) -> Transfer {
+    transfer_from(sender, flash, 0)
+}
+
+/// A file of `len` bytes that isn't the same from one block to the next.
+fn file(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 7 + i / 251) as u8).collect()
+}
+
+/// Check that the flash holds `file`, padded to `len` with `0x1a`.
+fn assert_holds(transfer: &Transfer, file: &[u8], len: usize) {
+    let bytes = &transfer.flash.bytes;
+    assert!(bytes[..file.len()] == *file, "file");
+    assert!(bytes[file.len()..len].iter().all(|b| *b == 0x1a), "padding");
+}
+
+/// The receiver's answers, one letter each: `C`, `A`ck, `N`ak or `X` for cancel.
+fn answers(transfer: &Transfer) -> String {
+    transfer
+        .heard
+        .iter()
+        .map(|b| match *b {
+            CRC_MODE => 'C',
+            ACK => 'A',
+            NAK => 'N',
+            CAN => 'X',
+            _ => '?',
+        })
+        .collect()
+}
+
+#[test]
+fn crc() {
+    // The standard check value for CRC-16/XMODEM.
+    assert_eq!(crc16(b"123456789"), 0x31c3);
+}
+
+#[test]
+fn short_blocks() {
+    let data = file(300);
+    let t = transfer(Sender::new(&data, false), RamFlash::new(8192));
+    assert_eq!(t.outcome, Ok(384));
+    assert_holds(&t, &data, 384);
+    assert_eq!(answers(&t), "CAAAA");
+    assert_eq!(t.flash.erases, 1);
+}
+
+#[test]
+fn long_blocks() {
+    let data = file(5000);
+    let t = transfer(Sender::new(&data, true), RamFlash::new(8192));
+    // Four 1K blocks, then 904 bytes in eight short ones.
+    assert_eq!(t.outcome, Ok(4096 + 1024));
+    assert_holds(&t, &data, 5120);
+    assert_eq!(answers(&t), format!("C{}", "A".repeat(13)));
+    assert_eq!(t.flash.erases, 2);
+    assert!(
+        t.flash.bytes[5120..].iter().all(|b| *b == 0xff),
+        "beyond the file"
+    );
+}
+
+#[test]
+fn empty_file() {
+    let t = transfer(Sender::new(&[], false), RamFlash::new(4096));
+    assert_eq!(t.outcome, Ok(0));
+    assert_eq!(answers(&t), "CA");
+}
+
+#[test]
+fn damaged_blocks() {
+    let mut sender = Sender::new(&file(600), false);
+    sender.damage = |block, attempt, frame| match (block, attempt) {
+        // A flipped bit in the data.
+        (1, 0) => frame[50] ^= 0x08,
+        // A block number that doesn't match its complement.
+        (2, 0) | (2, 1) => frame[2] ^= 0x01,
+        // A flipped bit in the CRC.
+        (4, 0) => *frame.last_mut().unwrap() ^= 0x80,
+        _ => {}
+    };
+    let t = transfer(sender, RamFlash::new(4096));
+    assert_eq!(t.outcome, Ok(640));
+    assert_holds(&t, &file(600), 640);
+    assert_eq!(answers(&t), "CANANNAANAA");
+}
+
+#[test]
+fn lost_bytes() {
+    let mut sender = Sender::new(&file(400), false);
+    sender.damage = |block, attempt, frame| {
+        if (block, attempt) == (2, 0) {
+            frame.truncate(100);
+        }
+    };
+    let t = transfer(sender, RamFlash::new(4096));
+    assert_eq!(t.outcome, Ok(512));
+    assert_holds(&t, &file(400), 512);
+    assert_eq!(answers(&t), "CAANAAA");
+    assert_eq!(t.ms, 1_000, "waited");
+}
+
+#[test]
+fn lost_ack() {
+    let mut sender = Sender::new(&file(400), false);
+    sender.lose_ack = |block, attempt| (block, attempt) == (1, 0);
+    let t = transfer(sender, RamFlash::new(4096));
+    // The repeated block is acknowledged again, but not written twice.
+    assert_eq!(t.outcome, Ok(512));
+    assert_holds(&t, &file(400), 512);
+    assert_eq!(answers(&t), "CAAAAAA");
+}
+
+#[test]
+fn noise_before_the_first_block() {
+    let mut sender = Sender::new(&file(200), false);
+    sender.damage = |block, attempt, frame| {
+        if (block, attempt) == (0, 0) {
+            frame.splice(0..0, *b"\r\n+++");
+        }
+    };
+    let t = transfer(sender, RamFlash::new(4096));
+    assert_eq!(t.outcome, Ok(256));
+    assert_holds(&t, &file(200), 256);
+}
+
+#[test]
+fn block_numbers_wrapping() {
+    // 300 blocks, so the block number goes past 255 and back through 0.
+    let data = file(300 * 128);
+    let t = transfer(Sender::new(&data, false), RamFlash::new(64 * 1024));
+    assert_eq!(t.outcome, Ok(300 * 128));
+    assert_holds(&t, &data, 300 * 128);
+}
+
+#[test]
+fn no_sender() {
+    let mut quiet = Sender::new(&file(10), false);
+    // Never hears the first `C`.
+    quiet.started = true;
+    let t = transfer(quiet, RamFlash::new(4096));
+    assert_eq!(t.outcome, Err(Error::Timeout));
+    assert_eq!(answers(&t), format!("{}XXX", "C".repeat(20)));
+    assert_eq!(t.ms, 60_000, "gave up after");
+}
+
+#[test]
+fn cancelled_by_the_sender() {
+    let mut sender = Sender::new(&file(400), false);
+    sender.damage = |block, _, frame| {
+        if block == 2 {
+            *frame = vec![CAN, CAN];
+        }
+    };
+    let t = transfer(sender, RamFlash::new(4096));
+    assert_eq!(t.outcome, Err(Error::Cancelled));
+    assert_eq!(answers(&t), "CAA");
+}
+
+#[test]
+fn too_many_errors() {
+    let mut sender = Sender::new(&file(400), false);
+    sender.damage = |block, _, frame| {
+        if block == 1 {
+            frame[10] ^= 0xff;
+        }
+    };
+    let t = transfer(sender, RamFlash::new(4096));
+    assert_eq!(t.outcome, Err(Error::TooManyErrors));
+    assert_eq!(answers(&t), format!("CA{}XXX", "N".repeat(10)));
+}
+
+#[test]
+fn block_out_of_sequence() {
+    let mut sender = Sender::new(&file(400), false);
+    sender.damage = |block, _, frame| {
+        if block == 2 {
+            // Block 5, as far as the receiver can tell.
+            frame[1] = 5;
+            frame[2] = !5;
+        }
+    };
+    let t = transfer(sender, RamFlash::new(4096));
+    assert_eq!(t.outcome, Err(Error::OutOfSequence));
+    assert_eq!(answers(&t), "CAAXXX");
+}
+
+#[test]
+fn file_too_large() {
+    let data = file(4096 + 100);
+    let t = transfer(Sender::new(&data, true), RamFlash::new(4096));
+    assert_eq!(t.outcome, Err(Error::TooLarge));
+    assert_holds(&t, &data[..4096], 4096);
+    assert_eq!(answers(&t), "CAAAAXXX");
+}
+
+#[test]
+fn flash_failure() {
+    let mut flash = RamFlash::new(4096);
+    flash.broken_from = Some(256);
+    let t = transfer(Sender::new(&file(400), false), flash);
+    assert_eq!(t.outcome, Err(Error::Flash(NorFlashErrorKind::Other)));
+    assert_eq!(answers(&t), "CAAXXX");
+}
+
+#[test]
+fn clock_wrapping() {
+    let mut sender = Sender::new(&file(400), false);
+    sender.damage = |block, attempt, frame| {
+        if (block, attempt) == (1, 0) {
+            frame.truncate(10);
+        }
+    };
+    let t = transfer_from(sender, RamFlash::new(4096), u32::MAX - 450);
+    assert_eq!(t.outcome, Ok(512));
+    assert_eq!(answers(&t), "CANAAAA");
+    assert_eq!(t.ms, 1_000, "waited");
+}